@@ -0,0 +1,61 @@
+//! リクエストごとに実行する純粋な処理のマイクロベンチマーク（`cargo bench --bench hot_paths`）
+//!
+//! データベース・ルーターを含む計測は`loadgen`（`cargo run --release --bin loadgen`）で行う。
+
+#[path = "../src/bin/loadgen/latency.rs"]
+mod latency;
+#[path = "../tests/common/google.rs"]
+#[allow(dead_code)]
+mod google;
+
+use chrono::Utc;
+use jsonwebtoken::jwk::JwkSet;
+use latency::Latencies;
+use patchouli::google_auth::verify_google_id_token;
+use std::{hint::black_box, time::Instant};
+
+const WARMUP: usize = 1_000;
+const ITERATIONS: usize = 20_000;
+
+/// `f`を`ITERATIONS`回実行し、1回ごとの処理時間を表示する
+fn bench(name: &str, mut f: impl FnMut()) {
+    for _ in 0..WARMUP {
+        f();
+    }
+    let mut samples = Vec::with_capacity(ITERATIONS);
+    let started = Instant::now();
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        f();
+        samples.push(start.elapsed());
+    }
+    Latencies::new(samples).print_row(name, started.elapsed());
+}
+
+fn main() {
+    // `cargo test --benches`ではベンチマークを実行しない
+    if std::env::args().any(|arg| arg == "--bench") {
+        run();
+    }
+}
+
+fn run() {
+    latency::print_header();
+
+    let jwks: JwkSet = serde_json::from_str(include_str!("../tests/fixtures/google_test_jwks.json")).unwrap();
+    let id_token = google::id_token("google-bench", "bench@example.com", "Bench");
+    let now = Utc::now();
+    bench("verify_google_id_token", || {
+        let claims = verify_google_id_token(black_box(&id_token), &jwks.keys, google::CLIENT_ID, now).unwrap();
+        black_box(claims);
+    });
+
+    let tampered = format!("{}x", id_token);
+    bench("verify_google_id_token (tampered)", || {
+        let result = verify_google_id_token(black_box(&tampered), &jwks.keys, google::CLIENT_ID, now);
+        black_box(result.is_err());
+    });
+
+    // 参考: 計測の下限（Instantの呼び出し自体のコスト）
+    bench("noop", || black_box(()));
+}
@@ -0,0 +1,4 @@
+// `sqlx::migrate!`はマイグレーションのファイルだけを変更しても再ビルドされないため、変更を検知させる
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
@@ -0,0 +1,54 @@
+-- マイグレーション導入時点のスキーマ（それ以前から運用しているデータベースには不足しているカラムだけを追加する）
+
+CREATE TABLE IF NOT EXISTS registered_users (
+    id BIGSERIAL PRIMARY KEY,
+    google_id TEXT NOT NULL UNIQUE,
+    email TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    registered_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_login TIMESTAMPTZ,
+    is_root BOOLEAN NOT NULL DEFAULT FALSE,
+    can_invite BOOLEAN NOT NULL DEFAULT TRUE,
+    invited_by BIGINT REFERENCES registered_users(id) ON DELETE SET NULL,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    metadata TEXT NOT NULL DEFAULT '{}',
+    email_verified BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+ALTER TABLE registered_users ADD COLUMN IF NOT EXISTS metadata TEXT NOT NULL DEFAULT '{}';
+ALTER TABLE registered_users ADD COLUMN IF NOT EXISTS email_verified BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE IF NOT EXISTS invite_codes (
+    id BIGSERIAL PRIMARY KEY,
+    code TEXT NOT NULL UNIQUE,
+    created_by BIGINT NOT NULL REFERENCES registered_users(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMPTZ,
+    used_by BIGINT REFERENCES registered_users(id),
+    used_at TIMESTAMPTZ,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    note TEXT,
+    metadata TEXT NOT NULL DEFAULT '{}'
+);
+
+ALTER TABLE invite_codes ADD COLUMN IF NOT EXISTS note TEXT;
+ALTER TABLE invite_codes ADD COLUMN IF NOT EXISTS metadata TEXT NOT NULL DEFAULT '{}';
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    actor_user_id BIGINT,
+    action TEXT NOT NULL,
+    target_user_id BIGINT,
+    metadata TEXT NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    idempotency_key TEXT PRIMARY KEY,
+    request_hash TEXT NOT NULL,
+    status_code INTEGER,
+    content_type TEXT,
+    response_body BYTEA,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,14 @@
+-- プログラムからのアクセス用のAPIキー（キーそのものは保存せず、SHA-256のハッシュのみ保持する）
+
+CREATE TABLE IF NOT EXISTS api_keys (
+    id BIGSERIAL PRIMARY KEY,
+    key_hash TEXT NOT NULL UNIQUE,
+    created_by BIGINT NOT NULL REFERENCES registered_users(id),
+    name TEXT NOT NULL,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    expires_at TIMESTAMPTZ,
+    scopes TEXT NOT NULL DEFAULT '[]',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_keys_created_by ON api_keys(created_by);
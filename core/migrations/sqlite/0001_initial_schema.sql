@@ -0,0 +1,51 @@
+-- マイグレーション導入時点のスキーマ（それ以前から運用しているデータベースでは何もしない）
+
+CREATE TABLE IF NOT EXISTS registered_users (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    google_id TEXT NOT NULL UNIQUE,
+    email TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    registered_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_login DATETIME,
+    is_root BOOLEAN NOT NULL DEFAULT FALSE,
+    can_invite BOOLEAN NOT NULL DEFAULT TRUE,
+    invited_by INTEGER,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    metadata TEXT NOT NULL DEFAULT '{}',
+    email_verified BOOLEAN NOT NULL DEFAULT FALSE,
+    FOREIGN KEY (invited_by) REFERENCES registered_users(id)
+);
+
+CREATE TABLE IF NOT EXISTS invite_codes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    code TEXT NOT NULL UNIQUE,
+    created_by INTEGER NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at DATETIME,
+    used_by INTEGER,
+    used_at DATETIME,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    note TEXT,
+    metadata TEXT NOT NULL DEFAULT '{}',
+    FOREIGN KEY (created_by) REFERENCES registered_users(id),
+    FOREIGN KEY (used_by) REFERENCES registered_users(id)
+);
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    actor_user_id INTEGER,
+    action TEXT NOT NULL,
+    target_user_id INTEGER,
+    metadata TEXT NOT NULL DEFAULT '{}',
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    idempotency_key TEXT PRIMARY KEY,
+    request_hash TEXT NOT NULL,
+    status_code INTEGER,
+    content_type TEXT,
+    response_body BLOB,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at DATETIME NOT NULL
+);
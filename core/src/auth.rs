@@ -0,0 +1,265 @@
+use crate::{
+    database::{ApiKey, RegisteredUser},
+    error::{AppError, ErrorCode},
+    error_reporting,
+    extract::Query,
+    AppState, SessionQuery,
+};
+use anyhow::Context;
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts},
+};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tracing::{warn, Span};
+
+/// `session_id`クエリ（または`Authorization: Bearer <session_id>`）のセッションに対応するログイン中のユーザー
+///
+/// 両方ある場合は`Authorization`ヘッダーを優先する。`Authorization`がなく`X-Api-Key`（`api_key_header`）がある場合は
+/// APIキーの所有者として扱う（キーが無効なら401、ルートのスコープをキーが持っていなければ403）。
+/// セッションがなければ401、ユーザーが未登録または利用停止中なら403を返す。
+/// 読み込んだユーザーはリクエストのextensionsに`Arc<RegisteredUser>`として（使った資格情報は`Credential`として）保持し、
+/// 同じリクエストの他のエクストラクターと共有する。
+pub struct AuthUser(pub Arc<RegisteredUser>);
+
+/// rootユーザーのみ通す（それ以外は403）
+pub struct RootUser(pub Arc<RegisteredUser>);
+
+/// `AuthUser`が認証に使った資格情報（ユーザーと一緒にextensionsに保持する）
+///
+/// SSEのように接続が長く続く場合に、同じ資格情報でユーザーを取得し直すために使う。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Credential {
+    /// セッションID（`Authorization: Bearer`またはクエリの`session_id`）
+    Session(String),
+    /// APIキー（`X-Api-Key`）
+    ApiKey(String),
+}
+
+impl Credential {
+    /// リクエストが示している資格情報（`AuthUser`と同じ優先順。有効かどうかは確かめない）
+    pub(crate) fn presented(parts: &Parts, state: &AppState) -> Option<Credential> {
+        if let Some(token) = bearer_token(parts) {
+            return Some(Credential::Session(token.to_string()));
+        }
+        if let Some(key) = parts.headers.get(state.config.api_key_header.as_str()) {
+            return Some(Credential::ApiKey(key.to_str().unwrap_or_default().to_string()));
+        }
+        axum::extract::Query::<SessionQuery>::try_from_uri(&parts.uri)
+            .ok()
+            .map(|query| Credential::Session(query.0.session_id))
+    }
+
+    /// 資格情報を区別するための値（資格情報そのものを保存しないようSHA-256にする）
+    pub(crate) fn fingerprint(&self) -> String {
+        match self {
+            Credential::Session(session_id) => format!("session:{}", hash_api_key(session_id)),
+            Credential::ApiKey(key) => format!("api_key:{}", hash_api_key(key)),
+        }
+    }
+
+    /// 資格情報に対応するユーザー（ログアウト・キーの無効化・利用停止・削除の後はエラー）
+    pub(crate) async fn user(&self, state: &AppState) -> Result<RegisteredUser, AppError> {
+        match self {
+            Credential::Session(session_id) => user_for_session(state, session_id).await,
+            Credential::ApiKey(key) => user_for_api_key(state, key).await,
+        }
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for AuthUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        // 同じリクエストで複数のエクストラクター（RootUser等）が使われてもDBへの問い合わせは1回にする
+        if let Some(user) = parts.extensions.get::<Arc<RegisteredUser>>() {
+            return Ok(AuthUser(user.clone()));
+        }
+
+        let credential = match Credential::presented(parts, state) {
+            Some(credential) => credential,
+            // `session_id`がない・不正な場合のエラー（400）は`Query`に任せる
+            None => Credential::Session(Query::<SessionQuery>::from_request_parts(parts, state).await?.0.session_id),
+        };
+        let user = match &credential {
+            Credential::ApiKey(key) => {
+                let api_key = active_api_key(state, key).await?;
+                let user = api_key_owner(state, &api_key).await?;
+                let required = parts.extensions.get::<RequiredScope>().and_then(|required| required.0);
+                if !required.is_some_and(|scope| api_key.scopes.iter().any(|granted| granted == scope.as_str())) {
+                    warn!("API key {} lacks scope {:?} for {}", api_key.id, required, parts.uri.path());
+                    return Err(ErrorCode::ApiKeyScopeMissing.into());
+                }
+                user
+            }
+            Credential::Session(_) => credential.user(state).await?,
+        };
+        let user = Arc::new(user);
+        parts.extensions.insert(user.clone());
+        parts.extensions.insert(credential);
+        Ok(AuthUser(user))
+    }
+}
+
+/// `Authorization: Bearer <token>`のトークン部分（ヘッダーがない・Bearerでない場合は`None`）
+fn bearer_token(parts: &Parts) -> Option<&str> {
+    let value = parts.headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme.eq_ignore_ascii_case("bearer").then(|| token.trim())
+}
+
+/// セッションIDからログイン中のユーザーを取得する（REST・gRPCで共通）
+///
+/// セッションがなければ`invalid_session`、ユーザーが未登録・利用停止中ならそれぞれのエラーを返す。
+pub(crate) async fn user_for_session(state: &AppState, session_id: &str) -> Result<RegisteredUser, AppError> {
+    // 読み取りロックを保持したままDBにアクセスしない
+    let email = state
+        .sessions
+        .read()
+        .await
+        .get(session_id)
+        .map(|session| session.email.clone())
+        .ok_or(ErrorCode::InvalidSession)?;
+
+    let user = state
+        .user_cache
+        .get_by_email(&state.database, &email)
+        .await
+        .context("Database error during session user lookup")?;
+    check_user(state, user, &format!("Session for {}", email))
+}
+
+/// APIキーで呼び出せる操作（ルートごとに`routes.rs`で1つ指定する）
+///
+/// スコープを指定していないルート（APIキーの管理等）はAPIキーでは呼び出せない。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiKeyScope {
+    ReadUsers,
+    WriteUsers,
+    ReadInvites,
+    WriteInvites,
+    ReadEvents,
+    ReadAdmin,
+    WriteAdmin,
+}
+
+impl ApiKeyScope {
+    pub const ALL: &'static [ApiKeyScope] = &[
+        ApiKeyScope::ReadUsers,
+        ApiKeyScope::WriteUsers,
+        ApiKeyScope::ReadInvites,
+        ApiKeyScope::WriteInvites,
+        ApiKeyScope::ReadEvents,
+        ApiKeyScope::ReadAdmin,
+        ApiKeyScope::WriteAdmin,
+    ];
+
+    /// キーの`scopes`に保存する文字列
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ApiKeyScope::ReadUsers => "read:users",
+            ApiKeyScope::WriteUsers => "write:users",
+            ApiKeyScope::ReadInvites => "read:invites",
+            ApiKeyScope::WriteInvites => "write:invites",
+            ApiKeyScope::ReadEvents => "read:events",
+            ApiKeyScope::ReadAdmin => "read:admin",
+            ApiKeyScope::WriteAdmin => "write:admin",
+        }
+    }
+}
+
+/// ルートがAPIキーに求めるスコープ（`routes::into_router`がextensionsに入れる）
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RequiredScope(pub(crate) Option<ApiKeyScope>);
+
+/// APIキーの先頭に付ける文字列（ログ・設定ファイルに紛れたキーを見つけやすくする）
+const API_KEY_PREFIX: &str = "pk_";
+const API_KEY_BYTES: usize = 32;
+
+/// 新しいAPIキー（OSの乱数から32バイトを16進数にして`pk_`を付ける）
+pub(crate) fn generate_api_key() -> Result<String, AppError> {
+    let mut bytes = [0u8; API_KEY_BYTES];
+    getrandom::getrandom(&mut bytes).map_err(|e| anyhow::anyhow!("Failed to generate API key: {}", e))?;
+    Ok(format!("{}{}", API_KEY_PREFIX, hex(&bytes)))
+}
+
+/// APIキーのSHA-256（16進数）。データベースにはこの値のみ保存する
+///
+/// パスワードと違いキーは32バイトの乱数で総当たりできないため、bcrypt等の遅いハッシュやソルトは使わない
+/// （リクエストごとに照合するため、ハッシュから索引で1回で引けることを優先する）。
+pub(crate) fn hash_api_key(key: &str) -> String {
+    hex(&Sha256::digest(key.as_bytes()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// APIキーの所有者を取得する（キーが存在しない・無効化済み・期限切れなら`invalid_api_key`）
+///
+/// キーは`hash_api_key`のSHA-256で照合する。スコープは確かめない（ルートごとの確認は`AuthUser`で行う）。
+async fn user_for_api_key(state: &AppState, key: &str) -> Result<RegisteredUser, AppError> {
+    let api_key = active_api_key(state, key).await?;
+    api_key_owner(state, &api_key).await
+}
+
+/// 有効なAPIキー（存在しない・無効化済み・期限切れなら`invalid_api_key`）
+async fn active_api_key(state: &AppState, key: &str) -> Result<ApiKey, AppError> {
+    let now = state.clock.now();
+    let api_key = state
+        .database
+        .get_api_key_by_hash(&hash_api_key(key))
+        .await
+        .context("Database error during API key lookup")?
+        .filter(|api_key| api_key.is_active && api_key.expires_at.is_none_or(|expires_at| now < expires_at))
+        .ok_or(ErrorCode::InvalidApiKey)?;
+    Ok(api_key)
+}
+
+async fn api_key_owner(state: &AppState, api_key: &ApiKey) -> Result<RegisteredUser, AppError> {
+    let user = state
+        .user_cache
+        .get_by_id(&state.database, api_key.created_by)
+        .await
+        .context("Database error during API key user lookup")?;
+    check_user(state, user, &format!("API key {}", api_key.id))
+}
+
+/// 認証したユーザーが登録済みで利用停止中でないことを確認する（`subject`はログ用）
+fn check_user(state: &AppState, user: Option<RegisteredUser>, subject: &str) -> Result<RegisteredUser, AppError> {
+    match user {
+        Some(user) if !user.is_active => {
+            warn!("{} rejected: user {} is banned", subject, user.email);
+            Err(ErrorCode::UserSuspended.into())
+        }
+        Some(user) => {
+            // リクエストのスパン（ログの`user_id`）に記録する
+            Span::current().record("user_id", user.id);
+            error_reporting::set_user(&state.config, user.id);
+            Ok(user)
+        }
+        None => {
+            warn!("{} rejected: user is not registered", subject);
+            Err(ErrorCode::UserNotRegistered.into())
+        }
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for RootUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let AuthUser(user) = AuthUser::from_request_parts(parts, state).await?;
+
+        if !user.is_root {
+            warn!("User {} attempted to access {} without root permission", user.email, parts.uri.path());
+            return Err(ErrorCode::InsufficientPermission.into());
+        }
+
+        Ok(RootUser(user))
+    }
+}
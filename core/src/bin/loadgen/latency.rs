@@ -0,0 +1,44 @@
+//! レイテンシーの集計と表示（`loadgen`と`benches/hot_paths.rs`で共有する）
+
+use std::time::Duration;
+
+/// 1回ごとの処理時間（昇順に並べて保持する）
+pub struct Latencies {
+    samples: Vec<Duration>,
+}
+
+impl Latencies {
+    pub fn new(mut samples: Vec<Duration>) -> Self {
+        assert!(!samples.is_empty(), "no samples were recorded");
+        samples.sort_unstable();
+        Latencies { samples }
+    }
+
+    /// `p`パーセンタイル（nearest-rank法）
+    pub fn percentile(&self, p: f64) -> Duration {
+        let rank = ((p / 100.0) * self.samples.len() as f64).ceil() as usize;
+        self.samples[rank.clamp(1, self.samples.len()) - 1]
+    }
+
+    /// `elapsed`（全体の経過時間）からスループットを求めて1行で表示する
+    pub fn print_row(&self, name: &str, elapsed: Duration) {
+        let per_second = self.samples.len() as f64 / elapsed.as_secs_f64();
+        println!(
+            "{:<36} {:>8} {:>12.0} {:>12} {:>12} {:>12}",
+            name,
+            self.samples.len(),
+            per_second,
+            micros(self.percentile(50.0)),
+            micros(self.percentile(99.0)),
+            micros(self.percentile(100.0)),
+        );
+    }
+}
+
+pub fn print_header() {
+    println!("{:<36} {:>8} {:>12} {:>12} {:>12} {:>12}", "SCENARIO", "COUNT", "PER_SEC", "P50", "P99", "MAX");
+}
+
+fn micros(duration: Duration) -> String {
+    format!("{:.1}us", duration.as_secs_f64() * 1_000_000.0)
+}
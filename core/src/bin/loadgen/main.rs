@@ -0,0 +1,191 @@
+//! プロセス内のルーターに並行してリクエストを送り、ホットパスのレイテンシーを計測する
+//!
+//! `cargo run --release --bin loadgen -- --requests 5000 --concurrency 32`
+//!
+//! インメモリのSQLiteに状態を作り、ネットワークを介さずに`build_router`のルーターへ`oneshot`で送る。
+//! 計測するのは認証（セッションからのユーザーの取得）、ユーザー数ごとの`GET /v1/admin/users`、招待コードの検証。
+//! ID Tokenの検証のような純粋な処理は`benches/hot_paths.rs`（`cargo bench`）で計測する。
+
+mod latency;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header::AUTHORIZATION, Request},
+    Router,
+};
+use clap::Parser;
+use latency::Latencies;
+use patchouli::{build_router, build_state, config::Config, database::RegisteredUser, AppState, UserSession};
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tower::ServiceExt;
+
+#[derive(Parser)]
+#[command(name = "loadgen", about = "Measure request latencies against an in-process router")]
+struct Args {
+    /// シナリオごとのリクエスト数（`/v1/admin/users`は`--list-requests`）
+    #[arg(long, default_value_t = 2000)]
+    requests: usize,
+    /// `/v1/admin/users`のユーザー数ごとのリクエスト数
+    #[arg(long, default_value_t = 50)]
+    list_requests: usize,
+    /// 同時に実行するリクエスト数
+    #[arg(long, default_value_t = 16)]
+    concurrency: usize,
+    /// `/v1/admin/users`を計測するユーザー数（カンマ区切り）
+    #[arg(long, value_delimiter = ',', default_value = "100,1000,10000")]
+    table_sizes: Vec<usize>,
+    /// 計測前に捨てるリクエストの割合（キャッシュ・接続プールを温める）
+    #[arg(long, default_value_t = 0.1)]
+    warmup: f64,
+}
+
+/// 計測用の状態（インメモリのSQLiteとrootユーザーのセッション）
+struct Target {
+    state: AppState,
+    app: Router,
+    root: RegisteredUser,
+    session_id: String,
+}
+
+impl Target {
+    async fn new(user_cache_ttl_secs: u64) -> anyhow::Result<Self> {
+        let state = build_state(Config {
+            database_url: "sqlite::memory:".to_string(),
+            user_cache_ttl_secs,
+            ..Config::default()
+        })
+        .await?;
+        let root = state.database.register_user("loadgen-root", "root@loadgen.invalid", "Root", None).await?;
+        let session_id = "loadgen-root".to_string();
+        state.sessions.write().await.insert(
+            session_id.clone(),
+            UserSession {
+                user_id: root.google_id.clone(),
+                email: root.email.clone(),
+            },
+        );
+        let app = build_router(state.clone());
+        Ok(Target {
+            state,
+            app,
+            root,
+            session_id,
+        })
+    }
+
+    /// `uri`へのGETを計測する（2xx以外は失敗とする）
+    async fn get(&self, args: &Args, requests: usize, uri: &str) -> (Latencies, Duration) {
+        let app = self.app.clone();
+        let uri = uri.to_string();
+        let authorization = format!("Bearer {}", self.session_id);
+        run(args, requests, move |_| {
+            let request = Request::get(uri.as_str()).header(AUTHORIZATION, authorization.as_str()).body(Body::empty());
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request.unwrap()).await.unwrap();
+                let status = response.status();
+                to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                assert!(status.is_success(), "unexpected status {}", status);
+            }
+        })
+        .await
+    }
+}
+
+/// `op`を`requests`回（`concurrency`並列）実行し、1回ごとの処理時間と全体の経過時間を返す
+async fn run<F, Fut>(args: &Args, requests: usize, op: F) -> (Latencies, Duration)
+where
+    F: Fn(usize) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    let warmup = (requests as f64 * args.warmup) as usize;
+    for i in 0..warmup {
+        op(i).await;
+    }
+
+    let next = Arc::new(AtomicUsize::new(0));
+    let started = Instant::now();
+    let workers: Vec<_> = (0..args.concurrency.max(1))
+        .map(|_| {
+            let next = next.clone();
+            let op = op.clone();
+            tokio::spawn(async move {
+                let mut samples = Vec::new();
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    if i >= requests {
+                        break samples;
+                    }
+                    let start = Instant::now();
+                    op(i).await;
+                    samples.push(start.elapsed());
+                }
+            })
+        })
+        .collect();
+    let mut samples = Vec::with_capacity(requests);
+    for worker in workers {
+        samples.extend(worker.await.expect("worker panicked"));
+    }
+    (Latencies::new(samples), started.elapsed())
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    if cfg!(debug_assertions) {
+        eprintln!("warning: this is a debug build; run with --release for meaningful numbers");
+    }
+    println!("requests={} concurrency={} warmup={}", args.requests, args.concurrency, args.warmup);
+    latency::print_header();
+
+    // 認証: セッションの検索 + メールアドレスでのユーザーの取得（キャッシュの有無で比べる）
+    for (name, ttl) in [("auth GET /v1/userinfo (no cache)", 0), ("auth GET /v1/userinfo (user cache)", 60)] {
+        let target = Target::new(ttl).await?;
+        let (latencies, elapsed) = target.get(&args, args.requests, "/v1/userinfo").await;
+        latencies.print_row(name, elapsed);
+    }
+
+    // 招待コードの検証（登録時に呼ばれるデータベースの検索）
+    let target = Target::new(60).await?;
+    let mut codes = Vec::new();
+    for _ in 0..100 {
+        codes.push(target.state.database.create_invite_code(target.root.id).await?.code);
+    }
+    let codes = Arc::new(codes);
+    let database = target.state.database.clone();
+    let (latencies, elapsed) = run(&args, args.requests, move |i| {
+        let database = database.clone();
+        let codes = codes.clone();
+        async move {
+            let invite = database.validate_invite_code(&codes[i % codes.len()]).await.unwrap();
+            assert!(invite.is_some(), "invite code should be valid");
+        }
+    })
+    .await;
+    latencies.print_row("validate_invite_code", elapsed);
+
+    // ユーザー一覧（全件を返すため、ユーザー数に比例して遅くなる）
+    let mut sizes = args.table_sizes.clone();
+    sizes.sort_unstable();
+    let mut registered = 1;
+    for size in sizes {
+        while registered < size {
+            let email = format!("user{}@loadgen.invalid", registered);
+            let google_id = format!("loadgen-{}", registered);
+            target.state.database.register_user(&google_id, &email, "Loadgen User", None).await?;
+            registered += 1;
+        }
+        let (latencies, elapsed) = target.get(&args, args.list_requests, "/v1/admin/users").await;
+        latencies.print_row(&format!("list_users ({} users)", size), elapsed);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,214 @@
+use crate::{
+    clock::SystemClock,
+    config::Config,
+    database::{self, Database, InviteCode, RegisteredUser, UserFilterParams},
+    ids::RandomIds,
+};
+use anyhow::{bail, Context};
+use chrono::{Duration, Utc};
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Patchouliのコアサーバー（サブコマンドを省略した場合は`serve`）
+#[derive(Parser)]
+#[command(name = "patchouli", version, about)]
+pub struct Cli {
+    /// 結果をJSONで標準出力に書き出す（スクリプト向け）
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// HTTPサーバーを起動する
+    Serve,
+    /// データベースのテーブルを作成・更新して終了する
+    Migrate,
+    /// 最初のユーザーとしてrootユーザーを作成する（ユーザーが既に存在する場合は失敗する）
+    CreateRoot {
+        #[arg(long)]
+        email: String,
+        #[arg(long)]
+        name: String,
+    },
+    /// 招待コードを作成して表示する
+    Invite {
+        /// 有効期限（例: 30m、72h、7d。最大8760h、省略時は無期限）
+        #[arg(long, value_parser = parse_expires_in)]
+        expires_in: Option<Duration>,
+        /// 作成者のメールアドレス（省略時はrootユーザー）
+        #[arg(long)]
+        created_by: Option<String>,
+    },
+    /// ユーザーを管理する
+    User {
+        #[command(subcommand)]
+        command: UserCommand,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum UserCommand {
+    /// 登録ユーザーの一覧を表示する
+    List,
+}
+
+/// `serve`以外のサブコマンドを実行する（失敗した場合はエラーを返し、終了コードは1になる）
+pub async fn run(command: Command, json: bool) -> anyhow::Result<()> {
+    let config = Config::load()?;
+    let database = database::connect(&config.database_url, Arc::new(SystemClock), Arc::new(RandomIds))
+        .await
+        .context("Failed to open the database")?;
+
+    match command {
+        Command::Serve => unreachable!("serve is handled by main"),
+        Command::Migrate => {
+            if json {
+                print_json(&serde_json::json!({ "migrated": true }))?;
+            } else {
+                println!("Database schema is up to date");
+            }
+        }
+        Command::CreateRoot { email, name } => {
+            let user = create_root(&database, &email, &name).await?;
+            if json {
+                print_json(&user)?;
+            } else {
+                println!("Created root user {} (id {})", user.email, user.id);
+            }
+        }
+        Command::Invite { expires_in, created_by } => {
+            let invite = create_invite(&database, expires_in, created_by.as_deref()).await?;
+            if json {
+                print_json(&InviteOutput {
+                    invite_url: config.invite_url(&invite.code),
+                    invite,
+                })?;
+            } else {
+                // 標準出力にはコードのみを出す（`code=$(patchouli invite)`で受け取れるように）
+                println!("{}", invite.code);
+            }
+        }
+        Command::User { command: UserCommand::List } => {
+            let users = database
+                .get_all_registered_users(&UserFilterParams::default())
+                .await
+                .context("Failed to list users")?;
+            if json {
+                print_json(&users)?;
+            } else {
+                print_user_table(&users);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct InviteOutput {
+    #[serde(flatten)]
+    invite: InviteCode,
+    invite_url: String,
+}
+
+async fn create_root(database: &Database, email: &str, name: &str) -> anyhow::Result<RegisteredUser> {
+    if !email.contains('@') {
+        bail!("--email must be an email address (got {:?})", email);
+    }
+    if name.trim().is_empty() {
+        bail!("--name must not be empty");
+    }
+    // 最初に登録したユーザーがrootになる（/v1/root/existsと同じ判定）
+    if database.count_registered_users().await? > 0 {
+        bail!("A root user already exists; create-root only works on an empty database");
+    }
+
+    // Google IDは初回ログイン時まで分からないため、メールアドレスから一意な仮の値を作る
+    // （ログイン時のユーザー照合はメールアドレスで行う）。ROOT_EMAILに関係なくrootユーザーにする
+    let user = database
+        .register_user(&format!("cli:{}", email), email, name.trim(), Some(email))
+        .await
+        .context("Failed to create root user")?;
+    Ok(user)
+}
+
+async fn create_invite(
+    database: &Database,
+    expires_in: Option<Duration>,
+    created_by: Option<&str>,
+) -> anyhow::Result<InviteCode> {
+    let creator = match created_by {
+        Some(email) => database
+            .get_user_by_email(email)
+            .await?
+            .with_context(|| format!("User {} is not registered", email))?,
+        None => database
+            .get_all_registered_users(&UserFilterParams {
+                is_root: Some(true),
+                ..Default::default()
+            })
+            .await?
+            .into_iter()
+            .next()
+            .context("No root user exists yet; run `patchouli create-root` first")?,
+    };
+    if !creator.can_invite || !creator.is_active {
+        bail!("User {} is not allowed to create invite codes", creator.email);
+    }
+
+    let invite = database
+        .create_invite_code(creator.id)
+        .await
+        .context("Failed to create invite code")?;
+    let invite = match expires_in {
+        Some(expires_in) => database
+            .update_invite(invite.id, None, Some(Utc::now() + expires_in))
+            .await
+            .context("Failed to set invite expiry")?,
+        None => invite,
+    };
+    Ok(invite)
+}
+
+/// `30m`・`72h`・`7d`形式の期間を読み取る
+fn parse_expires_in(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("expected a positive duration such as 30m, 72h or 7d (got {:?})", value);
+    let split = value.len().checked_sub(1).filter(|&i| value.is_char_boundary(i)).ok_or_else(invalid)?;
+    let (amount, unit) = value.split_at(split);
+    let amount: i64 = amount.parse().ok().filter(|&amount| amount > 0).ok_or_else(invalid)?;
+    let duration = match unit {
+        "m" => Duration::try_minutes(amount),
+        "h" => Duration::try_hours(amount),
+        "d" => Duration::try_days(amount),
+        _ => None,
+    };
+    // PATCH /v1/invite/:invite_idのexpires_in_hoursと同じ上限
+    duration
+        .filter(|duration| duration.num_minutes() <= crate::MAX_INVITE_EXPIRY_HOURS as i64 * 60)
+        .ok_or_else(|| format!("must be at most {}h (got {:?})", crate::MAX_INVITE_EXPIRY_HOURS, value))
+}
+
+fn print_json<T: Serialize>(value: &T) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+fn print_user_table(users: &[RegisteredUser]) {
+    println!("{:>6}  {:<32}  {:<24}  {:<5}  {:<6}  {:<6}  REGISTERED", "ID", "EMAIL", "NAME", "ROOT", "INVITE", "ACTIVE");
+    for user in users {
+        println!(
+            "{:>6}  {:<32}  {:<24}  {:<5}  {:<6}  {:<6}  {}",
+            user.id,
+            user.email,
+            user.name,
+            user.is_root,
+            user.can_invite,
+            user.is_active,
+            user.registered_at.format("%Y-%m-%d %H:%M"),
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, Request, State},
+    http::{header::FORWARDED, request::Parts, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use ipnet::IpNet;
+use std::{
+    convert::Infallible,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::Arc,
+};
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// UNIXソケットの接続の送信元として扱うアドレス（`TRUSTED_PROXIES`に127.0.0.1を含めるとプロキシのヘッダーを使う）
+pub const UNIX_SOCKET_PEER: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+/// リクエストの送信元のIPアドレス（`resolve`ミドルウェアがextensionsに入れる）
+///
+/// 接続元が`TRUSTED_PROXIES`のプロキシなら`Forwarded`・`X-Forwarded-For`から求め、それ以外は接続元のアドレス。
+/// 接続元が分からない（`ConnectInfo`のないテストのルーター等）場合は`None`。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub Option<IpAddr>);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<ClientIp>().copied().unwrap_or(ClientIp(None)))
+    }
+}
+
+/// `ClientIp`を求めてextensionsに入れる（ログのスパンで使うため`TraceLayer`より外側に置く）
+pub async fn resolve(State(trusted): State<Arc<[IpNet]>>, mut request: Request, next: Next) -> Response {
+    let peer = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| addr.ip());
+    let client_ip = ClientIp(peer.map(|peer| client_ip(peer, request.headers(), &trusted)));
+    request.extensions_mut().insert(client_ip);
+    next.run(request).await
+}
+
+/// 接続元`peer`とプロキシのヘッダーから送信元を求める
+///
+/// 経由したプロキシは右端から順に追記するため、右から見て最初の信頼できないアドレスを送信元とする（それより左は
+/// クライアントが自由に書ける）。`peer`が信頼できない場合はヘッダーを無視する。`Forwarded`があれば
+/// `X-Forwarded-For`より優先し、読めない値（`unknown`・難読化した識別子）があればその手前の信頼できるプロキシで止める。
+pub fn client_ip(peer: IpAddr, headers: &HeaderMap, trusted: &[IpNet]) -> IpAddr {
+    let is_trusted = |ip: &IpAddr| trusted.iter().any(|net| net.contains(ip));
+    if !is_trusted(&peer) {
+        return peer;
+    }
+
+    let hops = if headers.contains_key(FORWARDED) {
+        header_values(headers, FORWARDED.as_str()).flat_map(forwarded_for).collect::<Vec<_>>()
+    } else {
+        header_values(headers, X_FORWARDED_FOR).map(|hop| parse_ip(hop.trim())).collect()
+    };
+
+    let mut client = peer;
+    for hop in hops.into_iter().rev() {
+        let Some(ip) = hop else {
+            break;
+        };
+        client = ip;
+        if !is_trusted(&ip) {
+            break;
+        }
+    }
+    client
+}
+
+/// 同じ名前のヘッダーが複数あれば順に連結した、カンマ区切りの各要素
+fn header_values<'a>(headers: &'a HeaderMap, name: &str) -> impl Iterator<Item = &'a str> {
+    headers
+        .get_all(name)
+        .iter()
+        .flat_map(|value| value.to_str().unwrap_or_default().split(','))
+}
+
+/// `Forwarded`の要素（`for=192.0.2.60;proto=http`）の`for`（RFC 7239）
+fn forwarded_for(element: &str) -> Option<Option<IpAddr>> {
+    element.split(';').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        key.trim().eq_ignore_ascii_case("for").then(|| parse_ip(value.trim().trim_matches('"')))
+    })
+}
+
+/// `192.0.2.60`・`192.0.2.60:4711`・`[2001:db8::1]`・`[2001:db8::1]:4711`・`2001:db8::1`
+fn parse_ip(value: &str) -> Option<IpAddr> {
+    if let Some(rest) = value.strip_prefix('[') {
+        return rest.split_once(']')?.0.parse().ok();
+    }
+    value
+        .parse()
+        .ok()
+        .or_else(|| value.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
@@ -0,0 +1,51 @@
+//! 現在時刻の取得（テストで時刻を進められるよう`AppState`・`Database`に差し替え可能な時計を持たせる）
+
+use chrono::{DateTime, Duration, Utc};
+use std::sync::{Arc, Mutex};
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+pub type SharedClock = Arc<dyn Clock>;
+
+/// システムの時計（本番用）
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// `advance`・`set`を呼ぶまで止まっている時計（テスト用）
+#[derive(Debug)]
+pub struct MockClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl MockClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        MockClock { now: Mutex::new(now) }
+    }
+
+    /// 現在のシステム時刻で止まった時計
+    pub fn starting_now() -> Self {
+        MockClock::new(Utc::now())
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap() = now;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+}
@@ -0,0 +1,531 @@
+use anyhow::{bail, Context};
+use crate::telemetry::LogFormat;
+use ipnet::IpNet;
+use serde::Deserialize;
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+const DEFAULT_CONFIG_FILE: &str = "patchouli.toml";
+
+/// 起動時に一度だけ読み込む設定
+///
+/// `patchouli.toml`（`PATCHOULI_CONFIG`でパスを指定可能、なければ省略可）を読み込み、
+/// 項目名を大文字にした環境変数（例: `database_url` → `DATABASE_URL`）で上書きする。
+/// OpenTelemetryの`OTEL_*`は標準の環境変数のため対象外。
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub google_client_id: String,
+    pub google_client_secret: String,
+    pub redirect_url: String,
+    pub frontend_url: String,
+    pub discord_bot_url: String,
+    pub database_url: String,
+    /// このメールアドレスのユーザーをrootユーザーにする（未設定なら最初に登録したユーザー）
+    pub root_email: Option<String>,
+    pub google_jwks_url: String,
+    pub google_token_url: String,
+    pub google_userinfo_url: String,
+    pub google_jwks_min_ttl_secs: u64,
+    pub webhook_url: Option<String>,
+    pub sse_max_connections_per_user: usize,
+    pub sse_heartbeat_secs: u64,
+    pub api_legacy_aliases: bool,
+    pub api_legacy_sunset: String,
+    pub api_docs_enabled: bool,
+    pub metrics_enabled: bool,
+    pub metrics_token: Option<String>,
+    /// `Forwarded`・`X-Forwarded-For`を信頼するプロキシ（CIDRまたはIPアドレス。空ならヘッダーを使わない）
+    pub trusted_proxies: Vec<TrustedProxy>,
+    /// APIキーで認証するリクエストヘッダー（`Authorization`がない場合に参照する）
+    pub api_key_header: String,
+    pub request_timeout_secs: u64,
+    pub request_body_limit_bytes: usize,
+    pub idempotency_key_ttl_secs: u64,
+    /// ユーザーが1日（UTC）に作成できる招待コードの数（0は無制限）
+    pub invite_daily_limit: u32,
+    /// ユーザーが同時に持てる未使用の有効な招待コードの数（0は無制限）
+    pub invite_total_limit: u32,
+    pub admin_stats_ttl_secs: u64,
+    pub admin_overview_ttl_secs: u64,
+    /// `/v1/system/status`の集計結果を再利用する時間（認証不要のため短時間でもキャッシュする）
+    pub system_status_ttl_secs: u64,
+    /// CSVエクスポートで招待コードを先頭8文字に伏せる
+    pub export_mask_codes: bool,
+    /// 負荷試験用の`POST /v1/dev/seed`を公開する（`dev-tools`フィーチャーでビルドした場合のみ有効）
+    pub dev_seed_enabled: bool,
+    /// 認証時に取得したユーザーを再利用する時間（0でキャッシュしない）
+    pub user_cache_ttl_secs: u64,
+    pub invite_cache_ttl_secs: u64,
+    pub bind_addr: String,
+    pub port: u16,
+    pub listen: Option<String>,
+    pub listen_socket_mode: String,
+    pub tls_cert_path: Option<PathBuf>,
+    pub tls_key_path: Option<PathBuf>,
+    pub http_redirect: bool,
+    pub http_port: u16,
+    /// gRPCサーバーのポート（未設定ならgRPCは無効。`bind_addr`で待ち受ける）
+    pub grpc_port: Option<u16>,
+    pub log_format: String,
+    pub sentry_dsn: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            google_client_id: String::new(),
+            google_client_secret: String::new(),
+            redirect_url: "http://localhost:8080/callback".to_string(),
+            frontend_url: "http://localhost:3000".to_string(),
+            discord_bot_url: "http://localhost:3001".to_string(),
+            database_url: "sqlite:./patchouli.db".to_string(),
+            root_email: None,
+            google_jwks_url: "https://www.googleapis.com/oauth2/v3/certs".to_string(),
+            google_token_url: "https://oauth2.googleapis.com/token".to_string(),
+            google_userinfo_url: "https://www.googleapis.com/oauth2/v2/userinfo".to_string(),
+            google_jwks_min_ttl_secs: 60,
+            webhook_url: None,
+            sse_max_connections_per_user: 5,
+            sse_heartbeat_secs: 15,
+            api_legacy_aliases: true,
+            api_legacy_sunset: "Wed, 31 Mar 2027 00:00:00 GMT".to_string(),
+            api_docs_enabled: true,
+            metrics_enabled: false,
+            metrics_token: None,
+            trusted_proxies: Vec::new(),
+            api_key_header: "X-Api-Key".to_string(),
+            request_timeout_secs: 30,
+            request_body_limit_bytes: 1024 * 1024,
+            idempotency_key_ttl_secs: 24 * 60 * 60,
+            invite_daily_limit: 10,
+            invite_total_limit: 50,
+            admin_stats_ttl_secs: 60,
+            admin_overview_ttl_secs: 30,
+            system_status_ttl_secs: 30,
+            export_mask_codes: false,
+            dev_seed_enabled: false,
+            user_cache_ttl_secs: 30,
+            invite_cache_ttl_secs: 30,
+            bind_addr: "0.0.0.0".to_string(),
+            port: 8080,
+            listen: None,
+            listen_socket_mode: "660".to_string(),
+            tls_cert_path: None,
+            tls_key_path: None,
+            http_redirect: false,
+            http_port: 80,
+            grpc_port: None,
+            log_format: "text".to_string(),
+            sentry_dsn: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> anyhow::Result<Self> {
+        let mut config = match std::env::var("PATCHOULI_CONFIG") {
+            Ok(path) => Self::from_file(Path::new(&path))?,
+            Err(_) if Path::new(DEFAULT_CONFIG_FILE).exists() => Self::from_file(Path::new(DEFAULT_CONFIG_FILE))?,
+            Err(_) => Config::default(),
+        };
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let content =
+            std::fs::read_to_string(path).with_context(|| format!("Failed to read config file {}", path.display()))?;
+        toml::from_str(&content).with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    fn apply_env(&mut self) -> anyhow::Result<()> {
+        env_string("GOOGLE_CLIENT_ID", &mut self.google_client_id);
+        env_string("GOOGLE_CLIENT_SECRET", &mut self.google_client_secret);
+        env_string("REDIRECT_URL", &mut self.redirect_url);
+        env_string("FRONTEND_URL", &mut self.frontend_url);
+        env_string("DISCORD_BOT_URL", &mut self.discord_bot_url);
+        env_string("DATABASE_URL", &mut self.database_url);
+        env_optional("ROOT_EMAIL", &mut self.root_email)?;
+        env_string("GOOGLE_JWKS_URL", &mut self.google_jwks_url);
+        env_string("GOOGLE_TOKEN_URL", &mut self.google_token_url);
+        env_string("GOOGLE_USERINFO_URL", &mut self.google_userinfo_url);
+        env_parse("GOOGLE_JWKS_MIN_TTL_SECS", &mut self.google_jwks_min_ttl_secs)?;
+        env_optional("WEBHOOK_URL", &mut self.webhook_url)?;
+        env_parse("SSE_MAX_CONNECTIONS_PER_USER", &mut self.sse_max_connections_per_user)?;
+        env_parse("SSE_HEARTBEAT_SECS", &mut self.sse_heartbeat_secs)?;
+        env_bool("API_LEGACY_ALIASES", &mut self.api_legacy_aliases)?;
+        env_string("API_LEGACY_SUNSET", &mut self.api_legacy_sunset);
+        env_bool("API_DOCS_ENABLED", &mut self.api_docs_enabled)?;
+        env_bool("METRICS_ENABLED", &mut self.metrics_enabled)?;
+        env_optional("METRICS_TOKEN", &mut self.metrics_token)?;
+        env_parse_list("TRUSTED_PROXIES", &mut self.trusted_proxies)?;
+        env_string("API_KEY_HEADER", &mut self.api_key_header);
+        env_parse("REQUEST_TIMEOUT_SECS", &mut self.request_timeout_secs)?;
+        env_parse("REQUEST_BODY_LIMIT_BYTES", &mut self.request_body_limit_bytes)?;
+        env_parse("IDEMPOTENCY_KEY_TTL_SECS", &mut self.idempotency_key_ttl_secs)?;
+        env_parse("INVITE_DAILY_LIMIT", &mut self.invite_daily_limit)?;
+        env_parse("INVITE_TOTAL_LIMIT", &mut self.invite_total_limit)?;
+        env_parse("ADMIN_STATS_TTL_SECS", &mut self.admin_stats_ttl_secs)?;
+        env_parse("ADMIN_OVERVIEW_TTL_SECS", &mut self.admin_overview_ttl_secs)?;
+        env_parse("SYSTEM_STATUS_TTL_SECS", &mut self.system_status_ttl_secs)?;
+        env_bool("EXPORT_MASK_CODES", &mut self.export_mask_codes)?;
+        env_bool("DEV_SEED_ENABLED", &mut self.dev_seed_enabled)?;
+        env_parse("USER_CACHE_TTL_SECS", &mut self.user_cache_ttl_secs)?;
+        env_parse("INVITE_CACHE_TTL_SECS", &mut self.invite_cache_ttl_secs)?;
+        env_string("BIND_ADDR", &mut self.bind_addr);
+        env_parse("PORT", &mut self.port)?;
+        env_optional("LISTEN", &mut self.listen)?;
+        env_string("LISTEN_SOCKET_MODE", &mut self.listen_socket_mode);
+        env_optional("TLS_CERT_PATH", &mut self.tls_cert_path)?;
+        env_optional("TLS_KEY_PATH", &mut self.tls_key_path)?;
+        env_bool("HTTP_REDIRECT", &mut self.http_redirect)?;
+        env_parse("HTTP_PORT", &mut self.http_port)?;
+        env_optional("GRPC_PORT", &mut self.grpc_port)?;
+        env_string("LOG_FORMAT", &mut self.log_format);
+        env_optional("SENTRY_DSN", &mut self.sentry_dsn)?;
+        Ok(())
+    }
+
+    /// サーバーの起動に必要なGoogle OAuthの認証情報を確認する（CLIの管理コマンドでは不要）
+    pub fn require_oauth_credentials(&self) -> anyhow::Result<()> {
+        if self.google_client_id.is_empty() {
+            bail!("GOOGLE_CLIENT_ID must be set");
+        }
+        if self.google_client_secret.is_empty() {
+            bail!("GOOGLE_CLIENT_SECRET must be set");
+        }
+        // .env.exampleの値のままリリースビルドを起動しない
+        if !cfg!(debug_assertions)
+            && (self.google_client_id.starts_with("your_") || self.google_client_secret.starts_with("your_"))
+        {
+            bail!("GOOGLE_CLIENT_ID/GOOGLE_CLIENT_SECRET still contain the placeholder values from .env.example");
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        let urls = [
+            ("REDIRECT_URL", Some(&self.redirect_url)),
+            ("FRONTEND_URL", Some(&self.frontend_url)),
+            ("DISCORD_BOT_URL", Some(&self.discord_bot_url)),
+            ("GOOGLE_JWKS_URL", Some(&self.google_jwks_url)),
+            ("GOOGLE_TOKEN_URL", Some(&self.google_token_url)),
+            ("GOOGLE_USERINFO_URL", Some(&self.google_userinfo_url)),
+            ("WEBHOOK_URL", self.webhook_url.as_ref()),
+        ];
+        for (name, url) in urls {
+            if let Some(url) = url {
+                reqwest::Url::parse(url).with_context(|| format!("{} is not a valid URL (got {:?})", name, url))?;
+            }
+        }
+
+        if let Some(email) = &self.root_email
+            && !email.contains('@')
+        {
+            bail!("ROOT_EMAIL must be an email address (got {:?})", email);
+        }
+        if let Some(dsn) = &self.sentry_dsn {
+            dsn.parse::<sentry::types::Dsn>().context("SENTRY_DSN is not a valid Sentry DSN")?;
+        }
+        if self.metrics_token.as_ref().is_some_and(|token| token.is_empty()) {
+            bail!("METRICS_TOKEN must not be empty");
+        }
+        if axum::http::HeaderName::from_bytes(self.api_key_header.as_bytes()).is_err() {
+            bail!("API_KEY_HEADER must be a valid header name (got {:?})", self.api_key_header);
+        }
+        if self.request_timeout_secs == 0 {
+            bail!("REQUEST_TIMEOUT_SECS must be at least 1");
+        }
+        if self.request_body_limit_bytes == 0 {
+            bail!("REQUEST_BODY_LIMIT_BYTES must be at least 1");
+        }
+        if self.idempotency_key_ttl_secs == 0 {
+            bail!("IDEMPOTENCY_KEY_TTL_SECS must be at least 1");
+        }
+        if self.sse_heartbeat_secs == 0 {
+            bail!("SSE_HEARTBEAT_SECS must be at least 1");
+        }
+        if self.tls_cert_path.is_some() != self.tls_key_path.is_some() {
+            bail!("TLS_CERT_PATH and TLS_KEY_PATH must be set together");
+        }
+        if self.http_redirect && self.tls_cert_path.is_none() {
+            bail!("HTTP_REDIRECT requires TLS_CERT_PATH and TLS_KEY_PATH");
+        }
+        if self.unix_socket_path()?.is_some() && self.tls_cert_path.is_some() {
+            bail!("TLS_CERT_PATH/TLS_KEY_PATH cannot be combined with LISTEN=unix:");
+        }
+        self.listen_addr()?;
+        self.listen_socket_mode()?;
+        self.log_format()?;
+        Ok(())
+    }
+
+    /// 待ち受けアドレス（`BIND_ADDR`はIPv4・IPv6アドレスまたは`localhost`、`PORT=0`でOSが空きポートを割り当てる）
+    pub fn listen_addr(&self) -> anyhow::Result<SocketAddr> {
+        let ip = if self.bind_addr == "localhost" {
+            IpAddr::V4(Ipv4Addr::LOCALHOST)
+        } else {
+            self.bind_addr
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .parse()
+                .with_context(|| {
+                    format!("BIND_ADDR must be an IPv4/IPv6 address or \"localhost\" (got {:?})", self.bind_addr)
+                })?
+        };
+        Ok(SocketAddr::new(ip, self.port))
+    }
+
+    /// gRPCの待ち受けアドレス（`GRPC_PORT`が未設定なら`None`）
+    pub fn grpc_addr(&self) -> anyhow::Result<Option<SocketAddr>> {
+        let Some(port) = self.grpc_port else {
+            return Ok(None);
+        };
+        Ok(Some(SocketAddr::new(self.listen_addr()?.ip(), port)))
+    }
+
+    /// `LISTEN=unix:<パス>`が指定されていればそのパスを返す
+    pub fn unix_socket_path(&self) -> anyhow::Result<Option<PathBuf>> {
+        match &self.listen {
+            Some(listen) => match listen.strip_prefix("unix:") {
+                Some(path) if !path.is_empty() => Ok(Some(PathBuf::from(path))),
+                _ => bail!("LISTEN must be in the form unix:<path> (got {:?})", listen),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// ソケットファイルのパーミッション（8進数）
+    pub fn listen_socket_mode(&self) -> anyhow::Result<u32> {
+        u32::from_str_radix(&self.listen_socket_mode, 8)
+            .ok()
+            .filter(|&mode| mode <= 0o777)
+            .with_context(|| {
+                format!(
+                    "LISTEN_SOCKET_MODE must be an octal permission such as 660 (got {:?})",
+                    self.listen_socket_mode
+                )
+            })
+    }
+
+    /// ログの出力形式（`text`または`json`）
+    pub fn log_format(&self) -> anyhow::Result<LogFormat> {
+        match self.log_format.as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => bail!("LOG_FORMAT must be \"text\" or \"json\" (got {:?})", other),
+        }
+    }
+
+    /// `ROOT_EMAIL`のユーザーか（大文字・小文字は区別しない）
+    pub fn is_root_email(&self, email: &str) -> bool {
+        self.root_email.as_ref().is_some_and(|root_email| root_email.eq_ignore_ascii_case(email))
+    }
+
+    /// 招待コードの登録用URL
+    pub fn invite_url(&self, code: &str) -> String {
+        format!("{}/login?register=true&invite={}", self.frontend_url, code)
+    }
+
+    /// Web認証の完了後にセッションを渡すフロントエンドのURL
+    pub fn frontend_callback_url(&self, session_id: &str, email: &str) -> String {
+        format!(
+            "{}/callback?session_id={}&user_email={}",
+            self.frontend_url,
+            urlencoding::encode(session_id),
+            urlencoding::encode(email)
+        )
+    }
+
+    pub fn google_jwks_min_ttl(&self) -> Duration {
+        Duration::from_secs(self.google_jwks_min_ttl_secs)
+    }
+
+    pub fn sse_heartbeat(&self) -> Duration {
+        Duration::from_secs(self.sse_heartbeat_secs)
+    }
+
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs)
+    }
+
+    pub fn idempotency_key_ttl(&self) -> Duration {
+        Duration::from_secs(self.idempotency_key_ttl_secs)
+    }
+
+    pub fn admin_stats_ttl(&self) -> Duration {
+        Duration::from_secs(self.admin_stats_ttl_secs)
+    }
+
+    pub fn admin_overview_ttl(&self) -> Duration {
+        Duration::from_secs(self.admin_overview_ttl_secs)
+    }
+
+    pub fn system_status_ttl(&self) -> Duration {
+        Duration::from_secs(self.system_status_ttl_secs)
+    }
+
+    pub fn user_cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.user_cache_ttl_secs)
+    }
+
+    pub fn invite_cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.invite_cache_ttl_secs)
+    }
+}
+
+/// 起動時のログに出すため、秘密情報を伏せて表示する
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("google_client_id", &self.google_client_id)
+            .field("google_client_secret", &"[redacted]")
+            .field("redirect_url", &self.redirect_url)
+            .field("frontend_url", &self.frontend_url)
+            .field("discord_bot_url", &self.discord_bot_url)
+            .field("database_url", &redact_url_password(&self.database_url))
+            .field("root_email", &self.root_email)
+            .field("google_jwks_url", &self.google_jwks_url)
+            .field("google_token_url", &self.google_token_url)
+            .field("google_userinfo_url", &self.google_userinfo_url)
+            .field("google_jwks_min_ttl_secs", &self.google_jwks_min_ttl_secs)
+            // Webhook URLはパスにトークンを含むことが多い
+            .field("webhook_url", &self.webhook_url.as_ref().map(|_| "[redacted]"))
+            .field("sse_max_connections_per_user", &self.sse_max_connections_per_user)
+            .field("sse_heartbeat_secs", &self.sse_heartbeat_secs)
+            .field("api_legacy_aliases", &self.api_legacy_aliases)
+            .field("api_legacy_sunset", &self.api_legacy_sunset)
+            .field("api_docs_enabled", &self.api_docs_enabled)
+            .field("metrics_enabled", &self.metrics_enabled)
+            .field("metrics_token", &self.metrics_token.as_ref().map(|_| "[redacted]"))
+            .field("trusted_proxies", &self.trusted_proxies)
+            .field("api_key_header", &self.api_key_header)
+            .field("request_timeout_secs", &self.request_timeout_secs)
+            .field("request_body_limit_bytes", &self.request_body_limit_bytes)
+            .field("idempotency_key_ttl_secs", &self.idempotency_key_ttl_secs)
+            .field("invite_daily_limit", &self.invite_daily_limit)
+            .field("invite_total_limit", &self.invite_total_limit)
+            .field("admin_stats_ttl_secs", &self.admin_stats_ttl_secs)
+            .field("admin_overview_ttl_secs", &self.admin_overview_ttl_secs)
+            .field("system_status_ttl_secs", &self.system_status_ttl_secs)
+            .field("export_mask_codes", &self.export_mask_codes)
+            .field("dev_seed_enabled", &self.dev_seed_enabled)
+            .field("user_cache_ttl_secs", &self.user_cache_ttl_secs)
+            .field("invite_cache_ttl_secs", &self.invite_cache_ttl_secs)
+            .field("bind_addr", &self.bind_addr)
+            .field("port", &self.port)
+            .field("listen", &self.listen)
+            .field("listen_socket_mode", &self.listen_socket_mode)
+            .field("tls_cert_path", &self.tls_cert_path)
+            .field("tls_key_path", &self.tls_key_path)
+            .field("http_redirect", &self.http_redirect)
+            .field("http_port", &self.http_port)
+            .field("grpc_port", &self.grpc_port)
+            .field("log_format", &self.log_format)
+            .field("sentry_dsn", &self.sentry_dsn.as_ref().map(|_| "[redacted]"))
+            .finish()
+    }
+}
+
+/// `TRUSTED_PROXIES`の1件（CIDRまたはIPアドレス。IPアドレスだけの場合は/32・/128）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct TrustedProxy(pub IpNet);
+
+impl FromStr for TrustedProxy {
+    type Err = ipnet::AddrParseError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value
+            .parse::<IpNet>()
+            .or_else(|e| value.parse::<IpAddr>().map(IpNet::from).map_err(|_| e))
+            .map(TrustedProxy)
+    }
+}
+
+impl TryFrom<String> for TrustedProxy {
+    type Error = ipnet::AddrParseError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+fn redact_url_password(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(mut parsed) if parsed.password().is_some() => {
+            let _ = parsed.set_password(Some("[redacted]"));
+            parsed.to_string()
+        }
+        _ => url.to_string(),
+    }
+}
+
+fn env_string(name: &str, target: &mut String) {
+    if let Ok(value) = std::env::var(name) {
+        *target = value;
+    }
+}
+
+/// カンマ区切りの値（空の要素は無視する）
+fn env_parse_list<T>(name: &str, target: &mut Vec<T>) -> anyhow::Result<()>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    if let Ok(value) = std::env::var(name) {
+        *target = value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| item.parse().with_context(|| format!("{} has an invalid value (got {:?})", name, item)))
+            .collect::<anyhow::Result<_>>()?;
+    }
+    Ok(())
+}
+
+fn env_parse<T>(name: &str, target: &mut T) -> anyhow::Result<()>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    if let Ok(value) = std::env::var(name) {
+        *target = value
+            .parse()
+            .with_context(|| format!("{} has an invalid value (got {:?})", name, value))?;
+    }
+    Ok(())
+}
+
+fn env_optional<T>(name: &str, target: &mut Option<T>) -> anyhow::Result<()>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    if let Ok(value) = std::env::var(name) {
+        *target = Some(
+            value
+                .parse()
+                .with_context(|| format!("{} has an invalid value (got {:?})", name, value))?,
+        );
+    }
+    Ok(())
+}
+
+fn env_bool(name: &str, target: &mut bool) -> anyhow::Result<()> {
+    if let Ok(value) = std::env::var(name) {
+        *target = match value.as_str() {
+            "true" | "1" => true,
+            "false" | "0" => false,
+            _ => bail!("{} must be true/false or 1/0 (got {:?})", name, value),
+        };
+    }
+    Ok(())
+}
@@ -0,0 +1,627 @@
+use crate::{clock::SharedClock, ids::SharedIdGenerator};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::pool::PoolOptions;
+use std::{
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use utoipa::ToSchema;
+
+#[cfg(feature = "postgres")]
+mod postgres;
+mod sqlite;
+
+#[cfg(feature = "postgres")]
+pub use postgres::PostgresDatabase;
+pub use sqlite::SqliteDatabase;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RegisteredUser {
+    pub id: i64,
+    pub google_id: String,
+    pub email: String,
+    pub name: String,
+    pub registered_at: DateTime<Utc>,
+    pub last_login: Option<DateTime<Utc>>,
+    pub is_root: bool,
+    pub can_invite: bool,
+    pub invited_by: Option<i64>,
+    pub is_active: bool,
+    /// 拡張用の任意の属性（JSONオブジェクト）
+    #[schema(value_type = Object)]
+    pub metadata: serde_json::Value,
+    /// Googleがメールアドレスを確認済みと返した（Googleログイン完了時に設定）
+    pub email_verified: bool,
+}
+
+/// コネクションプールの状態（メトリクス用）
+#[derive(Debug, Clone, Copy)]
+pub struct PoolStatus {
+    /// 開いている接続数（使用中とアイドルの合計）
+    pub size: u32,
+    pub idle: usize,
+    /// 起動してからプールが接続を渡した回数（アイドルの接続の再利用と新しい接続の合計）
+    pub acquired: u64,
+}
+
+/// 接続を渡した回数を`acquired`に数えるプールの設定（`PoolStatus::acquired`）
+fn counting_pool_options<DB: sqlx::Database>(acquired: Arc<AtomicU64>) -> PoolOptions<DB> {
+    let on_connect = acquired.clone();
+    PoolOptions::new()
+        .after_connect(move |_, _| {
+            on_connect.fetch_add(1, Ordering::Relaxed);
+            Box::pin(async { Ok(()) })
+        })
+        .before_acquire(move |_, _| {
+            acquired.fetch_add(1, Ordering::Relaxed);
+            Box::pin(async { Ok(true) })
+        })
+}
+
+/// 保存した処理結果のレスポンス（Idempotency-Keyの再送時にそのまま返す）
+#[derive(Debug, Clone)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub body: Vec<u8>,
+}
+
+/// Idempotency-Keyを登録しようとした結果
+#[derive(Debug, Clone)]
+pub enum IdempotencyState {
+    /// 新しいキーとして登録した（呼び出し側が処理し、`complete_idempotency_key`で結果を保存する）
+    Started,
+    /// 同じキーの最初のリクエストがまだ処理中
+    InProgress,
+    /// 同じキーが別の内容のリクエストに使われている
+    Mismatch,
+    /// 同じ内容のリクエストが処理済み
+    Completed(StoredResponse),
+}
+
+/// BAN処理の結果
+#[derive(Debug, Clone)]
+pub struct BanOutcome {
+    pub invites_deactivated: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct InviteCode {
+    pub id: i64,
+    pub code: String,
+    pub created_by: i64,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub used_by: Option<i64>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub is_active: bool,
+    /// 作成者が付けるメモ（誰に渡したか等）
+    pub note: Option<String>,
+    /// 拡張用の任意の属性（JSONオブジェクト）
+    #[schema(value_type = Object)]
+    pub metadata: serde_json::Value,
+}
+
+/// プログラムからのアクセス用のAPIキー（キーそのものは作成時に一度だけ返し、データベースにはハッシュのみ保存する）
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ApiKey {
+    pub id: i64,
+    /// 所有者（APIキーでのリクエストはこのユーザーとして扱う）
+    pub created_by: i64,
+    pub name: String,
+    /// `false`なら無効化済み
+    pub is_active: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// scopesカラムのJSON配列を読み取る（壊れた値は空として扱う）
+fn parse_scopes(raw: &str) -> Vec<String> {
+    serde_json::from_str(raw).unwrap_or_else(|_| {
+        tracing::warn!("Ignoring malformed scopes column value: {:?}", raw);
+        Vec::new()
+    })
+}
+
+/// metadataカラムのJSON文字列を読み取る（壊れた値やオブジェクト以外は空のオブジェクトとして扱う）
+fn parse_metadata(raw: &str) -> serde_json::Value {
+    match serde_json::from_str(raw) {
+        Ok(value @ serde_json::Value::Object(_)) => value,
+        _ => {
+            tracing::warn!("Ignoring malformed metadata column value: {:?}", raw);
+            serde_json::Value::Object(serde_json::Map::new())
+        }
+    }
+}
+
+/// 招待者による絞り込み
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvitedByFilter {
+    /// 招待者がいないユーザー（rootユーザー）
+    NoInviter,
+    User(i64),
+}
+
+/// ユーザー一覧の絞り込み条件（Noneの項目は条件に含めない）
+#[derive(Debug, Clone, Default)]
+pub struct UserFilterParams {
+    pub is_root: Option<bool>,
+    pub can_invite: Option<bool>,
+    pub invited_by: Option<InvitedByFilter>,
+    pub registered_after: Option<DateTime<Utc>>,
+    pub registered_before: Option<DateTime<Utc>>,
+}
+
+/// ユーザーが作成した招待コードの集計
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct InviteSummary {
+    pub total: i64,
+    pub outstanding: i64,
+    pub used: i64,
+}
+
+/// 招待コードが使用された記録（ダッシュボードの最近のアクティビティ）
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct InviteActivity {
+    pub invite_id: i64,
+    pub code: String,
+    pub used_by_email: String,
+    pub used_by_name: String,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+/// CSVエクスポート用の招待コード（作成者・使用者はメールアドレス。削除済みのユーザーは`None`）
+#[derive(Debug, Clone, Serialize)]
+pub struct InviteExportRow {
+    pub id: i64,
+    pub code: String,
+    pub created_by_email: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub used_by_email: Option<String>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub is_active: bool,
+    pub note: Option<String>,
+}
+
+/// CSVエクスポート用の監査ログ（実行者・対象はメールアドレス。削除済みのユーザーは`None`）
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditExportRow {
+    pub id: i64,
+    pub actor_email: Option<String>,
+    pub action: String,
+    pub target_email: Option<String>,
+    pub metadata: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+/// 他のシステムから移行する監査ログの1件（`created_at`は元の記録日時）
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct AuditEntry {
+    pub actor_user_id: Option<i64>,
+    pub action: String,
+    pub target_user_id: Option<i64>,
+    #[serde(default = "empty_metadata")]
+    #[schema(value_type = Object)]
+    pub metadata: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+fn empty_metadata() -> serde_json::Value {
+    serde_json::Value::Object(serde_json::Map::new())
+}
+
+/// 監査ログの取り込み結果（同じ実行者・操作・対象・日時の記録が既にあるものは`skipped`）
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AuditImportCounts {
+    pub imported: u64,
+    pub skipped: u64,
+}
+
+/// 負荷試験用に登録するユーザー（`dev-tools`フィーチャーのみ）
+#[cfg(feature = "dev-tools")]
+#[derive(Debug, Clone)]
+pub struct SeedUser {
+    pub google_id: String,
+    pub email: String,
+    pub name: String,
+    pub registered_at: DateTime<Utc>,
+    pub last_login: Option<DateTime<Utc>>,
+    pub can_invite: bool,
+    pub invited_by: Option<i64>,
+    pub is_active: bool,
+}
+
+/// 負荷試験用に作成する招待コード（`dev-tools`フィーチャーのみ）
+#[cfg(feature = "dev-tools")]
+#[derive(Debug, Clone)]
+pub struct SeedInvite {
+    pub code: String,
+    pub created_by: i64,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub used_by: Option<i64>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub is_active: bool,
+    pub note: Option<String>,
+}
+
+/// 負荷試験用のデータを1文で挿入する行数（SQLiteのバインド変数の上限を超えないようにする）
+#[cfg(feature = "dev-tools")]
+pub const SEED_ROWS_PER_STATEMENT: usize = 500;
+
+/// システム全体の利用状況（管理者向け統計）
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SystemStats {
+    pub total_users: i64,
+    /// 直近30日以内にログインしたユーザー数
+    pub active_users: i64,
+    pub total_invites: i64,
+    /// 未使用・有効・期限内の招待コード数
+    pub pending_invites: i64,
+    pub used_invites: i64,
+    /// 未使用のまま期限切れになった招待コード数
+    pub expired_invites: i64,
+    /// 直近7日以内に登録したユーザー数
+    pub new_users_this_week: i64,
+}
+
+/// 招待コードの状態別の件数（`total`から他の3つを引いた残りは期限内に無効化されたもの）
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct InviteStats {
+    pub total: i64,
+    /// 未使用・有効・期限内
+    pub active: i64,
+    pub used: i64,
+    /// 未使用のまま期限切れになったもの
+    pub expired: i64,
+}
+
+/// ユーザー数と直近のログイン状況（管理者向け概要）
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct UserActivity {
+    pub total_users: i64,
+    /// 直近7日以内にログインしたユーザー数
+    pub active_7d: i64,
+    /// 直近30日以内にログインしたユーザー数
+    pub active_30d: i64,
+}
+
+/// 週ごとの登録・招待件数（週の開始は月曜日、UTC）
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct WeeklyStats {
+    /// 週の開始日（YYYY-MM-DD）
+    pub week_start: String,
+    pub new_users: i64,
+    pub new_invites: i64,
+    pub invite_uses: i64,
+}
+
+/// 最終ログイン（未ログインなら登録）からこの日数が経過したユーザーを休眠ユーザーとして扱う
+pub const INACTIVE_USER_DAYS: i64 = 90;
+/// 期限なしで未使用のままこの日数が経過した招待コードは見直しを促す
+pub const STALE_INVITE_DAYS: i64 = 30;
+
+/// `now`の日付（UTC）の0時
+pub fn start_of_day(now: DateTime<Utc>) -> DateTime<Utc> {
+    now.date_naive()
+        .and_hms_opt(0, 0, 0)
+        .expect("midnight is a valid time")
+        .and_utc()
+}
+
+/// 管理者の対応が必要な作業の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PendingActionKind {
+    /// 未使用のまま期限切れになった招待コードを削除する（`DELETE /v1/invite/expired`）
+    CleanupExpiredInvites,
+    /// 利用停止中のユーザーが作成した有効な招待コードを無効化する
+    DeactivateBannedUserInvites,
+    /// 招待権限を失ったユーザーの有効な招待コードを引き継ぐ（`POST /v1/invite/:invite_id/transfer`）
+    ReassignOrphanedInvites,
+    /// 期限なしで長期間使われていない招待コードを見直す
+    ReviewStaleInvites,
+    /// 長期間ログインしていないユーザーを見直す
+    ReviewInactiveUsers,
+}
+
+/// 管理者の対応が必要な作業と対象の件数
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PendingAction {
+    pub action: PendingActionKind,
+    pub count: u64,
+}
+
+/// 適用済みのマイグレーション（sqlxの`_sqlx_migrations`の1行）
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MigrationRecord {
+    pub version: i64,
+    pub description: String,
+    pub installed_on: DateTime<Utc>,
+    /// `false`なら適用に失敗している（次の起動時にエラーになる）
+    pub success: bool,
+}
+
+/// 招待コード一覧の絞り込み条件（Noneの項目は条件に含めない）
+#[derive(Debug, Clone, Default)]
+pub struct InviteFilterParams {
+    /// 作成者で絞り込む（Noneの場合は全ユーザーの招待コード）
+    pub created_by: Option<i64>,
+    pub is_active: Option<bool>,
+    pub used: Option<bool>,
+    pub expired: Option<bool>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+}
+
+/// データベース操作の抽象化（SQLite以外のバックエンドを追加できるようにする）
+#[async_trait]
+pub trait DatabaseTrait: Send + Sync {
+    /// 招待なしで登録する（`root_email`を指定した場合はそのメールアドレスのユーザーのみ、
+    /// 指定しない場合は最初のユーザーがrootユーザーになる）
+    async fn register_user(
+        &self,
+        google_id: &str,
+        email: &str,
+        name: &str,
+        root_email: Option<&str>,
+    ) -> Result<RegisteredUser, sqlx::Error>;
+
+    async fn register_invited_user(
+        &self,
+        google_id: &str,
+        email: &str,
+        name: &str,
+        invited_by: i64,
+    ) -> Result<RegisteredUser, sqlx::Error>;
+
+    fn pool_status(&self) -> PoolStatus;
+
+    /// 接続できるか確認する（`SELECT 1`）
+    async fn ping(&self) -> Result<(), sqlx::Error>;
+
+    /// プールの接続を閉じる（終了時。以降の問い合わせはすべてエラーになる）
+    async fn close(&self);
+
+    /// Idempotency-Keyを処理中として登録する（期限切れのキーはここで削除する）
+    ///
+    /// キーは一意制約で守るため、同じキーの最初のリクエストが並行しても`Started`になるのは1つだけ。
+    async fn begin_idempotency_key(
+        &self,
+        key: &str,
+        request_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<IdempotencyState, sqlx::Error>;
+
+    /// 処理中のキーに処理結果を保存する
+    async fn complete_idempotency_key(&self, key: &str, response: &StoredResponse) -> Result<(), sqlx::Error>;
+
+    /// 処理中のキーを削除する（処理に失敗し、再送時にもう一度実行させる場合）
+    async fn release_idempotency_key(&self, key: &str) -> Result<(), sqlx::Error>;
+
+    /// 起動時のマイグレーションが適用済みか確認する（各テーブルで使用する全カラムを参照できるか）
+    async fn check_schema(&self) -> Result<(), sqlx::Error>;
+
+    /// 適用済みのマイグレーション（バージョンの昇順）
+    async fn get_applied_migrations(&self) -> Result<Vec<MigrationRecord>, sqlx::Error>;
+
+    /// 未適用のマイグレーションを適用し、今回適用したものを返す（開発用。リリースビルドには含めない）
+    #[cfg(all(feature = "dev", debug_assertions))]
+    async fn run_pending_migrations(&self) -> Result<Vec<MigrationRecord>, sqlx::Error>;
+
+    async fn is_user_registered(&self, email: &str) -> Result<bool, sqlx::Error>;
+
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<RegisteredUser>, sqlx::Error>;
+
+    async fn get_user_by_id(&self, user_id: i64) -> Result<Option<RegisteredUser>, sqlx::Error>;
+
+    async fn update_last_login(&self, email: &str) -> Result<(), sqlx::Error>;
+
+    /// Googleで確認済みのメールアドレスとして記録する
+    async fn mark_email_verified(&self, email: &str) -> Result<(), sqlx::Error>;
+
+    /// ログインを監査ログに`login`として記録する（`method`はログインの経路。未登録のユーザーは記録しない）
+    async fn record_login(&self, email: &str, method: &str, client_ip: Option<IpAddr>) -> Result<(), sqlx::Error>;
+
+    /// metadataを置き換える（ユーザーが存在しない場合は`None`）
+    async fn update_user_metadata(
+        &self,
+        user_id: i64,
+        metadata: &serde_json::Value,
+    ) -> Result<Option<RegisteredUser>, sqlx::Error>;
+
+    async fn get_all_registered_users(
+        &self,
+        filter: &UserFilterParams,
+    ) -> Result<Vec<RegisteredUser>, sqlx::Error>;
+
+    /// 書き出し用に`limit`件ずつ取得する（IDの降順。`before_id`を指定した場合はそれより小さいIDのみ）
+    async fn get_registered_users_page(
+        &self,
+        filter: &UserFilterParams,
+        before_id: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<RegisteredUser>, sqlx::Error>;
+
+    async fn delete_user(&self, user_id: i64) -> Result<bool, sqlx::Error>;
+
+    /// ユーザーを無効化し、未使用の招待コードも無効化する（監査ログと同一トランザクション）
+    async fn ban_user(
+        &self,
+        actor_user_id: i64,
+        user_id: i64,
+        sessions_revoked: usize,
+    ) -> Result<Option<BanOutcome>, sqlx::Error>;
+
+    /// ユーザーの利用停止を解除する（無効化した招待コードは元に戻さない）
+    async fn unban_user(&self, actor_user_id: i64, user_id: i64) -> Result<bool, sqlx::Error>;
+
+    /// 登録済みのユーザーをrootユーザーにして監査ログに記録する（`ROOT_EMAIL`を後から設定した場合。
+    /// 該当するユーザーがいない・既にrootユーザーの場合は`None`）
+    async fn grant_root(&self, email: &str) -> Result<Option<RegisteredUser>, sqlx::Error>;
+
+    /// rootユーザーが他のユーザーをrootユーザーにして監査ログに記録する（利用停止中のユーザーは対象外。
+    /// 該当するユーザーがいない・既にrootユーザーの場合は`None`）
+    async fn promote_to_root(&self, actor_user_id: i64, user_id: i64) -> Result<Option<RegisteredUser>, sqlx::Error>;
+
+    /// 招待権限を変更して監査ログに記録する（ユーザーが存在しない場合は`false`）
+    async fn set_can_invite(&self, actor_user_id: i64, user_id: i64, can_invite: bool) -> Result<bool, sqlx::Error>;
+
+    async fn create_invite_code(&self, created_by: i64) -> Result<InviteCode, sqlx::Error>;
+
+    async fn validate_invite_code(&self, code: &str) -> Result<Option<InviteCode>, sqlx::Error>;
+
+    async fn get_invite_code_by_id(&self, invite_id: i64) -> Result<Option<InviteCode>, sqlx::Error>;
+
+    async fn use_invite_code(&self, code: &str, used_by: i64) -> Result<(), sqlx::Error>;
+
+    /// メモと有効期限を更新する（`None`の項目は変更しない）
+    async fn update_invite(
+        &self,
+        invite_id: i64,
+        note: Option<&str>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<InviteCode, sqlx::Error>;
+
+    /// 未使用の招待コードを無効化する（存在しない・使用済みの場合は`None`）
+    async fn deactivate_invite(&self, invite_id: i64) -> Result<Option<InviteCode>, sqlx::Error>;
+
+    /// 招待コードの作成者を変更して監査ログに記録する（招待コードが存在しない場合は`false`）
+    async fn transfer_invite(
+        &self,
+        actor_user_id: i64,
+        invite_id: i64,
+        new_owner_id: i64,
+    ) -> Result<bool, sqlx::Error>;
+
+    async fn get_invite_codes(&self, filter: &InviteFilterParams) -> Result<Vec<InviteCode>, sqlx::Error>;
+
+    /// 書き出し用に`limit`件ずつ取得する（IDの降順。`before_id`を指定した場合はそれより小さいIDのみ）
+    async fn get_invite_codes_page(
+        &self,
+        filter: &InviteFilterParams,
+        before_id: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<InviteCode>, sqlx::Error>;
+
+    /// ユーザーが今日（UTCの0時以降）作成した招待コードの件数（削除済みは含まない）
+    async fn count_invites_created_today(&self, user_id: i64) -> Result<i64, sqlx::Error>;
+
+    /// ユーザーが作成した未使用の有効な招待コードの件数（期限切れも含む）
+    async fn count_active_invites_by_user(&self, user_id: i64) -> Result<i64, sqlx::Error>;
+
+    /// 未使用のまま期限切れになった招待コードの件数
+    async fn count_expired_invites(&self) -> Result<u64, sqlx::Error>;
+
+    /// 未使用のまま期限切れになった招待コードを削除し、削除した件数を返す
+    async fn delete_expired_invites(&self) -> Result<u64, sqlx::Error>;
+
+    async fn count_registered_users(&self) -> Result<i64, sqlx::Error>;
+
+    async fn get_invite_summary_by_user(&self, user_id: i64) -> Result<InviteSummary, sqlx::Error>;
+
+    /// 管理者向けの統計を1回のクエリで集計する
+    async fn get_system_stats(&self) -> Result<SystemStats, sqlx::Error>;
+
+    /// 招待コードの状態別の件数を1回のクエリで集計する
+    async fn get_invite_stats(&self) -> Result<InviteStats, sqlx::Error>;
+
+    /// ユーザー数と直近7日・30日以内にログインしたユーザー数を1回のクエリで集計する
+    async fn get_user_activity(&self) -> Result<UserActivity, sqlx::Error>;
+
+    /// 今週を含む直近`weeks`週分の件数を古い順に返す（件数0の週も含む）
+    /// 管理者の対応が必要な作業ごとの対象件数（0件の作業も含む）
+    async fn get_pending_actions(&self) -> Result<Vec<PendingAction>, sqlx::Error>;
+
+    async fn get_weekly_stats(&self, weeks: u32) -> Result<Vec<WeeklyStats>, sqlx::Error>;
+
+    async fn count_invitees(&self, user_id: i64) -> Result<i64, sqlx::Error>;
+
+    async fn get_recent_invite_activity(
+        &self,
+        user_id: i64,
+        limit: i64,
+    ) -> Result<Vec<InviteActivity>, sqlx::Error>;
+
+    /// CSVエクスポート用に全招待コードを`limit`件ずつ取得する（IDの降順。`before_id`は`get_invite_codes_page`と同じ）
+    async fn get_invite_export_page(
+        &self,
+        before_id: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<InviteExportRow>, sqlx::Error>;
+
+    /// CSVエクスポート用に監査ログを`limit`件ずつ取得する（記録日時の新しい順。`since`以降に記録されたもののみ）
+    ///
+    /// `before_id`を指定した場合は、その記録より後に並ぶものを返す。
+    async fn get_audit_export_page(
+        &self,
+        since: Option<DateTime<Utc>>,
+        before_id: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<AuditExportRow>, sqlx::Error>;
+
+    /// 監査ログを1つのトランザクションで取り込む（既に同じ記録があるものは飛ばす）
+    async fn import_audit_entries(&self, entries: &[AuditEntry]) -> Result<AuditImportCounts, sqlx::Error>;
+
+    /// `created_by`のAPIキーを登録する（`key_hash`はキーのSHA-256。監査ログの実行者は`actor_user_id`）
+    async fn create_api_key(
+        &self,
+        actor_user_id: i64,
+        created_by: i64,
+        name: &str,
+        key_hash: &str,
+        scopes: &[String],
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<ApiKey, sqlx::Error>;
+
+    /// ハッシュが一致するAPIキー（無効化済み・期限切れも含む。判定は呼び出し側で行う）
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>, sqlx::Error>;
+
+    async fn get_api_key_by_id(&self, key_id: i64) -> Result<Option<ApiKey>, sqlx::Error>;
+
+    /// ユーザーが所有するAPIキー（作成日時の新しい順。無効化済みも含む）
+    async fn get_api_keys_by_user(&self, user_id: i64) -> Result<Vec<ApiKey>, sqlx::Error>;
+
+    /// APIキーを無効化する（監査ログに記録する。存在しない場合は`None`）
+    async fn deactivate_api_key(&self, actor_user_id: i64, key_id: i64) -> Result<Option<ApiKey>, sqlx::Error>;
+
+    /// メールアドレスが`@<domain>`で終わらないユーザーの数（シードの実行前に本番のデータでないことを確認する）
+    #[cfg(feature = "dev-tools")]
+    async fn count_users_outside_domain(&self, domain: &str) -> Result<i64, sqlx::Error>;
+
+    /// 負荷試験用のユーザーを1つのトランザクションで複数行ずつ登録し、(ユーザーID, 招待権限)を返す（順序は不定）
+    #[cfg(feature = "dev-tools")]
+    async fn insert_seed_users(&self, users: &[SeedUser]) -> Result<Vec<(i64, bool)>, sqlx::Error>;
+
+    /// 負荷試験用の招待コードを1つのトランザクションで複数行ずつ作成する
+    #[cfg(feature = "dev-tools")]
+    async fn insert_seed_invites(&self, invites: &[SeedInvite]) -> Result<u64, sqlx::Error>;
+}
+
+/// ハンドラーから利用するデータベース（バックエンドは`connect`で選択される）
+pub type Database = Arc<dyn DatabaseTrait>;
+
+/// `database_url`のデータベースに接続し、テーブルを準備する
+///
+/// `postgres://`または`postgresql://`で始まる場合はPostgreSQL（`postgres`フィーチャーが必要）、
+/// それ以外はSQLiteとして扱う。登録日時・有効期限の判定などの現在時刻は`clock`から取る。
+pub async fn connect(database_url: &str, clock: SharedClock, ids: SharedIdGenerator) -> Result<Database, sqlx::Error> {
+    if database_url.starts_with("postgres://") || database_url.starts_with("postgresql://") {
+        #[cfg(feature = "postgres")]
+        return Ok(Arc::new(PostgresDatabase::new(database_url, clock, ids).await?));
+
+        #[cfg(not(feature = "postgres"))]
+        return Err(sqlx::Error::Configuration(
+            "DATABASE_URL points to PostgreSQL but the `postgres` feature is not enabled".into(),
+        ));
+    }
+
+    Ok(Arc::new(SqliteDatabase::new(database_url, clock, ids).await?))
+}
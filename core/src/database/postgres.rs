@@ -0,0 +1,1518 @@
+use super::{
+    counting_pool_options, parse_metadata, parse_scopes, start_of_day, ApiKey, AuditEntry, AuditExportRow, AuditImportCounts, BanOutcome, DatabaseTrait, IdempotencyState, InviteActivity, InviteCode, InviteExportRow, InviteFilterParams, InviteStats, InviteSummary,
+    InvitedByFilter, MigrationRecord, PendingAction, PendingActionKind, PoolStatus, RegisteredUser, SystemStats,
+    StoredResponse, UserActivity, UserFilterParams, WeeklyStats, INACTIVE_USER_DAYS, STALE_INVITE_DAYS,
+};
+#[cfg(feature = "dev-tools")]
+use super::{SeedInvite, SeedUser, SEED_ROWS_PER_STATEMENT};
+use crate::{clock::SharedClock, ids::SharedIdGenerator};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{
+    migrate::{MigrateDatabase, Migrator}, postgres::PgRow, PgConnection, Pool, Postgres, QueryBuilder, Row,
+};
+use std::{
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tracing::{info, instrument, warn};
+
+const USER_COLUMNS: &str =
+    "id, google_id, email, name, registered_at, last_login, is_root, can_invite, invited_by, is_active, metadata, email_verified";
+
+const INVITE_COLUMNS: &str =
+    "id, code, created_by, created_at, expires_at, used_by, used_at, is_active, note, metadata";
+
+fn user_from_row(row: &PgRow) -> RegisteredUser {
+    RegisteredUser {
+        id: row.get("id"),
+        google_id: row.get("google_id"),
+        email: row.get("email"),
+        name: row.get("name"),
+        registered_at: row.get("registered_at"),
+        last_login: row.get("last_login"),
+        is_root: row.get("is_root"),
+        can_invite: row.get("can_invite"),
+        invited_by: row.get("invited_by"),
+        is_active: row.get("is_active"),
+        metadata: parse_metadata(row.get("metadata")),
+        email_verified: row.get("email_verified"),
+    }
+}
+
+fn invite_from_row(row: &PgRow) -> InviteCode {
+    InviteCode {
+        id: row.get("id"),
+        code: row.get("code"),
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+        expires_at: row.get("expires_at"),
+        used_by: row.get("used_by"),
+        used_at: row.get("used_at"),
+        is_active: row.get("is_active"),
+        note: row.get("note"),
+        metadata: parse_metadata(row.get("metadata")),
+    }
+}
+
+/// api_keysのSELECT・RETURNINGで使用するカラム（`key_hash`は返さない）
+const API_KEY_COLUMNS: &str = "id, created_by, name, is_active, expires_at, scopes, created_at";
+
+fn api_key_from_row(row: &PgRow) -> ApiKey {
+    ApiKey {
+        id: row.get("id"),
+        created_by: row.get("created_by"),
+        name: row.get("name"),
+        is_active: row.get("is_active"),
+        expires_at: row.get("expires_at"),
+        scopes: parse_scopes(row.get("scopes")),
+        created_at: row.get("created_at"),
+    }
+}
+
+/// ユーザー一覧の絞り込み条件をWHERE句に追加する（`WHERE 1 = 1`の後に続ける）
+fn push_user_filters(query: &mut QueryBuilder<'_, Postgres>, filter: &UserFilterParams) {
+    if let Some(is_root) = filter.is_root {
+        query.push(" AND is_root = ").push_bind(is_root);
+    }
+    if let Some(can_invite) = filter.can_invite {
+        query.push(" AND can_invite = ").push_bind(can_invite);
+    }
+    match filter.invited_by {
+        Some(InvitedByFilter::NoInviter) => {
+            query.push(" AND invited_by IS NULL");
+        }
+        Some(InvitedByFilter::User(user_id)) => {
+            query.push(" AND invited_by = ").push_bind(user_id);
+        }
+        None => {}
+    }
+    if let Some(registered_after) = filter.registered_after {
+        query.push(" AND registered_at >= ").push_bind(registered_after);
+    }
+    if let Some(registered_before) = filter.registered_before {
+        query.push(" AND registered_at <= ").push_bind(registered_before);
+    }
+}
+
+/// 招待コード一覧の絞り込み条件をWHERE句に追加する（`WHERE 1 = 1`の後に続ける）
+fn push_invite_filters(query: &mut QueryBuilder<'_, Postgres>, filter: &InviteFilterParams, now: DateTime<Utc>) {
+    if let Some(created_by) = filter.created_by {
+        query.push(" AND created_by = ").push_bind(created_by);
+    }
+    if let Some(is_active) = filter.is_active {
+        query.push(" AND is_active = ").push_bind(is_active);
+    }
+    match filter.used {
+        Some(true) => {
+            query.push(" AND used_by IS NOT NULL");
+        }
+        Some(false) => {
+            query.push(" AND used_by IS NULL");
+        }
+        None => {}
+    }
+    match filter.expired {
+        Some(true) => {
+            query.push(" AND expires_at IS NOT NULL AND expires_at < ").push_bind(now);
+        }
+        Some(false) => {
+            query.push(" AND (expires_at IS NULL OR expires_at >= ").push_bind(now).push(")");
+        }
+        None => {}
+    }
+    if let Some(created_after) = filter.created_after {
+        query.push(" AND created_at >= ").push_bind(created_after);
+    }
+    if let Some(created_before) = filter.created_before {
+        query.push(" AND created_at <= ").push_bind(created_before);
+    }
+}
+
+/// `migrations/postgres`のマイグレーション（バイナリに埋め込み、起動時に未適用のものを適用する）
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/postgres");
+
+#[derive(Clone)]
+pub struct PostgresDatabase {
+    pool: Pool<Postgres>,
+    acquired: Arc<AtomicU64>,
+    clock: SharedClock,
+    ids: SharedIdGenerator,
+}
+
+impl PostgresDatabase {
+    pub async fn new(database_url: &str, clock: SharedClock, ids: SharedIdGenerator) -> Result<Self, sqlx::Error> {
+        if !Postgres::database_exists(database_url).await.unwrap_or(false) {
+            Postgres::create_database(database_url).await?;
+        }
+
+        let acquired = Arc::new(AtomicU64::new(0));
+        let pool = counting_pool_options(acquired.clone()).connect(database_url).await?;
+        MIGRATOR.run(&pool).await?;
+
+        Ok(PostgresDatabase {
+            pool,
+            acquired,
+            clock,
+            ids,
+        })
+    }
+}
+
+#[async_trait]
+impl DatabaseTrait for PostgresDatabase {
+    #[instrument(skip(self))]
+    async fn register_user(
+        &self,
+        google_id: &str,
+        email: &str,
+        name: &str,
+        root_email: Option<&str>,
+    ) -> Result<RegisteredUser, sqlx::Error> {
+        let now = self.clock.now();
+        // 件数の確認と登録の間に他のユーザーが登録しないよう、同じトランザクションで行う
+        let mut tx = self.pool.begin().await?;
+
+        let is_root = match root_email {
+            Some(root_email) => root_email.eq_ignore_ascii_case(email),
+            None => {
+                let row = sqlx::query("SELECT COUNT(*) as count FROM registered_users").fetch_one(&mut *tx).await?;
+                row.get::<i64, _>("count") == 0
+            }
+        };
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO registered_users (google_id, email, name, registered_at, last_login, is_root, can_invite, invited_by)
+            VALUES ($1, $2, $3, $4, $4, $5, $6, $7)
+            RETURNING {}
+            "#,
+            USER_COLUMNS
+        ))
+        .bind(google_id)
+        .bind(email)
+        .bind(name)
+        .bind(now)
+        .bind(is_root)
+        .bind(is_root) // rootユーザーのみcan_invite=true
+        .bind(None::<i64>) // 招待なしのユーザーはinvited_by=NULL
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(user_from_row(&row))
+    }
+
+    #[instrument(skip(self))]
+    async fn register_invited_user(
+        &self,
+        google_id: &str,
+        email: &str,
+        name: &str,
+        invited_by: i64,
+    ) -> Result<RegisteredUser, sqlx::Error> {
+        let now = self.clock.now();
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO registered_users (google_id, email, name, registered_at, last_login, is_root, can_invite, invited_by)
+            VALUES ($1, $2, $3, $4, $4, FALSE, FALSE, $5)
+            RETURNING {}
+            "#,
+            USER_COLUMNS
+        ))
+        .bind(google_id)
+        .bind(email)
+        .bind(name)
+        .bind(now)
+        .bind(invited_by)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(user_from_row(&row))
+    }
+
+    fn pool_status(&self) -> PoolStatus {
+        PoolStatus {
+            size: self.pool.size(),
+            idle: self.pool.num_idle(),
+            acquired: self.acquired.load(Ordering::Relaxed),
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn ping(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn close(&self) {
+        self.pool.close().await;
+    }
+
+    #[instrument(skip(self))]
+    async fn check_schema(&self) -> Result<(), sqlx::Error> {
+        let queries = [
+            format!("SELECT {} FROM registered_users LIMIT 0", USER_COLUMNS),
+            format!("SELECT {} FROM invite_codes LIMIT 0", INVITE_COLUMNS),
+            "SELECT id, actor_user_id, action, target_user_id, metadata, created_at FROM audit_log LIMIT 0".to_string(),
+            "SELECT idempotency_key, request_hash, status_code, content_type, response_body, created_at, expires_at \
+             FROM idempotency_keys LIMIT 0"
+                .to_string(),
+            format!("SELECT key_hash, {} FROM api_keys LIMIT 0", API_KEY_COLUMNS),
+        ];
+        for query in &queries {
+            sqlx::query(query).execute(&self.pool).await?;
+        }
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn get_applied_migrations(&self) -> Result<Vec<MigrationRecord>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT version, description, installed_on, success FROM _sqlx_migrations ORDER BY version",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(MigrationRecord {
+                    version: row.try_get("version")?,
+                    description: row.try_get("description")?,
+                    installed_on: row.try_get("installed_on")?,
+                    success: row.try_get("success")?,
+                })
+            })
+            .collect()
+    }
+
+    #[cfg(all(feature = "dev", debug_assertions))]
+    #[instrument(skip(self))]
+    async fn run_pending_migrations(&self) -> Result<Vec<MigrationRecord>, sqlx::Error> {
+        let before = self.get_applied_migrations().await?;
+        MIGRATOR.run(&self.pool).await?;
+        let after = self.get_applied_migrations().await?;
+        Ok(after
+            .into_iter()
+            .filter(|migration| !before.iter().any(|applied| applied.version == migration.version))
+            .collect())
+    }
+
+    #[instrument(skip(self))]
+    async fn begin_idempotency_key(
+        &self,
+        key: &str,
+        request_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<IdempotencyState, sqlx::Error> {
+        let now = self.clock.now();
+        sqlx::query("DELETE FROM idempotency_keys WHERE expires_at < $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+
+        let inserted = sqlx::query(
+            r#"
+            INSERT INTO idempotency_keys (idempotency_key, request_hash, created_at, expires_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (idempotency_key) DO NOTHING
+            "#
+        )
+        .bind(key)
+        .bind(request_hash)
+        .bind(now)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        if inserted > 0 {
+            return Ok(IdempotencyState::Started);
+        }
+
+        let row = sqlx::query(
+            "SELECT request_hash, status_code, content_type, response_body FROM idempotency_keys WHERE idempotency_key = $1",
+        )
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await?;
+        // 登録に失敗した直後に先行のリクエストが失敗して削除された場合は、処理中として再送させる
+        let Some(row) = row else {
+            return Ok(IdempotencyState::InProgress);
+        };
+        if row.get::<String, _>("request_hash") != request_hash {
+            return Ok(IdempotencyState::Mismatch);
+        }
+        Ok(match row.get::<Option<i32>, _>("status_code") {
+            Some(status) => IdempotencyState::Completed(StoredResponse {
+                status: status as u16,
+                content_type: row.get("content_type"),
+                body: row.get::<Option<Vec<u8>>, _>("response_body").unwrap_or_default(),
+            }),
+            None => IdempotencyState::InProgress,
+        })
+    }
+
+    #[instrument(skip(self, response))]
+    async fn complete_idempotency_key(&self, key: &str, response: &StoredResponse) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE idempotency_keys SET status_code = $1, content_type = $2, response_body = $3 WHERE idempotency_key = $4",
+        )
+        .bind(response.status as i32)
+        .bind(response.content_type.as_deref())
+        .bind(&response.body)
+        .bind(key)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn release_idempotency_key(&self, key: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM idempotency_keys WHERE idempotency_key = $1 AND status_code IS NULL")
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn is_user_registered(&self, email: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("SELECT COUNT(*) as count FROM registered_users WHERE email = $1")
+            .bind(email)
+            .fetch_one(&self.pool)
+            .await?;
+
+        let count: i64 = result.get("count");
+        Ok(count > 0)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<RegisteredUser>, sqlx::Error> {
+        let result = sqlx::query(&format!("SELECT {} FROM registered_users WHERE email = $1", USER_COLUMNS))
+            .bind(email)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(result.map(|row| user_from_row(&row)))
+    }
+
+    #[instrument(skip(self))]
+    async fn get_user_by_id(&self, user_id: i64) -> Result<Option<RegisteredUser>, sqlx::Error> {
+        let result = sqlx::query(&format!("SELECT {} FROM registered_users WHERE id = $1", USER_COLUMNS))
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(result.map(|row| user_from_row(&row)))
+    }
+
+    #[instrument(skip(self))]
+    async fn update_last_login(&self, email: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE registered_users SET last_login = $1 WHERE email = $2")
+            .bind(self.clock.now())
+            .bind(email)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn record_login(&self, email: &str, method: &str, client_ip: Option<IpAddr>) -> Result<(), sqlx::Error> {
+        let metadata = serde_json::json!({ "method": method, "client_ip": client_ip });
+        sqlx::query(
+            r#"
+            INSERT INTO audit_log (actor_user_id, action, target_user_id, metadata, created_at)
+            SELECT id, 'login', id, $1, $2 FROM registered_users WHERE email = $3
+            "#
+        )
+        .bind(metadata.to_string())
+        .bind(self.clock.now())
+        .bind(email)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn mark_email_verified(&self, email: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE registered_users SET email_verified = TRUE WHERE email = $1")
+            .bind(email)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn update_user_metadata(
+        &self,
+        user_id: i64,
+        metadata: &serde_json::Value,
+    ) -> Result<Option<RegisteredUser>, sqlx::Error> {
+        let row = sqlx::query(&format!(
+            "UPDATE registered_users SET metadata = $1 WHERE id = $2 RETURNING {}",
+            USER_COLUMNS
+        ))
+        .bind(metadata.to_string())
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(user_from_row))
+    }
+
+    #[instrument(skip(self))]
+    async fn get_all_registered_users(
+        &self,
+        filter: &UserFilterParams,
+    ) -> Result<Vec<RegisteredUser>, sqlx::Error> {
+        let mut query = QueryBuilder::<Postgres>::new(format!(
+            "SELECT {} FROM registered_users WHERE 1 = 1",
+            USER_COLUMNS
+        ));
+
+        push_user_filters(&mut query, filter);
+        query.push(" ORDER BY registered_at DESC");
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+
+        Ok(rows.iter().map(user_from_row).collect())
+    }
+
+    #[instrument(skip(self))]
+    async fn get_registered_users_page(
+        &self,
+        filter: &UserFilterParams,
+        before_id: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<RegisteredUser>, sqlx::Error> {
+        let mut query = QueryBuilder::<Postgres>::new(format!(
+            "SELECT {} FROM registered_users WHERE 1 = 1",
+            USER_COLUMNS
+        ));
+        push_user_filters(&mut query, filter);
+        if let Some(before_id) = before_id {
+            query.push(" AND id < ").push_bind(before_id);
+        }
+        query.push(" ORDER BY id DESC LIMIT ").push_bind(limit);
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+
+        Ok(rows.iter().map(user_from_row).collect())
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_user(&self, user_id: i64) -> Result<bool, sqlx::Error> {
+        info!("Starting delete operation for user ID: {}", user_id);
+
+        let mut tx = self.pool.begin().await?;
+
+        // rootユーザーと存在しないユーザーは削除できない
+        let user_check = sqlx::query("SELECT is_root, email FROM registered_users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        match user_check {
+            Some(row) => {
+                let is_root: bool = row.get("is_root");
+                let email: String = row.get("email");
+                if is_root {
+                    warn!("Attempted to delete root user: {}", email);
+                    tx.rollback().await?;
+                    return Ok(false);
+                }
+            }
+            None => {
+                warn!("User ID {} not found for deletion", user_id);
+                tx.rollback().await?;
+                return Ok(false);
+            }
+        }
+
+        let invite_result = sqlx::query("DELETE FROM invite_codes WHERE created_by = $1 OR used_by = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        info!("Deleted {} invite codes", invite_result.rows_affected());
+
+        // 削除したユーザーのAPIキーは使えなくなるため残さない
+        let api_key_result = sqlx::query("DELETE FROM api_keys WHERE created_by = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        info!("Deleted {} API keys", api_key_result.rows_affected());
+
+        // 招待したユーザーのinvited_byは外部キー制約によりNULLになる
+        let result = sqlx::query("DELETE FROM registered_users WHERE id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        info!("User deletion affected {} rows", result.rows_affected());
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[instrument(skip(self))]
+    async fn ban_user(
+        &self,
+        actor_user_id: i64,
+        user_id: i64,
+        sessions_revoked: usize,
+    ) -> Result<Option<BanOutcome>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query("UPDATE registered_users SET is_active = FALSE WHERE id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Ok(None);
+        }
+
+        let invites_deactivated = sqlx::query(
+            "UPDATE invite_codes SET is_active = FALSE WHERE created_by = $1 AND used_by IS NULL AND is_active = TRUE"
+        )
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        insert_audit_log(
+            &mut tx,
+            Some(actor_user_id),
+            "ban_user",
+            Some(user_id),
+            serde_json::json!({
+                "sessions_revoked": sessions_revoked,
+                "invites_deactivated": invites_deactivated,
+            }),
+            self.clock.now(),
+        )
+        .await?;
+
+        tx.commit().await?;
+        info!("User ID {} banned by user ID {}", user_id, actor_user_id);
+
+        Ok(Some(BanOutcome { invites_deactivated }))
+    }
+
+    #[instrument(skip(self))]
+    async fn unban_user(&self, actor_user_id: i64, user_id: i64) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query("UPDATE registered_users SET is_active = TRUE WHERE id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Ok(false);
+        }
+
+        let now = self.clock.now();
+        insert_audit_log(&mut tx, Some(actor_user_id), "unban_user", Some(user_id), serde_json::json!({}), now).await?;
+
+        tx.commit().await?;
+        info!("User ID {} unbanned by user ID {}", user_id, actor_user_id);
+
+        Ok(true)
+    }
+
+    #[instrument(skip(self))]
+    async fn grant_root(&self, email: &str) -> Result<Option<RegisteredUser>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(row) = sqlx::query(&format!(
+            "UPDATE registered_users SET is_root = TRUE, can_invite = TRUE \
+             WHERE LOWER(email) = LOWER($1) AND is_root = FALSE RETURNING {}",
+            USER_COLUMNS
+        ))
+        .bind(email)
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            tx.rollback().await?;
+            return Ok(None);
+        };
+        let user = user_from_row(&row);
+
+        let metadata = serde_json::json!({ "reason": "root_email" });
+        insert_audit_log(&mut tx, None, "grant_root", Some(user.id), metadata, self.clock.now()).await?;
+
+        tx.commit().await?;
+        info!("User ID {} granted root by ROOT_EMAIL", user.id);
+
+        Ok(Some(user))
+    }
+
+    #[instrument(skip(self))]
+    async fn promote_to_root(&self, actor_user_id: i64, user_id: i64) -> Result<Option<RegisteredUser>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(row) = sqlx::query(&format!(
+            "UPDATE registered_users SET is_root = TRUE, can_invite = TRUE \
+             WHERE id = $1 AND is_root = FALSE AND is_active = TRUE RETURNING {}",
+            USER_COLUMNS
+        ))
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            tx.rollback().await?;
+            return Ok(None);
+        };
+        let user = user_from_row(&row);
+
+        let now = self.clock.now();
+        insert_audit_log(&mut tx, Some(actor_user_id), "promote_to_root", Some(user_id), serde_json::json!({}), now).await?;
+
+        tx.commit().await?;
+        info!("User ID {} promoted to root by user ID {}", user.id, actor_user_id);
+
+        Ok(Some(user))
+    }
+
+    #[instrument(skip(self))]
+    async fn set_can_invite(&self, actor_user_id: i64, user_id: i64, can_invite: bool) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query("UPDATE registered_users SET can_invite = $1 WHERE id = $2")
+            .bind(can_invite)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Ok(false);
+        }
+
+        let metadata = serde_json::json!({ "can_invite": can_invite });
+        let now = self.clock.now();
+        insert_audit_log(&mut tx, Some(actor_user_id), "set_can_invite", Some(user_id), metadata, now).await?;
+
+        tx.commit().await?;
+        info!("User ID {} can_invite set to {} by user ID {}", user_id, can_invite, actor_user_id);
+
+        Ok(true)
+    }
+
+    #[instrument(skip(self))]
+    async fn create_invite_code(&self, created_by: i64) -> Result<InviteCode, sqlx::Error> {
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO invite_codes (code, created_by, created_at, is_active)
+            VALUES ($1, $2, $3, TRUE)
+            RETURNING {}
+            "#,
+            INVITE_COLUMNS
+        ))
+        .bind(self.ids.new_id().to_string())
+        .bind(created_by)
+        .bind(self.clock.now())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(invite_from_row(&row))
+    }
+
+    #[instrument(skip(self))]
+    async fn validate_invite_code(&self, code: &str) -> Result<Option<InviteCode>, sqlx::Error> {
+        let result = sqlx::query(&format!(
+            r#"
+            SELECT {}
+            FROM invite_codes
+            WHERE code = $1 AND is_active = TRUE AND used_by IS NULL
+              AND (expires_at IS NULL OR expires_at >= $2)
+            "#,
+            INVITE_COLUMNS
+        ))
+        .bind(code)
+        .bind(self.clock.now())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.map(|row| invite_from_row(&row)))
+    }
+
+    #[instrument(skip(self))]
+    async fn get_invite_code_by_id(&self, invite_id: i64) -> Result<Option<InviteCode>, sqlx::Error> {
+        let result = sqlx::query(&format!("SELECT {} FROM invite_codes WHERE id = $1", INVITE_COLUMNS))
+            .bind(invite_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(result.map(|row| invite_from_row(&row)))
+    }
+
+    #[instrument(skip(self))]
+    async fn use_invite_code(&self, code: &str, used_by: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE invite_codes SET used_by = $1, used_at = $2 WHERE code = $3")
+            .bind(used_by)
+            .bind(self.clock.now())
+            .bind(code)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn update_invite(
+        &self,
+        invite_id: i64,
+        note: Option<&str>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<InviteCode, sqlx::Error> {
+        let row = sqlx::query(&format!(
+            r#"
+            UPDATE invite_codes
+            SET note = COALESCE($1, note), expires_at = COALESCE($2, expires_at)
+            WHERE id = $3
+            RETURNING {}
+            "#,
+            INVITE_COLUMNS
+        ))
+        .bind(note)
+        .bind(expires_at)
+        .bind(invite_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(invite_from_row(&row))
+    }
+
+    #[instrument(skip(self))]
+    async fn deactivate_invite(&self, invite_id: i64) -> Result<Option<InviteCode>, sqlx::Error> {
+        let row = sqlx::query(&format!(
+            "UPDATE invite_codes SET is_active = FALSE WHERE id = $1 AND used_by IS NULL RETURNING {}",
+            INVITE_COLUMNS
+        ))
+        .bind(invite_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(invite_from_row))
+    }
+
+    #[instrument(skip(self))]
+    async fn transfer_invite(
+        &self,
+        actor_user_id: i64,
+        invite_id: i64,
+        new_owner_id: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(row) = sqlx::query("SELECT created_by FROM invite_codes WHERE id = $1")
+            .bind(invite_id)
+            .fetch_optional(&mut *tx)
+            .await?
+        else {
+            tx.rollback().await?;
+            return Ok(false);
+        };
+        let previous_owner_id: i64 = row.get("created_by");
+
+        sqlx::query("UPDATE invite_codes SET created_by = $1 WHERE id = $2")
+            .bind(new_owner_id)
+            .bind(invite_id)
+            .execute(&mut *tx)
+            .await?;
+
+        insert_audit_log(
+            &mut tx,
+            Some(actor_user_id),
+            "transfer_invite",
+            Some(new_owner_id),
+            serde_json::json!({
+                "invite_id": invite_id,
+                "previous_owner_id": previous_owner_id,
+            }),
+            self.clock.now(),
+        )
+        .await?;
+
+        tx.commit().await?;
+        info!("Invite {} transferred from user ID {} to user ID {}", invite_id, previous_owner_id, new_owner_id);
+
+        Ok(true)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_invite_codes(&self, filter: &InviteFilterParams) -> Result<Vec<InviteCode>, sqlx::Error> {
+        let mut query = QueryBuilder::<Postgres>::new(format!(
+            "SELECT {} FROM invite_codes WHERE 1 = 1",
+            INVITE_COLUMNS
+        ));
+
+        push_invite_filters(&mut query, filter, self.clock.now());
+        query.push(" ORDER BY created_at DESC");
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+
+        Ok(rows.iter().map(invite_from_row).collect())
+    }
+
+    #[instrument(skip(self))]
+    async fn get_invite_codes_page(
+        &self,
+        filter: &InviteFilterParams,
+        before_id: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<InviteCode>, sqlx::Error> {
+        let mut query = QueryBuilder::<Postgres>::new(format!(
+            "SELECT {} FROM invite_codes WHERE 1 = 1",
+            INVITE_COLUMNS
+        ));
+        push_invite_filters(&mut query, filter, self.clock.now());
+        if let Some(before_id) = before_id {
+            query.push(" AND id < ").push_bind(before_id);
+        }
+        query.push(" ORDER BY id DESC LIMIT ").push_bind(limit);
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+
+        Ok(rows.iter().map(invite_from_row).collect())
+    }
+
+    #[instrument(skip(self))]
+    async fn count_invites_created_today(&self, user_id: i64) -> Result<i64, sqlx::Error> {
+        let result = sqlx::query(
+            "SELECT COUNT(*) as count FROM invite_codes WHERE created_by = $1 AND created_at >= $2",
+        )
+        .bind(user_id)
+        .bind(start_of_day(self.clock.now()))
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result.get::<i64, _>("count"))
+    }
+
+    #[instrument(skip(self))]
+    async fn count_active_invites_by_user(&self, user_id: i64) -> Result<i64, sqlx::Error> {
+        let result = sqlx::query(
+            "SELECT COUNT(*) as count FROM invite_codes WHERE created_by = $1 AND used_by IS NULL AND is_active = TRUE",
+        )
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result.get::<i64, _>("count"))
+    }
+
+    #[instrument(skip(self))]
+    async fn count_expired_invites(&self) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "SELECT COUNT(*) as count FROM invite_codes WHERE used_by IS NULL AND expires_at < $1",
+        )
+        .bind(self.clock.now())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result.get::<i64, _>("count") as u64)
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_expired_invites(&self) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "DELETE FROM invite_codes WHERE used_by IS NULL AND expires_at < $1",
+        )
+        .bind(self.clock.now())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    #[instrument(skip(self))]
+    async fn count_registered_users(&self) -> Result<i64, sqlx::Error> {
+        let result = sqlx::query("SELECT COUNT(*) as count FROM registered_users")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(result.get("count"))
+    }
+
+    #[instrument(skip(self))]
+    async fn get_invite_summary_by_user(&self, user_id: i64) -> Result<InviteSummary, sqlx::Error> {
+        let row = sqlx::query(
+            r#"
+            SELECT COUNT(*) as total,
+                   COUNT(*) FILTER (WHERE used_by IS NULL AND is_active
+                                      AND (expires_at IS NULL OR expires_at >= $2)) as outstanding,
+                   COUNT(*) FILTER (WHERE used_by IS NOT NULL) as used
+            FROM invite_codes
+            WHERE created_by = $1
+            "#
+        )
+        .bind(user_id)
+        .bind(self.clock.now())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(InviteSummary {
+            total: row.get("total"),
+            outstanding: row.get("outstanding"),
+            used: row.get("used"),
+        })
+    }
+
+    #[instrument(skip(self))]
+    async fn get_user_activity(&self) -> Result<UserActivity, sqlx::Error> {
+        let row = sqlx::query(
+            r#"
+            SELECT COUNT(*) as total_users,
+                   COUNT(*) FILTER (WHERE last_login >= $1 - INTERVAL '7 days') as active_7d,
+                   COUNT(*) FILTER (WHERE last_login >= $1 - INTERVAL '30 days') as active_30d
+            FROM registered_users
+            "#
+        )
+        .bind(self.clock.now())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(UserActivity {
+            total_users: row.get("total_users"),
+            active_7d: row.get("active_7d"),
+            active_30d: row.get("active_30d"),
+        })
+    }
+
+    #[instrument(skip(self))]
+    async fn get_system_stats(&self) -> Result<SystemStats, sqlx::Error> {
+        let row = sqlx::query(
+            r#"
+            WITH user_stats AS (
+                SELECT COUNT(*) as total_users,
+                       COUNT(*) FILTER (WHERE last_login >= $1 - INTERVAL '30 days') as active_users,
+                       COUNT(*) FILTER (WHERE registered_at >= $1 - INTERVAL '7 days') as new_users_this_week
+                FROM registered_users
+            ),
+            invite_stats AS (
+                SELECT COUNT(*) as total_invites,
+                       COUNT(*) FILTER (WHERE used_by IS NULL AND is_active
+                                          AND (expires_at IS NULL OR expires_at >= $1)) as pending_invites,
+                       COUNT(*) FILTER (WHERE used_by IS NOT NULL) as used_invites,
+                       COUNT(*) FILTER (WHERE used_by IS NULL AND expires_at < $1) as expired_invites
+                FROM invite_codes
+            )
+            SELECT * FROM user_stats, invite_stats
+            "#
+        )
+        .bind(self.clock.now())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(SystemStats {
+            total_users: row.get("total_users"),
+            active_users: row.get("active_users"),
+            total_invites: row.get("total_invites"),
+            pending_invites: row.get("pending_invites"),
+            used_invites: row.get("used_invites"),
+            expired_invites: row.get("expired_invites"),
+            new_users_this_week: row.get("new_users_this_week"),
+        })
+    }
+
+    #[instrument(skip(self))]
+    async fn get_invite_stats(&self) -> Result<InviteStats, sqlx::Error> {
+        let row = sqlx::query(
+            r#"
+            SELECT COUNT(*) as total,
+                   COUNT(*) FILTER (WHERE used_by IS NULL AND is_active
+                                      AND (expires_at IS NULL OR expires_at >= $1)) as active,
+                   COUNT(*) FILTER (WHERE used_by IS NOT NULL) as used,
+                   COUNT(*) FILTER (WHERE used_by IS NULL AND expires_at < $1) as expired
+            FROM invite_codes
+            "#
+        )
+        .bind(self.clock.now())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(InviteStats {
+            total: row.get("total"),
+            active: row.get("active"),
+            used: row.get("used"),
+            expired: row.get("expired"),
+        })
+    }
+
+    #[instrument(skip(self))]
+    async fn get_pending_actions(&self) -> Result<Vec<PendingAction>, sqlx::Error> {
+        let now = self.clock.now();
+        // 有効期限内で未使用の招待コード（作成者と結合するためicで参照する）
+        const OPEN_INVITE: &str =
+            "ic.is_active = TRUE AND ic.used_by IS NULL AND (ic.expires_at IS NULL OR ic.expires_at >= $1)";
+        let checks = [
+            (
+                PendingActionKind::CleanupExpiredInvites,
+                "SELECT COUNT(*) as count FROM invite_codes WHERE used_by IS NULL AND expires_at < $1".to_string(),
+                now,
+            ),
+            (
+                PendingActionKind::DeactivateBannedUserInvites,
+                format!(
+                    "SELECT COUNT(*) as count FROM invite_codes ic JOIN registered_users u ON ic.created_by = u.id \
+                     WHERE {} AND u.is_active = FALSE",
+                    OPEN_INVITE
+                ),
+                now,
+            ),
+            (
+                PendingActionKind::ReassignOrphanedInvites,
+                format!(
+                    "SELECT COUNT(*) as count FROM invite_codes ic JOIN registered_users u ON ic.created_by = u.id \
+                     WHERE {} AND u.is_active = TRUE AND u.can_invite = FALSE",
+                    OPEN_INVITE
+                ),
+                now,
+            ),
+            (
+                PendingActionKind::ReviewStaleInvites,
+                "SELECT COUNT(*) as count FROM invite_codes \
+                 WHERE is_active = TRUE AND used_by IS NULL AND expires_at IS NULL AND created_at < $1"
+                    .to_string(),
+                now - chrono::Duration::days(STALE_INVITE_DAYS),
+            ),
+            (
+                PendingActionKind::ReviewInactiveUsers,
+                "SELECT COUNT(*) as count FROM registered_users \
+                 WHERE is_active = TRUE AND COALESCE(last_login, registered_at) < $1"
+                    .to_string(),
+                now - chrono::Duration::days(INACTIVE_USER_DAYS),
+            ),
+        ];
+
+        let mut actions = Vec::with_capacity(checks.len());
+        for (action, sql, at) in checks {
+            let row = sqlx::query(&sql).bind(at).fetch_one(&self.pool).await?;
+            actions.push(PendingAction {
+                action,
+                count: row.get::<i64, _>("count") as u64,
+            });
+        }
+        Ok(actions)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_weekly_stats(&self, weeks: u32) -> Result<Vec<WeeklyStats>, sqlx::Error> {
+        // date_trunc('week')はISO週（月曜日始まり）で切り捨てる
+        let rows = sqlx::query(
+            r#"
+            WITH weeks AS (
+                SELECT generate_series(
+                    date_trunc('week', $1 AT TIME ZONE 'UTC') - ($2 - 1) * INTERVAL '7 days',
+                    date_trunc('week', $1 AT TIME ZONE 'UTC'),
+                    INTERVAL '7 days'
+                ) as week_start
+            )
+            SELECT to_char(w.week_start, 'YYYY-MM-DD') as week_start,
+                   (SELECT COUNT(*) FROM registered_users
+                    WHERE date_trunc('week', registered_at AT TIME ZONE 'UTC') = w.week_start) as new_users,
+                   (SELECT COUNT(*) FROM invite_codes
+                    WHERE date_trunc('week', created_at AT TIME ZONE 'UTC') = w.week_start) as new_invites,
+                   (SELECT COUNT(*) FROM invite_codes
+                    WHERE date_trunc('week', used_at AT TIME ZONE 'UTC') = w.week_start) as invite_uses
+            FROM weeks w
+            ORDER BY w.week_start
+            "#
+        )
+        .bind(self.clock.now())
+        .bind(weeks as i32)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let stats = rows
+            .into_iter()
+            .map(|row| WeeklyStats {
+                week_start: row.get("week_start"),
+                new_users: row.get("new_users"),
+                new_invites: row.get("new_invites"),
+                invite_uses: row.get("invite_uses"),
+            })
+            .collect();
+
+        Ok(stats)
+    }
+
+    #[instrument(skip(self))]
+    async fn count_invitees(&self, user_id: i64) -> Result<i64, sqlx::Error> {
+        let result = sqlx::query("SELECT COUNT(*) as count FROM registered_users WHERE invited_by = $1")
+            .bind(user_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(result.get("count"))
+    }
+
+    #[instrument(skip(self))]
+    async fn get_recent_invite_activity(
+        &self,
+        user_id: i64,
+        limit: i64,
+    ) -> Result<Vec<InviteActivity>, sqlx::Error> {
+        let rows = sqlx::query(
+            r#"
+            SELECT i.id as invite_id, i.code, u.email as used_by_email, u.name as used_by_name, i.used_at
+            FROM invite_codes i
+            JOIN registered_users u ON u.id = i.used_by
+            WHERE i.created_by = $1
+            ORDER BY i.used_at DESC
+            LIMIT $2
+            "#
+        )
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let activity = rows
+            .into_iter()
+            .map(|row| InviteActivity {
+                invite_id: row.get("invite_id"),
+                code: row.get("code"),
+                used_by_email: row.get("used_by_email"),
+                used_by_name: row.get("used_by_name"),
+                used_at: row.get("used_at"),
+            })
+            .collect();
+
+        Ok(activity)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_invite_export_page(
+        &self,
+        before_id: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<InviteExportRow>, sqlx::Error> {
+        let rows = sqlx::query(
+            r#"
+            SELECT i.id, i.code, c.email as created_by_email, i.created_at, i.expires_at,
+                   u.email as used_by_email, i.used_at, i.is_active, i.note
+            FROM invite_codes i
+            LEFT JOIN registered_users c ON c.id = i.created_by
+            LEFT JOIN registered_users u ON u.id = i.used_by
+            WHERE $1 IS NULL OR i.id < $1
+            ORDER BY i.id DESC
+            LIMIT $2
+            "#
+        )
+        .bind(before_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let invites = rows
+            .into_iter()
+            .map(|row| InviteExportRow {
+                id: row.get("id"),
+                code: row.get("code"),
+                created_by_email: row.get("created_by_email"),
+                created_at: row.get("created_at"),
+                expires_at: row.get("expires_at"),
+                used_by_email: row.get("used_by_email"),
+                used_at: row.get("used_at"),
+                is_active: row.get("is_active"),
+                note: row.get("note"),
+            })
+            .collect();
+
+        Ok(invites)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_audit_export_page(
+        &self,
+        since: Option<DateTime<Utc>>,
+        before_id: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<AuditExportRow>, sqlx::Error> {
+        // 取り込んだ記録はIDと記録日時の順が一致しないため、`before_id`の行の(記録日時, ID)より前を返す
+        let rows = sqlx::query(
+            r#"
+            SELECT a.id, actor.email as actor_email, a.action, target.email as target_email, a.metadata, a.created_at
+            FROM audit_log a
+            LEFT JOIN registered_users actor ON actor.id = a.actor_user_id
+            LEFT JOIN registered_users target ON target.id = a.target_user_id
+            WHERE ($1::TIMESTAMPTZ IS NULL OR a.created_at >= $1)
+              AND ($2 IS NULL OR (a.created_at, a.id) < (SELECT created_at, id FROM audit_log WHERE id = $2))
+            ORDER BY a.created_at DESC, a.id DESC
+            LIMIT $3
+            "#
+        )
+        .bind(since)
+        .bind(before_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let entries = rows
+            .into_iter()
+            .map(|row| AuditExportRow {
+                id: row.get("id"),
+                actor_email: row.get("actor_email"),
+                action: row.get("action"),
+                target_email: row.get("target_email"),
+                metadata: parse_metadata(row.get("metadata")),
+                created_at: row.get("created_at"),
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    #[instrument(skip(self, entries), fields(entries = entries.len()))]
+    async fn import_audit_entries(&self, entries: &[AuditEntry]) -> Result<AuditImportCounts, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut counts = AuditImportCounts { imported: 0, skipped: 0 };
+
+        for entry in entries {
+            let existing = sqlx::query(
+                r#"
+                SELECT id FROM audit_log
+                WHERE actor_user_id IS NOT DISTINCT FROM $1 AND action = $2 AND target_user_id IS NOT DISTINCT FROM $3 AND created_at = $4
+                LIMIT 1
+                "#
+            )
+            .bind(entry.actor_user_id)
+            .bind(&entry.action)
+            .bind(entry.target_user_id)
+            .bind(entry.created_at)
+            .fetch_optional(&mut *tx)
+            .await?;
+            if existing.is_some() {
+                counts.skipped += 1;
+                continue;
+            }
+
+            sqlx::query(
+                r#"
+                INSERT INTO audit_log (actor_user_id, action, target_user_id, metadata, created_at)
+                VALUES ($1, $2, $3, $4, $5)
+                "#
+            )
+            .bind(entry.actor_user_id)
+            .bind(&entry.action)
+            .bind(entry.target_user_id)
+            .bind(entry.metadata.to_string())
+            .bind(entry.created_at)
+            .execute(&mut *tx)
+            .await?;
+            counts.imported += 1;
+        }
+
+        tx.commit().await?;
+        info!("Imported {} audit log entries ({} skipped)", counts.imported, counts.skipped);
+
+        Ok(counts)
+    }
+
+    #[instrument(skip(self, key_hash))]
+    async fn create_api_key(
+        &self,
+        actor_user_id: i64,
+        created_by: i64,
+        name: &str,
+        key_hash: &str,
+        scopes: &[String],
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<ApiKey, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let now = self.clock.now();
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO api_keys (key_hash, created_by, name, is_active, expires_at, scopes, created_at)
+            VALUES ($1, $2, $3, TRUE, $4, $5, $6)
+            RETURNING {}
+            "#,
+            API_KEY_COLUMNS
+        ))
+        .bind(key_hash)
+        .bind(created_by)
+        .bind(name)
+        .bind(expires_at)
+        .bind(serde_json::json!(scopes).to_string())
+        .bind(now)
+        .fetch_one(&mut *tx)
+        .await?;
+        let api_key = api_key_from_row(&row);
+
+        let metadata = serde_json::json!({ "api_key_id": api_key.id, "name": name });
+        insert_audit_log(&mut tx, Some(actor_user_id), "create_api_key", Some(created_by), metadata, now).await?;
+
+        tx.commit().await?;
+        info!("API key {} for user ID {} created by user ID {}", api_key.id, created_by, actor_user_id);
+
+        Ok(api_key)
+    }
+
+    #[instrument(skip(self, key_hash))]
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>, sqlx::Error> {
+        let row = sqlx::query(&format!("SELECT {} FROM api_keys WHERE key_hash = $1", API_KEY_COLUMNS))
+            .bind(key_hash)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.as_ref().map(api_key_from_row))
+    }
+
+    #[instrument(skip(self))]
+    async fn get_api_key_by_id(&self, key_id: i64) -> Result<Option<ApiKey>, sqlx::Error> {
+        let row = sqlx::query(&format!("SELECT {} FROM api_keys WHERE id = $1", API_KEY_COLUMNS))
+            .bind(key_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.as_ref().map(api_key_from_row))
+    }
+
+    #[instrument(skip(self))]
+    async fn get_api_keys_by_user(&self, user_id: i64) -> Result<Vec<ApiKey>, sqlx::Error> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM api_keys WHERE created_by = $1 ORDER BY created_at DESC, id DESC",
+            API_KEY_COLUMNS
+        ))
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(api_key_from_row).collect())
+    }
+
+    #[instrument(skip(self))]
+    async fn deactivate_api_key(&self, actor_user_id: i64, key_id: i64) -> Result<Option<ApiKey>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(row) = sqlx::query(&format!(
+            "UPDATE api_keys SET is_active = FALSE WHERE id = $1 RETURNING {}",
+            API_KEY_COLUMNS
+        ))
+        .bind(key_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            tx.rollback().await?;
+            return Ok(None);
+        };
+        let api_key = api_key_from_row(&row);
+
+        let metadata = serde_json::json!({ "api_key_id": key_id });
+        let now = self.clock.now();
+        insert_audit_log(&mut tx, Some(actor_user_id), "revoke_api_key", Some(api_key.created_by), metadata, now)
+            .await?;
+
+        tx.commit().await?;
+        info!("API key {} revoked by user ID {}", key_id, actor_user_id);
+
+        Ok(Some(api_key))
+    }
+
+    #[cfg(feature = "dev-tools")]
+    #[instrument(skip(self))]
+    async fn count_users_outside_domain(&self, domain: &str) -> Result<i64, sqlx::Error> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM registered_users WHERE email NOT LIKE $1")
+            .bind(format!("%@{}", domain))
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(row.get("count"))
+    }
+
+    #[cfg(feature = "dev-tools")]
+    #[instrument(skip(self, users), fields(count = users.len()))]
+    async fn insert_seed_users(&self, users: &[SeedUser]) -> Result<Vec<(i64, bool)>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut inserted = Vec::with_capacity(users.len());
+
+        for chunk in users.chunks(SEED_ROWS_PER_STATEMENT) {
+            let mut query = QueryBuilder::new(
+                "INSERT INTO registered_users \
+                 (google_id, email, name, registered_at, last_login, is_root, can_invite, invited_by, is_active) ",
+            );
+            query.push_values(chunk, |mut row, user| {
+                row.push_bind(&user.google_id)
+                    .push_bind(&user.email)
+                    .push_bind(&user.name)
+                    .push_bind(user.registered_at)
+                    .push_bind(user.last_login)
+                    .push_bind(false)
+                    .push_bind(user.can_invite)
+                    .push_bind(user.invited_by)
+                    .push_bind(user.is_active);
+            });
+            query.push(" RETURNING id, can_invite");
+            let rows = query.build().fetch_all(&mut *tx).await?;
+            inserted.extend(rows.iter().map(|row| (row.get::<i64, _>("id"), row.get::<bool, _>("can_invite"))));
+        }
+
+        tx.commit().await?;
+        Ok(inserted)
+    }
+
+    #[cfg(feature = "dev-tools")]
+    #[instrument(skip(self, invites), fields(count = invites.len()))]
+    async fn insert_seed_invites(&self, invites: &[SeedInvite]) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut inserted = 0;
+
+        for chunk in invites.chunks(SEED_ROWS_PER_STATEMENT) {
+            let mut query = QueryBuilder::new(
+                "INSERT INTO invite_codes (code, created_by, created_at, expires_at, used_by, used_at, is_active, note) ",
+            );
+            query.push_values(chunk, |mut row, invite| {
+                row.push_bind(&invite.code)
+                    .push_bind(invite.created_by)
+                    .push_bind(invite.created_at)
+                    .push_bind(invite.expires_at)
+                    .push_bind(invite.used_by)
+                    .push_bind(invite.used_at)
+                    .push_bind(invite.is_active)
+                    .push_bind(&invite.note);
+            });
+            inserted += query.build().execute(&mut *tx).await?.rows_affected();
+        }
+
+        tx.commit().await?;
+        Ok(inserted)
+    }
+}
+
+/// 監査ログを記録する（呼び出し側のトランザクション内で実行できるよう接続を受け取る）
+#[instrument(skip(conn))]
+async fn insert_audit_log(
+    conn: &mut PgConnection,
+    actor_user_id: Option<i64>,
+    action: &str,
+    target_user_id: Option<i64>,
+    metadata: serde_json::Value,
+    created_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO audit_log (actor_user_id, action, target_user_id, metadata, created_at)
+        VALUES ($1, $2, $3, $4, $5)
+        "#
+    )
+    .bind(actor_user_id)
+    .bind(action)
+    .bind(target_user_id)
+    .bind(metadata.to_string())
+    .bind(created_at)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
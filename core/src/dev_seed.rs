@@ -0,0 +1,189 @@
+//! 負荷試験用のデータを大量に作る`POST /v1/dev/seed`（`dev-tools`フィーチャーでビルドし、`dev_seed_enabled`の場合のみ）
+//!
+//! 生成したユーザーのメールアドレスは予約済みのドメイン（`SEED_EMAIL_DOMAIN`）にするため、
+//! `DELETE FROM registered_users WHERE email LIKE '%@seed.invalid'`等でまとめて削除できる。
+
+use crate::{
+    auth::RootUser,
+    database::{SeedInvite, SeedUser},
+    error::AppError,
+    extract::{FieldErrors, Validate, ValidatedJson},
+    AppState,
+};
+use anyhow::Context;
+use axum::{extract::State, Json};
+use chrono::{DateTime, Duration, Utc};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// 生成するユーザーのメールアドレスのドメイン（RFC 2606で予約された`.invalid`）
+pub const SEED_EMAIL_DOMAIN: &str = "seed.invalid";
+/// これより多くの（シード以外の）ユーザーがいるデータベースでは実行しない
+const MAX_REAL_USERS: i64 = 5;
+const MAX_SEED_USERS: u32 = 200_000;
+const MAX_SEED_INVITES: u32 = 1_000_000;
+/// 1トランザクションで挿入する行数
+const ROWS_PER_TRANSACTION: usize = 10_000;
+/// 登録日時・招待コードの作成日時を散らす期間
+const SEED_HISTORY_DAYS: i64 = 365;
+
+const GIVEN_NAMES: &[&str] = &[
+    "Haruto", "Yui", "Sota", "Hina", "Ren", "Aoi", "Yuto", "Mei", "Riku", "Sakura", "Emma", "Liam", "Olivia", "Noah",
+    "Ava", "Lucas", "Mia", "Ethan", "Sofia", "Leo",
+];
+const FAMILY_NAMES: &[&str] = &[
+    "Sato", "Suzuki", "Takahashi", "Tanaka", "Watanabe", "Ito", "Yamamoto", "Nakamura", "Kobayashi", "Kato", "Smith",
+    "Johnson", "Brown", "Garcia", "Miller", "Davis", "Martin", "Wilson", "Moore", "Clark",
+];
+const INVITE_NOTES: &[&str] = &["チームメンバー", "イベント参加者", "社内テスト", "外部協力者", "再招待"];
+
+#[derive(Deserialize)]
+pub struct SeedRequest {
+    /// 登録するユーザー数
+    #[serde(default)]
+    pub users: u32,
+    /// 作成する招待コード数
+    #[serde(default)]
+    pub invites: u32,
+}
+
+impl Validate for SeedRequest {
+    fn validate(&self) -> Result<(), FieldErrors> {
+        let mut errors = FieldErrors::default();
+        if self.users == 0 && self.invites == 0 {
+            errors.add("users", "usersかinvitesに1以上を指定してください");
+        }
+        if self.users > MAX_SEED_USERS {
+            errors.add("users", format!("{}以下を指定してください", MAX_SEED_USERS));
+        }
+        if self.invites > MAX_SEED_INVITES {
+            errors.add("invites", format!("{}以下を指定してください", MAX_SEED_INVITES));
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SeedResponse {
+    pub users: u64,
+    pub invites: u64,
+    pub elapsed_ms: u64,
+    /// 挿入したユーザーと招待コードの合計行数 / 経過秒数
+    pub rows_per_second: f64,
+}
+
+/// 過去`SEED_HISTORY_DAYS`日のランダムな日時
+fn random_past(rng: &mut StdRng, now: DateTime<Utc>) -> DateTime<Utc> {
+    now - Duration::seconds(rng.gen_range(0..SEED_HISTORY_DAYS * 24 * 60 * 60))
+}
+
+/// `from`から`now`までのランダムな日時
+fn random_between(rng: &mut StdRng, from: DateTime<Utc>, now: DateTime<Utc>) -> DateTime<Utc> {
+    let seconds = (now - from).num_seconds().max(1);
+    from + Duration::seconds(rng.gen_range(0..seconds))
+}
+
+fn random_user(rng: &mut StdRng, run: &str, index: u32, inviters: &[i64], now: DateTime<Utc>) -> SeedUser {
+    let given = GIVEN_NAMES.choose(rng).expect("names are not empty");
+    let family = FAMILY_NAMES.choose(rng).expect("names are not empty");
+    let registered_at = random_past(rng, now);
+    SeedUser {
+        google_id: format!("seed-{}-{}", run, index),
+        email: format!("{}.{}.{}{}@{}", given, family, run, index, SEED_EMAIL_DOMAIN).to_lowercase(),
+        name: format!("{} {}", given, family),
+        registered_at,
+        last_login: rng.gen_bool(0.8).then(|| random_between(rng, registered_at, now)),
+        can_invite: rng.gen_bool(0.2),
+        invited_by: if rng.gen_bool(0.9) { inviters.choose(rng).copied() } else { None },
+        is_active: rng.gen_bool(0.97),
+    }
+}
+
+fn random_invite(rng: &mut StdRng, created_by: i64, used_by: Option<i64>, now: DateTime<Utc>) -> SeedInvite {
+    let created_at = random_past(rng, now);
+    let expires_at = rng.gen_bool(0.5).then(|| created_at + Duration::hours(rng.gen_range(1..=720)));
+    let used_by = used_by.filter(|_| rng.gen_bool(0.4));
+    // 使用日時は作成から有効期限（過ぎていなければ現在）までの間
+    let used_until = expires_at.map_or(now, |expires_at| expires_at.min(now));
+    let used_at = used_by.map(|_| random_between(rng, created_at, used_until));
+    SeedInvite {
+        code: Uuid::new_v4().to_string(),
+        created_by,
+        created_at,
+        expires_at,
+        used_by,
+        used_at,
+        is_active: used_by.is_some() || rng.gen_bool(0.9),
+        note: rng.gen_bool(0.2).then(|| INVITE_NOTES.choose(rng).expect("notes are not empty").to_string()),
+    }
+}
+
+/// ユーザー・招待コードをまとめて作成し、1秒あたりの挿入行数を返す
+pub async fn seed(
+    RootUser(user): RootUser,
+    State(state): State<AppState>,
+    ValidatedJson(request): ValidatedJson<SeedRequest>,
+) -> Result<Json<SeedResponse>, AppError> {
+    let real_users = state
+        .database
+        .count_users_outside_domain(SEED_EMAIL_DOMAIN)
+        .await
+        .context("Database error during seed")?;
+    if real_users > MAX_REAL_USERS {
+        warn!(user_id = user.id, real_users, "Seed refused: database has real users");
+        return Err(AppError::Validation(format!(
+            "シード以外のユーザーが{}人を超えているデータベースには実行できません",
+            MAX_REAL_USERS
+        )));
+    }
+
+    let started = Instant::now();
+    let now = state.clock.now();
+    let mut rng = StdRng::from_entropy();
+    // 繰り返し実行してもGoogle ID・メールアドレスが重複しないよう、実行ごとに異なる値を付ける
+    let run = Uuid::new_v4().simple().to_string()[..8].to_string();
+
+    let mut inviters = vec![user.id];
+    let mut user_ids = Vec::with_capacity(request.users as usize);
+    let mut next_index = 0;
+    while next_index < request.users {
+        let batch_end = (next_index + ROWS_PER_TRANSACTION as u32).min(request.users);
+        let batch: Vec<SeedUser> =
+            (next_index..batch_end).map(|index| random_user(&mut rng, &run, index, &inviters, now)).collect();
+        let inserted = state.database.insert_seed_users(&batch).await.context("Failed to insert seed users")?;
+        inviters.extend(inserted.iter().filter(|(_, can_invite)| *can_invite).map(|(id, _)| *id));
+        user_ids.extend(inserted.into_iter().map(|(id, _)| id));
+        next_index = batch_end;
+    }
+
+    // 使用済みの招待コードは、なるべく同じユーザーが重ならないよう順に割り当てる
+    user_ids.shuffle(&mut rng);
+    let mut used_by = user_ids.iter().copied().cycle();
+    let mut invites = 0;
+    let mut remaining = request.invites as usize;
+    while remaining > 0 {
+        let batch_size = remaining.min(ROWS_PER_TRANSACTION);
+        let batch: Vec<SeedInvite> = (0..batch_size)
+            .map(|_| {
+                let created_by = *inviters.choose(&mut rng).expect("inviters include the caller");
+                random_invite(&mut rng, created_by, used_by.next(), now)
+            })
+            .collect();
+        invites += state.database.insert_seed_invites(&batch).await.context("Failed to insert seed invites")?;
+        remaining -= batch_size;
+    }
+
+    let elapsed = started.elapsed();
+    let rows = user_ids.len() as u64 + invites;
+    let rows_per_second = rows as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+    info!(user_id = user.id, users = user_ids.len(), invites, ?elapsed, rows_per_second, "Seed data inserted");
+    Ok(Json(SeedResponse {
+        users: user_ids.len() as u64,
+        invites,
+        elapsed_ms: elapsed.as_millis() as u64,
+        rows_per_second,
+    }))
+}
@@ -0,0 +1,287 @@
+use axum::{
+    http::{header::RETRY_AFTER, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use crate::{error_reporting, extract::FieldErrors, request_id};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use utoipa::ToSchema;
+
+/// クライアントが分岐に使うエラーコード（`ErrorResponse.error`に入る）
+///
+/// コードの変更・削除はAPIの破壊的変更になる。追加した場合は`ALL`にも加えること。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    InvalidSession,
+    InvalidIdToken,
+    InvalidMetricsToken,
+    InvalidApiKey,
+    UserNotRegistered,
+    UserSuspended,
+    InsufficientPermission,
+    ApiKeyScopeMissing,
+    RootUserProtected,
+    InviteRequired,
+    InvalidInvite,
+    AuthTokenNotFound,
+    UserNotFound,
+    InviteNotFound,
+    ApiKeyNotFound,
+    InviteNotResendable,
+    InviteAlreadyUsed,
+    IdempotencyConflict,
+    IdempotencyInProgress,
+    CannotTargetSelf,
+    ConfirmationMismatch,
+    TokenExchangeFailed,
+    ValidationFailed,
+    PayloadTooLarge,
+    InviteDailyLimitExceeded,
+    InviteTotalLimitExceeded,
+    TooManyConnections,
+    UpstreamUnavailable,
+    Timeout,
+    InternalError,
+}
+
+impl ErrorCode {
+    pub const ALL: &'static [ErrorCode] = &[
+        ErrorCode::InvalidSession,
+        ErrorCode::InvalidIdToken,
+        ErrorCode::InvalidMetricsToken,
+        ErrorCode::InvalidApiKey,
+        ErrorCode::UserNotRegistered,
+        ErrorCode::UserSuspended,
+        ErrorCode::InsufficientPermission,
+        ErrorCode::ApiKeyScopeMissing,
+        ErrorCode::RootUserProtected,
+        ErrorCode::InviteRequired,
+        ErrorCode::InvalidInvite,
+        ErrorCode::AuthTokenNotFound,
+        ErrorCode::UserNotFound,
+        ErrorCode::InviteNotFound,
+        ErrorCode::ApiKeyNotFound,
+        ErrorCode::InviteNotResendable,
+        ErrorCode::InviteAlreadyUsed,
+        ErrorCode::IdempotencyConflict,
+        ErrorCode::IdempotencyInProgress,
+        ErrorCode::CannotTargetSelf,
+        ErrorCode::ConfirmationMismatch,
+        ErrorCode::TokenExchangeFailed,
+        ErrorCode::ValidationFailed,
+        ErrorCode::PayloadTooLarge,
+        ErrorCode::InviteDailyLimitExceeded,
+        ErrorCode::InviteTotalLimitExceeded,
+        ErrorCode::TooManyConnections,
+        ErrorCode::UpstreamUnavailable,
+        ErrorCode::Timeout,
+        ErrorCode::InternalError,
+    ];
+
+    pub fn status(self) -> StatusCode {
+        match self {
+            ErrorCode::InvalidSession
+            | ErrorCode::InvalidIdToken
+            | ErrorCode::InvalidMetricsToken
+            | ErrorCode::InvalidApiKey => StatusCode::UNAUTHORIZED,
+            ErrorCode::UserNotRegistered
+            | ErrorCode::UserSuspended
+            | ErrorCode::InsufficientPermission
+            | ErrorCode::ApiKeyScopeMissing
+            | ErrorCode::RootUserProtected
+            | ErrorCode::InviteRequired
+            | ErrorCode::InvalidInvite => StatusCode::FORBIDDEN,
+            ErrorCode::AuthTokenNotFound
+            | ErrorCode::UserNotFound
+            | ErrorCode::InviteNotFound
+            | ErrorCode::ApiKeyNotFound => StatusCode::NOT_FOUND,
+            ErrorCode::InviteNotResendable
+            | ErrorCode::InviteAlreadyUsed
+            | ErrorCode::IdempotencyConflict
+            | ErrorCode::IdempotencyInProgress => StatusCode::CONFLICT,
+            ErrorCode::CannotTargetSelf
+            | ErrorCode::ConfirmationMismatch
+            | ErrorCode::TokenExchangeFailed
+            | ErrorCode::ValidationFailed => StatusCode::BAD_REQUEST,
+            ErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::InviteDailyLimitExceeded
+            | ErrorCode::InviteTotalLimitExceeded
+            | ErrorCode::TooManyConnections => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::UpstreamUnavailable => StatusCode::BAD_GATEWAY,
+            ErrorCode::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            ErrorCode::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            ErrorCode::InvalidSession => "セッションが無効または期限切れです",
+            ErrorCode::InvalidIdToken => "Google ID Tokenの検証に失敗しました",
+            ErrorCode::InvalidMetricsToken => "メトリクスのトークンが無効です",
+            ErrorCode::InvalidApiKey => "APIキーが無効、無効化済み、または期限切れです",
+            ErrorCode::UserNotRegistered => "ユーザーが登録されていません",
+            ErrorCode::UserSuspended => "このアカウントは利用停止されています",
+            ErrorCode::InsufficientPermission => "この操作を行う権限がありません",
+            ErrorCode::ApiKeyScopeMissing => "APIキーにこの操作のスコープがありません",
+            ErrorCode::RootUserProtected => "rootユーザーは対象にできません",
+            ErrorCode::InviteRequired => "新規登録には招待コードが必要です",
+            ErrorCode::InvalidInvite => "招待コードが無効、使用済み、または期限切れです",
+            ErrorCode::AuthTokenNotFound => "認証トークンが存在しません",
+            ErrorCode::UserNotFound => "ユーザーが見つかりません",
+            ErrorCode::InviteNotFound => "招待コードが見つかりません",
+            ErrorCode::ApiKeyNotFound => "APIキーが見つかりません",
+            ErrorCode::InviteNotResendable => "使用済み・無効・期限切れの招待コードは再送できません",
+            ErrorCode::InviteAlreadyUsed => "使用済みの招待コードは変更できません",
+            ErrorCode::IdempotencyConflict => "同じIdempotency-Keyが別の内容のリクエストに使われています",
+            ErrorCode::IdempotencyInProgress => "同じIdempotency-Keyのリクエストを処理中です",
+            ErrorCode::CannotTargetSelf => "自分自身を対象にすることはできません",
+            ErrorCode::ConfirmationMismatch => "確認用の文字列が一致しません",
+            ErrorCode::TokenExchangeFailed => "認可コードをトークンに交換できませんでした",
+            ErrorCode::ValidationFailed => "リクエストの内容が不正です",
+            ErrorCode::PayloadTooLarge => "リクエストボディが大きすぎます",
+            ErrorCode::InviteDailyLimitExceeded => "今日作成できる招待コードの上限に達しています",
+            ErrorCode::InviteTotalLimitExceeded => "未使用の招待コードの数が上限に達しています",
+            ErrorCode::TooManyConnections => "同時接続数の上限に達しています",
+            ErrorCode::UpstreamUnavailable => "外部サービスとの通信に失敗しました",
+            ErrorCode::Timeout => "リクエストの処理がタイムアウトしました",
+            ErrorCode::InternalError => "サーバー内部でエラーが発生しました",
+        }
+    }
+}
+
+/// エラーレスポンスのボディ
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ErrorResponse {
+    pub error: ErrorCode,
+    pub message: String,
+    /// 項目ごとの入力エラー（`validation_failed`の場合のみ、項目名 → メッセージ）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub details: Option<serde_json::Value>,
+    /// レスポンスの`X-Request-Id`と同じ値（問い合わせ時にサーバーログと照合する）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// ハンドラー共通のエラー型
+///
+/// 原因（DBエラー等）はレスポンスに含めずログに出力する。
+#[derive(Debug)]
+pub enum AppError {
+    Code(ErrorCode),
+    /// リクエスト全体に関する入力エラー
+    Validation(String),
+    /// 特定の項目に関する入力エラー（`details`に項目名付きで返す）
+    InvalidField {
+        field: &'static str,
+        message: String,
+    },
+    /// リクエストボディの入力チェックに失敗した（422、`details`に項目ごとのメッセージを返す）
+    InvalidFields(FieldErrors),
+    /// 上限に達した（`Retry-After`に再試行できるまでの秒数を付ける）
+    RetryAfter {
+        code: ErrorCode,
+        seconds: u64,
+    },
+    /// 外部サービス（Google等）との通信に失敗した
+    Upstream(anyhow::Error),
+    Internal(anyhow::Error),
+}
+
+impl AppError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            AppError::Code(code) | AppError::RetryAfter { code, .. } => *code,
+            AppError::Validation(_) | AppError::InvalidField { .. } | AppError::InvalidFields(_) => {
+                ErrorCode::ValidationFailed
+            }
+            AppError::Upstream(_) => ErrorCode::UpstreamUnavailable,
+            AppError::Internal(_) => ErrorCode::InternalError,
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::InvalidFields(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            _ => self.code().status(),
+        }
+    }
+
+    pub fn invalid_field(field: &'static str, message: impl Into<String>) -> Self {
+        AppError::InvalidField {
+            field,
+            message: message.into(),
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let code = self.code();
+        let status = self.status();
+        let retry_after = match &self {
+            AppError::RetryAfter { seconds, .. } => Some(*seconds),
+            _ => None,
+        };
+        let (message, details) = match self {
+            AppError::Validation(message) => {
+                warn!("Validation failed: {}", message);
+                (message, None)
+            }
+            AppError::InvalidField { field, message } => {
+                warn!("Validation failed for {}: {}", field, message);
+                (
+                    code.description().to_string(),
+                    Some(serde_json::json!({ field: message })),
+                )
+            }
+            AppError::InvalidFields(errors) => {
+                let errors = errors.into_inner();
+                warn!("Validation failed for {:?}", errors.keys());
+                (code.description().to_string(), Some(serde_json::json!(errors)))
+            }
+            AppError::Upstream(e) => {
+                warn!("Upstream error: {:?}", e);
+                (code.description().to_string(), None)
+            }
+            AppError::Internal(e) => {
+                warn!("Internal error: {:?}", e);
+                error_reporting::capture_internal(&e);
+                (code.description().to_string(), None)
+            }
+            AppError::Code(_) | AppError::RetryAfter { .. } => (code.description().to_string(), None),
+        };
+
+        let body = ErrorResponse {
+            error: code,
+            message,
+            details,
+            request_id: request_id::current(),
+        };
+        let mut response = (status, Json(body)).into_response();
+        if let Some(seconds) = retry_after {
+            response.headers_mut().insert(RETRY_AFTER, HeaderValue::from(seconds));
+        }
+        response
+    }
+}
+
+impl From<ErrorCode> for AppError {
+    fn from(code: ErrorCode) -> Self {
+        AppError::Code(code)
+    }
+}
+
+impl From<anyhow::Error> for AppError {
+    fn from(e: anyhow::Error) -> Self {
+        AppError::Internal(e)
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(e: sqlx::Error) -> Self {
+        AppError::Internal(e.into())
+    }
+}
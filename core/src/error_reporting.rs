@@ -0,0 +1,102 @@
+use crate::{config::Config, request_id::RequestId};
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use sentry::{integrations::tracing::EventFilter, Hub, SentryFutureExt};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tracing::{Level, Subscriber};
+use tracing_subscriber::registry::LookupSpan;
+
+/// `sentry_dsn`が設定されていればSentryのクライアントとパニックフックを初期化する
+///
+/// 返り値は終了まで保持すること（dropした時点で未送信のイベントを送信する）。
+/// イベントはバックグラウンドのスレッドから送信するため、リクエストの処理は送信を待たない。
+pub fn init(config: &Config) -> Option<sentry::ClientInitGuard> {
+    client_options(config).map(sentry::init)
+}
+
+/// `init`で使うクライアントの設定（テストでは`transport`を差し替えて`sentry::init`に渡す）
+pub fn client_options(config: &Config) -> Option<sentry::ClientOptions> {
+    let dsn = config.sentry_dsn.as_deref()?;
+    Some(
+        (
+            dsn,
+            sentry::ClientOptions {
+                release: sentry::release_name!(),
+                // IPアドレス等は送らない（ユーザーIDはハッシュ化して`set_user`で設定する）
+                send_default_pii: false,
+                ..Default::default()
+            },
+        )
+            .into(),
+    )
+}
+
+/// ハンドラーのpanicのログのtarget（Sentryにはパニックフックが送るため、`tracing_layer`はイベントにしない）
+pub const PANIC_TARGET: &str = "patchouli::panic";
+
+/// `error!`をイベントとして、`warn!`・`info!`をパンくずとしてSentryに送るレイヤー
+///
+/// `TraceLayer`の5xxのログ（`response failed`）と`PANIC_TARGET`のログはパンくずにする（500の原因は
+/// `capture_internal`・パニックフックが送るため、イベントにすると同じ障害が2件になる）。
+/// スパンはSentryのトランザクションにしない（性能計測はOpenTelemetryで行う）。
+pub fn tracing_layer<S>() -> sentry::integrations::tracing::SentryLayer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    sentry::integrations::tracing::layer()
+        .event_filter(|metadata| match *metadata.level() {
+            Level::ERROR if metadata.target().starts_with("tower_http::") || metadata.target() == PANIC_TARGET => {
+                EventFilter::Breadcrumb
+            }
+            Level::ERROR => EventFilter::Event,
+            Level::WARN | Level::INFO => EventFilter::Breadcrumb,
+            _ => EventFilter::Ignore,
+        })
+        .span_filter(|_| false)
+}
+
+/// リクエストごとにHubを分け、送信するイベントにリクエストID・メソッド・ルートを付ける
+///
+/// `request_id::propagate`より内側に置くこと（リクエストIDを読むため）。
+pub async fn bind_request(request: Request, next: Next) -> Response {
+    let hub = Arc::new(Hub::new_from_top(Hub::current()));
+    hub.configure_scope(|scope| {
+        if let Some(RequestId(id)) = request.extensions().get::<RequestId>() {
+            scope.set_tag("request_id", id);
+        }
+        if let Some(path) = request.extensions().get::<MatchedPath>() {
+            scope.set_tag("route", path.as_str());
+        }
+        scope.set_tag("method", request.method());
+    });
+    next.run(request).bind_hub(hub).await
+}
+
+/// 処理中のリクエストのイベントにユーザーを付ける
+///
+/// IDはそのままだと連番で推測できるため、サーバーの秘密情報と合わせてハッシュ化する。
+pub fn set_user(config: &Config, user_id: i64) {
+    if config.sentry_dsn.is_none() {
+        return;
+    }
+    let digest = Sha256::new()
+        .chain_update(config.google_client_secret.as_bytes())
+        .chain_update(user_id.to_be_bytes())
+        .finalize();
+    let hashed: String = digest[..8].iter().map(|byte| format!("{:02x}", byte)).collect();
+    sentry::configure_scope(|scope| {
+        scope.set_user(Some(sentry::User {
+            id: Some(hashed),
+            ..Default::default()
+        }))
+    });
+}
+
+/// `AppError::Internal`をSentryに送る（Sentryが無効なら何もしない）
+pub fn capture_internal(error: &anyhow::Error) {
+    sentry::integrations::anyhow::capture_anyhow(error);
+}
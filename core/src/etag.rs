@@ -0,0 +1,69 @@
+use crate::error::AppError;
+use anyhow::anyhow;
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{
+        header::{CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_NONE_MATCH},
+        HeaderValue, Method, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// GETの200レスポンスにボディのハッシュから作った弱いETagを付け、
+/// `If-None-Match`が一致すれば304（ボディなし）を返す
+///
+/// ボディ全体をバッファするため、対象はJSONのレスポンスのみ（NDJSON・CSV等のストリーミングはそのまま返す）。
+pub async fn conditional(request: Request, next: Next) -> Response {
+    if request.method() != Method::GET {
+        return next.run(request).await;
+    }
+    let if_none_match = request.headers().get(IF_NONE_MATCH).cloned();
+
+    let response = next.run(request).await;
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+    if response.status() != StatusCode::OK || !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => return AppError::Internal(anyhow!(e).context("Failed to buffer response body")).into_response(),
+    };
+
+    let etag = weak_etag(&bytes);
+    // 中間のキャッシュには保存させず、ブラウザには毎回再検証させる
+    parts.headers.insert(CACHE_CONTROL, HeaderValue::from_static("private, no-cache"));
+    parts.headers.insert(ETAG, etag.clone());
+
+    if if_none_match.is_some_and(|value| matches(&value, &etag)) {
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(CONTENT_LENGTH);
+        return Response::from_parts(parts, Body::empty());
+    }
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+fn weak_etag(bytes: &[u8]) -> HeaderValue {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    HeaderValue::from_str(&format!("W/\"{:016x}\"", hasher.finish())).expect("hex digits are a valid header value")
+}
+
+/// `If-None-Match`は弱い比較で判定する（`*`またはカンマ区切りのいずれかが一致すればよい）
+fn matches(if_none_match: &HeaderValue, etag: &HeaderValue) -> bool {
+    let Ok(value) = if_none_match.to_str() else {
+        return false;
+    };
+    let etag = etag.to_str().unwrap_or_default().trim_start_matches("W/");
+    value
+        .split(',')
+        .map(|candidate| candidate.trim())
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
@@ -0,0 +1,214 @@
+use crate::{
+    auth::Credential,
+    database::{InviteCode, RegisteredUser},
+    AppState,
+};
+use axum::response::sse::Event;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc,
+    },
+    time::MissedTickBehavior,
+};
+use tracing::info;
+
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// サーバー内で発生したイベント（購読者やWebhookに配信される）
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event")]
+pub enum ServerEvent {
+    #[serde(rename = "user.created")]
+    UserCreated {
+        user_id: i64,
+        email: String,
+        invited_by: Option<i64>,
+        timestamp: DateTime<Utc>,
+    },
+    #[serde(rename = "invite.used")]
+    InviteUsed {
+        invite_id: i64,
+        code: String,
+        created_by: i64,
+        used_by: i64,
+        timestamp: DateTime<Utc>,
+    },
+    #[serde(rename = "invite.resent")]
+    InviteResent {
+        invite_id: i64,
+        code: String,
+        created_by: i64,
+        resent_by: i64,
+        timestamp: DateTime<Utc>,
+    },
+    /// ユーザーの状態（利用停止・rootユーザー・招待権限・metadata）が変更された
+    #[serde(rename = "user.updated")]
+    UserUpdated {
+        user_id: i64,
+        /// 変更の種類（`banned`・`unbanned`・`promoted`・`can_invite`・`metadata`）
+        change: String,
+        updated_by: i64,
+        timestamp: DateTime<Utc>,
+    },
+}
+
+impl ServerEvent {
+    pub fn user_created(user: &RegisteredUser, timestamp: DateTime<Utc>) -> Self {
+        ServerEvent::UserCreated {
+            user_id: user.id,
+            email: user.email.clone(),
+            invited_by: user.invited_by,
+            timestamp,
+        }
+    }
+
+    pub fn invite_used(invite: &InviteCode, used_by: i64, timestamp: DateTime<Utc>) -> Self {
+        ServerEvent::InviteUsed {
+            invite_id: invite.id,
+            code: invite.code.clone(),
+            created_by: invite.created_by,
+            used_by,
+            timestamp,
+        }
+    }
+
+    pub fn user_updated(user_id: i64, change: &str, updated_by: i64, timestamp: DateTime<Utc>) -> Self {
+        ServerEvent::UserUpdated {
+            user_id,
+            change: change.to_string(),
+            updated_by,
+            timestamp,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ServerEvent::UserCreated { .. } => "user.created",
+            ServerEvent::InviteUsed { .. } => "invite.used",
+            ServerEvent::InviteResent { .. } => "invite.resent",
+            ServerEvent::UserUpdated { .. } => "user.updated",
+        }
+    }
+
+    /// このイベントをユーザーに配信してよいか
+    pub fn visible_to(&self, user: &RegisteredUser) -> bool {
+        match self {
+            ServerEvent::UserCreated { .. } => user.is_root,
+            ServerEvent::InviteUsed { created_by, .. } | ServerEvent::InviteResent { created_by, .. } => {
+                user.is_root || *created_by == user.id
+            }
+            ServerEvent::UserUpdated { user_id, .. } => user.is_root || *user_id == user.id,
+        }
+    }
+}
+
+pub fn channel() -> broadcast::Sender<ServerEvent> {
+    let (sender, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+    sender
+}
+
+/// イベントを配信する（購読者がいない場合は何もしない）
+pub fn publish(sender: &broadcast::Sender<ServerEvent>, event: ServerEvent) {
+    if sender.send(event).is_err() {
+        info!("Server event published with no active subscribers");
+    }
+}
+
+/// `receiver`のイベントのうち購読者が閲覧できるものを`sender`に送る（`sender`が閉じられるか、購読者を認証できなくなるまで）
+///
+/// 購読した時点のユーザーで判定し続けると、利用停止・削除・rootの解除の後も閲覧権限のないイベントが届くため、
+/// イベントごとと`recheck`の間隔ごとに`credential`からユーザーを取得し直す（`UserCache`経由）。
+/// ログアウト・APIキーの無効化・利用停止・削除で認証できなくなったら、`sender`をdropしてストリームを閉じる。
+pub(crate) async fn forward(
+    state: AppState,
+    credential: Credential,
+    mut receiver: broadcast::Receiver<ServerEvent>,
+    sender: mpsc::Sender<Result<Event, axum::Error>>,
+    recheck: Duration,
+    _connection: ConnectionGuard,
+) {
+    let mut recheck = tokio::time::interval_at(tokio::time::Instant::now() + recheck, recheck);
+    recheck.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        let event = tokio::select! {
+            _ = sender.closed() => return,
+            _ = recheck.tick() => None,
+            event = receiver.recv() => match event {
+                Ok(event) => Some(event),
+                // 取りこぼしたイベントは送らない
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return,
+            },
+        };
+
+        let user = match credential.user(&state).await {
+            Ok(user) => user,
+            Err(e) => {
+                info!("Closing event stream: subscriber can no longer authenticate ({:?})", e.code());
+                return;
+            }
+        };
+        if let Some(event) = event
+            && event.visible_to(&user)
+            && sender.send(Event::default().event(event.name()).json_data(&event)).await.is_err()
+        {
+            return;
+        }
+    }
+}
+
+/// ユーザーごとのイベントストリーム同時接続数を制限する
+#[derive(Clone)]
+pub struct ConnectionTracker {
+    counts: Arc<Mutex<HashMap<i64, usize>>>,
+    max_per_user: usize,
+}
+
+/// 接続が閉じられたとき（ストリームがdropされたとき）に接続数を戻す
+pub struct ConnectionGuard {
+    user_id: i64,
+    counts: Arc<Mutex<HashMap<i64, usize>>>,
+}
+
+impl ConnectionTracker {
+    pub fn new(max_per_user: usize) -> Self {
+        ConnectionTracker {
+            counts: Arc::new(Mutex::new(HashMap::new())),
+            max_per_user,
+        }
+    }
+
+    pub fn try_acquire(&self, user_id: i64) -> Option<ConnectionGuard> {
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        let count = counts.entry(user_id).or_insert(0);
+        if *count >= self.max_per_user {
+            return None;
+        }
+        *count += 1;
+
+        Some(ConnectionGuard {
+            user_id,
+            counts: self.counts.clone(),
+        })
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(count) = counts.get_mut(&self.user_id) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                counts.remove(&self.user_id);
+            }
+        }
+    }
+}
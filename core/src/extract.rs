@@ -0,0 +1,95 @@
+use crate::error::{AppError, ErrorCode};
+use axum::{
+    async_trait,
+    extract::{FromRequest, FromRequestParts, Request},
+    http::{request::Parts, StatusCode},
+};
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+
+/// 項目ごとの入力エラー（項目名 → メッセージ）
+#[derive(Debug, Default)]
+pub struct FieldErrors(BTreeMap<&'static str, String>);
+
+impl FieldErrors {
+    pub fn add(&mut self, field: &'static str, message: impl Into<String>) {
+        self.0.entry(field).or_insert_with(|| message.into());
+    }
+
+    pub fn into_result(self) -> Result<(), FieldErrors> {
+        if self.0.is_empty() { Ok(()) } else { Err(self) }
+    }
+
+    pub fn into_inner(self) -> BTreeMap<&'static str, String> {
+        self.0
+    }
+}
+
+/// リクエストボディの入力チェック
+pub trait Validate {
+    fn validate(&self) -> Result<(), FieldErrors>;
+}
+
+/// `Json<T>`を読み取り、`Validate`で入力チェックまで行う
+///
+/// 読み取れない場合は400（ボディが上限を超えた場合は413）、入力チェックに失敗した場合は422を返す。
+pub struct ValidatedJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::Json(value) = axum::Json::<T>::from_request(request, state).await.map_err(|e| {
+            if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+                AppError::from(ErrorCode::PayloadTooLarge)
+            } else {
+                AppError::Validation(e.body_text())
+            }
+        })?;
+        value.validate().map_err(AppError::InvalidFields)?;
+        Ok(ValidatedJson(value))
+    }
+}
+
+/// `axum::extract::Path`と同じだが、失敗時はJSONのエラーレスポンスを返す
+pub struct Path<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Path<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::extract::Path(value) = axum::extract::Path::<T>::from_request_parts(parts, state)
+            .await
+            .map_err(|e| AppError::Validation(e.body_text()))?;
+        Ok(Path(value))
+    }
+}
+
+/// `axum::extract::Query`と同じだが、失敗時はJSONのエラーレスポンスを返す
+pub struct Query<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Query<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::extract::Query(value) = axum::extract::Query::<T>::from_request_parts(parts, state)
+            .await
+            .map_err(|e| AppError::Validation(e.body_text()))?;
+        Ok(Query(value))
+    }
+}
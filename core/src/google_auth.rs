@@ -0,0 +1,154 @@
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{
+    decode, decode_header,
+    errors::ErrorKind,
+    jwk::{Jwk, JwkSet},
+    Algorithm, DecodingKey, Validation,
+};
+use reqwest::header::CACHE_CONTROL;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+const GOOGLE_ISSUERS: [&str; 2] = ["accounts.google.com", "https://accounts.google.com"];
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GoogleIdTokenClaims {
+    pub sub: String,
+    pub email: String,
+    #[serde(default)]
+    pub email_verified: bool,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// 有効期限（UNIX時間の秒）
+    pub exp: i64,
+}
+
+/// Googleの公開鍵キャッシュ（Cache-Controlのmax-ageまで再利用する）
+#[derive(Debug, Clone)]
+pub struct JwkCache {
+    pub keys: Vec<Jwk>,
+    pub expires_at: Instant,
+}
+
+#[derive(Debug)]
+pub enum IdTokenError {
+    /// トークンヘッダーのkidに対応する公開鍵が見つからない
+    UnknownKey,
+    /// 署名・有効期限・aud・issの検証に失敗した
+    Invalid(jsonwebtoken::errors::Error),
+    /// メールアドレスがGoogleで未確認
+    EmailNotVerified,
+}
+
+impl std::fmt::Display for IdTokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IdTokenError::UnknownKey => write!(f, "no matching Google signing key"),
+            IdTokenError::Invalid(e) => write!(f, "invalid ID token: {}", e),
+            IdTokenError::EmailNotVerified => write!(f, "email not verified"),
+        }
+    }
+}
+
+/// Cache-Controlヘッダーからmax-ageを取り出す
+fn parse_max_age(cache_control: &str) -> Option<Duration> {
+    cache_control
+        .split(',')
+        .filter_map(|directive| directive.trim().strip_prefix("max-age="))
+        .find_map(|secs| secs.trim().parse().ok())
+        .map(Duration::from_secs)
+}
+
+/// 公開鍵とレスポンスのmax-ageを取得する
+pub async fn fetch_google_jwks(jwks_url: &str) -> Result<(Vec<Jwk>, Option<Duration>), reqwest::Error> {
+    let response = reqwest::Client::new()
+        .get(jwks_url)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let max_age = response
+        .headers()
+        .get(CACHE_CONTROL)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_max_age);
+    let jwk_set: JwkSet = response.json().await?;
+
+    Ok((jwk_set.keys, max_age))
+}
+
+/// キャッシュが有効ならそれを返し、期限切れの場合のみGoogleから再取得する
+pub async fn cached_google_jwks(
+    cache: &RwLock<Option<JwkCache>>,
+    jwks_url: &str,
+    min_ttl: Duration,
+) -> Result<Vec<Jwk>, reqwest::Error> {
+    {
+        let cached = cache.read().await;
+        if let Some(entry) = cached.as_ref()
+            && Instant::now() < entry.expires_at
+        {
+            return Ok(entry.keys.clone());
+        }
+    }
+
+    let mut cached = cache.write().await;
+    // 書き込みロック待ちの間に他のリクエストが更新している可能性がある
+    if let Some(entry) = cached.as_ref()
+        && Instant::now() < entry.expires_at
+    {
+        return Ok(entry.keys.clone());
+    }
+
+    let (keys, max_age) = fetch_google_jwks(jwks_url).await?;
+    // max-ageが0や未指定でもmin_ttlより短い間隔では再取得しない
+    let ttl = max_age.unwrap_or_default().max(min_ttl);
+    info!("Fetched {} Google JWKs, caching for {:?}", keys.len(), ttl);
+
+    *cached = Some(JwkCache {
+        keys: keys.clone(),
+        expires_at: Instant::now() + ttl,
+    });
+
+    Ok(keys)
+}
+
+/// Google One Tapが返すID Tokenを公開鍵で検証し、クレームを取り出す（有効期限は`now`で判定する）
+pub fn verify_google_id_token(
+    id_token: &str,
+    keys: &[Jwk],
+    client_id: &str,
+    now: DateTime<Utc>,
+) -> Result<GoogleIdTokenClaims, IdTokenError> {
+    let header = decode_header(id_token).map_err(IdTokenError::Invalid)?;
+    let kid = header.kid.ok_or(IdTokenError::UnknownKey)?;
+
+    let jwk = keys
+        .iter()
+        .find(|key| key.common.key_id.as_deref() == Some(kid.as_str()))
+        .ok_or(IdTokenError::UnknownKey)?;
+    let decoding_key = DecodingKey::from_jwk(jwk).map_err(IdTokenError::Invalid)?;
+
+    let mut validation = Validation::new(Algorithm::RS256);
+    validation.set_audience(&[client_id]);
+    validation.set_issuer(&GOOGLE_ISSUERS);
+    // jsonwebtokenはシステム時刻で判定するため、expの存在だけを確認させて期限は下で判定する
+    validation.validate_exp = false;
+
+    let claims = decode::<GoogleIdTokenClaims>(id_token, &decoding_key, &validation)
+        .map_err(IdTokenError::Invalid)?
+        .claims;
+
+    if claims.exp < now.timestamp() - validation.leeway as i64 {
+        return Err(IdTokenError::Invalid(ErrorKind::ExpiredSignature.into()));
+    }
+
+    if !claims.email_verified {
+        warn!("Google ID token rejected: email not verified for sub {}", claims.sub);
+        return Err(IdTokenError::EmailNotVerified);
+    }
+
+    Ok(claims)
+}
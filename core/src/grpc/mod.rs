@@ -0,0 +1,412 @@
+//! gRPC API（`core/proto/patchouli.proto`）
+//!
+//! `GRPC_PORT`を設定した場合のみ、REST APIとは別のポートで同じ`AppState`を使って待ち受ける。
+//! 権限のチェック・キャッシュの無効化はRESTの対応するハンドラーと揃えること。
+
+pub mod proto;
+
+use crate::{
+    auth,
+    database::{InviteCode, InviteFilterParams, RegisteredUser, UserFilterParams},
+    error::{AppError, ErrorCode},
+    error_reporting, AppState,
+};
+use anyhow::{anyhow, Context as _};
+use axum::http::StatusCode;
+use chrono::{DateTime, SecondsFormat, Utc};
+use proto::*;
+use std::{convert::Infallible, future::Future};
+use tokio::net::TcpListener;
+use tonic::{
+    body::BoxBody,
+    codec::ProstCodec,
+    codegen::{http, Body, BoxFuture, Context, InterceptedService, Poll, Service, StdError},
+    metadata::MetadataValue,
+    server::{Grpc, NamedService},
+    transport::{server::TcpIncoming, Server},
+    Code, Status,
+};
+use tracing::{info, warn};
+
+/// エラー時に`ErrorResponse.error`と同じエラーコードを入れるメタデータ
+pub const ERROR_CODE_METADATA: &str = "patchouli-error-code";
+
+/// gRPCサーバーを起動する（`shutdown`が完了すると新しい接続を受け付けず、処理中のリクエストを待って終了する）
+pub async fn serve(listener: TcpListener, state: AppState, shutdown: impl Future<Output = ()>) -> anyhow::Result<()> {
+    let incoming = TcpIncoming::from_listener(listener, true, None).map_err(|e| anyhow!(e))?;
+    Server::builder()
+        // RESTのTraceLayerと同じく、認証後に`user_id`を記録する
+        .trace_fn(|request| tracing::info_span!("grpc", path = %request.uri().path(), user_id = tracing::field::Empty))
+        .add_service(InterceptedService::new(UserService::new(state.clone()), require_session))
+        .add_service(InterceptedService::new(InviteService::new(state.clone()), require_session))
+        .add_service(AuthService::new(state))
+        .serve_with_incoming_shutdown(incoming, shutdown)
+        .await
+        .context("gRPC server error")
+}
+
+/// `authorization: Bearer <session_id>`のセッションID（`require_session`がextensionsに入れる）
+#[derive(Clone)]
+struct SessionId(String);
+
+/// 認証が必要なサービスのインターセプター（RESTの`session_id`クエリに相当）
+// 戻り値の型はtonicの`Interceptor`で決まっている
+#[allow(clippy::result_large_err)]
+fn require_session(mut request: tonic::Request<()>) -> Result<tonic::Request<()>, Status> {
+    let session_id = request
+        .metadata()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .filter(|session_id| !session_id.is_empty())
+        .ok_or_else(|| AppError::Validation("authorizationに`Bearer <session_id>`を指定してください".to_string()))?
+        .to_string();
+    request.extensions_mut().insert(SessionId(session_id));
+    Ok(request)
+}
+
+/// ログイン中のユーザー（RESTの`AuthUser`と同じく、未登録・利用停止中なら拒否する）
+async fn current_user<T>(state: &AppState, request: &tonic::Request<T>) -> Result<RegisteredUser, Status> {
+    let SessionId(session_id) = request
+        .extensions()
+        .get::<SessionId>()
+        .ok_or_else(|| Status::internal("session interceptor is not installed"))?;
+    Ok(auth::user_for_session(state, session_id).await?)
+}
+
+/// RESTの`RootUser`に相当
+async fn root_user<T>(state: &AppState, request: &tonic::Request<T>) -> Result<RegisteredUser, Status> {
+    let user = current_user(state, request).await?;
+    if !user.is_root {
+        // メソッド名はスパンの`path`に記録されている
+        warn!("User {} attempted to call a root-only gRPC method without root permission", user.email);
+        return Err(AppError::from(ErrorCode::InsufficientPermission).into());
+    }
+    Ok(user)
+}
+
+impl From<AppError> for Status {
+    /// HTTPのステータスに対応するgRPCのコードにし、エラーコードをメタデータに入れる
+    fn from(error: AppError) -> Self {
+        let code = error.code();
+        let grpc_code = match error.status() {
+            StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => Code::InvalidArgument,
+            StatusCode::UNAUTHORIZED => Code::Unauthenticated,
+            StatusCode::FORBIDDEN => Code::PermissionDenied,
+            StatusCode::NOT_FOUND => Code::NotFound,
+            StatusCode::CONFLICT => Code::FailedPrecondition,
+            StatusCode::PAYLOAD_TOO_LARGE | StatusCode::TOO_MANY_REQUESTS => Code::ResourceExhausted,
+            StatusCode::BAD_GATEWAY => Code::Unavailable,
+            StatusCode::GATEWAY_TIMEOUT => Code::DeadlineExceeded,
+            _ => Code::Internal,
+        };
+        let message = match error {
+            AppError::Validation(message) | AppError::InvalidField { message, .. } => message,
+            AppError::Upstream(e) => {
+                warn!("Upstream error: {:?}", e);
+                code.description().to_string()
+            }
+            AppError::Internal(e) => {
+                warn!("Internal error: {:?}", e);
+                error_reporting::capture_internal(&e);
+                code.description().to_string()
+            }
+            _ => code.description().to_string(),
+        };
+
+        let mut status = Status::new(grpc_code, message);
+        if let Ok(serde_json::Value::String(code)) = serde_json::to_value(code)
+            && let Ok(value) = MetadataValue::try_from(code)
+        {
+            status.metadata_mut().insert(ERROR_CODE_METADATA, value);
+        }
+        status
+    }
+}
+
+/// 1つのメソッドを処理する（リクエストのデコード・レスポンスのエンコードはtonicが行う）
+async fn unary<B, Req, Resp, F, Fut>(state: AppState, request: http::Request<B>, handler: F) -> http::Response<BoxBody>
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send,
+    Req: prost::Message + Default + Send + 'static,
+    Resp: prost::Message + Send + 'static,
+    F: Fn(AppState, tonic::Request<Req>) -> Fut,
+    Fut: Future<Output = Result<Resp, Status>>,
+{
+    let service = tower::service_fn(move |request| {
+        let response = handler(state.clone(), request);
+        async move { response.await.map(tonic::Response::new) }
+    });
+    Grpc::new(ProstCodec::<Resp, Req>::default()).unary(service, request).await
+}
+
+/// サービスのメソッド名とハンドラーを対応付ける（tonic-buildが生成するサーバーの代わり）
+macro_rules! grpc_service {
+    ($service:ident, $name:literal, { $($method:literal => $handler:path),* $(,)? }) => {
+        #[derive(Clone)]
+        pub struct $service {
+            state: AppState,
+        }
+
+        impl $service {
+            pub fn new(state: AppState) -> Self {
+                Self { state }
+            }
+        }
+
+        impl NamedService for $service {
+            const NAME: &'static str = $name;
+        }
+
+        impl<B> Service<http::Request<B>> for $service
+        where
+            B: Body + Send + 'static,
+            B::Error: Into<StdError> + Send + 'static,
+        {
+            type Response = http::Response<BoxBody>;
+            type Error = Infallible;
+            type Future = BoxFuture<Self::Response, Self::Error>;
+
+            fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn call(&mut self, request: http::Request<B>) -> Self::Future {
+                let state = self.state.clone();
+                let method = request
+                    .uri()
+                    .path()
+                    .strip_prefix(concat!("/", $name, "/"))
+                    .unwrap_or_default()
+                    .to_string();
+                Box::pin(async move {
+                    Ok(match method.as_str() {
+                        $($method => unary(state, request, $handler).await,)*
+                        _ => Status::unimplemented(format!("{}/{} is not implemented", $name, method)).to_http(),
+                    })
+                })
+            }
+        }
+    };
+}
+
+grpc_service!(UserService, "patchouli.v1.UserService", {
+    "GetUser" => get_user,
+    "ListUsers" => list_users,
+    "DeleteUser" => delete_user,
+});
+
+grpc_service!(InviteService, "patchouli.v1.InviteService", {
+    "CreateInvite" => create_invite,
+    "ListInvites" => list_invites,
+    "RevokeInvite" => revoke_invite,
+});
+
+grpc_service!(AuthService, "patchouli.v1.AuthService", {
+    "IntrospectToken" => introspect_token,
+});
+
+/// RESTと同じRFC 3339形式（UTCは`Z`）
+fn timestamp(value: &DateTime<Utc>) -> String {
+    value.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+fn user_message(user: RegisteredUser) -> User {
+    User {
+        id: user.id,
+        email: user.email,
+        name: user.name,
+        registered_at: timestamp(&user.registered_at),
+        last_login: user.last_login.as_ref().map(timestamp),
+        is_root: user.is_root,
+        can_invite: user.can_invite,
+        invited_by: user.invited_by,
+        is_active: user.is_active,
+    }
+}
+
+fn invite_message(state: &AppState, invite: InviteCode) -> Invite {
+    Invite {
+        id: invite.id,
+        invite_url: state.config.invite_url(&invite.code),
+        code: invite.code,
+        created_by: invite.created_by,
+        created_at: timestamp(&invite.created_at),
+        expires_at: invite.expires_at.as_ref().map(timestamp),
+        used_by: invite.used_by,
+        used_at: invite.used_at.as_ref().map(timestamp),
+        is_active: invite.is_active,
+        note: invite.note,
+    }
+}
+
+async fn get_user(state: AppState, request: tonic::Request<GetUserRequest>) -> Result<User, Status> {
+    let user = current_user(&state, &request).await?;
+    let user_id = request.get_ref().user_id;
+    // 本人またはrootユーザーのみ
+    if user_id != user.id && !user.is_root {
+        warn!("User {} attempted to get user {} without permission", user.email, user_id);
+        return Err(AppError::from(ErrorCode::InsufficientPermission).into());
+    }
+
+    let target = state
+        .database
+        .get_user_by_id(user_id)
+        .await
+        .context("Database error during gRPC user lookup")
+        .map_err(AppError::Internal)?
+        .ok_or(AppError::from(ErrorCode::UserNotFound))?;
+    Ok(user_message(target))
+}
+
+async fn list_users(state: AppState, request: tonic::Request<ListUsersRequest>) -> Result<ListUsersResponse, Status> {
+    let user = root_user(&state, &request).await?;
+    let users = state
+        .database
+        .get_all_registered_users(&UserFilterParams::default())
+        .await
+        .context("Failed to get users list")
+        .map_err(AppError::Internal)?;
+
+    info!(user_id = user.id, "Root user listed users over gRPC");
+    Ok(ListUsersResponse {
+        users: users.into_iter().map(user_message).collect(),
+    })
+}
+
+async fn delete_user(state: AppState, request: tonic::Request<DeleteUserRequest>) -> Result<DeleteUserResponse, Status> {
+    let user = root_user(&state, &request).await?;
+    let user_id = request.get_ref().user_id;
+    if user_id == user.id {
+        return Err(AppError::from(ErrorCode::CannotTargetSelf).into());
+    }
+
+    let target = state
+        .database
+        .get_user_by_id(user_id)
+        .await
+        .context("Database error during gRPC user deletion")
+        .map_err(AppError::Internal)?
+        .ok_or(AppError::from(ErrorCode::UserNotFound))?;
+    if target.is_root {
+        warn!("User {} attempted to delete root user {} over gRPC", user.email, target.email);
+        return Err(AppError::from(ErrorCode::RootUserProtected).into());
+    }
+
+    // 確認の後に削除された場合も見つからない扱いにする
+    let deleted = state
+        .database
+        .delete_user(user_id)
+        .await
+        .with_context(|| format!("Database error during user deletion - ID: {}", user_id))
+        .map_err(AppError::Internal)?;
+    if !deleted {
+        return Err(AppError::from(ErrorCode::UserNotFound).into());
+    }
+    state.user_cache.invalidate_id(user_id);
+    state.invite_cache.invalidate_created_by(user_id);
+
+    info!(user_id = user.id, target_user_id = user_id, "Root user deleted user over gRPC");
+    Ok(DeleteUserResponse {})
+}
+
+async fn create_invite(state: AppState, request: tonic::Request<CreateInviteRequest>) -> Result<Invite, Status> {
+    let user = current_user(&state, &request).await?;
+    if !user.can_invite {
+        warn!("User {} attempted to create invite code without permission", user.email);
+        return Err(AppError::from(ErrorCode::InsufficientPermission).into());
+    }
+    crate::check_invite_daily_limit(&state, &user).await?;
+    crate::check_invite_total_limit(&state, &user).await?;
+
+    let invite = state
+        .database
+        .create_invite_code(user.id)
+        .await
+        .context("Failed to create invite code")
+        .map_err(AppError::Internal)?;
+
+    info!(user_id = user.id, invite_id = invite.id, "Invite code created over gRPC");
+    Ok(invite_message(&state, invite))
+}
+
+async fn list_invites(state: AppState, request: tonic::Request<ListInvitesRequest>) -> Result<ListInvitesResponse, Status> {
+    let user = current_user(&state, &request).await?;
+    let all = request.get_ref().all;
+    // 全ユーザーの招待コードを参照できるのはrootユーザーのみ
+    if all && !user.is_root {
+        warn!("User {} attempted to list all invite codes without root permission", user.email);
+        return Err(AppError::from(ErrorCode::InsufficientPermission).into());
+    }
+
+    let filter = InviteFilterParams {
+        created_by: if all { None } else { Some(user.id) },
+        ..Default::default()
+    };
+    let invites = state
+        .database
+        .get_invite_codes(&filter)
+        .await
+        .context("Failed to get invite codes")
+        .map_err(AppError::Internal)?;
+    Ok(ListInvitesResponse {
+        invites: invites.into_iter().map(|invite| invite_message(&state, invite)).collect(),
+    })
+}
+
+async fn revoke_invite(state: AppState, request: tonic::Request<RevokeInviteRequest>) -> Result<Invite, Status> {
+    let user = current_user(&state, &request).await?;
+    let invite_id = request.get_ref().invite_id;
+    let invite = state
+        .database
+        .get_invite_code_by_id(invite_id)
+        .await
+        .context("Database error during invite revocation")
+        .map_err(AppError::Internal)?
+        .ok_or(AppError::from(ErrorCode::InviteNotFound))?;
+
+    // 作成者本人またはrootユーザーのみ
+    if invite.created_by != user.id && !user.is_root {
+        warn!("User {} attempted to revoke invite {} without permission", user.email, invite_id);
+        return Err(AppError::from(ErrorCode::InsufficientPermission).into());
+    }
+
+    // 確認の後に使用された場合も使用済みとして扱う
+    let invite = state
+        .database
+        .deactivate_invite(invite_id)
+        .await
+        .context("Failed to deactivate invite code")
+        .map_err(AppError::Internal)?
+        .ok_or(AppError::from(ErrorCode::InviteAlreadyUsed))?;
+    state.invite_cache.invalidate(&invite.code).await;
+
+    info!(user_id = user.id, invite_id, "Invite revoked over gRPC");
+    Ok(invite_message(&state, invite))
+}
+
+/// セッションIDが有効かを返す（認証不要。無効なセッションはエラーではなく`active: false`）
+async fn introspect_token(
+    state: AppState,
+    request: tonic::Request<IntrospectTokenRequest>,
+) -> Result<IntrospectTokenResponse, Status> {
+    match auth::user_for_session(&state, &request.get_ref().token).await {
+        Ok(user) => Ok(IntrospectTokenResponse {
+            active: true,
+            user: Some(user_message(user)),
+        }),
+        Err(e)
+            if matches!(
+                e.code(),
+                ErrorCode::InvalidSession | ErrorCode::UserNotRegistered | ErrorCode::UserSuspended
+            ) =>
+        {
+            Ok(IntrospectTokenResponse {
+                active: false,
+                user: None,
+            })
+        }
+        Err(e) => Err(e.into()),
+    }
+}
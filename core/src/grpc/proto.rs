@@ -0,0 +1,106 @@
+//! `core/proto/patchouli.proto`のメッセージ（タグ番号はprotoファイルと揃える）
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct User {
+    #[prost(int64, tag = "1")]
+    pub id: i64,
+    #[prost(string, tag = "2")]
+    pub email: String,
+    #[prost(string, tag = "3")]
+    pub name: String,
+    #[prost(string, tag = "4")]
+    pub registered_at: String,
+    #[prost(string, optional, tag = "5")]
+    pub last_login: Option<String>,
+    #[prost(bool, tag = "6")]
+    pub is_root: bool,
+    #[prost(bool, tag = "7")]
+    pub can_invite: bool,
+    #[prost(int64, optional, tag = "8")]
+    pub invited_by: Option<i64>,
+    #[prost(bool, tag = "9")]
+    pub is_active: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Invite {
+    #[prost(int64, tag = "1")]
+    pub id: i64,
+    #[prost(string, tag = "2")]
+    pub code: String,
+    #[prost(int64, tag = "3")]
+    pub created_by: i64,
+    #[prost(string, tag = "4")]
+    pub created_at: String,
+    #[prost(string, optional, tag = "5")]
+    pub expires_at: Option<String>,
+    #[prost(int64, optional, tag = "6")]
+    pub used_by: Option<i64>,
+    #[prost(string, optional, tag = "7")]
+    pub used_at: Option<String>,
+    #[prost(bool, tag = "8")]
+    pub is_active: bool,
+    #[prost(string, optional, tag = "9")]
+    pub note: Option<String>,
+    #[prost(string, tag = "10")]
+    pub invite_url: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetUserRequest {
+    #[prost(int64, tag = "1")]
+    pub user_id: i64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListUsersRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListUsersResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub users: Vec<User>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DeleteUserRequest {
+    #[prost(int64, tag = "1")]
+    pub user_id: i64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DeleteUserResponse {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CreateInviteRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListInvitesRequest {
+    #[prost(bool, tag = "1")]
+    pub all: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListInvitesResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub invites: Vec<Invite>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RevokeInviteRequest {
+    #[prost(int64, tag = "1")]
+    pub invite_id: i64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct IntrospectTokenRequest {
+    #[prost(string, tag = "1")]
+    pub token: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct IntrospectTokenResponse {
+    #[prost(bool, tag = "1")]
+    pub active: bool,
+    #[prost(message, optional, tag = "2")]
+    pub user: Option<User>,
+}
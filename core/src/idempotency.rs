@@ -0,0 +1,160 @@
+use crate::{
+    auth::Credential,
+    database::{Database, IdempotencyState, StoredResponse},
+    error::{AppError, ErrorCode},
+    AppState,
+};
+use anyhow::anyhow;
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{
+        header::{CACHE_CONTROL, CONTENT_TYPE},
+        HeaderValue, Method, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http_body_util::LengthLimitError;
+use sha2::{Digest, Sha256};
+use std::error::Error as _;
+use tracing::warn;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+/// 保存したレスポンスを返した場合に付けるヘッダー
+const REPLAYED_HEADER: &str = "idempotent-replayed";
+const MAX_KEY_LEN: usize = 255;
+
+/// POSTリクエストの`Idempotency-Key`ヘッダーを処理する（ヘッダーがなければ何もしない）
+///
+/// 同じキー・同じ内容の再送には保存したレスポンスを返し、内容が違えば409（`idempotency_conflict`）、
+/// 最初のリクエストが処理中なら409（`idempotency_in_progress`）を返す。
+/// キーはリクエストが示す資格情報（セッションID・APIキーのハッシュ。どちらもなければ未認証として1つ）ごとに別のものとして扱い、
+/// 保存したレスポンスは同じ資格情報での再送にだけ返す。内容はパス・クエリ・ボディで比較する。
+/// 5xxのレスポンスと`Cache-Control: no-store`のレスポンス（作成したAPIキー等）は保存せず、再送時にもう一度処理する。
+pub async fn enforce(State(state): State<AppState>, request: Request, next: Next) -> Result<Response, AppError> {
+    if request.method() != Method::POST {
+        return Ok(next.run(request).await);
+    }
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(next.run(request).await);
+    };
+    let key = key
+        .to_str()
+        .ok()
+        .filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LEN)
+        .ok_or_else(|| {
+            AppError::Validation(format!("Idempotency-Keyは1〜{}文字の英数字・記号で指定してください", MAX_KEY_LEN))
+        })?
+        .to_string();
+
+    // 上限は外側のRequestBodyLimitLayerで制限済み
+    let (parts, body) = request.into_parts();
+    let principal = Credential::presented(&parts, &state).map_or_else(|| "anonymous".to_string(), |c| c.fingerprint());
+    let key = format!("{}:{}", principal, key);
+    let body = to_bytes(body, usize::MAX).await.map_err(|e| {
+        if std::iter::successors(e.source(), |&source| source.source()).any(|source| source.is::<LengthLimitError>()) {
+            AppError::from(ErrorCode::PayloadTooLarge)
+        } else {
+            AppError::Validation("リクエストボディを読み取れませんでした".to_string())
+        }
+    })?;
+    let request_hash = {
+        let path_and_query = parts.uri.path_and_query().map(|pq| pq.as_str()).unwrap_or_default();
+        let digest = Sha256::new()
+            .chain_update(path_and_query.as_bytes())
+            .chain_update([0])
+            .chain_update(&body)
+            .finalize();
+        digest.iter().map(|byte| format!("{:02x}", byte)).collect::<String>()
+    };
+
+    let expires_at = state.clock.now() + state.config.idempotency_key_ttl();
+    let stored = match state
+        .database
+        .begin_idempotency_key(&key, &request_hash, expires_at)
+        .await
+        .map_err(|e| AppError::Internal(anyhow!(e).context("Database error during idempotency key check")))?
+    {
+        IdempotencyState::Started => None,
+        IdempotencyState::InProgress => return Err(ErrorCode::IdempotencyInProgress.into()),
+        IdempotencyState::Mismatch => return Err(ErrorCode::IdempotencyConflict.into()),
+        IdempotencyState::Completed(stored) => Some(stored),
+    };
+    if let Some(stored) = stored {
+        return Ok(replay(stored));
+    }
+
+    // タイムアウト等で処理が中断された場合もキーを解放する
+    let mut guard = PendingKey {
+        database: state.database.clone(),
+        key: Some(key),
+    };
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+
+    let (parts, body) = response.into_parts();
+    let body = to_bytes(body, usize::MAX)
+        .await
+        .map_err(|e| AppError::Internal(anyhow!(e).context("Failed to buffer response for idempotency key")))?;
+    let no_store = parts
+        .headers
+        .get_all(CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| value.split(',').any(|directive| directive.trim().eq_ignore_ascii_case("no-store")));
+    if !parts.status.is_server_error() && !no_store {
+        let stored = StoredResponse {
+            status: parts.status.as_u16(),
+            content_type: parts
+                .headers
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+            body: body.to_vec(),
+        };
+        let key = guard.key.take().unwrap_or_default();
+        if let Err(e) = state.database.complete_idempotency_key(&key, &stored).await {
+            warn!("Failed to store response for idempotency key: {:?}", e);
+            guard.key = Some(key);
+        }
+    } else if no_store {
+        // 再送がすぐに処理されるよう、レスポンスを返す前にキーを解放する
+        let key = guard.key.take().unwrap_or_default();
+        if let Err(e) = state.database.release_idempotency_key(&key).await {
+            warn!("Failed to release idempotency key: {:?}", e);
+        }
+    }
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let status = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+    let mut response = (status, stored.body).into_response();
+    let headers = response.headers_mut();
+    headers.remove(CONTENT_TYPE);
+    if let Some(content_type) = stored.content_type.and_then(|value| HeaderValue::from_str(&value).ok()) {
+        headers.insert(CONTENT_TYPE, content_type);
+    }
+    headers.insert(REPLAYED_HEADER, HeaderValue::from_static("true"));
+    response
+}
+
+/// 結果を保存できなかった処理中のキーをdrop時に削除する
+struct PendingKey {
+    database: Database,
+    key: Option<String>,
+}
+
+impl Drop for PendingKey {
+    fn drop(&mut self) {
+        let Some(key) = self.key.take() else {
+            return;
+        };
+        let database = self.database.clone();
+        tokio::spawn(async move {
+            if let Err(e) = database.release_idempotency_key(&key).await {
+                warn!("Failed to release idempotency key: {:?}", e);
+            }
+        });
+    }
+}
@@ -0,0 +1,41 @@
+//! セッションID・招待コード・リクエストIDの採番（テストで固定の値にできるよう`AppState`・`Database`に差し替え可能な生成器を持たせる）
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use uuid::Uuid;
+
+pub trait IdGenerator: Send + Sync {
+    fn new_id(&self) -> Uuid;
+}
+
+pub type SharedIdGenerator = Arc<dyn IdGenerator>;
+
+/// ランダムなUUID v4（本番用）
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RandomIds;
+
+impl IdGenerator for RandomIds {
+    fn new_id(&self) -> Uuid {
+        Uuid::new_v4()
+    }
+}
+
+/// `00000000-0000-0000-0000-000000000001`から順に採番する（テスト用）
+#[derive(Debug, Default)]
+pub struct SequentialIds {
+    next: AtomicU64,
+}
+
+impl SequentialIds {
+    pub fn new() -> Self {
+        SequentialIds::default()
+    }
+}
+
+impl IdGenerator for SequentialIds {
+    fn new_id(&self) -> Uuid {
+        Uuid::from_u128(u128::from(self.next.fetch_add(1, Ordering::Relaxed) + 1))
+    }
+}
@@ -0,0 +1,58 @@
+use crate::clock::SharedClock;
+use crate::database::{Database, InviteCode};
+use moka::future::Cache;
+use std::time::Duration;
+use tracing::warn;
+
+const INVITE_CACHE_MAX_ENTRIES: u64 = 10_000;
+
+/// `validate_invite_code`の結果のキャッシュ（招待コード → 有効な招待コード、無効なら`None`）
+///
+/// 登録画面から同じコードが繰り返し検証されるため、無効という結果もキャッシュする。
+/// 招待コードを使用・変更・無効化した箇所では`invalidate`・`invalidate_created_by`を呼ぶこと。
+#[derive(Clone)]
+pub struct InviteCodeCache {
+    invites: Cache<String, Option<InviteCode>>,
+    clock: SharedClock,
+}
+
+impl InviteCodeCache {
+    pub fn new(ttl: Duration, clock: SharedClock) -> Self {
+        InviteCodeCache {
+            invites: Cache::builder()
+                .max_capacity(INVITE_CACHE_MAX_ENTRIES)
+                .time_to_live(ttl)
+                .support_invalidation_closures()
+                .build(),
+            clock,
+        }
+    }
+
+    pub async fn validate(&self, database: &Database, code: &str) -> Result<Option<InviteCode>, sqlx::Error> {
+        if let Some(cached) = self.invites.get(code).await {
+            // キャッシュ中に有効期限を過ぎた場合は無効として扱う
+            let now = self.clock.now();
+            return Ok(cached.filter(|invite| invite.expires_at.is_none_or(|expires_at| now <= expires_at)));
+        }
+
+        let invite = database.validate_invite_code(code).await?;
+        self.invites.insert(code.to_string(), invite.clone()).await;
+        Ok(invite)
+    }
+
+    pub async fn invalidate(&self, code: &str) {
+        self.invites.invalidate(code).await;
+    }
+
+    /// 指定ユーザーが作成した招待コードをすべて無効化する（利用停止・削除時）
+    pub fn invalidate_created_by(&self, user_id: i64) {
+        let result = self
+            .invites
+            .invalidate_entries_if(move |_, invite| invite.as_ref().is_some_and(|invite| invite.created_by == user_id));
+        if let Err(e) = result {
+            // 発生するのはsupport_invalidation_closuresを指定し忘れた場合のみ
+            warn!("Failed to invalidate cached invites of user {}: {:?}", user_id, e);
+            self.invites.invalidate_all();
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use serde_json::{Map, Value};
+
+/// RFC 7396のJSON Merge Patchを`base`に適用する
+///
+/// パッチがオブジェクトの場合はキーごとに再帰的にマージし、値が`null`のキーは削除する。
+/// オブジェクト以外（配列を含む）のパッチは`base`をそのまま置き換える。
+pub fn json_merge_patch(base: &mut Value, patch: Value) {
+    let Value::Object(patch) = patch else {
+        *base = patch;
+        return;
+    };
+    if !base.is_object() {
+        *base = Value::Object(Map::new());
+    }
+    let Value::Object(base) = base else {
+        unreachable!("base was just replaced with an object");
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            base.remove(&key);
+        } else {
+            json_merge_patch(base.entry(key).or_insert(Value::Null), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::json_merge_patch;
+    use serde_json::{json, Value};
+
+    fn merged(mut base: Value, patch: Value) -> Value {
+        json_merge_patch(&mut base, patch);
+        base
+    }
+
+    #[test]
+    fn null_removes_keys() {
+        assert_eq!(merged(json!({"a": "b", "c": "d"}), json!({"a": null})), json!({"c": "d"}));
+        // 存在しないキーの削除は何もしない
+        assert_eq!(merged(json!({"a": "b"}), json!({"x": null})), json!({"a": "b"}));
+        // 新しく追加するオブジェクトの中のnullも残さない
+        assert_eq!(merged(json!({}), json!({"a": {"bb": {"ccc": null}}})), json!({"a": {"bb": {}}}));
+    }
+
+    #[test]
+    fn objects_are_merged_recursively() {
+        let base = json!({"title": "Goodbye!", "author": {"givenName": "John", "familyName": "Doe"}, "tags": ["a"]});
+        let patch = json!({"title": "Hello!", "phoneNumber": "+01-123-456-7890", "author": {"familyName": null}});
+        assert_eq!(
+            merged(base, patch),
+            json!({
+                "title": "Hello!",
+                "author": {"givenName": "John"},
+                "tags": ["a"],
+                "phoneNumber": "+01-123-456-7890",
+            })
+        );
+        assert_eq!(merged(json!({"a": {"b": "c"}}), json!({"a": {"b": "d", "c": null}})), json!({"a": {"b": "d"}}));
+    }
+
+    #[test]
+    fn non_object_patches_replace_the_target() {
+        assert_eq!(merged(json!({"a": "b"}), json!(["c"])), json!(["c"]));
+        assert_eq!(merged(json!({"a": "foo"}), json!(null)), json!(null));
+        assert_eq!(merged(json!({"a": "foo"}), json!("bar")), json!("bar"));
+        // 配列は要素ごとにマージせず置き換える
+        assert_eq!(merged(json!({"a": [{"b": "c"}]}), json!({"a": [1]})), json!({"a": [1]}));
+        assert_eq!(merged(json!({"a": ["b"]}), json!({"a": "c"})), json!({"a": "c"}));
+    }
+
+    #[test]
+    fn non_object_targets_are_replaced_by_object_patches() {
+        assert_eq!(merged(json!(["a", "b"]), json!({"a": "b"})), json!({"a": "b"}));
+        assert_eq!(merged(json!({"a": "b"}), json!({"a": {"bb": "c"}})), json!({"a": {"bb": "c"}}));
+        assert_eq!(merged(json!(null), json!({"a": null, "b": 1})), json!({"b": 1}));
+        assert_eq!(merged(json!({"e": null}), json!({"a": 1})), json!({"e": null, "a": 1}));
+    }
+
+    #[test]
+    fn empty_patch_leaves_the_target_unchanged() {
+        let base = json!({"a": {"b": [1, 2]}, "c": null});
+        assert_eq!(merged(base.clone(), json!({})), base);
+    }
+}
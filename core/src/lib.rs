@@ -0,0 +1,3246 @@
+use axum::{
+    error_handling::HandleErrorLayer,
+    extract::{DefaultBodyLimit, Request, State},
+    http::{
+        header::{CACHE_CONTROL, CONTENT_TYPE, LINK},
+        HeaderName, HeaderValue, StatusCode,
+    },
+    middleware::{self, Next},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Html, IntoResponse, Json, Redirect, Response,
+    },
+    routing::get,
+    BoxError, Extension, Router,
+};
+mod auth;
+pub mod cli;
+pub mod client_ip;
+pub mod clock;
+pub mod config;
+pub mod database;
+#[cfg(feature = "dev-tools")]
+mod dev_seed;
+pub mod error;
+pub mod error_reporting;
+mod etag;
+mod events;
+mod extract;
+pub mod google_auth;
+pub mod grpc;
+mod idempotency;
+pub mod ids;
+mod invite_cache;
+mod json_utils;
+mod list_format;
+mod openapi;
+mod prometheus;
+mod request_id;
+pub mod routes;
+pub mod telemetry;
+pub mod tls;
+pub mod unix_socket;
+mod user_cache;
+mod webhook;
+use auth::{ApiKeyScope, AuthUser, Credential, RootUser};
+use client_ip::ClientIp;
+use clock::{SharedClock, SystemClock};
+use config::Config;
+use error::{AppError, ErrorCode};
+use events::{ConnectionTracker, ServerEvent};
+use extract::{FieldErrors, Path, Query, Validate, ValidatedJson};
+use google_auth::JwkCache;
+use ids::{RandomIds, SharedIdGenerator};
+use invite_cache::InviteCodeCache;
+use ipnet::IpNet;
+use json_utils::json_merge_patch;
+use list_format::ListFormat;
+use metrics_exporter_prometheus::PrometheusHandle;
+use user_cache::UserCache;
+use database::{
+    ApiKey, AuditEntry, AuditImportCounts, Database, InviteActivity, InviteCode, InviteFilterParams, InviteStats,
+    InviteSummary, InvitedByFilter, MigrationRecord, PendingAction, RegisteredUser, SystemStats, UserActivity,
+    UserFilterParams, WeeklyStats,
+};
+use oauth2::{
+    basic::BasicClient,
+    reqwest::async_http_client,
+    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, RedirectUrl, Scope,
+    TokenResponse, TokenUrl,
+};
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::{broadcast, mpsc, RwLock};
+use tokio_stream::{wrappers::ReceiverStream, Stream};
+use tower::{
+    timeout::{error::Elapsed, TimeoutLayer},
+    ServiceBuilder,
+};
+use tower_http::{
+    catch_panic::CatchPanicLayer,
+    compression::{
+        predicate::{Predicate, SizeAbove},
+        CompressionLayer, DefaultPredicate,
+    },
+    cors::CorsLayer,
+    limit::RequestBodyLimitLayer,
+    set_header::SetResponseHeaderLayer,
+    trace::TraceLayer,
+};
+use tracing::{error, info, warn};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+/// ハンドラーで共有する状態（`build_state`で作る）
+#[derive(Clone)]
+pub struct AppState {
+    pub config: Arc<Config>,
+    pub oauth_client: BasicClient,
+    pub google_jwks: Arc<RwLock<Option<JwkCache>>>,
+    pub sessions: Arc<RwLock<HashMap<String, UserSession>>>,
+    pub auth_tokens: Arc<RwLock<HashMap<String, Option<String>>>>,
+    pub database: Database,
+    /// 現在時刻（テストでは`MockClock`に差し替える）
+    pub clock: SharedClock,
+    /// セッションID・認証トークン等の採番（テストでは`SequentialIds`に差し替える）
+    pub ids: SharedIdGenerator,
+    pub user_cache: UserCache,
+    pub invite_cache: InviteCodeCache,
+    pub events: broadcast::Sender<ServerEvent>,
+    pub event_connections: ConnectionTracker,
+    pub admin_stats: Arc<RwLock<Option<CachedStats>>>,
+    pub admin_overview: Arc<RwLock<Option<CachedOverview>>>,
+    pub system_status: Arc<RwLock<Option<CachedStatus>>>,
+    /// `metrics_enabled`の場合のみ（`/metrics`の出力に使う）
+    pub metrics: Option<PrometheusHandle>,
+}
+
+/// 管理者向け統計のキャッシュ（集計クエリが重いため一定時間再利用する）
+pub struct CachedStats {
+    stats: SystemStats,
+    expires_at: Instant,
+}
+
+/// 管理画面の概要のキャッシュ（すべての項目を取得できた場合のみ保存する）
+pub struct CachedOverview {
+    overview: AdminOverviewResponse,
+    expires_at: Instant,
+}
+
+/// `/v1/system/status`のキャッシュ（認証不要のエンドポイントのため集計クエリの頻度を抑える）
+pub struct CachedStatus {
+    status: SystemStatusResponse,
+    expires_at: Instant,
+}
+
+/// ログイン中のセッション（`AppState::sessions`のキーは`session_id`）
+#[derive(Clone, Debug)]
+pub struct UserSession {
+    pub user_id: String,
+    pub email: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+struct AuthRequest {
+    code: String,
+    state: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(tag = "grant_type", rename_all = "snake_case")]
+enum CreateTokenRequest {
+    GoogleIdToken {
+        id_token: String,
+        invite_code: Option<String>,
+    },
+}
+
+const MAX_ID_TOKEN_LEN: usize = 4096;
+
+impl Validate for CreateTokenRequest {
+    fn validate(&self) -> Result<(), FieldErrors> {
+        let mut errors = FieldErrors::default();
+        let CreateTokenRequest::GoogleIdToken { id_token, invite_code } = self;
+        if id_token.is_empty() || id_token.len() > MAX_ID_TOKEN_LEN {
+            errors.add("id_token", format!("1〜{}文字で指定してください", MAX_ID_TOKEN_LEN));
+        }
+        // 招待コードはUUID形式で発行している
+        if let Some(code) = invite_code
+            && Uuid::parse_str(code).is_err()
+        {
+            errors.add("invite_code", "招待コードの形式が不正です");
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize)]
+struct GoogleUserInfo {
+    id: String,
+    email: String,
+    name: String,
+    #[serde(default)]
+    verified_email: bool,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AuthResponse {
+    pub session_id: String,
+    pub user_email: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AuthTokenResponse {
+    pub auth_token: String,
+    pub login_url: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AuthStatusResponse {
+    pub status: String,
+    pub session_id: Option<String>,
+    pub user_email: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct InviteCodeResponse {
+    pub invite_code: String,
+    pub invite_url: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct InviteResendResponse {
+    pub invite_id: i64,
+    pub event: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct DeleteExpiredInvitesResponse {
+    /// 削除した（dry_runの場合は削除対象の）招待コードの件数
+    pub deleted: u64,
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct InviteCodesListResponse {
+    pub invite_codes: Vec<InviteCode>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UsersListResponse {
+    pub users: Vec<RegisteredUser>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct DeleteUserResponse {
+    pub success: bool,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct DashboardUser {
+    pub email: String,
+    pub name: String,
+    pub is_root: bool,
+    pub can_invite: bool,
+    pub registered_at: chrono::DateTime<chrono::Utc>,
+    pub last_login: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct DashboardResponse {
+    pub user: DashboardUser,
+    pub invites: InviteSummary,
+    pub invitees: i64,
+    pub recent_activity: Vec<InviteActivity>,
+}
+
+/// OIDC UserInfoのクレーム（`sub`はユーザーID）
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UserInfoResponse {
+    pub sub: String,
+    pub email: String,
+    pub name: String,
+    pub email_verified: bool,
+}
+
+impl From<&RegisteredUser> for UserInfoResponse {
+    fn from(user: &RegisteredUser) -> Self {
+        UserInfoResponse {
+            sub: user.id.to_string(),
+            email: user.email.clone(),
+            name: user.name.clone(),
+            email_verified: user.email_verified,
+        }
+    }
+}
+
+/// ユーザーが実行できる操作（フラグから導出した値をサーバー側で計算する）
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct PermissionsResponse {
+    pub can_invite: bool,
+    pub is_root: bool,
+    pub can_self_delete: bool,
+    pub can_view_all_users: bool,
+    pub can_create_invites: bool,
+}
+
+impl PermissionsResponse {
+    fn for_user(user: &RegisteredUser) -> Self {
+        PermissionsResponse {
+            can_invite: user.can_invite,
+            is_root: user.is_root,
+            // 自分自身を削除するAPIはない（rootによる削除も自分自身は対象外）
+            can_self_delete: false,
+            can_view_all_users: user.is_root,
+            can_create_invites: user.can_invite && user.is_active,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BanUserResponse {
+    pub banned: bool,
+    pub sessions_revoked: usize,
+    pub invites_deactivated: u64,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UnbanUserResponse {
+    pub unbanned: bool,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct PromoteUserResponse {
+    /// 既にrootユーザーだった場合は`false`
+    pub promoted: bool,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct SetCanInviteResponse {
+    pub user_id: i64,
+    pub can_invite: bool,
+}
+
+/// 作成したAPIキー（`key`はこのレスポンスでのみ返し、後から取得する方法はない）
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct CreateApiKeyResponse {
+    /// `X-Api-Key`ヘッダーに指定するキー
+    pub key: String,
+    pub api_key: ApiKey,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ErrorCatalogEntry {
+    pub code: ErrorCode,
+    pub status: u16,
+    pub description: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct RootExistsResponse {
+    pub root_exists: bool,
+}
+
+/// 設定からアプリケーションの状態を作る（データベースへの接続とテーブルの準備を含む）
+pub async fn build_state(config: Config) -> anyhow::Result<AppState> {
+    build_state_with_clock(config, Arc::new(SystemClock)).await
+}
+
+/// `build_state`と同じだが、現在時刻を`clock`から取る（テストで時刻を進める場合に使う）
+pub async fn build_state_with_clock(config: Config, clock: SharedClock) -> anyhow::Result<AppState> {
+    build_state_with(config, clock, Arc::new(RandomIds)).await
+}
+
+/// `build_state_with_clock`と同じだが、IDを`ids`で採番する（レスポンスを固定の値で比較するテスト用）
+pub async fn build_state_with(config: Config, clock: SharedClock, ids: SharedIdGenerator) -> anyhow::Result<AppState> {
+    let config = Arc::new(config);
+    let oauth_client = BasicClient::new(
+        ClientId::new(config.google_client_id.clone()),
+        Some(ClientSecret::new(config.google_client_secret.clone())),
+        AuthUrl::new("https://accounts.google.com/o/oauth2/auth".to_string())?,
+        Some(TokenUrl::new(config.google_token_url.clone())?),
+    )
+    .set_redirect_uri(RedirectUrl::new(config.redirect_url.clone())?);
+
+    let database = database::connect(&config.database_url, clock.clone(), ids.clone()).await?;
+    match &config.root_email {
+        // 設定する前に一般ユーザーとして登録していた場合はrootユーザーに変更する
+        Some(email) => {
+            if let Some(user) = database.grant_root(email).await? {
+                warn!(user_id = user.id, "Existing user granted root by ROOT_EMAIL");
+            }
+        }
+        None if database.count_registered_users().await? == 0 => {
+            warn!("ROOT_EMAIL is not set; the first user to register will become root");
+        }
+        None => {}
+    }
+    let events = events::channel();
+    webhook::spawn_forwarder(&events, config.webhook_url.clone());
+
+    Ok(AppState {
+        config: config.clone(),
+        oauth_client,
+        google_jwks: Arc::new(RwLock::new(None)),
+        sessions: Arc::new(RwLock::new(HashMap::new())),
+        auth_tokens: Arc::new(RwLock::new(HashMap::new())),
+        database,
+        user_cache: UserCache::new(config.user_cache_ttl()),
+        invite_cache: InviteCodeCache::new(config.invite_cache_ttl(), clock.clone()),
+        clock,
+        ids,
+        events,
+        event_connections: ConnectionTracker::new(config.sse_max_connections_per_user),
+        admin_stats: Arc::new(RwLock::new(None)),
+        admin_overview: Arc::new(RwLock::new(None)),
+        system_status: Arc::new(RwLock::new(None)),
+        metrics: config.metrics_enabled.then(prometheus::install).transpose()?,
+    })
+}
+
+/// Ctrl+CまたはSIGTERMを受け取るまで待つ
+pub async fn shutdown_signal() {
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                warn!("Failed to listen for SIGTERM: {:?}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate => {}
+    }
+    info!("Shutdown signal received");
+}
+
+/// ルーター構築時の設定
+#[derive(Clone, Debug)]
+struct RouterOptions {
+    /// バージョンなしの旧パスを非推奨エイリアスとして残すか
+    legacy_aliases: bool,
+    /// 旧パスの`Sunset`ヘッダーに設定する廃止予定日時（HTTP-date形式）
+    legacy_sunset: String,
+    /// `/openapi.json`と`/docs`を公開するか
+    docs: bool,
+    /// これを超えたリクエストは打ち切って504を返す（SSEはレスポンス開始までが対象）
+    request_timeout: Duration,
+    /// Sentryに送るイベントにリクエストの情報を付けるか（`sentry_dsn`設定時）
+    error_reporting: bool,
+    /// `Forwarded`・`X-Forwarded-For`を信頼する接続元
+    trusted_proxies: Arc<[IpNet]>,
+    /// リクエストボディの上限（バイト）
+    body_limit: usize,
+}
+
+/// これより小さいレスポンスは圧縮しない（圧縮しても小さくならず、CPUを使うだけのため）
+const COMPRESSION_MIN_BYTES: u16 = 1024;
+
+impl RouterOptions {
+    fn from_config(config: &Config) -> Self {
+        RouterOptions {
+            legacy_aliases: config.api_legacy_aliases,
+            legacy_sunset: config.api_legacy_sunset.clone(),
+            docs: config.api_docs_enabled,
+            request_timeout: config.request_timeout(),
+            error_reporting: config.sentry_dsn.is_some(),
+            trusted_proxies: config.trusted_proxies.iter().map(|proxy| proxy.0).collect(),
+            body_limit: config.request_body_limit_bytes,
+        }
+    }
+}
+
+/// バージョン付きで公開するAPIルート（`routes::api`の表から組み立てる）
+fn api_routes() -> Router<AppState> {
+    routes::into_router(routes::api())
+}
+
+/// 全ルートとミドルウェアを組み立てる（ルートの有無等は`state.config`に従う）
+pub fn build_router(state: AppState) -> Router {
+    let opts = RouterOptions::from_config(&state.config);
+    let mut app = routes::into_router(routes::unversioned()).nest("/v1", api_routes());
+
+    if opts.legacy_aliases {
+        let sunset = HeaderValue::from_str(&opts.legacy_sunset)
+            .expect("API_LEGACY_SUNSET must be a valid header value");
+        // /protectedは/v1/dashboardと同じ内容を返す旧エンドポイント（次のリリースで削除予定）
+        let protected = get(dashboard).layer(SetResponseHeaderLayer::overriding(
+            LINK,
+            HeaderValue::from_static("</v1/dashboard>; rel=\"successor-version\""),
+        ));
+        let legacy = routes::into_router(routes::legacy())
+            .route("/protected", protected)
+            .layer(middleware::from_fn_with_state(sunset, deprecated_alias));
+        app = app.merge(legacy);
+    }
+    if opts.docs {
+        app = app.merge(openapi::routes());
+    }
+    let metrics_enabled = state.metrics.is_some();
+    let ids = state.ids.clone();
+    if metrics_enabled {
+        app = app.route("/metrics", get(prometheus::render));
+    }
+    // 開発用のルートはOpenAPIに載せず、旧パスの別名も作らない
+    #[cfg(feature = "dev-tools")]
+    if state.config.dev_seed_enabled {
+        app = app.route("/v1/dev/seed", axum::routing::post(dev_seed::seed));
+    }
+
+    // axumの既定の上限（2MB）ではなく設定値で制限する
+    let mut app = app
+        .layer(middleware::from_fn_with_state(state.clone(), idempotency::enforce))
+        .with_state(state)
+        .layer(DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(opts.body_limit))
+        .layer(middleware::map_response(payload_too_large_as_json))
+        .layer(CatchPanicLayer::custom(handle_panic))
+        .layer(
+            ServiceBuilder::new()
+                .layer(HandleErrorLayer::new(handle_timeout))
+                .layer(TimeoutLayer::new(opts.request_timeout)),
+        );
+    // タイムアウト（504）とpanic（500）も記録されるよう、それらのレイヤーの外側に置く
+    if metrics_enabled {
+        app = app.layer(middleware::from_fn(prometheus::track));
+    }
+
+    // ETagは圧縮前のボディから計算する（弱いETagのためContent-Encodingが違っても同じ値でよい）
+    let mut app = app
+        .layer(
+            CompressionLayer::new()
+                .gzip(true)
+                .br(true)
+                .compress_when(DefaultPredicate::new().and(SizeAbove::new(COMPRESSION_MIN_BYTES))),
+        )
+        .layer(CorsLayer::permissive())
+        .layer(TraceLayer::new_for_http().make_span_with(request_id::make_span))
+        .layer(middleware::from_fn_with_state(opts.trusted_proxies, client_ip::resolve));
+    if opts.error_reporting {
+        app = app.layer(middleware::from_fn(error_reporting::bind_request));
+    }
+    app.layer(middleware::from_fn_with_state(ids, request_id::propagate))
+}
+
+/// `RequestBodyLimitLayer`がContent-Lengthを見て返す413（テキスト）をJSONのエラーにする
+///
+/// Content-Lengthのないボディが上限を超えた場合は`ValidatedJson`が413を返す。
+async fn payload_too_large_as_json(response: Response) -> Response {
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+    if response.status() == StatusCode::PAYLOAD_TOO_LARGE && !is_json {
+        return AppError::from(ErrorCode::PayloadTooLarge).into_response();
+    }
+    response
+}
+
+/// ハンドラーがpanicした場合も接続を切らずに500を返す
+fn handle_panic(panic: Box<dyn std::any::Any + Send + 'static>) -> Response {
+    let detail = panic
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic payload".to_string());
+    error!(target: error_reporting::PANIC_TARGET, "Handler panicked: {}", detail);
+    AppError::from(ErrorCode::InternalError).into_response()
+}
+
+async fn handle_timeout(error: BoxError) -> AppError {
+    if error.is::<Elapsed>() {
+        ErrorCode::Timeout.into()
+    } else {
+        AppError::Internal(anyhow!(error))
+    }
+}
+
+/// バージョンなしの旧パスに非推奨ヘッダーと移行先のリンクを付ける
+async fn deprecated_alias(State(sunset): State<HeaderValue>, request: Request, next: Next) -> Response {
+    let successor = format!("</v1{}>; rel=\"successor-version\"", request.uri().path());
+    let mut response = next.run(request).await;
+
+    let headers = response.headers_mut();
+    headers.insert("Deprecation", HeaderValue::from_static("true"));
+    headers.insert("Sunset", sunset);
+    if !headers.contains_key(LINK)
+        && let Ok(link) = HeaderValue::from_str(&successor)
+    {
+        headers.insert(LINK, link);
+    }
+    response
+}
+
+#[utoipa::path(get, path = "/", tag = "auth", responses((status = 200, description = "トップページ", content_type = "text/html", body = String)))]
+async fn index() -> Html<&'static str> {
+    Html(r#"
+        <html>
+        <head><title>Patchouli Server</title></head>
+        <body>
+            <h1>Patchouli Knowledge Base Server</h1>
+            <p>Welcome to Patchouli! Please authenticate to access the API.</p>
+            <a href="/login">Login with Google</a>
+        </body>
+        </html>
+    "#)
+}
+
+#[utoipa::path(
+    get, path = "/login", tag = "auth",
+    params(
+        ("register" = Option<bool>, Query, description = "新規登録の場合はtrue"),
+        ("invite" = Option<String>, Query, description = "招待コード"),
+        ("token" = Option<String>, Query, description = "API認証用のauth_token"),
+    ),
+    responses(
+        (status = 308, description = "Googleの認可画面へリダイレクト"),
+        (status = 400, description = "クエリパラメーターが不正", body = ErrorResponse),
+    )
+)]
+async fn login(Query(query): Query<std::collections::HashMap<String, String>>, State(state): State<AppState>) -> Redirect {
+    let is_registration = query.get("register").map(|v| v == "true").unwrap_or(false);
+    let invite_code = query.get("invite").cloned();
+    // クエリには招待コード・auth_tokenが含まれるためそのままは出力しない
+    info!(
+        is_registration,
+        has_invite = invite_code.is_some(),
+        has_token = query.contains_key("token"),
+        "Login request received"
+    );
+    
+    let csrf_state = if let Some(token) = query.get("token") {
+        // API認証用のトークンが指定された場合はそれをstateに使用
+        let state_suffix = if is_registration { "register" } else { "login" };
+        let state_with_invite = if let Some(ref code) = invite_code {
+            format!("{}:{}:{}", token, state_suffix, code)
+        } else {
+            format!("{}:{}", token, state_suffix)
+        };
+        CsrfToken::new(state_with_invite)
+    } else {
+        // 通常のWeb認証の場合はランダムなCSRFトークンを生成
+        let state_suffix = if is_registration { "register" } else { "login" };
+        let state_with_invite = if let Some(ref code) = invite_code {
+            format!("{}:{}", state_suffix, code)
+        } else {
+            state_suffix.to_string()
+        };
+        CsrfToken::new(state_with_invite)
+    };
+
+    let (auth_url, _csrf_token) = state
+        .oauth_client
+        .authorize_url(|| csrf_state)
+        .add_scope(Scope::new("openid".to_string()))
+        .add_scope(Scope::new("email".to_string()))
+        .add_scope(Scope::new("profile".to_string()))
+        .url();
+
+    Redirect::permanent(auth_url.as_ref())
+}
+
+async fn send_discord_notification(
+    discord_bot_url: &str,
+    auth_token: &str,
+    user_email: &str,
+) -> Result<(), reqwest::Error> {
+    let notification_payload = serde_json::json!({
+        "auth_token": auth_token,
+        "user_email": user_email
+    });
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{}/auth-complete", discord_bot_url))
+        .json(&notification_payload)
+        .send()
+        .await?;
+
+    if response.status().is_success() {
+        info!("Discord notification sent successfully");
+    } else {
+        warn!("Discord notification failed with status: {}", response.status());
+    }
+
+    Ok(())
+}
+
+/// Googleログインでメールアドレスが確認済みだったことを記録する（失敗してもログインは続ける）
+async fn record_verified_email(state: &AppState, email: &str) {
+    if let Err(e) = state.database.mark_email_verified(email).await {
+        warn!("Failed to mark email as verified: {:?}", e);
+    }
+    state.user_cache.invalidate(email).await;
+}
+
+#[utoipa::path(
+    get, path = "/callback", tag = "auth", params(AuthRequest),
+    responses(
+        (status = 200, description = "認証結果ページ", content_type = "text/html", body = String),
+        (status = 400, description = "認可コードの交換に失敗", body = ErrorResponse),
+    )
+)]
+async fn callback(
+    Query(params): Query<AuthRequest>,
+    ClientIp(client_ip): ClientIp,
+    State(state): State<AppState>,
+) -> Result<Html<String>, AppError> {
+    let token_result = state
+        .oauth_client
+        .exchange_code(AuthorizationCode::new(params.code.clone()))
+        .request_async(async_http_client)
+        .await
+        .map_err(|e| {
+            warn!("Token exchange failed: {:?}", e);
+            AppError::from(ErrorCode::TokenExchangeFailed)
+        })?;
+
+    let access_token = token_result.access_token().secret().to_string();
+
+    let client = reqwest::Client::new();
+    let user_info: GoogleUserInfo = client
+        .get(&state.config.google_userinfo_url)
+        .bearer_auth(&access_token)
+        .send()
+        .await
+        .context("Failed to get user info")?
+        .json()
+        .await
+        .context("Failed to parse user info")?;
+
+    // stateパラメータから登録かログインか、招待コードを判定
+    let state_parts: Vec<&str> = params.state.split(':').collect();
+
+    // Web認証とAPI認証を区別して処理
+    let (is_registration, auth_token_str, invite_code) = if state_parts.len() >= 3 {
+        // API認証の場合: "token:register:invite_code" または "token:login"
+        let is_reg = state_parts.get(1).map(|&s| s == "register").unwrap_or(false);
+        let token = state_parts[0].to_string();
+        let invite = if state_parts.len() >= 3 { Some(state_parts[2]) } else { None };
+        (is_reg, token, invite)
+    } else if state_parts.len() == 2 {
+        // Web認証の場合: "register:invite_code" または "login" または "register"
+        if state_parts[0] == "register" || state_parts[0] == "login" {
+            let is_reg = state_parts[0] == "register";
+            let invite = if state_parts.len() == 2 { Some(state_parts[1]) } else { None };
+            (is_reg, params.state.clone(), invite)
+        } else {
+            // API認証だが招待コードなし: "token:register" または "token:login"
+            let is_reg = state_parts.get(1).map(|&s| s == "register").unwrap_or(false);
+            let token = state_parts[0].to_string();
+            (is_reg, token, None)
+        }
+    } else {
+        // 単純なケース: "register" または "login"
+        let is_reg = params.state == "register";
+        (is_reg, params.state.clone(), None)
+    };
+
+    let auth_token = &auth_token_str;
+
+    info!(is_registration, has_invite = invite_code.is_some(), "OAuth callback state parsed");
+
+    // 登録成功フラグ
+    let mut registration_successful = false;
+
+    // 登録処理かログイン処理かを判定
+    if is_registration {
+        // 既に登録済みかチェック
+        let already_registered = state
+            .database
+            .is_user_registered(&user_info.email)
+            .await
+            .context("Database error during registration check")?;
+        if already_registered {
+            // 既に登録済みの場合はエラー
+            return Ok(Html(format!(
+                r#"
+                <html>
+                <head><title>Registration Error</title></head>
+                <body>
+                    <h1>登録エラー</h1>
+                    <p>このアカウント（{}）は既に登録済みです。</p>
+                    <p><a href="/login">ログインページに戻る</a></p>
+                </body>
+                </html>
+                "#,
+                user_info.email
+            )));
+        }
+
+        // rootユーザーになるユーザー以外は招待コードが必要
+        if !registers_as_root(&state, &user_info.email).await? {
+            let Some(code) = invite_code else {
+                // 招待コードなしでの登録は拒否
+                return Ok(Html(
+                    r#"
+                    <html>
+                    <head><title>Registration Error</title></head>
+                    <body>
+                        <h1>登録エラー</h1>
+                        <p>新規登録には招待コードが必要です。</p>
+                        <p><a href="/login">ログインページに戻る</a></p>
+                    </body>
+                    </html>
+                    "#
+                    .to_string(),
+                ));
+            };
+
+            // 招待コードを検証
+            let Some(invite) = state
+                .invite_cache
+                .validate(&state.database, code)
+                .await
+                .context("Database error during invite validation")?
+            else {
+                // 無効な招待コード
+                return Ok(Html(
+                    r#"
+                    <html>
+                    <head><title>Registration Error</title></head>
+                    <body>
+                        <h1>登録エラー</h1>
+                        <p>無効な招待コードです。</p>
+                        <p><a href="/login">ログインページに戻る</a></p>
+                    </body>
+                    </html>
+                    "#
+                    .to_string(),
+                ));
+            };
+
+            info!("Valid invite code used: {}", code);
+            // 招待による新規登録
+            let registered_user = state
+                .database
+                .register_invited_user(&user_info.id, &user_info.email, &user_info.name, invite.created_by)
+                .await
+                .context("Failed to register invited user")?;
+            // 招待コードを使用済みにマーク
+            if let Err(e) = state.database.use_invite_code(code, registered_user.id).await {
+                warn!("Failed to mark invite code as used: {:?}", e);
+            }
+            state.invite_cache.invalidate(code).await;
+            events::publish(&state.events, ServerEvent::user_created(&registered_user, state.clock.now()));
+            events::publish(&state.events, ServerEvent::invite_used(&invite, registered_user.id, state.clock.now()));
+            info!(user_id = registered_user.id, invited_by = invite.created_by, "New user registered with invite");
+            registration_successful = true;
+        } else {
+            // rootユーザーは招待コードなしで登録可能
+            let registered_user = state
+                .database
+                .register_user(&user_info.id, &user_info.email, &user_info.name, state.config.root_email.as_deref())
+                .await
+                .context("Failed to register root user")?;
+            events::publish(&state.events, ServerEvent::user_created(&registered_user, state.clock.now()));
+            info!(user_id = registered_user.id, "Root user registered");
+            registration_successful = true;
+        }
+    } else {
+        // ログイン処理 - 登録済みかチェック
+        let user = state
+            .database
+            .get_user_by_email(&user_info.email)
+            .await
+            .context("Database error during login check")?;
+        match user {
+            None => {
+                // 未登録の場合はエラー
+                return Ok(Html(format!(
+                    r#"
+                    <html>
+                    <head><title>Login Error</title></head>
+                    <body>
+                        <h1>ログインエラー</h1>
+                        <p>このアカウント（{}）は登録されていません。</p>
+                        <p><a href="/register">新規登録ページへ</a></p>
+                    </body>
+                    </html>
+                    "#,
+                    user_info.email
+                )));
+            }
+            // 利用停止中のユーザーはログインできない
+            Some(user) if !user.is_active => {
+                warn!("Banned user attempted to log in: {}", user_info.email);
+                return Ok(Html(format!(
+                    r#"
+                    <html>
+                    <head><title>Login Error</title></head>
+                    <body>
+                        <h1>ログインエラー</h1>
+                        <p>このアカウント（{}）は利用停止されています。</p>
+                    </body>
+                    </html>
+                    "#,
+                    user_info.email
+                )));
+            }
+            Some(_) => {
+                // 最終ログイン時刻を更新
+                if let Err(e) = state.database.update_last_login(&user_info.email).await {
+                    warn!("Failed to update last login: {:?}", e);
+                }
+                state.user_cache.invalidate(&user_info.email).await;
+            }
+        }
+    }
+
+    // 登録が成功した場合は、再度登録済みかチェック（ダブルチェック）
+    if registration_successful {
+        let registered = state
+            .database
+            .is_user_registered(&user_info.email)
+            .await
+            .context("Database error during registration confirmation")?;
+        if !registered {
+            return Err(AppError::Internal(anyhow!(
+                "Registration marked successful but user not found in database: {}",
+                user_info.email
+            )));
+        }
+        info!(google_id = %user_info.id, "Registration confirmed in database");
+    }
+
+    if user_info.verified_email {
+        record_verified_email(&state, &user_info.email).await;
+    }
+
+    // セッション作成
+    let session_id = state.ids.new_id().to_string();
+    let user_session = UserSession {
+        user_id: user_info.id.clone(),
+        email: user_info.email.clone(),
+    };
+
+    {
+        let mut sessions = state.sessions.write().await;
+        sessions.insert(session_id.clone(), user_session);
+    }
+
+    if let Err(e) = state.database.record_login(&user_info.email, "browser", client_ip).await {
+        warn!("Failed to record login in audit log: {:?}", e);
+    }
+    info!(google_id = %user_info.id, client_ip = ?client_ip, "User logged in successfully via browser");
+
+    // API認証の場合のauth_token処理
+    {
+        let mut auth_tokens = state.auth_tokens.write().await;
+        if state_parts.len() > 1 && auth_tokens.contains_key(auth_token) {
+            auth_tokens.insert(auth_token.to_string(), Some(session_id.clone()));
+        }
+    }
+
+    // stateパラメータがauth_tokenかどうかで判定
+    let auth_tokens = state.auth_tokens.read().await;
+    let is_api_auth = auth_tokens.contains_key(auth_token);
+    drop(auth_tokens);
+
+    if is_api_auth {
+        // Discord通知を送信
+        let notification_result = send_discord_notification(&state.config.discord_bot_url, auth_token, &user_info.email).await;
+        if let Err(e) = notification_result {
+            warn!("Failed to send Discord notification: {:?}", e);
+        }
+
+        // API認証の場合はそのまま表示
+        Ok(Html(format!(
+            r#"
+            <html>
+            <head><title>{} Success</title></head>
+            <body>
+                <h1>{} Successful!</h1>
+                <p>Welcome, {}!</p>
+                <p><strong>API認証が完了しました。このウィンドウを閉じてください。</strong></p>
+            </body>
+            </html>
+            "#,
+            if is_registration { "Registration" } else { "Login" },
+            if is_registration { "Registration" } else { "Login" },
+            user_info.name
+        )))
+    } else {
+        // 通常のWeb認証の場合はフロントエンドにリダイレクト
+        let redirect_url = state.config.frontend_callback_url(&session_id, &user_info.email);
+
+        Ok(Html(format!(
+            r#"
+            <html>
+            <head>
+                <title>Redirecting...</title>
+                <script>
+                    window.location.href = '{}';
+                </script>
+            </head>
+            <body>
+                <p>Redirecting to application...</p>
+                <p>If you are not redirected automatically, <a href="{}">click here</a>.</p>
+            </body>
+            </html>
+            "#,
+            redirect_url, redirect_url
+        )))
+    }
+}
+
+/// 招待コードなしでrootユーザーとして登録するか
+///
+/// `ROOT_EMAIL`が設定されていればそのメールアドレスのみ（登録の順番は問わない）、
+/// 未設定なら最初のユーザーのみ。
+async fn registers_as_root(state: &AppState, email: &str) -> Result<bool, AppError> {
+    if state.config.root_email.is_some() {
+        return Ok(state.config.is_root_email(email));
+    }
+    let user_count = state
+        .database
+        .count_registered_users()
+        .await
+        .context("Database error during user count")?;
+    Ok(user_count == 0)
+}
+
+#[derive(Deserialize)]
+struct SessionQuery {
+    session_id: String,
+}
+
+/// ダッシュボード表示用の集計データを組み立てる
+async fn build_dashboard(state: &AppState, user: &RegisteredUser) -> Result<DashboardResponse, AppError> {
+    let (invites, invitees, recent_activity) = tokio::try_join!(
+        state.database.get_invite_summary_by_user(user.id),
+        state.database.count_invitees(user.id),
+        state.database.get_recent_invite_activity(user.id, 10),
+    )
+    .context("Database error during dashboard aggregation")?;
+
+    Ok(DashboardResponse {
+        user: DashboardUser {
+            email: user.email.clone(),
+            name: user.name.clone(),
+            is_root: user.is_root,
+            can_invite: user.can_invite,
+            registered_at: user.registered_at,
+            last_login: user.last_login,
+        },
+        invites,
+        invitees,
+        recent_activity,
+    })
+}
+
+#[utoipa::path(
+    get, path = "/v1/dashboard", tag = "users", security(("session_id" = [])),
+    responses(
+        (status = 200, body = DashboardResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "権限がない、または利用停止中", body = ErrorResponse),
+    )
+)]
+async fn dashboard(
+    AuthUser(user): AuthUser,
+    State(state): State<AppState>,
+) -> Result<Json<DashboardResponse>, AppError> {
+    build_dashboard(&state, &user).await.map(Json)
+}
+
+#[utoipa::path(
+    get, path = "/v1/userinfo", tag = "users", security(("bearer" = []), ("session_id" = [])),
+    responses(
+        (status = 200, body = UserInfoResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "未登録、または利用停止中", body = ErrorResponse),
+    )
+)]
+async fn userinfo(AuthUser(user): AuthUser) -> Json<UserInfoResponse> {
+    Json(user.as_ref().into())
+}
+
+#[utoipa::path(
+    get, path = "/v1/callback/api", tag = "auth", params(AuthRequest),
+    responses(
+        (status = 200, body = AuthResponse),
+        (status = 400, description = "認可コードの交換に失敗", body = ErrorResponse),
+        (status = 403, description = "利用停止中", body = ErrorResponse),
+    )
+)]
+async fn callback_api(
+    Query(params): Query<AuthRequest>,
+    ClientIp(client_ip): ClientIp,
+    State(state): State<AppState>,
+) -> Result<Json<AuthResponse>, AppError> {
+    let token_result = state
+        .oauth_client
+        .exchange_code(AuthorizationCode::new(params.code))
+        .request_async(async_http_client)
+        .await
+        .map_err(|e| {
+            warn!("Token exchange failed: {:?}", e);
+            AppError::from(ErrorCode::TokenExchangeFailed)
+        })?;
+
+    let access_token = token_result.access_token().secret().to_string();
+
+    let client = reqwest::Client::new();
+    let user_info: GoogleUserInfo = client
+        .get(&state.config.google_userinfo_url)
+        .bearer_auth(&access_token)
+        .send()
+        .await
+        .context("Failed to get user info")?
+        .json()
+        .await
+        .context("Failed to parse user info")?;
+
+    // 利用停止中のユーザーにはセッションを発行しない
+    let user = state
+        .database
+        .get_user_by_email(&user_info.email)
+        .await
+        .context("Database error during API login check")?;
+    if user.as_ref().is_some_and(|user| !user.is_active) {
+        warn!("Banned user attempted to log in via API: {}", user_info.email);
+        return Err(ErrorCode::UserSuspended.into());
+    }
+    if user.is_some() && user_info.verified_email {
+        record_verified_email(&state, &user_info.email).await;
+    }
+
+    let session_id = state.ids.new_id().to_string();
+    let user_session = UserSession {
+        user_id: user_info.id.clone(),
+        email: user_info.email.clone(),
+    };
+
+    {
+        let mut sessions = state.sessions.write().await;
+        sessions.insert(session_id.clone(), user_session);
+    }
+
+    if let Err(e) = state.database.record_login(&user_info.email, "api", client_ip).await {
+        warn!("Failed to record login in audit log: {:?}", e);
+    }
+    info!(google_id = %user_info.id, client_ip = ?client_ip, "User logged in successfully via API");
+
+    Ok(Json(AuthResponse {
+        session_id,
+        user_email: user_info.email,
+    }))
+}
+
+#[utoipa::path(get, path = "/v1/login/api", tag = "auth", responses((status = 200, body = AuthTokenResponse)))]
+async fn login_api(State(state): State<AppState>) -> Json<AuthTokenResponse> {
+    let auth_token = state.ids.new_id().to_string();
+
+    // auth_tokenをstateパラメータとして使用（CSRFトークンの代わり）
+    let (auth_url, _csrf_token) = state
+        .oauth_client
+        .authorize_url(|| CsrfToken::new(auth_token.clone()))
+        .add_scope(Scope::new("openid".to_string()))
+        .add_scope(Scope::new("email".to_string()))
+        .add_scope(Scope::new("profile".to_string()))
+        .url();
+
+    {
+        let mut auth_tokens = state.auth_tokens.write().await;
+        auth_tokens.insert(auth_token.clone(), None);
+    }
+
+    Json(AuthTokenResponse {
+        auth_token: auth_token.clone(),
+        login_url: auth_url.to_string(),
+    })
+}
+
+#[utoipa::path(
+    post, path = "/v1/auth/tokens/google-one-tap", tag = "auth", request_body = CreateTokenRequest,
+    responses(
+        (status = 200, body = AuthResponse),
+        (status = 400, description = "ボディを読み取れない（未対応のgrant_type等）", body = ErrorResponse),
+        (status = 401, description = "ID Tokenが無効", body = ErrorResponse),
+        (status = 403, description = "招待コードがない・無効、または利用停止中", body = ErrorResponse),
+        (status = 422, description = "項目の値が不正", body = ErrorResponse),
+        (status = 502, description = "Googleの公開鍵を取得できない", body = ErrorResponse),
+    )
+)]
+async fn google_one_tap(
+    ClientIp(client_ip): ClientIp,
+    State(state): State<AppState>,
+    ValidatedJson(request): ValidatedJson<CreateTokenRequest>,
+) -> Result<Json<AuthResponse>, AppError> {
+    let CreateTokenRequest::GoogleIdToken { id_token, invite_code } = request;
+
+    let keys = google_auth::cached_google_jwks(
+        &state.google_jwks,
+        &state.config.google_jwks_url,
+        state.config.google_jwks_min_ttl(),
+    )
+    .await
+    .map_err(|e| AppError::Upstream(anyhow::Error::new(e).context("Failed to fetch Google JWKs")))?;
+
+    let now = state.clock.now();
+    let claims = match google_auth::verify_google_id_token(&id_token, &keys, &state.config.google_client_id, now) {
+        Ok(claims) => claims,
+        Err(e) => {
+            warn!("Google ID token validation failed: {}", e);
+            return Err(ErrorCode::InvalidIdToken.into());
+        }
+    };
+    let name = claims.name.clone().unwrap_or_else(|| claims.email.clone());
+
+    // 登録済みならログイン、未登録なら通常フローと同じ条件で登録
+    let existing = state
+        .database
+        .get_user_by_email(&claims.email)
+        .await
+        .context("Database error during One Tap login")?;
+    match existing {
+        Some(user) => {
+            // 利用停止中のユーザーはログインできない
+            if !user.is_active {
+                warn!("Banned user attempted to log in via One Tap: {}", claims.email);
+                return Err(ErrorCode::UserSuspended.into());
+            }
+            if let Err(e) = state.database.update_last_login(&claims.email).await {
+                warn!("Failed to update last login: {:?}", e);
+            }
+            state.user_cache.invalidate(&claims.email).await;
+        }
+        None => {
+            if registers_as_root(&state, &claims.email).await? {
+                // rootユーザーは招待コードなしで登録可能
+                let registered_user = state
+                    .database
+                    .register_user(&claims.sub, &claims.email, &name, state.config.root_email.as_deref())
+                    .await
+                    .context("Failed to register root user")?;
+                events::publish(&state.events, ServerEvent::user_created(&registered_user, state.clock.now()));
+                info!(user_id = registered_user.id, "Root user registered via One Tap");
+            } else {
+                let Some(code) = invite_code.as_deref() else {
+                    warn!("One Tap registration without invite code: {}", claims.email);
+                    return Err(ErrorCode::InviteRequired.into());
+                };
+
+                let invite = state
+                    .invite_cache
+                    .validate(&state.database, code)
+                    .await
+                    .context("Database error during invite validation")?
+                    .ok_or(ErrorCode::InvalidInvite)?;
+
+                let registered_user = state
+                    .database
+                    .register_invited_user(&claims.sub, &claims.email, &name, invite.created_by)
+                    .await
+                    .context("Failed to register invited user")?;
+                if let Err(e) = state.database.use_invite_code(code, registered_user.id).await {
+                    warn!("Failed to mark invite code as used: {:?}", e);
+                }
+                state.invite_cache.invalidate(code).await;
+                events::publish(&state.events, ServerEvent::user_created(&registered_user, state.clock.now()));
+                events::publish(&state.events, ServerEvent::invite_used(&invite, registered_user.id, state.clock.now()));
+                info!(
+                    user_id = registered_user.id,
+                    invited_by = invite.created_by,
+                    "New user registered with invite via One Tap"
+                );
+            }
+        }
+    }
+
+    // ID Tokenの検証でemail_verifiedは確認済み
+    record_verified_email(&state, &claims.email).await;
+
+    let session_id = state.ids.new_id().to_string();
+    let user_session = UserSession {
+        user_id: claims.sub.clone(),
+        email: claims.email.clone(),
+    };
+
+    {
+        let mut sessions = state.sessions.write().await;
+        sessions.insert(session_id.clone(), user_session);
+    }
+
+    if let Err(e) = state.database.record_login(&claims.email, "google_one_tap", client_ip).await {
+        warn!("Failed to record login in audit log: {:?}", e);
+    }
+    info!(google_id = %claims.sub, client_ip = ?client_ip, "User logged in successfully via Google One Tap");
+
+    Ok(Json(AuthResponse {
+        session_id,
+        user_email: claims.email,
+    }))
+}
+
+#[utoipa::path(
+    get, path = "/v1/auth/status/{token}", tag = "auth",
+    params(("token" = String, Path, description = "/login/apiで発行したauth_token")),
+    responses(
+        (status = 200, body = AuthStatusResponse),
+        (status = 404, description = "auth_tokenが存在しない", body = ErrorResponse),
+    )
+)]
+async fn auth_status(
+    Path(token): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<AuthStatusResponse>, AppError> {
+    let auth_tokens = state.auth_tokens.read().await;
+    let session_id_opt = auth_tokens.get(&token).ok_or(ErrorCode::AuthTokenNotFound)?;
+
+    let Some(session_id) = session_id_opt else {
+        return Ok(Json(AuthStatusResponse {
+            status: "pending".to_string(),
+            session_id: None,
+            user_email: None,
+        }));
+    };
+
+    let sessions = state.sessions.read().await;
+    if let Some(session) = sessions.get(session_id) {
+        Ok(Json(AuthStatusResponse {
+            status: "completed".to_string(),
+            session_id: Some(session_id.clone()),
+            user_email: Some(session.email.clone()),
+        }))
+    } else {
+        Ok(Json(AuthStatusResponse {
+            status: "error".to_string(),
+            session_id: None,
+            user_email: None,
+        }))
+    }
+}
+
+#[utoipa::path(
+    get, path = "/logout", tag = "auth",
+    params(("session_id" = String, Query, description = "セッションID")),
+    responses(
+        (status = 200, description = "ログアウト完了ページ", content_type = "text/html", body = String),
+        (status = 400, description = "セッションが存在しない", body = ErrorResponse),
+    )
+)]
+async fn logout(
+    Query(query): Query<SessionQuery>,
+    State(state): State<AppState>,
+) -> Result<Html<&'static str>, AppError> {
+    let session = state
+        .sessions
+        .write()
+        .await
+        .remove(&query.session_id)
+        .ok_or_else(|| AppError::Validation("セッションが存在しません".to_string()))?;
+
+    info!("User {} logged out successfully", session.user_id);
+    Ok(Html(r#"
+        <html>
+        <head><title>Logged Out</title></head>
+        <body>
+            <h1>Logged Out Successfully</h1>
+            <p><a href="/">Return to Home</a></p>
+        </body>
+        </html>
+    "#))
+}
+
+#[utoipa::path(
+    get, path = "/v1/invite/create", tag = "invites", security(("session_id" = [])),
+    responses(
+        (status = 200, body = InviteCodeResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "招待権限がない", body = ErrorResponse),
+        (status = 429, description = "今日作成できる招待コード、または未使用の招待コードの数が上限に達した（日ごとの上限の場合のみ`Retry-After`にUTCの翌0時までの秒数）", body = ErrorResponse),
+    )
+)]
+async fn create_invite(
+    AuthUser(user): AuthUser,
+    State(state): State<AppState>,
+) -> Result<Json<InviteCodeResponse>, AppError> {
+    // rootユーザーのみ招待コード作成可能
+    if !user.can_invite {
+        warn!("User {} attempted to create invite code without permission", user.email);
+        return Err(ErrorCode::InsufficientPermission.into());
+    }
+    check_invite_daily_limit(&state, &user).await?;
+    check_invite_total_limit(&state, &user).await?;
+
+    // 招待コードを作成
+    let invite = state
+        .database
+        .create_invite_code(user.id)
+        .await
+        .context("Failed to create invite code")?;
+
+    let invite_url = state.config.invite_url(&invite.code);
+
+    info!(user_id = user.id, invite_id = invite.id, "Invite code created");
+
+    Ok(Json(InviteCodeResponse {
+        invite_code: invite.code,
+        invite_url,
+    }))
+}
+
+/// `invite_daily_limit`に達していれば429を返す（`Retry-After`はUTCの翌0時までの秒数）
+async fn check_invite_daily_limit(state: &AppState, user: &RegisteredUser) -> Result<(), AppError> {
+    let limit = state.config.invite_daily_limit;
+    if limit == 0 {
+        return Ok(());
+    }
+    let created = state
+        .database
+        .count_invites_created_today(user.id)
+        .await
+        .context("Database error during invite daily limit check")?;
+    if created < i64::from(limit) {
+        return Ok(());
+    }
+
+    warn!(user_id = user.id, created, limit, "Invite daily limit reached");
+    let now = state.clock.now();
+    let until_midnight = database::start_of_day(now) + chrono::Duration::days(1) - now;
+    Err(AppError::RetryAfter {
+        code: ErrorCode::InviteDailyLimitExceeded,
+        // 切り上げて、0時より前に再試行させない
+        seconds: (until_midnight.num_milliseconds().max(1) as u64).div_ceil(1000),
+    })
+}
+
+/// 未使用の有効な招待コードが`invite_total_limit`に達していれば429を返す
+///
+/// 使用・無効化されるまで枠は空かないため、`Retry-After`は付けない。
+async fn check_invite_total_limit(state: &AppState, user: &RegisteredUser) -> Result<(), AppError> {
+    let limit = state.config.invite_total_limit;
+    if limit == 0 {
+        return Ok(());
+    }
+    let active = state
+        .database
+        .count_active_invites_by_user(user.id)
+        .await
+        .context("Database error during invite total limit check")?;
+    if active < i64::from(limit) {
+        return Ok(());
+    }
+
+    warn!(user_id = user.id, active, limit, "Invite total limit reached");
+    Err(ErrorCode::InviteTotalLimitExceeded.into())
+}
+
+#[derive(Deserialize, IntoParams)]
+struct ListInvitesQuery {
+    is_active: Option<bool>,
+    used: Option<bool>,
+    expired: Option<bool>,
+    created_after: Option<chrono::DateTime<chrono::Utc>>,
+    created_before: Option<chrono::DateTime<chrono::Utc>>,
+    /// trueの場合は全ユーザーの招待コードを対象にする（rootユーザーのみ）
+    #[serde(default)]
+    all: bool,
+}
+
+#[utoipa::path(
+    get, path = "/v1/invite/list", tag = "invites", security(("session_id" = [])), params(ListInvitesQuery),
+    responses(
+        (status = 200, description = "`Accept`で形式を選べる（NDJSONは1行に1件、CSVは1行目がヘッダー）", content(
+            ("application/json" = InviteCodesListResponse),
+            ("application/x-ndjson" = InviteCode),
+            ("text/csv" = String),
+        )),
+        (status = 304, description = "If-None-Matchが現在のETagと一致（JSONのみ）"),
+        (status = 400, description = "日時の範囲指定が不正", body = ErrorResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "allの指定はrootユーザーのみ", body = ErrorResponse),
+    )
+)]
+async fn list_invites(
+    AuthUser(user): AuthUser,
+    format: ListFormat,
+    Query(query): Query<ListInvitesQuery>,
+    State(state): State<AppState>,
+) -> Result<Response, AppError> {
+    // 全ユーザーの招待コードを参照できるのはrootユーザーのみ
+    if query.all && !user.is_root {
+        warn!("User {} attempted to list all invite codes without root permission", user.email);
+        return Err(ErrorCode::InsufficientPermission.into());
+    }
+
+    if let (Some(after), Some(before)) = (query.created_after, query.created_before)
+        && after >= before
+    {
+        return Err(AppError::invalid_field("created_before", "created_afterより後の日時を指定してください"));
+    }
+
+    // 招待コードを絞り込み条件付きで取得（通常は自分が作成したもののみ）
+    let filter = InviteFilterParams {
+        created_by: if query.all { None } else { Some(user.id) },
+        is_active: query.is_active,
+        used: query.used,
+        expired: query.expired,
+        created_after: query.created_after,
+        created_before: query.created_before,
+    };
+    if format != ListFormat::Json {
+        let database = state.database.clone();
+        return Ok(list_format::stream(format, move |before_id| {
+            let database = database.clone();
+            let filter = filter.clone();
+            async move {
+                database
+                    .get_invite_codes_page(&filter, before_id, list_format::EXPORT_PAGE_SIZE)
+                    .await
+            }
+        }));
+    }
+    let invite_codes = state
+        .database
+        .get_invite_codes(&filter)
+        .await
+        .context("Failed to get invite codes")?;
+
+    Ok(list_format::json(Json(InviteCodesListResponse { invite_codes })))
+}
+
+#[utoipa::path(
+    post, path = "/v1/invite/{invite_id}/resend-notification", tag = "invites", security(("session_id" = [])),
+    params(("invite_id" = i64, Path, description = "招待コードID")),
+    responses(
+        (status = 200, body = InviteResendResponse),
+        (status = 400, description = "パスのIDが数値ではない", body = ErrorResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "作成者またはrootユーザーではない", body = ErrorResponse),
+        (status = 404, description = "招待コードが存在しない", body = ErrorResponse),
+        (status = 409, description = "使用済み・無効・期限切れ", body = ErrorResponse),
+    )
+)]
+async fn resend_invite_notification(
+    AuthUser(user): AuthUser,
+    Path(invite_id): Path<i64>,
+    State(state): State<AppState>,
+) -> Result<Json<InviteResendResponse>, AppError> {
+    let invite = state
+        .database
+        .get_invite_code_by_id(invite_id)
+        .await
+        .context("Database error during invite resend")?
+        .ok_or(ErrorCode::InviteNotFound)?;
+
+    // 作成者本人またはrootユーザーのみ再送可能
+    if invite.created_by != user.id && !user.is_root {
+        warn!("User {} attempted to resend invite {} without permission", user.email, invite_id);
+        return Err(ErrorCode::InsufficientPermission.into());
+    }
+
+    // 使用済み・無効・期限切れの招待コードは再送できない
+    let expired = invite.expires_at.is_some_and(|expires_at| state.clock.now() > expires_at);
+    if invite.used_by.is_some() || !invite.is_active || expired {
+        return Err(ErrorCode::InviteNotResendable.into());
+    }
+
+    // 通知の送信自体はイベントの購読者（Webhook等）が行う
+    events::publish(
+        &state.events,
+        ServerEvent::InviteResent {
+            invite_id: invite.id,
+            code: invite.code,
+            created_by: invite.created_by,
+            resent_by: user.id,
+            timestamp: state.clock.now(),
+        },
+    );
+    info!(user_id = user.id, invite_id, "Invite resend notification requested");
+
+    Ok(Json(InviteResendResponse {
+        invite_id,
+        event: "invite.resent".to_string(),
+    }))
+}
+
+#[derive(Deserialize, ToSchema)]
+struct UpdateInviteRequest {
+    /// 招待コードのメモ
+    note: Option<String>,
+    /// 現在時刻から何時間後に期限切れにするか
+    expires_in_hours: Option<u64>,
+}
+
+const MAX_INVITE_NOTE_CHARS: usize = 200;
+const MAX_INVITE_EXPIRY_HOURS: u64 = 24 * 365;
+
+impl Validate for UpdateInviteRequest {
+    fn validate(&self) -> Result<(), FieldErrors> {
+        let mut errors = FieldErrors::default();
+        if let Some(note) = &self.note
+            && note.chars().count() > MAX_INVITE_NOTE_CHARS
+        {
+            errors.add("note", format!("{}文字以内で指定してください", MAX_INVITE_NOTE_CHARS));
+        }
+        if let Some(hours) = self.expires_in_hours
+            && !(1..=MAX_INVITE_EXPIRY_HOURS).contains(&hours)
+        {
+            errors.add("expires_in_hours", format!("1以上{}以下を指定してください", MAX_INVITE_EXPIRY_HOURS));
+        }
+        errors.into_result()
+    }
+}
+
+#[utoipa::path(
+    patch, path = "/v1/invite/{invite_id}", tag = "invites", security(("session_id" = [])),
+    params(("invite_id" = i64, Path, description = "招待コードID")),
+    request_body = UpdateInviteRequest,
+    responses(
+        (status = 200, body = InviteCode),
+        (status = 400, description = "ボディを読み取れない、または更新する項目がない", body = ErrorResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "作成者またはrootユーザーではない", body = ErrorResponse),
+        (status = 404, description = "招待コードが存在しない", body = ErrorResponse),
+        (status = 409, description = "使用済み", body = ErrorResponse),
+        (status = 422, description = "項目の値が不正", body = ErrorResponse),
+    )
+)]
+async fn update_invite(
+    AuthUser(user): AuthUser,
+    Path(invite_id): Path<i64>,
+    State(state): State<AppState>,
+    ValidatedJson(request): ValidatedJson<UpdateInviteRequest>,
+) -> Result<Json<InviteCode>, AppError> {
+    if request.note.is_none() && request.expires_in_hours.is_none() {
+        return Err(AppError::Validation("noteまたはexpires_in_hoursを指定してください".to_string()));
+    }
+    let expires_at = request
+        .expires_in_hours
+        .map(|hours| state.clock.now() + chrono::Duration::hours(hours as i64));
+
+    let invite = state
+        .database
+        .get_invite_code_by_id(invite_id)
+        .await
+        .context("Database error during invite update")?
+        .ok_or(ErrorCode::InviteNotFound)?;
+
+    // 作成者本人またはrootユーザーのみ変更可能
+    if invite.created_by != user.id && !user.is_root {
+        warn!("User {} attempted to update invite {} without permission", user.email, invite_id);
+        return Err(ErrorCode::InsufficientPermission.into());
+    }
+    if invite.used_by.is_some() {
+        return Err(ErrorCode::InviteAlreadyUsed.into());
+    }
+
+    let invite = state
+        .database
+        .update_invite(invite_id, request.note.as_deref(), expires_at)
+        .await
+        .context("Failed to update invite code")?;
+    state.invite_cache.invalidate(&invite.code).await;
+    info!(user_id = user.id, invite_id, "Invite updated");
+
+    Ok(Json(invite))
+}
+
+#[utoipa::path(
+    post, path = "/v1/invite/{invite_id}/clone", tag = "invites", security(("session_id" = [])),
+    params(("invite_id" = i64, Path, description = "複製元の招待コードID")),
+    responses(
+        (status = 201, body = InviteCodeResponse),
+        (status = 400, description = "パスのIDが数値ではない", body = ErrorResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "作成者またはrootユーザーではない、または招待権限がない", body = ErrorResponse),
+        (status = 404, description = "招待コードが存在しない", body = ErrorResponse),
+        (status = 429, description = "今日作成できる招待コード、または未使用の招待コードの数が上限に達した（日ごとの上限の場合のみ`Retry-After`にUTCの翌0時までの秒数）", body = ErrorResponse),
+    )
+)]
+async fn clone_invite(
+    AuthUser(user): AuthUser,
+    Path(invite_id): Path<i64>,
+    State(state): State<AppState>,
+) -> Result<(StatusCode, Json<InviteCodeResponse>), AppError> {
+    let original = state
+        .database
+        .get_invite_code_by_id(invite_id)
+        .await
+        .context("Database error during invite clone")?
+        .ok_or(ErrorCode::InviteNotFound)?;
+
+    // 作成者本人またはrootユーザーのみ複製可能（新しい招待コードの作成者は実行したユーザー）
+    if original.created_by != user.id && !user.is_root {
+        warn!("User {} attempted to clone invite {} without permission", user.email, invite_id);
+        return Err(ErrorCode::InsufficientPermission.into());
+    }
+    if !user.can_invite {
+        warn!("User {} attempted to clone invite {} without invite permission", user.email, invite_id);
+        return Err(ErrorCode::InsufficientPermission.into());
+    }
+    check_invite_daily_limit(&state, &user).await?;
+    check_invite_total_limit(&state, &user).await?;
+
+    // 元の有効期間（作成から期限まで）を引き継ぎ、期限は現在時刻から数え直す
+    // （期限切れ・使用済みの招待コードを複製しても新しい招待コードはすぐには期限切れにならない）
+    let expires_at = original
+        .expires_at
+        .map(|expires_at| state.clock.now() + (expires_at - original.created_at));
+
+    let invite = state
+        .database
+        .create_invite_code(user.id)
+        .await
+        .context("Failed to create invite code")?;
+    let invite = if original.note.is_some() || expires_at.is_some() {
+        state
+            .database
+            .update_invite(invite.id, original.note.as_deref(), expires_at)
+            .await
+            .context("Failed to copy invite settings")?
+    } else {
+        invite
+    };
+
+    info!(user_id = user.id, invite_id = invite.id, cloned_from = invite_id, "Invite cloned");
+
+    Ok((
+        StatusCode::CREATED,
+        Json(InviteCodeResponse {
+            invite_url: state.config.invite_url(&invite.code),
+            invite_code: invite.code,
+        }),
+    ))
+}
+
+#[derive(Deserialize, ToSchema)]
+struct TransferInviteRequest {
+    /// 新しい作成者のユーザーID
+    new_owner_id: i64,
+}
+
+impl Validate for TransferInviteRequest {
+    fn validate(&self) -> Result<(), FieldErrors> {
+        let mut errors = FieldErrors::default();
+        if self.new_owner_id < 1 {
+            errors.add("new_owner_id", "ユーザーIDを指定してください");
+        }
+        errors.into_result()
+    }
+}
+
+#[utoipa::path(
+    post, path = "/v1/invite/{invite_id}/transfer", tag = "invites", security(("session_id" = [])),
+    params(("invite_id" = i64, Path, description = "招待コードID")),
+    request_body = TransferInviteRequest,
+    responses(
+        (status = 200, body = InviteCode),
+        (status = 400, description = "ボディを読み取れない、または新しい作成者が存在しない・招待権限がない・利用停止中", body = ErrorResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "rootユーザーではない", body = ErrorResponse),
+        (status = 404, description = "招待コードが存在しない", body = ErrorResponse),
+        (status = 409, description = "使用済み", body = ErrorResponse),
+        (status = 422, description = "new_owner_idが不正", body = ErrorResponse),
+    )
+)]
+async fn transfer_invite(
+    RootUser(user): RootUser,
+    Path(invite_id): Path<i64>,
+    State(state): State<AppState>,
+    ValidatedJson(request): ValidatedJson<TransferInviteRequest>,
+) -> Result<Json<InviteCode>, AppError> {
+    let invite = state
+        .database
+        .get_invite_code_by_id(invite_id)
+        .await
+        .context("Database error during invite transfer")?
+        .ok_or(ErrorCode::InviteNotFound)?;
+    if invite.used_by.is_some() {
+        return Err(ErrorCode::InviteAlreadyUsed.into());
+    }
+
+    // 新しい作成者は招待コードを発行できるユーザーでなければならない
+    let new_owner = state
+        .database
+        .get_user_by_id(request.new_owner_id)
+        .await
+        .context("Database error during invite transfer")?
+        .ok_or_else(|| AppError::invalid_field("new_owner_id", "存在しないユーザーです"))?;
+    if !new_owner.can_invite || !new_owner.is_active {
+        return Err(AppError::invalid_field("new_owner_id", "招待権限のある有効なユーザーを指定してください"));
+    }
+
+    if !state
+        .database
+        .transfer_invite(user.id, invite_id, new_owner.id)
+        .await
+        .context("Failed to transfer invite code")?
+    {
+        return Err(ErrorCode::InviteNotFound.into());
+    }
+    state.invite_cache.invalidate(&invite.code).await;
+    info!(user_id = user.id, invite_id, new_owner_id = new_owner.id, "Invite transferred");
+
+    let invite = state
+        .database
+        .get_invite_code_by_id(invite_id)
+        .await
+        .context("Database error during invite transfer")?
+        .ok_or(ErrorCode::InviteNotFound)?;
+    Ok(Json(invite))
+}
+
+#[derive(Deserialize, IntoParams)]
+struct DeleteExpiredInvitesQuery {
+    /// trueなら削除せずに件数だけを返す
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[utoipa::path(
+    delete, path = "/v1/invite/expired", tag = "invites", security(("session_id" = [])),
+    params(DeleteExpiredInvitesQuery),
+    responses(
+        (status = 200, body = DeleteExpiredInvitesResponse),
+        (status = 400, description = "dry_runが真偽値ではない", body = ErrorResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "rootユーザーではない", body = ErrorResponse),
+    )
+)]
+async fn delete_expired_invites(
+    RootUser(user): RootUser,
+    Query(query): Query<DeleteExpiredInvitesQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<DeleteExpiredInvitesResponse>, AppError> {
+    // 期限切れかつ未使用の招待コードのみが対象（使用済みの招待コードは招待履歴として残す）
+    let deleted = if query.dry_run {
+        state
+            .database
+            .count_expired_invites()
+            .await
+            .context("Database error during expired invites count")?
+    } else {
+        let deleted = state
+            .database
+            .delete_expired_invites()
+            .await
+            .context("Database error during expired invites deletion")?;
+        info!(user_id = user.id, deleted, "Expired invites deleted");
+        deleted
+    };
+
+    Ok(Json(DeleteExpiredInvitesResponse {
+        deleted,
+        dry_run: query.dry_run,
+    }))
+}
+
+#[utoipa::path(
+    get, path = "/v1/users/{user_id}/permissions", tag = "users", security(("session_id" = [])),
+    params(("user_id" = i64, Path, description = "ユーザーID")),
+    responses(
+        (status = 200, body = PermissionsResponse),
+        (status = 304, description = "If-None-Matchが現在のETagと一致"),
+        (status = 400, description = "パスのIDが数値ではない", body = ErrorResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "本人またはrootユーザーではない", body = ErrorResponse),
+        (status = 404, description = "ユーザーが存在しない", body = ErrorResponse),
+    )
+)]
+async fn user_permissions(
+    AuthUser(user): AuthUser,
+    Path(user_id): Path<i64>,
+    State(state): State<AppState>,
+) -> Result<Json<PermissionsResponse>, AppError> {
+    // 本人またはrootユーザーのみ参照可能
+    if user.id != user_id && !user.is_root {
+        warn!("User {} attempted to view permissions of user {}", user.email, user_id);
+        return Err(ErrorCode::InsufficientPermission.into());
+    }
+
+    if user.id == user_id {
+        return Ok(Json(PermissionsResponse::for_user(&user)));
+    }
+
+    let target = state
+        .database
+        .get_user_by_id(user_id)
+        .await
+        .context("Database error during permissions lookup")?
+        .ok_or(ErrorCode::UserNotFound)?;
+
+    Ok(Json(PermissionsResponse::for_user(&target)))
+}
+
+#[utoipa::path(
+    get, path = "/v1/users/{user_id}/metadata", tag = "users", security(("session_id" = [])),
+    params(("user_id" = i64, Path, description = "ユーザーID")),
+    responses(
+        (status = 200, description = "ユーザーのmetadata", body = Object),
+        (status = 304, description = "If-None-Matchが現在のETagと一致"),
+        (status = 400, description = "パスのIDが数値ではない", body = ErrorResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "本人またはrootユーザーではない", body = ErrorResponse),
+        (status = 404, description = "ユーザーが存在しない", body = ErrorResponse),
+    )
+)]
+async fn user_metadata(
+    AuthUser(user): AuthUser,
+    Path(user_id): Path<i64>,
+    State(state): State<AppState>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    // 本人またはrootユーザーのみ参照可能
+    if user.id != user_id && !user.is_root {
+        warn!("User {} attempted to view metadata of user {}", user.email, user_id);
+        return Err(ErrorCode::InsufficientPermission.into());
+    }
+
+    let metadata = if user.id == user_id {
+        user.metadata.clone()
+    } else {
+        state
+            .database
+            .get_user_by_id(user_id)
+            .await
+            .context("Database error during metadata lookup")?
+            .ok_or(ErrorCode::UserNotFound)?
+            .metadata
+    };
+
+    Ok(Json(metadata))
+}
+
+/// metadataに適用するJSON Merge Patch（RFC 7396、値が`null`のキーは削除する）
+#[derive(Deserialize, ToSchema)]
+#[serde(transparent)]
+struct UpdateMetadataRequest(#[schema(value_type = Object)] serde_json::Map<String, serde_json::Value>);
+
+/// 保存できるmetadataの大きさ（JSON文字列のバイト数）
+const MAX_METADATA_BYTES: usize = 16 * 1024;
+
+fn check_metadata_size(metadata: &serde_json::Map<String, serde_json::Value>) -> Result<(), FieldErrors> {
+    let mut errors = FieldErrors::default();
+    if serde_json::to_string(metadata).map_or(0, |json| json.len()) > MAX_METADATA_BYTES {
+        errors.add("metadata", format!("{}バイト以内にしてください", MAX_METADATA_BYTES));
+    }
+    errors.into_result()
+}
+
+impl Validate for UpdateMetadataRequest {
+    fn validate(&self) -> Result<(), FieldErrors> {
+        check_metadata_size(&self.0)
+    }
+}
+
+#[utoipa::path(
+    patch, path = "/v1/users/{user_id}/metadata", tag = "users", security(("session_id" = [])),
+    params(("user_id" = i64, Path, description = "ユーザーID")),
+    request_body = UpdateMetadataRequest,
+    responses(
+        (status = 200, description = "更新後のmetadata", body = Object),
+        (status = 400, description = "ボディがJSONオブジェクトではない", body = ErrorResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "本人またはrootユーザーではない", body = ErrorResponse),
+        (status = 404, description = "ユーザーが存在しない", body = ErrorResponse),
+        (status = 422, description = "パッチまたは更新後のmetadataが大きすぎる", body = ErrorResponse),
+    )
+)]
+async fn update_user_metadata(
+    AuthUser(user): AuthUser,
+    Path(user_id): Path<i64>,
+    State(state): State<AppState>,
+    ValidatedJson(UpdateMetadataRequest(patch)): ValidatedJson<UpdateMetadataRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    // 本人またはrootユーザーのみ変更可能
+    if user.id != user_id && !user.is_root {
+        warn!("User {} attempted to update metadata of user {}", user.email, user_id);
+        return Err(ErrorCode::InsufficientPermission.into());
+    }
+
+    let mut metadata = if user.id == user_id {
+        user.metadata.clone()
+    } else {
+        state
+            .database
+            .get_user_by_id(user_id)
+            .await
+            .context("Database error during metadata update")?
+            .ok_or(ErrorCode::UserNotFound)?
+            .metadata
+    };
+
+    json_merge_patch(&mut metadata, serde_json::Value::Object(patch));
+    if let serde_json::Value::Object(merged) = &metadata {
+        check_metadata_size(merged).map_err(AppError::InvalidFields)?;
+    }
+
+    let updated = state
+        .database
+        .update_user_metadata(user_id, &metadata)
+        .await
+        .context("Failed to update user metadata")?
+        .ok_or(ErrorCode::UserNotFound)?;
+    state.user_cache.invalidate(&updated.email).await;
+    events::publish(&state.events, ServerEvent::user_updated(user_id, "metadata", user.id, state.clock.now()));
+    info!(user_id = user.id, target_user_id = user_id, "User metadata updated");
+
+    Ok(Json(updated.metadata))
+}
+
+#[derive(Deserialize, IntoParams)]
+struct ListUsersQuery {
+    is_root: Option<bool>,
+    can_invite: Option<bool>,
+    invited_by: Option<String>,
+    registered_after: Option<chrono::DateTime<chrono::Utc>>,
+    registered_before: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// invited_byクエリを解釈する（"0"または"null"は招待者なしを表す）
+fn parse_invited_by_filter(value: &str) -> Option<InvitedByFilter> {
+    match value {
+        "0" | "null" => Some(InvitedByFilter::NoInviter),
+        _ => value.parse().ok().map(InvitedByFilter::User),
+    }
+}
+
+#[utoipa::path(
+    get, path = "/v1/admin/users", tag = "admin", security(("session_id" = [])), params(ListUsersQuery),
+    responses(
+        (status = 200, description = "`Accept`で形式を選べる（NDJSONは1行に1件、CSVは1行目がヘッダー）", content(
+            ("application/json" = UsersListResponse),
+            ("application/x-ndjson" = RegisteredUser),
+            ("text/csv" = String),
+        )),
+        (status = 304, description = "If-None-Matchが現在のETagと一致（JSONのみ）"),
+        (status = 400, description = "絞り込み条件が不正", body = ErrorResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "rootユーザーではない", body = ErrorResponse),
+    )
+)]
+async fn list_users(
+    RootUser(user): RootUser,
+    format: ListFormat,
+    Query(query): Query<ListUsersQuery>,
+    State(state): State<AppState>,
+) -> Result<Response, AppError> {
+    // 絞り込み条件に一致するユーザーを取得
+    let invited_by = match query.invited_by.as_deref() {
+        Some(value) => Some(
+            parse_invited_by_filter(value)
+                .ok_or_else(|| AppError::invalid_field("invited_by", "ユーザーID、0、nullのいずれかを指定してください"))?,
+        ),
+        None => None,
+    };
+    // 登録日時の範囲指定は開始 < 終了でなければならない
+    if let (Some(after), Some(before)) = (query.registered_after, query.registered_before)
+        && after >= before
+    {
+        return Err(AppError::invalid_field("registered_before", "registered_afterより後の日時を指定してください"));
+    }
+    let filter = UserFilterParams {
+        is_root: query.is_root,
+        can_invite: query.can_invite,
+        invited_by,
+        registered_after: query.registered_after,
+        registered_before: query.registered_before,
+    };
+    info!(user_id = user.id, "Root user accessed user list");
+    if format != ListFormat::Json {
+        let database = state.database.clone();
+        return Ok(list_format::stream(format, move |before_id| {
+            let database = database.clone();
+            let filter = filter.clone();
+            async move {
+                database
+                    .get_registered_users_page(&filter, before_id, list_format::EXPORT_PAGE_SIZE)
+                    .await
+            }
+        }));
+    }
+    let users = state
+        .database
+        .get_all_registered_users(&filter)
+        .await
+        .context("Failed to get users list")?;
+
+    Ok(list_format::json(Json(UsersListResponse { users })))
+}
+
+#[utoipa::path(
+    get, path = "/v1/admin/export/users.csv", tag = "admin", security(("session_id" = [])),
+    responses(
+        (status = 200, description = "全ユーザーのCSV（`Content-Disposition: attachment`）", content_type = "text/csv", body = String),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "rootユーザーではない", body = ErrorResponse),
+    )
+)]
+async fn export_users_csv(RootUser(user): RootUser, State(state): State<AppState>) -> Response {
+    info!(user_id = user.id, "Root user exported users as CSV");
+    let database = state.database.clone();
+    list_format::csv_attachment("users.csv", move |before_id| {
+        let database = database.clone();
+        async move {
+            let page = database
+                .get_registered_users_page(&UserFilterParams::default(), before_id, list_format::EXPORT_PAGE_SIZE)
+                .await?;
+            Ok(page.into_iter().map(list_format::UserExportRecord).collect())
+        }
+    })
+}
+
+/// `EXPORT_MASK_CODES`で書き出す招待コードの文字数
+const EXPORT_MASKED_CODE_LEN: usize = 8;
+
+#[utoipa::path(
+    get, path = "/v1/admin/export/invites.csv", tag = "admin", security(("session_id" = [])),
+    responses(
+        (status = 200, description = "全招待コードのCSV（`Content-Disposition: attachment`）", content_type = "text/csv", body = String),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "rootユーザーではない", body = ErrorResponse),
+    )
+)]
+async fn export_invites_csv(RootUser(user): RootUser, State(state): State<AppState>) -> Response {
+    info!(user_id = user.id, "Root user exported invite codes as CSV");
+    let database = state.database.clone();
+    let mask_codes = state.config.export_mask_codes;
+    list_format::csv_attachment("invites.csv", move |before_id| {
+        let database = database.clone();
+        async move {
+            let mut page = database.get_invite_export_page(before_id, list_format::EXPORT_PAGE_SIZE).await?;
+            if mask_codes {
+                for invite in &mut page {
+                    invite.code = invite.code.chars().take(EXPORT_MASKED_CODE_LEN).collect();
+                }
+            }
+            Ok(page)
+        }
+    })
+}
+
+#[derive(Deserialize, IntoParams)]
+struct ExportAuditLogQuery {
+    /// この日時以降に記録されたもののみ（RFC 3339）
+    since: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[utoipa::path(
+    get, path = "/v1/admin/export/audit-log.csv", tag = "admin", security(("session_id" = [])),
+    params(ExportAuditLogQuery),
+    responses(
+        (status = 200, description = "監査ログのCSV（新しい順、`Content-Disposition: attachment`）", content_type = "text/csv", body = String),
+        (status = 400, description = "sinceが不正", body = ErrorResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "rootユーザーではない", body = ErrorResponse),
+    )
+)]
+async fn export_audit_log_csv(
+    RootUser(user): RootUser,
+    Query(query): Query<ExportAuditLogQuery>,
+    State(state): State<AppState>,
+) -> Response {
+    info!(user_id = user.id, since = ?query.since, "Root user exported audit log as CSV");
+    let database = state.database.clone();
+    list_format::csv_attachment("audit-log.csv", move |before_id| {
+        let database = database.clone();
+        async move {
+            database
+                .get_audit_export_page(query.since, before_id, list_format::EXPORT_PAGE_SIZE)
+                .await
+        }
+    })
+}
+
+/// NDJSONの監査ログを読む（空行は無視する。行番号は1始まり）
+fn parse_audit_entries(body: &str) -> Result<Vec<(usize, AuditEntry)>, AppError> {
+    let mut entries = Vec::new();
+    for (index, line) in body.lines().enumerate() {
+        let line_number = index + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: AuditEntry = serde_json::from_str(line)
+            .map_err(|e| AppError::Validation(format!("{}行目を読み取れません: {}", line_number, e)))?;
+        if entry.action.trim().is_empty() {
+            return Err(AppError::Validation(format!("{}行目: actionを指定してください", line_number)));
+        }
+        entries.push((line_number, entry));
+    }
+    Ok(entries)
+}
+
+#[utoipa::path(
+    post, path = "/v1/admin/import/audit-log", tag = "admin", security(("session_id" = [])),
+    request_body(
+        content = AuditEntry, content_type = "application/x-ndjson",
+        description = "1行に1件の`AuditEntry`（移行元での記録日時を`created_at`に指定する）",
+    ),
+    responses(
+        (status = 200, body = AuditImportCounts),
+        (status = 400, description = "JSONとして読み取れない行がある", body = ErrorResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "rootユーザーではない", body = ErrorResponse),
+        (status = 422, description = "存在しないユーザーIDを参照している", body = ErrorResponse),
+    )
+)]
+async fn import_audit_log(
+    RootUser(user): RootUser,
+    State(state): State<AppState>,
+    body: String,
+) -> Result<Json<AuditImportCounts>, AppError> {
+    let entries = parse_audit_entries(&body)?;
+
+    // 参照しているユーザーがすべて存在する場合のみ取り込む
+    let mut user_exists = HashMap::new();
+    let mut errors = FieldErrors::default();
+    for (line_number, entry) in &entries {
+        for (field, user_id) in [("actor_user_id", entry.actor_user_id), ("target_user_id", entry.target_user_id)] {
+            let Some(user_id) = user_id else {
+                continue;
+            };
+            let exists = match user_exists.get(&user_id) {
+                Some(exists) => *exists,
+                None => {
+                    let exists = state
+                        .database
+                        .get_user_by_id(user_id)
+                        .await
+                        .context("Database error during audit log import")?
+                        .is_some();
+                    user_exists.insert(user_id, exists);
+                    exists
+                }
+            };
+            if !exists {
+                errors.add(field, format!("{}行目: ユーザーID {}は存在しません", line_number, user_id));
+            }
+        }
+    }
+    errors.into_result().map_err(AppError::InvalidFields)?;
+
+    let entries: Vec<AuditEntry> = entries.into_iter().map(|(_, entry)| entry).collect();
+    let counts = state
+        .database
+        .import_audit_entries(&entries)
+        .await
+        .context("Failed to import audit log entries")?;
+    info!(user_id = user.id, imported = counts.imported, skipped = counts.skipped, "Root user imported audit log");
+
+    Ok(Json(counts))
+}
+
+/// ユーザーを削除できない、または削除前に対応が必要な理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeletionBlockerReason {
+    /// rootユーザーは削除できない
+    IsRoot,
+    /// 自分自身は削除できない
+    IsSelf,
+    /// 有効な招待コードが削除と同時に消える（`POST /v1/invite/:invite_id/transfer`で引き継げる）
+    OwnsActiveInvites,
+    /// このユーザーが招待したユーザーの招待者がいなくなる
+    HasInvitees,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct DeletionBlocker {
+    pub reason: DeletionBlockerReason,
+    /// 対象の件数（`owns_active_invites`・`has_invitees`のみ）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct CanBeDeletedResponse {
+    pub can_delete: bool,
+    pub blockers: Vec<DeletionBlocker>,
+}
+
+/// ユーザー削除の事前チェック（削除処理自体は`delete_user`で従来どおり判定する）
+#[utoipa::path(
+    get, path = "/v1/admin/users/{user_id}/can-be-deleted", tag = "admin", security(("session_id" = [])),
+    params(("user_id" = i64, Path, description = "削除を検討しているユーザーID")),
+    responses(
+        (status = 200, body = CanBeDeletedResponse),
+        (status = 400, description = "パスのIDが数値ではない", body = ErrorResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "rootユーザーではない", body = ErrorResponse),
+        (status = 404, description = "ユーザーが存在しない", body = ErrorResponse),
+    )
+)]
+async fn user_can_be_deleted(
+    RootUser(user): RootUser,
+    Path(user_id): Path<i64>,
+    State(state): State<AppState>,
+) -> Result<Json<CanBeDeletedResponse>, AppError> {
+    let target = state
+        .database
+        .get_user_by_id(user_id)
+        .await
+        .context("Database error during deletion check")?
+        .ok_or(ErrorCode::UserNotFound)?;
+
+    let mut blockers = Vec::new();
+    if target.is_root {
+        blockers.push(DeletionBlocker { reason: DeletionBlockerReason::IsRoot, count: None });
+    }
+    if target.id == user.id {
+        blockers.push(DeletionBlocker { reason: DeletionBlockerReason::IsSelf, count: None });
+    }
+
+    let invites = state
+        .database
+        .get_invite_summary_by_user(target.id)
+        .await
+        .context("Database error during deletion check")?;
+    if invites.outstanding > 0 {
+        blockers.push(DeletionBlocker {
+            reason: DeletionBlockerReason::OwnsActiveInvites,
+            count: Some(invites.outstanding as usize),
+        });
+    }
+
+    let invitees = state
+        .database
+        .get_all_registered_users(&UserFilterParams {
+            invited_by: Some(InvitedByFilter::User(target.id)),
+            ..Default::default()
+        })
+        .await
+        .context("Database error during deletion check")?;
+    if !invitees.is_empty() {
+        blockers.push(DeletionBlocker {
+            reason: DeletionBlockerReason::HasInvitees,
+            count: Some(invitees.len()),
+        });
+    }
+
+    Ok(Json(CanBeDeletedResponse {
+        can_delete: blockers.is_empty(),
+        blockers,
+    }))
+}
+
+#[utoipa::path(
+    delete, path = "/v1/admin/users/{user_id}", tag = "admin", security(("session_id" = [])),
+    params(("user_id" = String, Path, description = "削除するユーザーID")),
+    responses(
+        (status = 200, body = DeleteUserResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "rootユーザーではない", body = ErrorResponse),
+    )
+)]
+async fn delete_user(
+    RootUser(user): RootUser,
+    Path(user_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<DeleteUserResponse>, AppError> {
+    info!(user_id = user.id, target_user_id = user_id, "Delete user request received");
+
+    // ユーザーIDを数値に変換
+    let target_user_id = match user_id.parse::<i64>() {
+        Ok(id) => id,
+        Err(_) => {
+            return Ok(Json(DeleteUserResponse {
+                success: false,
+                message: "無効なユーザーIDです".to_string(),
+            }));
+        }
+    };
+
+    // 自分自身の削除を防ぐ
+    if target_user_id == user.id {
+        return Ok(Json(DeleteUserResponse {
+            success: false,
+            message: "自分自身は削除できません".to_string(),
+        }));
+    }
+
+    // ユーザーを削除
+    info!("Attempting to delete user ID: {}", target_user_id);
+    match state.database.delete_user(target_user_id).await {
+        Ok(true) => {
+            state.user_cache.invalidate_id(target_user_id);
+            state.invite_cache.invalidate_created_by(target_user_id);
+            info!(user_id = user.id, target_user_id, "Root user deleted user");
+
+            Ok(Json(DeleteUserResponse {
+                success: true,
+                message: "ユーザーが正常に削除されました".to_string(),
+            }))
+        }
+        Ok(false) => {
+            warn!("Delete operation returned false for user ID: {}", target_user_id);
+            Ok(Json(DeleteUserResponse {
+                success: false,
+                message: "ユーザーが見つからないか、rootユーザーは削除できません".to_string(),
+            }))
+        }
+        Err(e) => {
+            warn!("Database error during user deletion - ID: {}, Error: {:?}", target_user_id, e);
+            Ok(Json(DeleteUserResponse {
+                success: false,
+                message: format!("削除中にデータベースエラーが発生しました: {}", e),
+            }))
+        }
+    }
+}
+
+#[utoipa::path(
+    post, path = "/v1/admin/users/{user_id}/ban", tag = "admin", security(("session_id" = [])),
+    params(("user_id" = i64, Path, description = "利用停止するユーザーID")),
+    responses(
+        (status = 200, body = BanUserResponse),
+        (status = 400, description = "自分自身は利用停止できない", body = ErrorResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "rootユーザーではない、または対象がrootユーザー", body = ErrorResponse),
+        (status = 404, description = "ユーザーが存在しない", body = ErrorResponse),
+    )
+)]
+async fn ban_user(
+    RootUser(user): RootUser,
+    Path(user_id): Path<i64>,
+    State(state): State<AppState>,
+) -> Result<Json<BanUserResponse>, AppError> {
+    // 自分自身のBANを防ぐ
+    if user_id == user.id {
+        return Err(ErrorCode::CannotTargetSelf.into());
+    }
+
+    let target = state
+        .database
+        .get_user_by_id(user_id)
+        .await
+        .context("Database error during user ban")?
+        .ok_or(ErrorCode::UserNotFound)?;
+
+    // rootユーザーはBANできない
+    if target.is_root {
+        warn!("User {} attempted to ban root user {}", user.email, target.email);
+        return Err(ErrorCode::RootUserProtected.into());
+    }
+
+    // 対象ユーザーの全セッションを無効化
+    let sessions_revoked = {
+        let mut sessions = state.sessions.write().await;
+        let before = sessions.len();
+        sessions.retain(|_, session| session.email != target.email);
+        before - sessions.len()
+    };
+
+    let outcome = state
+        .database
+        .ban_user(user.id, target.id, sessions_revoked)
+        .await
+        .with_context(|| format!("Database error during user ban - ID: {}", user_id))?
+        .ok_or(ErrorCode::UserNotFound)?;
+    state.user_cache.invalidate(&target.email).await;
+    state.invite_cache.invalidate_created_by(target.id);
+    events::publish(&state.events, ServerEvent::user_updated(target.id, "banned", user.id, state.clock.now()));
+
+    info!(
+        "Root user {} banned user {} (sessions revoked: {}, invites deactivated: {})",
+        user.email, target.email, sessions_revoked, outcome.invites_deactivated
+    );
+    Ok(Json(BanUserResponse {
+        banned: true,
+        sessions_revoked,
+        invites_deactivated: outcome.invites_deactivated,
+    }))
+}
+
+#[utoipa::path(
+    post, path = "/v1/admin/users/{user_id}/unban", tag = "admin", security(("session_id" = [])),
+    params(("user_id" = i64, Path, description = "利用停止を解除するユーザーID")),
+    responses(
+        (status = 200, body = UnbanUserResponse),
+        (status = 400, description = "パスのIDが数値ではない", body = ErrorResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "rootユーザーではない", body = ErrorResponse),
+        (status = 404, description = "ユーザーが存在しない", body = ErrorResponse),
+    )
+)]
+async fn unban_user(
+    RootUser(user): RootUser,
+    Path(user_id): Path<i64>,
+    State(state): State<AppState>,
+) -> Result<Json<UnbanUserResponse>, AppError> {
+    // 失効済みのセッションや無効化した招待コードは復元しない（再ログインが必要）
+    let unbanned = state
+        .database
+        .unban_user(user.id, user_id)
+        .await
+        .with_context(|| format!("Database error during user unban - ID: {}", user_id))?;
+    if !unbanned {
+        return Err(ErrorCode::UserNotFound.into());
+    }
+    state.user_cache.invalidate_id(user_id);
+    events::publish(&state.events, ServerEvent::user_updated(user_id, "unbanned", user.id, state.clock.now()));
+
+    info!(user_id = user.id, target_user_id = user_id, "Root user unbanned user");
+    Ok(Json(UnbanUserResponse { unbanned: true }))
+}
+
+/// rootユーザーへの昇格に必要な確認用の文字列（自動化の誤ったリクエストで昇格させないため）
+const PROMOTE_TO_ROOT_CONFIRMATION: &str = "PROMOTE_TO_ROOT";
+
+#[derive(Deserialize, ToSchema)]
+struct MakeRootRequest {
+    /// `PROMOTE_TO_ROOT`（大文字・小文字を区別する）
+    confirm_action: String,
+}
+
+impl Validate for MakeRootRequest {
+    fn validate(&self) -> Result<(), FieldErrors> {
+        // 一致しない場合は422ではなく400（confirmation_mismatch）にするため、ハンドラーで確認する
+        Ok(())
+    }
+}
+
+#[utoipa::path(
+    post, path = "/v1/users/{user_id}/promote", tag = "admin", security(("session_id" = [])),
+    params(("user_id" = i64, Path, description = "rootユーザーにするユーザーID")),
+    request_body = MakeRootRequest,
+    responses(
+        (status = 200, body = PromoteUserResponse),
+        (status = 400, description = "ボディを読み取れない、confirm_actionが一致しない、または対象が利用停止中", body = ErrorResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "rootユーザーではない", body = ErrorResponse),
+        (status = 404, description = "ユーザーが存在しない", body = ErrorResponse),
+    )
+)]
+async fn promote_user(
+    RootUser(user): RootUser,
+    Path(user_id): Path<i64>,
+    State(state): State<AppState>,
+    ValidatedJson(request): ValidatedJson<MakeRootRequest>,
+) -> Result<Json<PromoteUserResponse>, AppError> {
+    if request.confirm_action != PROMOTE_TO_ROOT_CONFIRMATION {
+        warn!(user_id = user.id, target_user_id = user_id, "Root promotion rejected: confirmation mismatch");
+        return Err(ErrorCode::ConfirmationMismatch.into());
+    }
+
+    let target = state
+        .database
+        .get_user_by_id(user_id)
+        .await
+        .context("Database error during root promotion")?
+        .ok_or(ErrorCode::UserNotFound)?;
+    if target.is_root {
+        return Ok(Json(PromoteUserResponse { promoted: false }));
+    }
+    if !target.is_active {
+        return Err(AppError::Validation("利用停止中のユーザーはrootユーザーにできません".to_string()));
+    }
+
+    let promoted = state
+        .database
+        .promote_to_root(user.id, target.id)
+        .await
+        .with_context(|| format!("Database error during root promotion - ID: {}", user_id))?;
+    state.user_cache.invalidate(&target.email).await;
+    if promoted.is_some() {
+        events::publish(&state.events, ServerEvent::user_updated(target.id, "promoted", user.id, state.clock.now()));
+    }
+
+    info!(user_id = user.id, target_user_id = user_id, "Root user promoted user to root");
+    Ok(Json(PromoteUserResponse { promoted: promoted.is_some() }))
+}
+
+/// `POST /v1/users/{user_id}/promote`の別名
+#[utoipa::path(
+    post, path = "/v1/users/{user_id}/make-root", tag = "admin", security(("session_id" = [])),
+    params(("user_id" = i64, Path, description = "rootユーザーにするユーザーID")),
+    request_body = MakeRootRequest,
+    responses(
+        (status = 200, body = PromoteUserResponse),
+        (status = 400, description = "ボディを読み取れない、confirm_actionが一致しない、または対象が利用停止中", body = ErrorResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "rootユーザーではない", body = ErrorResponse),
+        (status = 404, description = "ユーザーが存在しない", body = ErrorResponse),
+    )
+)]
+async fn make_root(
+    root: RootUser,
+    path: Path<i64>,
+    state: State<AppState>,
+    request: ValidatedJson<MakeRootRequest>,
+) -> Result<Json<PromoteUserResponse>, AppError> {
+    promote_user(root, path, state, request).await
+}
+
+#[derive(Deserialize, ToSchema)]
+struct SetCanInviteRequest {
+    can_invite: bool,
+}
+
+impl Validate for SetCanInviteRequest {
+    fn validate(&self) -> Result<(), FieldErrors> {
+        Ok(())
+    }
+}
+
+#[utoipa::path(
+    patch, path = "/v1/users/{user_id}/can-invite", tag = "admin", security(("session_id" = [])),
+    params(("user_id" = i64, Path, description = "招待権限を変更するユーザーID")),
+    request_body = SetCanInviteRequest,
+    responses(
+        (status = 200, body = SetCanInviteResponse),
+        (status = 400, description = "ボディを読み取れない、または自分の招待権限を取り消そうとした", body = ErrorResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "rootユーザーではない", body = ErrorResponse),
+        (status = 404, description = "ユーザーが存在しない", body = ErrorResponse),
+    )
+)]
+async fn set_can_invite(
+    RootUser(user): RootUser,
+    Path(user_id): Path<i64>,
+    State(state): State<AppState>,
+    ValidatedJson(request): ValidatedJson<SetCanInviteRequest>,
+) -> Result<Json<SetCanInviteResponse>, AppError> {
+    // rootユーザーが自分の招待権限を取り消すと招待コードを作れる管理者がいなくなり得る
+    if user.id == user_id && !request.can_invite {
+        warn!(user_id = user.id, "Root user attempted to revoke own invite permission");
+        return Err(ErrorCode::CannotTargetSelf.into());
+    }
+
+    let updated = state
+        .database
+        .set_can_invite(user.id, user_id, request.can_invite)
+        .await
+        .with_context(|| format!("Database error during invite permission update - ID: {}", user_id))?;
+    if !updated {
+        return Err(ErrorCode::UserNotFound.into());
+    }
+    state.user_cache.invalidate_id(user_id);
+    events::publish(&state.events, ServerEvent::user_updated(user_id, "can_invite", user.id, state.clock.now()));
+
+    info!(user_id = user.id, target_user_id = user_id, can_invite = request.can_invite, "Invite permission changed");
+    Ok(Json(SetCanInviteResponse { user_id, can_invite: request.can_invite }))
+}
+
+const MAX_API_KEY_NAME_CHARS: usize = 100;
+const MAX_API_KEY_EXPIRES_IN_DAYS: i64 = 3650;
+
+#[derive(Deserialize, ToSchema)]
+struct CreateApiKeyRequest {
+    /// 用途の分かる名前
+    name: String,
+    /// キーで呼び出せる操作（`read:users`等、1つ以上。スコープのないルートとAPIキーの管理はAPIキーでは呼び出せない）
+    #[serde(default)]
+    scopes: Vec<String>,
+    /// 有効期間（日数、1〜3650。省略時は無期限）
+    expires_in_days: Option<i64>,
+    /// キーを作成するユーザー（省略時は自分。他のユーザーはrootユーザーのみ指定できる）
+    user_id: Option<i64>,
+}
+
+impl Validate for CreateApiKeyRequest {
+    fn validate(&self) -> Result<(), FieldErrors> {
+        let mut errors = FieldErrors::default();
+        let name = self.name.trim();
+        if name.is_empty() || name.chars().count() > MAX_API_KEY_NAME_CHARS {
+            errors.add("name", format!("1文字以上{}文字以内で指定してください", MAX_API_KEY_NAME_CHARS));
+        }
+        if self.scopes.is_empty() {
+            errors.add("scopes", "1つ以上指定してください");
+        } else if let Some(scope) = self
+            .scopes
+            .iter()
+            .find(|scope| !ApiKeyScope::ALL.iter().any(|known| known.as_str() == scope.as_str()))
+        {
+            errors.add("scopes", format!("不明なスコープです: {}", scope));
+        }
+        if let Some(days) = self.expires_in_days
+            && !(1..=MAX_API_KEY_EXPIRES_IN_DAYS).contains(&days)
+        {
+            errors.add("expires_in_days", format!("1〜{}の範囲で指定してください", MAX_API_KEY_EXPIRES_IN_DAYS));
+        }
+        errors.into_result()
+    }
+}
+
+/// APIキーを作成する（OAuthを使えない連携先が`X-Api-Key`で作成者として認証するため）
+///
+/// セッションでのみ呼び出せる（ルートにスコープがないため、APIキーで新しいキーを作ることはできない）。
+#[utoipa::path(
+    post, path = "/v1/api-keys", tag = "auth", security(("session_id" = [])),
+    request_body = CreateApiKeyRequest,
+    responses(
+        (status = 201, body = CreateApiKeyResponse),
+        (status = 400, description = "ボディを読み取れない、または対象のユーザーが利用停止中", body = ErrorResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "ユーザーが未登録・利用停止中、APIキーで呼び出した、または他のユーザーを指定したがrootユーザーではない", body = ErrorResponse),
+        (status = 404, description = "対象のユーザーが存在しない", body = ErrorResponse),
+        (status = 422, description = "項目の値が不正", body = ErrorResponse),
+    )
+)]
+async fn create_api_key(
+    AuthUser(user): AuthUser,
+    State(state): State<AppState>,
+    ValidatedJson(request): ValidatedJson<CreateApiKeyRequest>,
+) -> Result<(StatusCode, [(HeaderName, &'static str); 1], Json<CreateApiKeyResponse>), AppError> {
+    // 本人またはrootユーザーのみ作成可能
+    let owner_id = match request.user_id {
+        Some(user_id) if user_id != user.id => {
+            if !user.is_root {
+                warn!("User {} attempted to create an API key for user {}", user.email, user_id);
+                return Err(ErrorCode::InsufficientPermission.into());
+            }
+            let target = state
+                .database
+                .get_user_by_id(user_id)
+                .await
+                .context("Database error during API key owner lookup")?
+                .ok_or(ErrorCode::UserNotFound)?;
+            if !target.is_active {
+                return Err(AppError::invalid_field("user_id", "利用停止中のユーザーは指定できません"));
+            }
+            target.id
+        }
+        _ => user.id,
+    };
+
+    let expires_at = request.expires_in_days.map(|days| state.clock.now() + chrono::Duration::days(days));
+    let key = auth::generate_api_key()?;
+    let api_key = state
+        .database
+        .create_api_key(user.id, owner_id, request.name.trim(), &auth::hash_api_key(&key), &request.scopes, expires_at)
+        .await
+        .context("Database error during API key creation")?;
+
+    info!(user_id = user.id, owner_id, api_key_id = api_key.id, "API key created");
+    // キーはこのレスポンスでしか返さないため、キャッシュにも冪等キーの記録にも残さない
+    Ok((StatusCode::CREATED, [(CACHE_CONTROL, "no-store")], Json(CreateApiKeyResponse { key, api_key })))
+}
+
+/// 自分のAPIキーの一覧（キーそのものは含まない）
+#[utoipa::path(
+    get, path = "/v1/api-keys", tag = "auth", security(("session_id" = [])),
+    responses(
+        (status = 200, body = Vec<ApiKey>),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "ユーザーが未登録・利用停止中、またはAPIキーで呼び出した", body = ErrorResponse),
+    )
+)]
+async fn list_api_keys(
+    AuthUser(user): AuthUser,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ApiKey>>, AppError> {
+    let api_keys = state
+        .database
+        .get_api_keys_by_user(user.id)
+        .await
+        .context("Database error during API key listing")?;
+
+    Ok(Json(api_keys))
+}
+
+/// APIキーを無効化する（作成者本人またはrootユーザーのみ。無効化したキーでのリクエストは401になる）
+#[utoipa::path(
+    delete, path = "/v1/api-keys/{key_id}", tag = "auth", security(("session_id" = [])),
+    params(("key_id" = i64, Path, description = "APIキーのID")),
+    responses(
+        (status = 200, body = ApiKey),
+        (status = 400, description = "パスのIDが数値ではない", body = ErrorResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "ユーザーが未登録・利用停止中、またはAPIキーで呼び出した", body = ErrorResponse),
+        (status = 404, description = "APIキーが存在しない（他のユーザーのキーを含む）", body = ErrorResponse),
+    )
+)]
+async fn revoke_api_key(
+    AuthUser(user): AuthUser,
+    Path(key_id): Path<i64>,
+    State(state): State<AppState>,
+) -> Result<Json<ApiKey>, AppError> {
+    // 他のユーザーのキーは存在するかどうかも明かさない
+    let api_key = state
+        .database
+        .get_api_key_by_id(key_id)
+        .await
+        .context("Database error during API key lookup")?
+        .filter(|api_key| api_key.created_by == user.id || user.is_root)
+        .ok_or(ErrorCode::ApiKeyNotFound)?;
+
+    let revoked = state
+        .database
+        .deactivate_api_key(user.id, api_key.id)
+        .await
+        .context("Database error during API key revocation")?
+        .ok_or(ErrorCode::ApiKeyNotFound)?;
+
+    info!(user_id = user.id, api_key_id = key_id, "API key revoked");
+    Ok(Json(revoked))
+}
+
+#[utoipa::path(
+    get, path = "/v1/admin/stats", tag = "admin", security(("session_id" = [])),
+    responses(
+        (status = 200, body = SystemStats),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "rootユーザーではない", body = ErrorResponse),
+    )
+)]
+async fn admin_stats(
+    RootUser(user): RootUser,
+    State(state): State<AppState>,
+) -> Result<Json<SystemStats>, AppError> {
+    {
+        let cached = state.admin_stats.read().await;
+        if let Some(entry) = cached.as_ref()
+            && Instant::now() < entry.expires_at
+        {
+            return Ok(Json(entry.stats.clone()));
+        }
+    }
+
+    let mut cached = state.admin_stats.write().await;
+    // 書き込みロック待ちの間に他のリクエストが更新している可能性がある
+    if let Some(entry) = cached.as_ref()
+        && Instant::now() < entry.expires_at
+    {
+        return Ok(Json(entry.stats.clone()));
+    }
+
+    let stats = state
+        .database
+        .get_system_stats()
+        .await
+        .context("Database error during admin stats aggregation")?;
+    *cached = Some(CachedStats {
+        stats: stats.clone(),
+        expires_at: Instant::now() + state.config.admin_stats_ttl(),
+    });
+
+    info!(user_id = user.id, "Root user refreshed admin stats");
+    Ok(Json(stats))
+}
+
+/// 招待コードの作成から使用までの件数
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct InviteFunnel {
+    pub created: i64,
+    /// 未使用・有効・期限内
+    pub pending: i64,
+    pub used: i64,
+    /// 未使用のまま期限切れ
+    pub expired: i64,
+}
+
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct RecentRegistration {
+    pub id: i64,
+    pub email: String,
+    pub name: String,
+    pub registered_at: chrono::DateTime<chrono::Utc>,
+    pub invited_by: Option<i64>,
+}
+
+/// 管理画面のトップに表示する概要（項目ごとに集計し、時間内に取得できなかった項目はnull）
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct AdminOverviewResponse {
+    pub users: Option<UserActivity>,
+    pub invites: Option<InviteFunnel>,
+    /// 新しい順
+    pub recent_registrations: Option<Vec<RecentRegistration>>,
+    /// ログインを開始して完了していない認証トークンの数（`/v1/login/api`）
+    pub pending_auth: usize,
+    /// 時間内に取得できなかった項目名（`users`・`invites`・`recent_registrations`）
+    pub unavailable: Vec<String>,
+    pub generated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// 概要の各項目の集計に使える時間（超えた項目はnullにして残りを返す）
+const ADMIN_OVERVIEW_BUDGET: Duration = Duration::from_secs(2);
+const RECENT_REGISTRATIONS_LIMIT: i64 = 10;
+
+/// 時間内に取得できなければ`unavailable`に項目名を加えてNoneを返す
+async fn overview_section<T>(
+    name: &'static str,
+    deadline: tokio::time::Instant,
+    query: impl std::future::Future<Output = Result<T, sqlx::Error>>,
+) -> Result<T, &'static str> {
+    match tokio::time::timeout_at(deadline, query).await {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(e)) => {
+            warn!("Database error during admin overview ({}): {:?}", name, e);
+            Err(name)
+        }
+        Err(_) => {
+            warn!("Admin overview section {} timed out", name);
+            Err(name)
+        }
+    }
+}
+
+#[utoipa::path(
+    get, path = "/v1/admin/overview", tag = "admin", security(("session_id" = [])),
+    responses(
+        (status = 200, body = AdminOverviewResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "rootユーザーではない", body = ErrorResponse),
+    )
+)]
+async fn admin_overview(
+    RootUser(user): RootUser,
+    State(state): State<AppState>,
+) -> Json<AdminOverviewResponse> {
+    {
+        let cached = state.admin_overview.read().await;
+        if let Some(entry) = cached.as_ref()
+            && Instant::now() < entry.expires_at
+        {
+            return Json(entry.overview.clone());
+        }
+    }
+
+    let mut cached = state.admin_overview.write().await;
+    // 書き込みロック待ちの間に他のリクエストが更新している可能性がある
+    if let Some(entry) = cached.as_ref()
+        && Instant::now() < entry.expires_at
+    {
+        return Json(entry.overview.clone());
+    }
+
+    // 遅い項目があっても他の項目は返せるよう、項目ごとに同じ期限で打ち切る
+    let deadline = tokio::time::Instant::now() + ADMIN_OVERVIEW_BUDGET;
+    let recent_filter = UserFilterParams::default();
+    let (users, stats, recent) = tokio::join!(
+        overview_section("users", deadline, state.database.get_user_activity()),
+        overview_section("invites", deadline, state.database.get_system_stats()),
+        overview_section(
+            "recent_registrations",
+            deadline,
+            state
+                .database
+                .get_registered_users_page(&recent_filter, None, RECENT_REGISTRATIONS_LIMIT),
+        ),
+    );
+    let pending_auth = state.auth_tokens.read().await.values().filter(|session| session.is_none()).count();
+
+    let unavailable: Vec<String> = [users.as_ref().err(), stats.as_ref().err(), recent.as_ref().err()]
+        .into_iter()
+        .flatten()
+        .map(|name| name.to_string())
+        .collect();
+    let overview = AdminOverviewResponse {
+        users: users.ok(),
+        invites: stats.ok().map(|stats| InviteFunnel {
+            created: stats.total_invites,
+            pending: stats.pending_invites,
+            used: stats.used_invites,
+            expired: stats.expired_invites,
+        }),
+        recent_registrations: recent.ok().map(|users| {
+            users
+                .into_iter()
+                .map(|user| RecentRegistration {
+                    id: user.id,
+                    email: user.email,
+                    name: user.name,
+                    registered_at: user.registered_at,
+                    invited_by: user.invited_by,
+                })
+                .collect()
+        }),
+        pending_auth,
+        unavailable,
+        generated_at: state.clock.now(),
+    };
+    // 一部が欠けた結果はキャッシュせず、次のリクエストで再度集計する
+    if overview.unavailable.is_empty() {
+        *cached = Some(CachedOverview {
+            overview: overview.clone(),
+            expires_at: Instant::now() + state.config.admin_overview_ttl(),
+        });
+    }
+
+    info!(user_id = user.id, unavailable = overview.unavailable.len(), "Root user refreshed admin overview");
+    Json(overview)
+}
+
+/// ヘルスチェックの結果
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct HealthResponse {
+    /// `ok`・`ready`・`unavailable`
+    pub status: String,
+    /// `unavailable`の理由
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl HealthResponse {
+    fn status(status: &str) -> Json<Self> {
+        Json(HealthResponse {
+            status: status.to_string(),
+            reason: None,
+        })
+    }
+
+    fn unavailable(reason: &str) -> (StatusCode, Json<Self>) {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(HealthResponse {
+                status: "unavailable".to_string(),
+                reason: Some(reason.to_string()),
+            }),
+        )
+    }
+}
+
+/// プローブの間隔（数秒）より短くする（DBが応答しない場合にプールの取得待ちで詰まらないように）
+const READINESS_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// プロセスが応答できるか（データベースには接続しない）
+#[utoipa::path(get, path = "/healthz", tag = "system", responses((status = 200, body = HealthResponse)))]
+async fn healthz() -> Json<HealthResponse> {
+    HealthResponse::status("ok")
+}
+
+/// リクエストを受け付けられるか（データベースへの接続とスキーマを確認する）
+#[utoipa::path(
+    get, path = "/readyz", tag = "system",
+    responses(
+        (status = 200, body = HealthResponse),
+        (status = 503, description = "データベースに接続できない、またはスキーマが古い", body = HealthResponse),
+    )
+)]
+async fn readyz(State(state): State<AppState>) -> Result<Json<HealthResponse>, (StatusCode, Json<HealthResponse>)> {
+    let check = async {
+        state.database.ping().await.map_err(|e| ("database unreachable", e))?;
+        state
+            .database
+            .check_schema()
+            .await
+            .map_err(|e| ("database schema is not up to date", e))
+    };
+
+    match tokio::time::timeout(READINESS_CHECK_TIMEOUT, check).await {
+        Ok(Ok(())) => Ok(HealthResponse::status("ready")),
+        Ok(Err((reason, e))) => {
+            warn!("Readiness check failed: {}: {:?}", reason, e);
+            Err(HealthResponse::unavailable(reason))
+        }
+        Err(_) => {
+            warn!("Readiness check timed out after {:?}", READINESS_CHECK_TIMEOUT);
+            Err(HealthResponse::unavailable("database check timed out"))
+        }
+    }
+}
+
+/// 登録ユーザー数と招待コードの状態別の件数
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct SystemStatusResponse {
+    pub users_registered: i64,
+    pub invite_stats: InviteStats,
+}
+
+/// 利用状況の概要（認証不要、`system_status_ttl_secs`の間キャッシュする）
+#[utoipa::path(get, path = "/v1/system/status", tag = "system", responses((status = 200, body = SystemStatusResponse)))]
+async fn system_status(State(state): State<AppState>) -> Result<Json<SystemStatusResponse>, AppError> {
+    {
+        let cached = state.system_status.read().await;
+        if let Some(entry) = cached.as_ref()
+            && Instant::now() < entry.expires_at
+        {
+            return Ok(Json(entry.status.clone()));
+        }
+    }
+
+    let mut cached = state.system_status.write().await;
+    // 書き込みロック待ちの間に他のリクエストが更新している可能性がある
+    if let Some(entry) = cached.as_ref()
+        && Instant::now() < entry.expires_at
+    {
+        return Ok(Json(entry.status.clone()));
+    }
+
+    let users_registered = state
+        .database
+        .count_registered_users()
+        .await
+        .context("Database error during system status user count")?;
+    let invite_stats = state
+        .database
+        .get_invite_stats()
+        .await
+        .context("Database error during system status invite stats")?;
+    let status = SystemStatusResponse {
+        users_registered,
+        invite_stats,
+    };
+    *cached = Some(CachedStatus {
+        status: status.clone(),
+        expires_at: Instant::now() + state.config.system_status_ttl(),
+    });
+
+    Ok(Json(status))
+}
+
+/// 管理者の対応が必要な作業の一覧（対象が1件以上あるもののみ）
+#[utoipa::path(
+    get, path = "/v1/system/pending-actions", tag = "system", security(("session_id" = [])),
+    responses(
+        (status = 200, body = Vec<PendingAction>),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "rootユーザーではない", body = ErrorResponse),
+    )
+)]
+async fn pending_actions(
+    _root: RootUser,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<PendingAction>>, AppError> {
+    let actions = state
+        .database
+        .get_pending_actions()
+        .await
+        .context("Database error during pending actions check")?;
+
+    Ok(Json(actions.into_iter().filter(|action| action.count > 0).collect()))
+}
+
+/// 適用済みのマイグレーションの一覧（データベースに接続せずに適用状況を確認するため）
+#[utoipa::path(
+    get, path = "/v1/system/migrations", tag = "system", security(("session_id" = [])),
+    responses(
+        (status = 200, body = Vec<MigrationRecord>),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "rootユーザーではない", body = ErrorResponse),
+    )
+)]
+async fn system_migrations(
+    _root: RootUser,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<MigrationRecord>>, AppError> {
+    let migrations = state
+        .database
+        .get_applied_migrations()
+        .await
+        .context("Database error during migration lookup")?;
+
+    Ok(Json(migrations))
+}
+
+/// 未適用のマイグレーションを再起動せずに適用し、今回適用したものを返す（開発用。リリースビルドには含めない）
+#[cfg(all(feature = "dev", debug_assertions))]
+pub(crate) async fn run_migrations(
+    _root: RootUser,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<MigrationRecord>>, AppError> {
+    let applied = state
+        .database
+        .run_pending_migrations()
+        .await
+        .context("Database error while running migrations")?;
+    info!(count = applied.len(), "Applied pending migrations");
+
+    Ok(Json(applied))
+}
+
+/// APIが返すエラーコードの一覧（クライアントで網羅的に処理するため）
+#[utoipa::path(get, path = "/v1/system/errors", tag = "system", responses((status = 200, body = Vec<ErrorCatalogEntry>)))]
+async fn system_errors() -> Json<Vec<ErrorCatalogEntry>> {
+    let catalog = ErrorCode::ALL
+        .iter()
+        .map(|&code| ErrorCatalogEntry {
+            code,
+            status: code.status().as_u16(),
+            description: code.description().to_string(),
+        })
+        .collect();
+    Json(catalog)
+}
+
+#[derive(Deserialize, IntoParams)]
+struct TimeseriesQuery {
+    /// 集計する週数（デフォルト: 12、最大: 52）
+    weeks: Option<u32>,
+}
+
+const MAX_TIMESERIES_WEEKS: u32 = 52;
+
+#[utoipa::path(
+    get, path = "/v1/admin/stats/timeseries", tag = "admin", security(("session_id" = [])), params(TimeseriesQuery),
+    responses(
+        (status = 200, body = Vec<WeeklyStats>),
+        (status = 400, description = "weeksが0", body = ErrorResponse),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "rootユーザーではない", body = ErrorResponse),
+    )
+)]
+async fn admin_stats_timeseries(
+    _root: RootUser,
+    Query(query): Query<TimeseriesQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<WeeklyStats>>, AppError> {
+    let weeks = query.weeks.unwrap_or(12).min(MAX_TIMESERIES_WEEKS);
+    if weeks == 0 {
+        return Err(AppError::invalid_field("weeks", "1以上を指定してください"));
+    }
+
+    let stats = state
+        .database
+        .get_weekly_stats(weeks)
+        .await
+        .context("Database error during admin stats timeseries")?;
+
+    Ok(Json(stats))
+}
+
+#[utoipa::path(get, path = "/v1/root/exists", tag = "auth", responses((status = 200, body = RootExistsResponse)))]
+async fn check_root_exists(State(state): State<AppState>) -> Result<Json<RootExistsResponse>, AppError> {
+    let count = state
+        .database
+        .count_registered_users()
+        .await
+        .context("Database error during root exists check")?;
+
+    Ok(Json(RootExistsResponse {
+        root_exists: count > 0,
+    }))
+}
+
+/// 送信待ちのイベントの上限（超えた場合は送信タスクが接続の読み出しを待つ）
+const EVENT_STREAM_BUFFER: usize = 16;
+
+#[utoipa::path(
+    get, path = "/v1/events", tag = "events", security(("session_id" = [])),
+    responses(
+        (status = 200, description = "Server-Sent Eventsストリーム", content_type = "text/event-stream"),
+        (status = 401, description = "セッションが無効", body = ErrorResponse),
+        (status = 403, description = "権限がない、または利用停止中", body = ErrorResponse),
+        (status = 429, description = "同時接続数の上限に達している", body = ErrorResponse),
+    )
+)]
+async fn event_stream(
+    AuthUser(user): AuthUser,
+    Extension(credential): Extension<Credential>,
+    State(state): State<AppState>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, AppError> {
+    // ユーザーごとの同時接続数を制限
+    let Some(connection_guard) = state.event_connections.try_acquire(user.id) else {
+        warn!("User {} exceeded concurrent event stream limit", user.email);
+        return Err(ErrorCode::TooManyConnections.into());
+    };
+    info!(user_id = user.id, "User subscribed to event stream");
+
+    // 配信のたびに権限を確認し直すため、送信はタスクで行う（接続数はタスクの終了まで保持する）
+    let (sender, receiver) = mpsc::channel(EVENT_STREAM_BUFFER);
+    let heartbeat = state.config.sse_heartbeat();
+    tokio::spawn(events::forward(
+        state.clone(),
+        credential,
+        state.events.subscribe(),
+        sender,
+        heartbeat,
+        connection_guard,
+    ));
+
+    // プロキシにアイドル接続を切られないよう一定間隔（デフォルト: 15秒）でコメント行を送る
+    Ok(Sse::new(ReceiverStream::new(receiver)).keep_alive(KeepAlive::new().interval(heartbeat).text("heartbeat")))
+}
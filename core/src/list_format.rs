@@ -0,0 +1,348 @@
+use crate::database::{AuditExportRow, InviteCode, InviteExportRow, RegisteredUser};
+use axum::{
+    async_trait,
+    body::Body,
+    extract::FromRequestParts,
+    http::{
+        header::{ACCEPT, CONTENT_DISPOSITION, CONTENT_TYPE, VARY},
+        request::Parts,
+        HeaderValue,
+    },
+    response::{IntoResponse, Response},
+    BoxError,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use std::{convert::Infallible, future::Future};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{error, Instrument, Span};
+
+/// NDJSON・CSVで書き出す際に1回のクエリで取得する件数
+pub const EXPORT_PAGE_SIZE: i64 = 500;
+
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+const CSV_CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+
+/// 一覧のレスポンス形式（`Accept`ヘッダーで選ぶ。指定がない・対応していない場合はJSON）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListFormat {
+    Json,
+    Ndjson,
+    Csv,
+}
+
+impl ListFormat {
+    /// `q`の最も大きい形式を選ぶ（同じ場合は先に書かれたもの。`q=0`は除外）
+    fn from_accept(accept: &str) -> Self {
+        let mut best: Option<(ListFormat, f32)> = None;
+        for range in accept.split(',') {
+            let mut params = range.split(';').map(str::trim);
+            let format = match params.next().unwrap_or_default().to_ascii_lowercase().as_str() {
+                "application/json" | "application/*" | "*/*" => ListFormat::Json,
+                "application/x-ndjson" => ListFormat::Ndjson,
+                "text/csv" | "text/*" => ListFormat::Csv,
+                _ => continue,
+            };
+            let q = params
+                .filter_map(|param| param.strip_prefix("q="))
+                .find_map(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+                best = Some((format, q));
+            }
+        }
+        best.map(|(format, _)| format).unwrap_or(ListFormat::Json)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ListFormat
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .headers
+            .get(ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .map(ListFormat::from_accept)
+            .unwrap_or(ListFormat::Json))
+    }
+}
+
+/// NDJSON・CSVで書き出す一覧の1行
+pub trait ListRecord: Serialize + Send + 'static {
+    const CSV_HEADER: &'static [&'static str];
+
+    /// 次のページの取得位置（`before_id`）に使う
+    fn id(&self) -> i64;
+
+    /// `CSV_HEADER`と同じ順の値（`None`は空文字列）
+    fn csv_record(&self) -> Vec<String>;
+}
+
+/// JSONのレスポンスに`Vary: Accept`を付ける（同じURLで形式が変わるためキャッシュを分けさせる）
+pub fn json(body: impl IntoResponse) -> Response {
+    let mut response = body.into_response();
+    response.headers_mut().insert(VARY, HeaderValue::from_static("accept"));
+    response
+}
+
+/// `fetch_page`でページごとに読みながらNDJSON・CSVを返す
+///
+/// 全件をメモリに載せないよう、1ページ書き出すごとに次のページを読む（チャンク転送）。
+/// 途中でデータベースのエラーが起きた場合はレスポンスを打ち切る（ステータスは送信済みのため）。
+pub fn stream<T, F, Fut>(format: ListFormat, mut fetch_page: F) -> Response
+where
+    T: ListRecord,
+    F: FnMut(Option<i64>) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<Vec<T>, sqlx::Error>> + Send,
+{
+    let content_type = match format {
+        ListFormat::Ndjson => NDJSON_CONTENT_TYPE,
+        ListFormat::Csv => CSV_CONTENT_TYPE,
+        ListFormat::Json => unreachable!("JSON lists are returned by the handler as a single document"),
+    };
+
+    // バッファを1つにして、クライアントが読むまで次のページを取得しない
+    let (tx, rx) = mpsc::channel::<Result<Vec<u8>, BoxError>>(1);
+    tokio::spawn(
+        async move {
+            if format == ListFormat::Csv && tx.send(encode_csv_header::<T>()).await.is_err() {
+                return;
+            }
+            let mut before_id = None;
+            loop {
+                let page = match fetch_page(before_id).await {
+                    Ok(page) => page,
+                    Err(e) => {
+                        error!("Failed to read list page for export: {:?}", e);
+                        let _ = tx.send(Err(e.into())).await;
+                        return;
+                    }
+                };
+                let Some(last) = page.last() else {
+                    return;
+                };
+                before_id = Some(last.id());
+                let chunk = match format {
+                    ListFormat::Csv => encode_csv(&page),
+                    _ => encode_ndjson(&page),
+                };
+                // 送信できないのはクライアントが切断した場合
+                if tx.send(chunk).await.is_err() || (page.len() as i64) < EXPORT_PAGE_SIZE {
+                    return;
+                }
+            }
+        }
+        .instrument(Span::current()),
+    );
+
+    let mut response = Body::from_stream(ReceiverStream::new(rx)).into_response();
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    headers.insert(VARY, HeaderValue::from_static("accept"));
+    response
+}
+
+/// ファイルとしてダウンロードさせるCSV（`Content-Disposition: attachment`、形式は`Accept`によらない）
+pub fn csv_attachment<T, F, Fut>(filename: &str, fetch_page: F) -> Response
+where
+    T: ListRecord,
+    F: FnMut(Option<i64>) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<Vec<T>, sqlx::Error>> + Send,
+{
+    let mut response = stream(ListFormat::Csv, fetch_page);
+    let headers = response.headers_mut();
+    headers.remove(VARY);
+    headers.insert(
+        CONTENT_DISPOSITION,
+        HeaderValue::from_str(&format!("attachment; filename=\"{}\"", filename))
+            .expect("export filename must be a valid header value"),
+    );
+    response
+}
+
+fn encode_ndjson<T: ListRecord>(records: &[T]) -> Result<Vec<u8>, BoxError> {
+    let mut buf = Vec::new();
+    for record in records {
+        serde_json::to_writer(&mut buf, record)?;
+        buf.push(b'\n');
+    }
+    Ok(buf)
+}
+
+fn encode_csv_header<T: ListRecord>() -> Result<Vec<u8>, BoxError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(T::CSV_HEADER)?;
+    Ok(writer.into_inner()?)
+}
+
+fn encode_csv<T: ListRecord>(records: &[T]) -> Result<Vec<u8>, BoxError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for record in records {
+        writer.write_record(record.csv_record())?;
+    }
+    Ok(writer.into_inner()?)
+}
+
+/// JSONと同じ形式の日時（RFC 3339、UTC）
+fn csv_datetime(value: &DateTime<Utc>) -> String {
+    value.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+fn csv_optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+impl ListRecord for RegisteredUser {
+    const CSV_HEADER: &'static [&'static str] = &[
+        "id",
+        "google_id",
+        "email",
+        "name",
+        "registered_at",
+        "last_login",
+        "is_root",
+        "can_invite",
+        "invited_by",
+        "is_active",
+        "metadata",
+    ];
+
+    fn id(&self) -> i64 {
+        self.id
+    }
+
+    fn csv_record(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.google_id.clone(),
+            self.email.clone(),
+            self.name.clone(),
+            csv_datetime(&self.registered_at),
+            csv_optional(self.last_login.as_ref().map(csv_datetime)),
+            self.is_root.to_string(),
+            self.can_invite.to_string(),
+            csv_optional(self.invited_by),
+            self.is_active.to_string(),
+            self.metadata.to_string(),
+        ]
+    }
+}
+
+impl ListRecord for InviteCode {
+    const CSV_HEADER: &'static [&'static str] = &[
+        "id",
+        "code",
+        "created_by",
+        "created_at",
+        "expires_at",
+        "used_by",
+        "used_at",
+        "is_active",
+        "note",
+        "metadata",
+    ];
+
+    fn id(&self) -> i64 {
+        self.id
+    }
+
+    fn csv_record(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.code.clone(),
+            self.created_by.to_string(),
+            csv_datetime(&self.created_at),
+            csv_optional(self.expires_at.as_ref().map(csv_datetime)),
+            csv_optional(self.used_by),
+            csv_optional(self.used_at.as_ref().map(csv_datetime)),
+            self.is_active.to_string(),
+            self.note.clone().unwrap_or_default(),
+            self.metadata.to_string(),
+        ]
+    }
+}
+
+/// `/v1/admin/export/users.csv`の1行（表計算ソフト向けに列を絞る）
+#[derive(Serialize)]
+#[serde(transparent)]
+pub struct UserExportRecord(pub RegisteredUser);
+
+impl ListRecord for UserExportRecord {
+    const CSV_HEADER: &'static [&'static str] =
+        &["id", "email", "name", "is_root", "can_invite", "created_at", "last_login"];
+
+    fn id(&self) -> i64 {
+        self.0.id
+    }
+
+    fn csv_record(&self) -> Vec<String> {
+        let user = &self.0;
+        vec![
+            user.id.to_string(),
+            user.email.clone(),
+            user.name.clone(),
+            user.is_root.to_string(),
+            user.can_invite.to_string(),
+            csv_datetime(&user.registered_at),
+            csv_optional(user.last_login.as_ref().map(csv_datetime)),
+        ]
+    }
+}
+
+impl ListRecord for InviteExportRow {
+    const CSV_HEADER: &'static [&'static str] = &[
+        "id",
+        "code",
+        "created_by_email",
+        "created_at",
+        "expires_at",
+        "used_by_email",
+        "used_at",
+        "is_active",
+        "note",
+    ];
+
+    fn id(&self) -> i64 {
+        self.id
+    }
+
+    fn csv_record(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.code.clone(),
+            self.created_by_email.clone().unwrap_or_default(),
+            csv_datetime(&self.created_at),
+            csv_optional(self.expires_at.as_ref().map(csv_datetime)),
+            self.used_by_email.clone().unwrap_or_default(),
+            csv_optional(self.used_at.as_ref().map(csv_datetime)),
+            self.is_active.to_string(),
+            self.note.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+impl ListRecord for AuditExportRow {
+    const CSV_HEADER: &'static [&'static str] =
+        &["id", "actor_email", "action", "target_email", "metadata", "created_at"];
+
+    fn id(&self) -> i64 {
+        self.id
+    }
+
+    fn csv_record(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.actor_email.clone().unwrap_or_default(),
+            self.action.clone(),
+            self.target_email.clone().unwrap_or_default(),
+            self.metadata.to_string(),
+            csv_datetime(&self.created_at),
+        ]
+    }
+}
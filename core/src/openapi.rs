@@ -0,0 +1,180 @@
+use crate::{database, AppState};
+use axum::{response::Html, routing::get, Json, Router};
+use utoipa::{
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Patchouli API"),
+    paths(
+        crate::index,
+        crate::login,
+        crate::login_api,
+        crate::callback,
+        crate::callback_api,
+        crate::auth_status,
+        crate::google_one_tap,
+        crate::dashboard,
+        crate::userinfo,
+        crate::logout,
+        crate::create_invite,
+        crate::list_invites,
+        crate::update_invite,
+        crate::resend_invite_notification,
+        crate::clone_invite,
+        crate::transfer_invite,
+        crate::delete_expired_invites,
+        crate::user_permissions,
+        crate::user_metadata,
+        crate::update_user_metadata,
+        crate::list_users,
+        crate::user_can_be_deleted,
+        crate::delete_user,
+        crate::ban_user,
+        crate::unban_user,
+        crate::promote_user,
+        crate::make_root,
+        crate::set_can_invite,
+        crate::create_api_key,
+        crate::list_api_keys,
+        crate::revoke_api_key,
+        crate::admin_stats,
+        crate::admin_stats_timeseries,
+        crate::admin_overview,
+        crate::export_users_csv,
+        crate::export_invites_csv,
+        crate::export_audit_log_csv,
+        crate::import_audit_log,
+        crate::check_root_exists,
+        crate::event_stream,
+        crate::system_errors,
+        crate::system_status,
+        crate::pending_actions,
+        crate::system_migrations,
+        crate::healthz,
+        crate::readyz,
+    ),
+    components(schemas(
+        crate::CreateTokenRequest,
+        crate::AuthResponse,
+        crate::AuthTokenResponse,
+        crate::AuthStatusResponse,
+        crate::InviteCodeResponse,
+        crate::InviteResendResponse,
+        crate::DeleteExpiredInvitesResponse,
+        crate::TransferInviteRequest,
+        crate::UpdateInviteRequest,
+        crate::UpdateMetadataRequest,
+        crate::MakeRootRequest,
+        crate::SetCanInviteRequest,
+        crate::CreateApiKeyRequest,
+        crate::InviteCodesListResponse,
+        crate::UsersListResponse,
+        crate::DeleteUserResponse,
+        crate::CanBeDeletedResponse,
+        crate::DeletionBlocker,
+        crate::DeletionBlockerReason,
+        crate::DashboardUser,
+        crate::DashboardResponse,
+        crate::UserInfoResponse,
+        crate::PermissionsResponse,
+        crate::BanUserResponse,
+        crate::UnbanUserResponse,
+        crate::PromoteUserResponse,
+        crate::SetCanInviteResponse,
+        crate::CreateApiKeyResponse,
+        crate::RootExistsResponse,
+        crate::ErrorCatalogEntry,
+        crate::HealthResponse,
+        crate::SystemStatusResponse,
+        crate::AdminOverviewResponse,
+        crate::InviteFunnel,
+        crate::RecentRegistration,
+        crate::error::ErrorCode,
+        crate::error::ErrorResponse,
+        database::RegisteredUser,
+        database::InviteCode,
+        database::ApiKey,
+        database::AuditEntry,
+        database::AuditImportCounts,
+        database::InviteSummary,
+        database::InviteActivity,
+        database::SystemStats,
+        database::InviteStats,
+        database::UserActivity,
+        database::WeeklyStats,
+        database::PendingAction,
+        database::PendingActionKind,
+        database::MigrationRecord,
+    )),
+    modifiers(&SessionSecurity),
+)]
+pub struct ApiDoc;
+
+/// 認証はクエリパラメータの`session_id`、または`Authorization: Bearer <session_id>`で行う
+///
+/// `X-Api-Key`（`API_KEY_HEADER`）のAPIキーはセッションの代わりにどのエンドポイントでも使える。
+struct SessionSecurity;
+
+impl Modify for SessionSecurity {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "session_id",
+            SecurityScheme::ApiKey(ApiKey::Query(ApiKeyValue::with_description(
+                "session_id",
+                "ログイン時に発行されるセッションID",
+            ))),
+        );
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("ログイン時に発行されるセッションID"))
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                "X-Api-Key",
+                "`POST /v1/api-keys`で作成したAPIキー（ヘッダー名は`API_KEY_HEADER`で変更できる）",
+            ))),
+        );
+    }
+}
+
+/// `/openapi.json`と`/docs`（認証不要）
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/openapi.json", get(openapi_json))
+        .route("/docs", get(swagger_ui))
+}
+
+async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+async fn swagger_ui() -> Html<&'static str> {
+    Html(r#"
+        <!DOCTYPE html>
+        <html>
+        <head>
+            <title>Patchouli API Docs</title>
+            <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+        </head>
+        <body>
+            <div id="swagger-ui"></div>
+            <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+            <script>
+                window.onload = () => {
+                    window.ui = SwaggerUIBundle({ url: '/openapi.json', dom_id: '#swagger-ui' });
+                };
+            </script>
+        </body>
+        </html>
+    "#)
+}
@@ -0,0 +1,98 @@
+use crate::{
+    error::{AppError, ErrorCode},
+    AppState,
+};
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header::AUTHORIZATION, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use std::time::Instant;
+use tracing::warn;
+
+/// リクエスト処理時間のヒストグラムのバケット（秒）
+const LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+/// Prometheusのレコーダーをプロセスに登録する（一度だけ呼ぶこと）
+pub fn install() -> anyhow::Result<PrometheusHandle> {
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full("http_request_duration_seconds".to_string()),
+            LATENCY_BUCKETS,
+        )?
+        .install_recorder()?;
+    Ok(handle)
+}
+
+/// ルート・メソッド・ステータスごとのリクエスト数と処理時間を記録する
+///
+/// ラベルのカーディナリティを抑えるため、ルートは実際のURIではなくルーティングのパターン
+/// （例: `/v1/invite/:invite_id`）を使う。どのルートにも一致しなかったリクエストは`unmatched`。
+pub async fn track(request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let method = request.method().to_string();
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+
+    let labels = [
+        ("method", method),
+        ("route", route),
+        ("status", response.status().as_u16().to_string()),
+    ];
+    counter!("http_requests_total", &labels).increment(1);
+    histogram!("http_request_duration_seconds", &labels).record(started.elapsed().as_secs_f64());
+    response
+}
+
+/// `GET /metrics`: Prometheusのテキスト形式でメトリクスを返す
+///
+/// `metrics_token`が設定されている場合は`Authorization: Bearer <token>`が必要。
+pub async fn render(State(state): State<AppState>, headers: HeaderMap) -> Result<String, AppError> {
+    if let Some(token) = &state.config.metrics_token {
+        let provided = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        if !provided.is_some_and(|provided| constant_time_eq(provided.as_bytes(), token.as_bytes())) {
+            return Err(ErrorCode::InvalidMetricsToken.into());
+        }
+    }
+    let Some(handle) = &state.metrics else {
+        return Err(ErrorCode::InternalError.into());
+    };
+
+    record_gauges(&state).await;
+    Ok(handle.render())
+}
+
+/// スクレイプ時点の値をゲージに設定する
+async fn record_gauges(state: &AppState) {
+    match state.database.get_system_stats().await {
+        Ok(stats) => {
+            gauge!("patchouli_registered_users").set(stats.total_users as f64);
+            gauge!("patchouli_active_invites").set(stats.pending_invites as f64);
+        }
+        Err(e) => warn!("Failed to collect stats for metrics: {:?}", e),
+    }
+
+    // ブラウザでのログイン完了を待っているAPI認証トークン
+    let pending_auth = state.auth_tokens.read().await.values().filter(|session| session.is_none()).count();
+    gauge!("patchouli_pending_auth_entries").set(pending_auth as f64);
+
+    let pool = state.database.pool_status();
+    gauge!("patchouli_db_pool_connections", "state" => "idle").set(pool.idle as f64);
+    gauge!("patchouli_db_pool_connections", "state" => "in_use").set((pool.size as usize).saturating_sub(pool.idle) as f64);
+    counter!("patchouli_db_pool_acquires_total").absolute(pool.acquired);
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
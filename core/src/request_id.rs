@@ -0,0 +1,76 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+use crate::{client_ip::ClientIp, ids::SharedIdGenerator, telemetry};
+use tracing::Span;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// クライアントから受け取った値をそのまま使う最大長
+const MAX_INCOMING_LEN: usize = 128;
+
+/// リクエストごとのID（リクエストのextensionsに入る）
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+tokio::task_local! {
+    static CURRENT: String;
+}
+
+/// 処理中のリクエストのID（`AppError`のレスポンスに含めるために使う）
+pub fn current() -> Option<String> {
+    CURRENT.try_with(|id| id.clone()).ok()
+}
+
+/// `X-Request-Id`を引き継ぐか新しく採番し、レスポンスヘッダーにも返す
+///
+/// `TraceLayer`より外側に置くこと（スパンに`request_id`を載せるため）。
+pub async fn propagate(State(ids): State<SharedIdGenerator>, mut request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| is_acceptable(value))
+        .map(str::to_string)
+        .unwrap_or_else(|| ids.new_id().to_string());
+
+    request.extensions_mut().insert(RequestId(id.clone()));
+    let mut response = CURRENT.scope(id.clone(), next.run(request)).await;
+
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// `TraceLayer`のスパンにメソッド・パス・ルート・送信元のIPアドレスと一緒にリクエストIDを記録する（`traceparent`があれば親に設定する）
+///
+/// クエリには`session_id`が含まれるためパスのみ記録する。`user_id`は認証後に`AuthUser`が記録する。
+pub fn make_span(request: &Request) -> Span {
+    let id = request
+        .extensions()
+        .get::<RequestId>()
+        .map(|id| id.0.as_str())
+        .unwrap_or_default();
+    let span = tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri().path(),
+        route = request.extensions().get::<MatchedPath>().map(|path| path.as_str()),
+        request_id = %id,
+        client_ip = request.extensions().get::<ClientIp>().and_then(|ip| ip.0).map(tracing::field::display),
+        user_id = tracing::field::Empty,
+    );
+    telemetry::set_remote_parent(&span, request.headers());
+    span
+}
+
+/// ログやヘッダーに載せて問題のない値だけ引き継ぐ
+fn is_acceptable(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= MAX_INCOMING_LEN
+        && value.bytes().all(|b| b.is_ascii_graphic())
+}
@@ -0,0 +1,354 @@
+//! ルートの一覧（ルーターはこの表から組み立て、`tests/openapi_contract.rs`がOpenAPIの定義と突き合わせる）
+//!
+//! エンドポイントを追加・変更したら、ここの`errors`とハンドラーの`#[utoipa::path]`の`responses`も合わせて更新する。
+
+use crate::{
+    admin_overview, admin_stats, admin_stats_timeseries, auth::{ApiKeyScope, RequiredScope}, auth_status, ban_user,
+    callback, callback_api, check_root_exists, clone_invite, create_api_key, create_invite, dashboard,
+    delete_expired_invites, delete_user, error::ErrorCode, etag, event_stream, export_audit_log_csv, export_invites_csv,
+    export_users_csv, google_one_tap, healthz, import_audit_log, index, list_api_keys, list_invites, list_users, login,
+    login_api, logout, make_root, pending_actions, promote_user, readyz, resend_invite_notification, revoke_api_key,
+    set_can_invite, system_errors, system_migrations, system_status, transfer_invite, unban_user, update_invite,
+    update_user_metadata, user_can_be_deleted, user_metadata, user_permissions, userinfo, AppState,
+};
+use axum::{
+    handler::Handler,
+    http::{Method, StatusCode},
+    middleware,
+    routing::{on, MethodFilter, MethodRouter},
+    Extension, Router,
+};
+use ErrorCode::*;
+
+/// ルートに必要な認証（`AuthUser`・`RootUser`のどちらを使うか）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Public,
+    User,
+    Root,
+}
+
+impl Access {
+    /// 認証の抽出で返し得るエラーコード（各ルートの`errors`には書かない）
+    pub fn errors(self) -> &'static [ErrorCode] {
+        match self {
+            Access::Public => &[],
+            Access::User => &[InvalidSession, InvalidApiKey, UserNotRegistered, UserSuspended, ApiKeyScopeMissing],
+            Access::Root => &[
+                InvalidSession,
+                InvalidApiKey,
+                UserNotRegistered,
+                UserSuspended,
+                InsufficientPermission,
+                ApiKeyScopeMissing,
+            ],
+        }
+    }
+}
+
+pub struct Route {
+    pub method: Method,
+    /// axumの形式のパス（`:user_id`等）
+    pub path: &'static str,
+    pub access: Access,
+    /// ハンドラー・パスやボディの抽出が返し得るエラーコード（タイムアウト等の全ルート共通のものは除く）
+    pub errors: &'static [ErrorCode],
+    /// OpenAPIに載せるか（CORSのプリフライト用のOPTIONS等は載せない）
+    pub documented: bool,
+    /// `/v1`を付けない非推奨の旧パスでも公開するか（`/v1`の導入前からあるルートのみ）
+    pub legacy: bool,
+    /// APIキーで呼び出すときに必要なスコープ（`None`ならAPIキーでは呼び出せない）
+    pub scope: Option<ApiKeyScope>,
+    handler: MethodRouter<AppState>,
+}
+
+impl Route {
+    fn new<H, T>(method: Method, path: &'static str, access: Access, errors: &'static [ErrorCode], handler: H) -> Self
+    where
+        H: Handler<T, AppState>,
+        T: 'static,
+    {
+        let filter = MethodFilter::try_from(method.clone()).expect("routes use standard methods");
+        Route {
+            method,
+            path,
+            access,
+            errors,
+            documented: true,
+            legacy: false,
+            scope: None,
+            handler: on(filter, handler),
+        }
+    }
+
+    /// `If-None-Match`に304を返す（`etag::conditional`）
+    fn conditional(mut self) -> Self {
+        self.handler = self.handler.layer(middleware::from_fn(etag::conditional));
+        self
+    }
+
+    fn undocumented(mut self) -> Self {
+        self.documented = false;
+        self
+    }
+
+    /// APIキーでの呼び出しを許すスコープ（付けなければセッションのみ）
+    fn scope(mut self, scope: ApiKeyScope) -> Self {
+        self.scope = Some(scope);
+        self
+    }
+
+    /// `/v1`の導入前からあるルート（旧パスの別名を作る。新しいルートには付けない）
+    fn legacy(mut self) -> Self {
+        self.legacy = true;
+        self
+    }
+}
+
+/// バージョン付きで公開するAPIルート（`/v1`を除いたパス）
+///
+/// 旧パスの別名は`.legacy()`を付けた`/v1`の導入前からあるルートにのみ作るため、新しいエンドポイントは`/v1`だけで公開される。
+pub fn api() -> Vec<Route> {
+    use Access::{Public, Root, User};
+    use ApiKeyScope::{ReadAdmin, ReadEvents, ReadInvites, ReadUsers, WriteAdmin, WriteInvites, WriteUsers};
+    let routes = vec![
+        Route::new(Method::GET, "/login/api", Public, &[], login_api).legacy(),
+        Route::new(
+            Method::GET,
+            "/callback/api",
+            Public,
+            &[ValidationFailed, TokenExchangeFailed, UserSuspended],
+            callback_api,
+        )
+        .legacy(),
+        Route::new(Method::GET, "/auth/status/:token", Public, &[AuthTokenNotFound], auth_status).legacy(),
+        Route::new(
+            Method::POST,
+            "/auth/tokens/google-one-tap",
+            Public,
+            &[ValidationFailed, InvalidIdToken, InviteRequired, InvalidInvite, UserSuspended],
+            google_one_tap,
+        )
+        .legacy(),
+        Route::new(Method::GET, "/dashboard", User, &[], dashboard).scope(ReadUsers).legacy(),
+        Route::new(Method::GET, "/userinfo", User, &[], userinfo).scope(ReadUsers),
+        Route::new(
+            Method::GET,
+            "/invite/create",
+            User,
+            &[InsufficientPermission, InviteDailyLimitExceeded, InviteTotalLimitExceeded],
+            create_invite,
+        )
+        .scope(WriteInvites)
+        .legacy(),
+        Route::new(Method::GET, "/invite/list", User, &[ValidationFailed, InsufficientPermission], list_invites)
+            .scope(ReadInvites)
+            .conditional()
+            .legacy(),
+        Route::new(Method::DELETE, "/invite/expired", Root, &[ValidationFailed], delete_expired_invites)
+            .scope(WriteInvites),
+        Route::new(
+            Method::PATCH,
+            "/invite/:invite_id",
+            User,
+            &[ValidationFailed, InsufficientPermission, InviteNotFound, InviteAlreadyUsed],
+            update_invite,
+        )
+        .scope(WriteInvites),
+        Route::new(
+            Method::POST,
+            "/invite/:invite_id/resend-notification",
+            User,
+            &[ValidationFailed, InsufficientPermission, InviteNotFound, InviteNotResendable],
+            resend_invite_notification,
+        )
+        .scope(WriteInvites)
+        .legacy(),
+        Route::new(
+            Method::POST,
+            "/invite/:invite_id/clone",
+            User,
+            &[
+                ValidationFailed,
+                InsufficientPermission,
+                InviteNotFound,
+                InviteDailyLimitExceeded,
+                InviteTotalLimitExceeded,
+            ],
+            clone_invite,
+        )
+        .scope(WriteInvites),
+        Route::new(
+            Method::POST,
+            "/invite/:invite_id/transfer",
+            Root,
+            &[ValidationFailed, InviteNotFound, InviteAlreadyUsed],
+            transfer_invite,
+        )
+        .scope(WriteInvites),
+        Route::new(
+            Method::GET,
+            "/users/:user_id/permissions",
+            User,
+            &[ValidationFailed, InsufficientPermission, UserNotFound],
+            user_permissions,
+        )
+        .scope(ReadUsers)
+        .conditional(),
+        Route::new(
+            Method::POST,
+            "/users/:user_id/promote",
+            Root,
+            &[ValidationFailed, ConfirmationMismatch, UserNotFound],
+            promote_user,
+        )
+        .scope(WriteUsers),
+        Route::new(
+            Method::POST,
+            "/users/:user_id/make-root",
+            Root,
+            &[ValidationFailed, ConfirmationMismatch, UserNotFound],
+            make_root,
+        )
+        .scope(WriteUsers),
+        Route::new(
+            Method::PATCH,
+            "/users/:user_id/can-invite",
+            Root,
+            &[ValidationFailed, CannotTargetSelf, UserNotFound],
+            set_can_invite,
+        )
+        .scope(WriteUsers),
+        Route::new(
+            Method::GET,
+            "/users/:user_id/metadata",
+            User,
+            &[ValidationFailed, InsufficientPermission, UserNotFound],
+            user_metadata,
+        )
+        .scope(ReadUsers)
+        .conditional(),
+        Route::new(
+            Method::PATCH,
+            "/users/:user_id/metadata",
+            User,
+            &[ValidationFailed, InsufficientPermission, UserNotFound],
+            update_user_metadata,
+        )
+        .scope(WriteUsers),
+        Route::new(
+            Method::POST,
+            "/api-keys",
+            User,
+            &[ValidationFailed, InsufficientPermission, UserNotFound],
+            create_api_key,
+        ),
+        Route::new(Method::GET, "/api-keys", User, &[], list_api_keys),
+        Route::new(Method::DELETE, "/api-keys/:key_id", User, &[ValidationFailed, ApiKeyNotFound], revoke_api_key),
+        Route::new(Method::GET, "/admin/users", Root, &[ValidationFailed], list_users)
+            .scope(ReadUsers)
+            .conditional()
+            .legacy(),
+        Route::new(Method::DELETE, "/admin/users/:user_id", Root, &[], delete_user).scope(WriteUsers).legacy(),
+        Route::new(Method::OPTIONS, "/admin/users/:user_id", Public, &[], || async { StatusCode::OK })
+            .undocumented()
+            .legacy(),
+        Route::new(
+            Method::GET,
+            "/admin/users/:user_id/can-be-deleted",
+            Root,
+            &[ValidationFailed, UserNotFound],
+            user_can_be_deleted,
+        )
+        .scope(ReadUsers),
+        Route::new(
+            Method::POST,
+            "/admin/users/:user_id/ban",
+            Root,
+            &[ValidationFailed, CannotTargetSelf, RootUserProtected, UserNotFound],
+            ban_user,
+        )
+        .scope(WriteUsers)
+        .legacy(),
+        Route::new(Method::POST, "/admin/users/:user_id/unban", Root, &[ValidationFailed, UserNotFound], unban_user)
+            .scope(WriteUsers)
+            .legacy(),
+        Route::new(Method::GET, "/admin/stats", Root, &[], admin_stats).scope(ReadAdmin).legacy(),
+        Route::new(Method::GET, "/admin/stats/timeseries", Root, &[ValidationFailed], admin_stats_timeseries)
+            .scope(ReadAdmin)
+            .legacy(),
+        Route::new(Method::GET, "/admin/overview", Root, &[], admin_overview).scope(ReadAdmin),
+        Route::new(Method::GET, "/admin/export/users.csv", Root, &[], export_users_csv).scope(ReadUsers),
+        Route::new(Method::GET, "/admin/export/invites.csv", Root, &[], export_invites_csv).scope(ReadInvites),
+        Route::new(Method::GET, "/admin/export/audit-log.csv", Root, &[ValidationFailed], export_audit_log_csv)
+            .scope(ReadAdmin),
+        Route::new(Method::POST, "/admin/import/audit-log", Root, &[ValidationFailed], import_audit_log)
+            .scope(WriteAdmin),
+        Route::new(Method::GET, "/root/exists", Public, &[], check_root_exists).legacy(),
+        Route::new(Method::GET, "/events", User, &[TooManyConnections], event_stream).scope(ReadEvents).legacy(),
+        Route::new(Method::GET, "/system/errors", Public, &[], system_errors),
+        Route::new(Method::GET, "/system/status", Public, &[], system_status),
+        Route::new(Method::GET, "/system/pending-actions", Root, &[], pending_actions).scope(ReadAdmin),
+        Route::new(Method::GET, "/system/migrations", Root, &[], system_migrations).scope(ReadAdmin),
+    ];
+    // 開発用（`dev`フィーチャーのデバッグビルドのみ。OpenAPIには載せない）
+    #[cfg(all(feature = "dev", debug_assertions))]
+    let routes = routes
+        .into_iter()
+        .chain([Route::new(Method::POST, "/system/run-migrations", Root, &[], crate::run_migrations)
+            .scope(WriteAdmin)
+            .undocumented()])
+        .collect();
+    routes
+}
+
+/// `/v1`を付けない非推奨の旧パスでも公開するルート（`api`のうち`.legacy()`を付けたもの）
+pub fn legacy() -> Vec<Route> {
+    api().into_iter().filter(|route| route.legacy).collect()
+}
+
+/// バージョンを付けないルート（ブラウザで直接開くページ（OAuthのリダイレクト先を含む）とプローブ）
+pub fn unversioned() -> Vec<Route> {
+    use Access::Public;
+    vec![
+        Route::new(Method::GET, "/", Public, &[], index),
+        Route::new(Method::GET, "/login", Public, &[ValidationFailed], login),
+        Route::new(Method::GET, "/callback", Public, &[ValidationFailed, TokenExchangeFailed], callback),
+        Route::new(Method::GET, "/logout", Public, &[ValidationFailed], logout),
+        // ロードバランサー・Kubernetesのプローブ用（認証不要）
+        Route::new(Method::GET, "/healthz", Public, &[], healthz),
+        Route::new(Method::GET, "/readyz", Public, &[], readyz),
+    ]
+}
+
+/// OpenAPIに載せるルート（パスは`/v1`を付けたもの）
+#[derive(Debug, Clone)]
+pub struct DocumentedRoute {
+    pub method: Method,
+    pub path: String,
+    pub access: Access,
+    pub errors: &'static [ErrorCode],
+}
+
+/// OpenAPIに載せるルートの一覧
+pub fn documented() -> Vec<DocumentedRoute> {
+    let unversioned = unversioned().into_iter().map(|route| (route.path.to_string(), route));
+    let api = api().into_iter().map(|route| (format!("/v1{}", route.path), route));
+    unversioned
+        .chain(api)
+        .filter(|(_, route)| route.documented)
+        .map(|(path, route)| DocumentedRoute {
+            method: route.method,
+            path,
+            access: route.access,
+            errors: route.errors,
+        })
+        .collect()
+}
+
+/// 同じパスの別のメソッドは1つのルートにまとめる
+pub fn into_router(routes: Vec<Route>) -> Router<AppState> {
+    routes.into_iter().fold(Router::new(), |router, route| {
+        router.route(route.path, route.handler.layer(Extension(RequiredScope(route.scope))))
+    })
+}
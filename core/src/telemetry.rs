@@ -0,0 +1,95 @@
+use axum::http::HeaderMap;
+use crate::{config::Config, error_reporting};
+use opentelemetry::{
+    global,
+    propagation::Extractor,
+    KeyValue,
+};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{propagation::TraceContextPropagator, runtime, trace, Resource};
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+/// ログの出力形式（`log_format`）
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// 人が読むためのテキスト形式（デフォルト）
+    Text,
+    /// 1行に1つのJSONオブジェクト（ログ収集基盤向け）
+    Json,
+}
+
+/// ログ出力を初期化する
+///
+/// どちらの形式でも`RUST_LOG`で出力レベルを絞り込める。JSON形式ではイベントのフィールドをトップレベルに展開し、
+/// リクエスト中のイベントには`span`としてリクエストのスパンのフィールド（`request_id`・`route`・`user_id`等）を含める。
+///
+/// `OTEL_EXPORTER_OTLP_ENDPOINT`が設定されている場合はスパンをOTLP（gRPC）で送信する（デフォルトは無効）。
+/// サービス名は`OTEL_SERVICE_NAME`（デフォルト: patchouli）。
+/// `sentry_dsn`が設定されている場合は`error!`をSentryにも送る（`error_reporting::init`の後に呼ぶこと）。
+pub fn init(config: &Config) -> anyhow::Result<()> {
+    let otel = match std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+        Ok(endpoint) => {
+            let service_name = std::env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| "patchouli".to_string());
+            let tracer = opentelemetry_otlp::new_pipeline()
+                .tracing()
+                .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(endpoint))
+                .with_trace_config(
+                    trace::config().with_resource(Resource::new([KeyValue::new("service.name", service_name)])),
+                )
+                .install_batch(runtime::Tokio)?;
+            global::set_text_map_propagator(TraceContextPropagator::new());
+            Some(tracing_opentelemetry::layer().with_tracer(tracer))
+        }
+        Err(_) => None,
+    };
+
+    let (text, json) = match config.log_format()? {
+        LogFormat::Text => (Some(tracing_subscriber::fmt::layer()), None),
+        LogFormat::Json => (
+            None,
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .flatten_event(true)
+                    .with_current_span(true)
+                    .with_span_list(false),
+            ),
+        ),
+    };
+
+    tracing_subscriber::registry()
+        .with(EnvFilter::from_default_env())
+        .with(text)
+        .with(json)
+        .with(otel)
+        .with(config.sentry_dsn.is_some().then(error_reporting::tracing_layer))
+        .init();
+    Ok(())
+}
+
+/// 未送信のスパンを送信してから終了する
+pub fn shutdown() {
+    global::shutdown_tracer_provider();
+}
+
+/// リクエストの`traceparent`・`tracestate`を親としてスパンに設定する
+///
+/// OTLPが無効な場合は何もしない（プロパゲーターが未設定のため）。
+pub fn set_remote_parent(span: &Span, headers: &HeaderMap) {
+    let parent = global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)));
+    span.set_parent(parent);
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
@@ -0,0 +1,120 @@
+use anyhow::Context;
+use axum::{
+    extract::Request,
+    http::{header::HOST, StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    Router,
+};
+use crate::config::Config;
+use axum_server::tls_rustls::RustlsConfig;
+use std::{net::SocketAddr, path::PathBuf};
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::{info, warn};
+
+/// HTTPSで直接公開する場合の設定（`tls_cert_path`と`tls_key_path`の両方を指定した場合のみ有効）
+#[derive(Clone, Debug)]
+pub struct TlsOptions {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    /// 平文HTTPを受け付けてHTTPSへリダイレクトするポート（`None`なら平文HTTPは受け付けない）
+    http_redirect_port: Option<u16>,
+}
+
+impl TlsOptions {
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let (cert_path, key_path) = (config.tls_cert_path.clone()?, config.tls_key_path.clone()?);
+        Some(TlsOptions {
+            cert_path,
+            key_path,
+            http_redirect_port: config.http_redirect.then_some(config.http_port),
+        })
+    }
+
+    async fn load(&self) -> anyhow::Result<RustlsConfig> {
+        RustlsConfig::from_pem_file(&self.cert_path, &self.key_path)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to load TLS certificate {} / key {} (unreadable, invalid, or not a matching pair)",
+                    self.cert_path.display(),
+                    self.key_path.display()
+                )
+            })
+    }
+}
+
+/// HTTPSでサーバーを起動する
+///
+/// 証明書はSIGHUPで読み直す（Let's Encrypt等の更新後に再起動不要）。読み直しに失敗した場合は古い証明書を使い続ける。
+pub async fn serve(listener: std::net::TcpListener, app: Router, opts: TlsOptions) -> anyhow::Result<()> {
+    // ringを使う（プロセスで一度だけ設定すればよく、既に設定済みならそのまま）
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    let config = opts.load().await?;
+    let addr = listener.local_addr()?;
+    info!("Server running on https://{}", addr);
+
+    tokio::spawn(reload_on_sighup(config.clone(), opts.clone()));
+    if let Some(port) = opts.http_redirect_port {
+        let redirect_addr = SocketAddr::new(addr.ip(), port);
+        let redirect_listener = tokio::net::TcpListener::bind(redirect_addr)
+            .await
+            .with_context(|| format!("Failed to bind {}", redirect_addr))?;
+        info!("Redirecting http://{} to HTTPS", redirect_listener.local_addr()?);
+        let https_port = addr.port();
+        let redirect = Router::new().fallback(move |request: Request| async move { redirect_to_https(request, https_port) });
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(redirect_listener, redirect).await {
+                warn!("HTTP redirect server stopped: {:?}", e);
+            }
+        });
+    }
+
+    axum_server::from_tcp_rustls(listener, config)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .await?;
+    Ok(())
+}
+
+async fn reload_on_sighup(config: RustlsConfig, opts: TlsOptions) {
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            warn!("Failed to listen for SIGHUP, TLS certificate reload disabled: {:?}", e);
+            return;
+        }
+    };
+    while hangup.recv().await.is_some() {
+        match config.reload_from_pem_file(&opts.cert_path, &opts.key_path).await {
+            Ok(()) => info!("Reloaded TLS certificate {}", opts.cert_path.display()),
+            Err(e) => warn!("Failed to reload TLS certificate, keeping the previous one: {:?}", e),
+        }
+    }
+}
+
+fn redirect_to_https(request: Request, https_port: u16) -> Response {
+    let Some(host) = request
+        .headers()
+        .get(HOST)
+        .and_then(|host| host.to_str().ok())
+        .and_then(|host| host.parse::<axum::http::uri::Authority>().ok())
+    else {
+        return (StatusCode::BAD_REQUEST, "Missing or invalid Host header").into_response();
+    };
+
+    let authority = if https_port == 443 {
+        host.host().to_string()
+    } else {
+        format!("{}:{}", host.host(), https_port)
+    };
+    let path_and_query = request.uri().path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+    match Uri::builder()
+        .scheme("https")
+        .authority(authority)
+        .path_and_query(path_and_query)
+        .build()
+    {
+        Ok(uri) => Redirect::permanent(&uri.to_string()).into_response(),
+        Err(_) => (StatusCode::BAD_REQUEST, "Invalid Host header").into_response(),
+    }
+}
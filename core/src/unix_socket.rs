@@ -0,0 +1,82 @@
+use anyhow::Context;
+use crate::client_ip::UNIX_SOCKET_PEER;
+use axum::{
+    extract::{ConnectInfo, Request},
+    Router,
+};
+use hyper::body::Incoming;
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::{conn::auto, graceful::GracefulShutdown},
+};
+use std::{
+    fs,
+    future::Future,
+    os::unix::fs::{FileTypeExt, PermissionsExt},
+    path::PathBuf,
+};
+use tokio::net::UnixListener;
+use tower::Service;
+use tracing::{debug, info, warn};
+
+/// Unixドメインソケットでサーバーを起動する
+///
+/// `shutdown`が完了したら新しい接続の受け付けをやめ、処理中の接続が終わるのを待ってからソケットファイルを削除する
+/// （TCPの`axum::serve(...).with_graceful_shutdown`と同じく、待つ時間に上限はない）。
+pub async fn serve(path: PathBuf, mode: u32, app: Router, shutdown: impl Future<Output = ()>) -> anyhow::Result<()> {
+    // 前回の異常終了で残ったソケットファイルは削除する（ソケット以外のファイルは消さない）
+    if let Ok(metadata) = fs::symlink_metadata(&path) {
+        anyhow::ensure!(
+            metadata.file_type().is_socket(),
+            "{} already exists and is not a socket",
+            path.display()
+        );
+        fs::remove_file(&path).with_context(|| format!("Failed to remove stale socket {}", path.display()))?;
+    }
+
+    let listener = UnixListener::bind(&path).with_context(|| format!("Failed to bind {}", path.display()))?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(mode))
+        .with_context(|| format!("Failed to set permissions on {}", path.display()))?;
+    info!("Server running on unix:{} (mode {:o})", path.display(), mode);
+
+    let graceful = GracefulShutdown::new();
+    tokio::pin!(shutdown);
+    loop {
+        let socket = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((socket, _)) => socket,
+                Err(e) => {
+                    warn!("Failed to accept connection on unix socket: {:?}", e);
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+
+        let service = app.clone();
+        let watcher = graceful.watcher();
+        tokio::spawn(async move {
+            // 接続元のIPアドレスがないため、ClientIpでは127.0.0.1からの接続として扱う
+            let hyper_service = hyper::service::service_fn(move |mut request: Request<Incoming>| {
+                request.extensions_mut().insert(ConnectInfo(UNIX_SOCKET_PEER));
+                service.clone().call(request)
+            });
+            let builder = auto::Builder::new(TokioExecutor::new());
+            let connection = builder.serve_connection_with_upgrades(TokioIo::new(socket), hyper_service);
+            if let Err(e) = watcher.watch(connection).await {
+                debug!("Unix socket connection closed with error: {:?}", e);
+            }
+        });
+    }
+
+    // リスナーを閉じて新しい接続を拒否してから、処理中の接続を待つ
+    drop(listener);
+    info!("Waiting for {} unix socket connection(s) to finish", graceful.count());
+    graceful.shutdown().await;
+
+    if let Err(e) = fs::remove_file(&path) {
+        warn!("Failed to remove socket {}: {:?}", path.display(), e);
+    }
+    info!("Removed socket {}", path.display());
+    Ok(())
+}
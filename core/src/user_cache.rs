@@ -0,0 +1,93 @@
+use crate::database::{Database, RegisteredUser};
+use moka::future::Cache;
+use std::time::Duration;
+use tracing::warn;
+
+const USER_CACHE_MAX_ENTRIES: u64 = 10_000;
+
+/// 認証時のユーザー取得を減らすためのキャッシュ（メールアドレス・ユーザーID → 登録ユーザー）
+///
+/// セッションはメールアドレス、APIキーはユーザーIDで引くため、それぞれ別のキャッシュに保持する。
+/// ユーザーの状態を変更した箇所では必ず`invalidate`・`invalidate_id`を呼ぶこと（両方のキャッシュから消える）。
+/// 呼び忘れてもTTL（`user_cache_ttl_secs`、デフォルト: 30秒）経過後には反映される。TTLが0ならキャッシュしない。
+#[derive(Clone)]
+pub struct UserCache {
+    by_email: Cache<String, RegisteredUser>,
+    by_id: Cache<i64, RegisteredUser>,
+    enabled: bool,
+}
+
+impl UserCache {
+    pub fn new(ttl: Duration) -> Self {
+        UserCache {
+            by_email: Cache::builder()
+                .max_capacity(USER_CACHE_MAX_ENTRIES)
+                .time_to_live(ttl)
+                .support_invalidation_closures()
+                .build(),
+            by_id: Cache::builder()
+                .max_capacity(USER_CACHE_MAX_ENTRIES)
+                .time_to_live(ttl)
+                .support_invalidation_closures()
+                .build(),
+            enabled: !ttl.is_zero(),
+        }
+    }
+
+    /// キャッシュになければDBから取得して保持する（未登録の結果はキャッシュしない）
+    pub async fn get_by_email(
+        &self,
+        database: &Database,
+        email: &str,
+    ) -> Result<Option<RegisteredUser>, sqlx::Error> {
+        if !self.enabled {
+            return database.get_user_by_email(email).await;
+        }
+        if let Some(user) = self.by_email.get(email).await {
+            return Ok(Some(user));
+        }
+
+        let user = database.get_user_by_email(email).await?;
+        if let Some(user) = &user {
+            self.by_email.insert(email.to_string(), user.clone()).await;
+        }
+        Ok(user)
+    }
+
+    /// `get_by_email`と同じだがユーザーIDで引く（APIキーの作成者の取得）
+    pub async fn get_by_id(&self, database: &Database, user_id: i64) -> Result<Option<RegisteredUser>, sqlx::Error> {
+        if !self.enabled {
+            return database.get_user_by_id(user_id).await;
+        }
+        if let Some(user) = self.by_id.get(&user_id).await {
+            return Ok(Some(user));
+        }
+
+        let user = database.get_user_by_id(user_id).await?;
+        if let Some(user) = &user {
+            self.by_id.insert(user_id, user.clone()).await;
+        }
+        Ok(user)
+    }
+
+    pub async fn invalidate(&self, email: &str) {
+        self.by_email.invalidate(email).await;
+        let email = email.to_string();
+        if let Err(e) = self.by_id.invalidate_entries_if(move |_, user| user.email == email) {
+            // 発生するのはsupport_invalidation_closuresを指定し忘れた場合のみ
+            warn!("Failed to invalidate cached user by email: {:?}", e);
+            self.by_id.invalidate_all();
+        }
+    }
+
+    pub fn invalidate_id(&self, user_id: i64) {
+        if let Err(e) = self.by_email.invalidate_entries_if(move |_, user| user.id == user_id) {
+            warn!("Failed to invalidate cached user {}: {:?}", user_id, e);
+            self.by_email.invalidate_all();
+        }
+        if let Err(e) = self.by_id.invalidate_entries_if(move |id, _| *id == user_id) {
+            warn!("Failed to invalidate cached user {}: {:?}", user_id, e);
+            self.by_id.invalidate_all();
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use crate::events::ServerEvent;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+/// Webhookで送信するペイロード（イベント名はペイロードの型ごとに固定）
+pub trait WebhookPayload: Serialize {
+    fn event_name() -> &'static str;
+}
+
+/// 送信するJSON（ペイロードの項目に`event`として`event_name`を加える）
+#[derive(Serialize)]
+struct Envelope<'a, P> {
+    event: &'static str,
+    #[serde(flatten)]
+    payload: &'a P,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserCreatedPayload {
+    pub user_id: i64,
+    pub email: String,
+    pub invited_by: Option<i64>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl WebhookPayload for UserCreatedPayload {
+    fn event_name() -> &'static str {
+        "user.created"
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct InviteUsedPayload {
+    pub invite_id: i64,
+    pub code: String,
+    pub created_by: i64,
+    pub used_by: i64,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl WebhookPayload for InviteUsedPayload {
+    fn event_name() -> &'static str {
+        "invite.used"
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct InviteResentPayload {
+    pub invite_id: i64,
+    pub code: String,
+    pub created_by: i64,
+    pub resent_by: i64,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl WebhookPayload for InviteResentPayload {
+    fn event_name() -> &'static str {
+        "invite.resent"
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserUpdatedPayload {
+    pub user_id: i64,
+    pub change: String,
+    pub updated_by: i64,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl WebhookPayload for UserUpdatedPayload {
+    fn event_name() -> &'static str {
+        "user.updated"
+    }
+}
+
+async fn deliver<P: WebhookPayload>(client: &reqwest::Client, webhook_url: &str, payload: &P) {
+    let envelope = Envelope {
+        event: P::event_name(),
+        payload,
+    };
+    match client.post(webhook_url).json(&envelope).send().await {
+        Ok(response) if response.status().is_success() => {}
+        Ok(response) => warn!(
+            "Webhook delivery of {} failed with status: {}",
+            P::event_name(),
+            response.status()
+        ),
+        Err(e) => warn!("Webhook delivery of {} failed: {:?}", P::event_name(), e.without_url()),
+    }
+}
+
+/// イベントを対応する型付きペイロードに変換して送信する
+async fn dispatch(client: &reqwest::Client, webhook_url: &str, event: ServerEvent) {
+    match event {
+        ServerEvent::UserCreated {
+            user_id,
+            email,
+            invited_by,
+            timestamp,
+        } => {
+            let payload = UserCreatedPayload {
+                user_id,
+                email,
+                invited_by,
+                timestamp,
+            };
+            deliver(client, webhook_url, &payload).await;
+        }
+        ServerEvent::InviteUsed {
+            invite_id,
+            code,
+            created_by,
+            used_by,
+            timestamp,
+        } => {
+            let payload = InviteUsedPayload {
+                invite_id,
+                code,
+                created_by,
+                used_by,
+                timestamp,
+            };
+            deliver(client, webhook_url, &payload).await;
+        }
+        ServerEvent::InviteResent {
+            invite_id,
+            code,
+            created_by,
+            resent_by,
+            timestamp,
+        } => {
+            let payload = InviteResentPayload {
+                invite_id,
+                code,
+                created_by,
+                resent_by,
+                timestamp,
+            };
+            deliver(client, webhook_url, &payload).await;
+        }
+        ServerEvent::UserUpdated {
+            user_id,
+            change,
+            updated_by,
+            timestamp,
+        } => {
+            let payload = UserUpdatedPayload {
+                user_id,
+                change,
+                updated_by,
+                timestamp,
+            };
+            deliver(client, webhook_url, &payload).await;
+        }
+    }
+}
+
+/// `webhook_url`が設定されている場合、イベントをHTTP POSTで転送するタスクを起動する
+pub fn spawn_forwarder(sender: &broadcast::Sender<ServerEvent>, webhook_url: Option<String>) {
+    let Some(webhook_url) = webhook_url else {
+        return;
+    };
+
+    let mut receiver = sender.subscribe();
+    // URLにはトークン等が含まれることがあるため、ホスト名のみ記録する
+    let host = reqwest::Url::parse(&webhook_url).ok().and_then(|url| url.host_str().map(str::to_string));
+    info!("Forwarding server events to webhook at {}", host.as_deref().unwrap_or("(invalid URL)"));
+
+    tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        loop {
+            match receiver.recv().await {
+                Ok(event) => dispatch(&client, &webhook_url, event).await,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("Webhook forwarder lagged, skipped {} events", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
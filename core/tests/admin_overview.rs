@@ -0,0 +1,44 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{
+    add_session,
+    fixtures::{InviteFixture, UserFixture},
+};
+use patchouli::{
+    build_router,
+    config::Config,
+    error::{ErrorCode, ErrorResponse},
+    AdminOverviewResponse,
+};
+
+const ROOT_SESSION: &str = "root-session";
+const USER_SESSION: &str = "user-session";
+
+#[tokio::test]
+async fn overview_aggregates_users_and_invites() {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let user = UserFixture::new("User").invited_by(&root).insert(&state.database).await;
+    InviteFixture::new(&root).insert(&state.database).await;
+    add_session(&state, ROOT_SESSION, &root).await;
+    add_session(&state, USER_SESSION, &user).await;
+    state.auth_tokens.write().await.insert("pending-token".to_string(), None);
+    let app = build_router(state);
+
+    let response = common::get(&app, &format!("/v1/admin/overview?session_id={}", ROOT_SESSION)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let overview: AdminOverviewResponse = response.json();
+    assert!(overview.unavailable.is_empty(), "{:?}", overview.unavailable);
+    let users = overview.users.unwrap();
+    assert_eq!((users.total_users, users.active_7d, users.active_30d), (2, 2, 2));
+    let invites = overview.invites.unwrap();
+    assert_eq!((invites.created, invites.pending), (1, 1));
+    let recent: Vec<_> = overview.recent_registrations.unwrap().into_iter().map(|user| user.email).collect();
+    assert_eq!(recent, ["user@example.com", "root@example.com"]);
+    assert_eq!(overview.pending_auth, 1);
+
+    let response = common::get(&app, &format!("/v1/admin/overview?session_id={}", USER_SESSION)).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    assert_eq!(response.json::<ErrorResponse>().error, ErrorCode::InsufficientPermission);
+}
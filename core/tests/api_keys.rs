@@ -0,0 +1,298 @@
+//! APIキー（`/v1/api-keys`の作成・一覧・無効化と`X-Api-Key`での認証・スコープ）
+
+mod common;
+
+use axum::http::StatusCode;
+use chrono::{Duration, Utc};
+use common::{fixtures::UserFixture, login_as, TestClient};
+use patchouli::{
+    build_router,
+    clock::{Clock, MockClock},
+    config::Config,
+    database::{ApiKey, RegisteredUser},
+    error::{ErrorCode, ErrorResponse},
+    AppState, CreateApiKeyResponse, UserInfoResponse,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+struct Setup {
+    state: AppState,
+    clock: Arc<MockClock>,
+    root: RegisteredUser,
+    alice: RegisteredUser,
+    client: TestClient,
+}
+
+async fn setup(config: Config) -> Setup {
+    let clock = Arc::new(MockClock::new(Utc::now()));
+    let state = common::state_with_clock(config, clock.clone()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let alice = UserFixture::new("Alice").invited_by(&root).insert(&state.database).await;
+    let client = TestClient::new(build_router(state.clone()));
+    Setup {
+        state,
+        clock,
+        root,
+        alice,
+        client,
+    }
+}
+
+#[tokio::test]
+async fn create_use_and_revoke() {
+    let Setup { state, alice, client, .. } = setup(Config::default()).await;
+    let alice_client = client.with_session(&login_as(&state, &alice).await);
+
+    let body = json!({ "name": "CI", "scopes": ["read:users"] });
+    let created: CreateApiKeyResponse = alice_client.post("/v1/api-keys", &body).await.expect(StatusCode::CREATED);
+    assert!(created.key.starts_with("pk_"));
+    assert_eq!((created.api_key.name.as_str(), created.api_key.created_by), ("CI", alice.id));
+    assert_eq!(created.api_key.scopes, vec!["read:users".to_string()]);
+    assert!(created.api_key.is_active);
+
+    // キーだけで（セッションなしで）作成者として認証される
+    let key_client = client.with_header("X-Api-Key", &created.key);
+    let info: UserInfoResponse = key_client.get("/v1/userinfo").await.expect(StatusCode::OK);
+    assert_eq!(info.email, alice.email);
+
+    // 一覧にキーそのもの・ハッシュは含まれない
+    let response = alice_client.get("/v1/api-keys").await;
+    let listed: Vec<Value> = response.expect(StatusCode::OK);
+    assert_eq!(listed.len(), 1);
+    assert!(listed[0].get("key").is_none() && listed[0].get("key_hash").is_none());
+    assert!(!String::from_utf8_lossy(&response.body).contains(&created.key));
+
+    let uri = format!("/v1/api-keys/{}", created.api_key.id);
+    let revoked: ApiKey = alice_client.delete(&uri).await.expect(StatusCode::OK);
+    assert!(!revoked.is_active);
+    let response = key_client.get("/v1/userinfo").await;
+    assert_eq!((response.status, response.error_code()), (StatusCode::UNAUTHORIZED, ErrorCode::InvalidApiKey));
+}
+
+#[tokio::test]
+async fn rejects_unknown_expired_and_suspended_keys() {
+    let Setup { state, clock, root, alice, client } = setup(Config::default()).await;
+    let alice_client = client.with_session(&login_as(&state, &alice).await);
+
+    let response = client.with_header("X-Api-Key", "pk_unknown").get("/v1/userinfo").await;
+    assert_eq!((response.status, response.error_code()), (StatusCode::UNAUTHORIZED, ErrorCode::InvalidApiKey));
+
+    let body = json!({ "name": "short-lived", "scopes": ["read:users"], "expires_in_days": 90 });
+    let created: CreateApiKeyResponse = alice_client.post("/v1/api-keys", &body).await.expect(StatusCode::CREATED);
+    assert_eq!(created.api_key.expires_at, Some(clock.now() + Duration::days(90)));
+    let key_client = client.with_header("X-Api-Key", &created.key);
+    assert_eq!(key_client.get("/v1/userinfo").await.status, StatusCode::OK);
+    clock.advance(Duration::days(90));
+    assert_eq!(key_client.get("/v1/userinfo").await.status, StatusCode::UNAUTHORIZED);
+
+    for body in [
+        json!({ "name": " ", "scopes": ["read:users"] }),
+        json!({ "name": "zero", "scopes": ["read:users"], "expires_in_days": 0 }),
+        json!({ "name": "too-long", "scopes": ["read:users"], "expires_in_days": 3651 }),
+        json!({ "name": "no-scopes" }),
+        json!({ "name": "empty-scopes", "scopes": [] }),
+        json!({ "name": "empty-scope", "scopes": [""] }),
+        json!({ "name": "unknown-scope", "scopes": ["read:users", "admin"] }),
+    ] {
+        let response = alice_client.post("/v1/api-keys", &body).await;
+        assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY, "{}", body);
+    }
+
+    // 所有者が利用停止されたキーは使えない
+    let bob = UserFixture::new("Bob").invited_by(&root).banned().insert(&state.database).await;
+    let key = "pk_bob";
+    state.database.create_api_key(root.id, bob.id, "bob", &hash(key), &["read:users".to_string()], None).await.unwrap();
+    let response = client.with_header("X-Api-Key", key).get("/v1/userinfo").await;
+    assert_eq!((response.status, response.error_code()), (StatusCode::FORBIDDEN, ErrorCode::UserSuspended));
+}
+
+#[tokio::test]
+async fn expiry_must_be_between_1_and_3650_days() {
+    let Setup { state, clock, alice, client, .. } = setup(Config::default()).await;
+    let alice_client = client.with_session(&login_as(&state, &alice).await);
+
+    // 0以下（既に期限切れのキー）と上限超えは作成しない
+    for days in [0, -1, -3650, i64::MIN, 3651] {
+        let body = json!({ "name": "CI", "scopes": ["read:users"], "expires_in_days": days });
+        let response = alice_client.post("/v1/api-keys", &body).await;
+        let error: ErrorResponse = response.expect(StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error.error, ErrorCode::ValidationFailed, "{}", days);
+        assert!(error.details.unwrap().get("expires_in_days").is_some(), "{}", days);
+    }
+    assert!(alice_client.get("/v1/api-keys").await.expect::<Vec<ApiKey>>(StatusCode::OK).is_empty());
+
+    for days in [1, 3650] {
+        let body = json!({ "name": "CI", "scopes": ["read:users"], "expires_in_days": days });
+        let created: CreateApiKeyResponse = alice_client.post("/v1/api-keys", &body).await.expect(StatusCode::CREATED);
+        assert_eq!(created.api_key.expires_at, Some(clock.now() + Duration::days(days)));
+    }
+}
+
+#[tokio::test]
+async fn keys_are_random_and_never_listed() {
+    let Setup { state, alice, client, .. } = setup(Config::default()).await;
+    let alice_client = client.with_session(&login_as(&state, &alice).await);
+
+    let mut keys = Vec::new();
+    for name in ["CI", "backup"] {
+        let body = json!({ "name": name, "scopes": ["read:users"] });
+        let created: CreateApiKeyResponse = alice_client.post("/v1/api-keys", &body).await.expect(StatusCode::CREATED);
+        // pk_ + 32バイトの16進数
+        let random = created.key.strip_prefix("pk_").unwrap();
+        assert_eq!(random.len(), 64);
+        assert!(random.chars().all(|c| c.is_ascii_hexdigit()));
+        keys.push(created.key);
+    }
+    assert_ne!(keys[0], keys[1]);
+
+    let response = alice_client.get("/v1/api-keys").await;
+    let listed: Vec<Value> = response.expect(StatusCode::OK);
+    assert_eq!(listed.len(), 2);
+    let body = String::from_utf8_lossy(&response.body);
+    for key in &keys {
+        assert!(!body.contains(key.as_str()) && !body.contains(&hash(key)));
+    }
+    assert!(listed.iter().all(|api_key| api_key.get("key").is_none() && api_key.get("key_hash").is_none()));
+}
+
+#[tokio::test]
+async fn keys_cannot_manage_keys() {
+    let Setup { state, alice, client, .. } = setup(Config::default()).await;
+    let alice_client = client.with_session(&login_as(&state, &alice).await);
+    let body = json!({ "name": "CI", "scopes": ["read:users", "write:users"] });
+    let created: CreateApiKeyResponse = alice_client.post("/v1/api-keys", &body).await.expect(StatusCode::CREATED);
+    let key_client = client.with_header("X-Api-Key", &created.key);
+
+    // どのスコープを持っていても、APIキーで一覧・作成・無効化はできない
+    let uri = format!("/v1/api-keys/{}", created.api_key.id);
+    for response in [
+        key_client.get("/v1/api-keys").await,
+        key_client.post("/v1/api-keys", &body).await,
+        key_client.delete(&uri).await,
+    ] {
+        assert_eq!((response.status, response.error_code()), (StatusCode::FORBIDDEN, ErrorCode::ApiKeyScopeMissing));
+    }
+    let listed: Vec<ApiKey> = alice_client.get("/v1/api-keys").await.expect(StatusCode::OK);
+    assert_eq!(listed.len(), 1);
+    assert!(listed[0].is_active);
+}
+
+#[tokio::test]
+async fn scopes_limit_what_keys_can_call() {
+    let Setup { state, root, alice, client, .. } = setup(Config::default()).await;
+    let root_client = client.with_session(&login_as(&state, &root).await);
+
+    let body = json!({ "name": "reader", "scopes": ["read:users"] });
+    let created: CreateApiKeyResponse = root_client.post("/v1/api-keys", &body).await.expect(StatusCode::CREATED);
+    let reader = client.with_header("X-Api-Key", &created.key);
+    assert_eq!(reader.get("/v1/userinfo").await.status, StatusCode::OK);
+    assert_eq!(reader.get("/v1/admin/users").await.status, StatusCode::OK);
+
+    // 所有者がrootユーザーでも、キーのスコープにない操作はできない
+    let ban = format!("/v1/admin/users/{}/ban", alice.id);
+    for response in [
+        reader.post(&ban, &json!({})).await,
+        reader.get("/v1/admin/stats").await,
+        reader.get("/v1/invite/list").await,
+        reader.get("/v1/events").await,
+    ] {
+        assert_eq!((response.status, response.error_code()), (StatusCode::FORBIDDEN, ErrorCode::ApiKeyScopeMissing));
+    }
+    let target = state.database.get_user_by_id(alice.id).await.unwrap().unwrap();
+    assert!(target.is_active);
+
+    // スコープがあっても所有者の権限を超えることはできない
+    let alice_client = client.with_session(&login_as(&state, &alice).await);
+    let body = json!({ "name": "writer", "scopes": ["read:users", "write:users"] });
+    let created: CreateApiKeyResponse = alice_client.post("/v1/api-keys", &body).await.expect(StatusCode::CREATED);
+    let response = client.with_header("X-Api-Key", &created.key).get("/v1/admin/users").await;
+    assert_eq!((response.status, response.error_code()), (StatusCode::FORBIDDEN, ErrorCode::InsufficientPermission));
+}
+
+#[tokio::test]
+async fn root_can_create_keys_for_other_users() {
+    let Setup { state, root, alice, client, .. } = setup(Config::default()).await;
+    let alice_client = client.with_session(&login_as(&state, &alice).await);
+    let root_client = client.with_session(&login_as(&state, &root).await);
+
+    let body = json!({ "name": "for-alice", "scopes": ["read:users"], "user_id": alice.id });
+    let created: CreateApiKeyResponse = root_client.post("/v1/api-keys", &body).await.expect(StatusCode::CREATED);
+    assert_eq!(created.api_key.created_by, alice.id);
+    let info: UserInfoResponse =
+        client.with_header("X-Api-Key", &created.key).get("/v1/userinfo").await.expect(StatusCode::OK);
+    assert_eq!(info.email, alice.email);
+    assert_eq!(alice_client.get("/v1/api-keys").await.expect::<Vec<ApiKey>>(StatusCode::OK).len(), 1);
+    // 作成したrootユーザーのキーにはならない
+    assert!(root_client.get("/v1/api-keys").await.expect::<Vec<ApiKey>>(StatusCode::OK).is_empty());
+
+    // 自分のIDの指定は省略と同じ。他のユーザーはrootユーザーのみ
+    let body = json!({ "name": "self", "scopes": ["read:users"], "user_id": alice.id });
+    alice_client.post("/v1/api-keys", &body).await.expect::<CreateApiKeyResponse>(StatusCode::CREATED);
+    let body = json!({ "name": "for-root", "scopes": ["read:users"], "user_id": root.id });
+    let response = alice_client.post("/v1/api-keys", &body).await;
+    assert_eq!((response.status, response.error_code()), (StatusCode::FORBIDDEN, ErrorCode::InsufficientPermission));
+    let body = json!({ "name": "nobody", "scopes": ["read:users"], "user_id": 9999 });
+    let response = root_client.post("/v1/api-keys", &body).await;
+    assert_eq!((response.status, response.error_code()), (StatusCode::NOT_FOUND, ErrorCode::UserNotFound));
+    let bob = UserFixture::new("Bob").invited_by(&root).banned().insert(&state.database).await;
+    let body = json!({ "name": "for-bob", "scopes": ["read:users"], "user_id": bob.id });
+    let error: ErrorResponse = root_client.post("/v1/api-keys", &body).await.expect(StatusCode::BAD_REQUEST);
+    assert!(error.details.unwrap().get("user_id").is_some());
+
+    let audit = state.database.get_audit_export_page(None, None, 10).await.unwrap();
+    let entry = audit.iter().find(|entry| entry.metadata["name"] == "for-alice").unwrap();
+    assert_eq!(entry.actor_email.as_deref(), Some(root.email.as_str()));
+    assert_eq!(entry.target_email.as_deref(), Some(alice.email.as_str()));
+}
+
+#[tokio::test]
+async fn only_the_owner_or_root_can_revoke() {
+    let Setup { state, root, alice, client, .. } = setup(Config::default()).await;
+    let bob = UserFixture::new("Bob").invited_by(&root).insert(&state.database).await;
+    let alice_client = client.with_session(&login_as(&state, &alice).await);
+    let bob_client = client.with_session(&login_as(&state, &bob).await);
+    let root_client = client.with_session(&login_as(&state, &root).await);
+
+    let body = json!({ "name": "CI", "scopes": ["read:users"] });
+    let created: CreateApiKeyResponse = alice_client.post("/v1/api-keys", &body).await.expect(StatusCode::CREATED);
+    let uri = format!("/v1/api-keys/{}", created.api_key.id);
+
+    let response = bob_client.delete(&uri).await;
+    assert_eq!((response.status, response.error_code()), (StatusCode::NOT_FOUND, ErrorCode::ApiKeyNotFound));
+    assert!(bob_client.get("/v1/api-keys").await.expect::<Vec<ApiKey>>(StatusCode::OK).is_empty());
+
+    let revoked: ApiKey = root_client.delete(&uri).await.expect(StatusCode::OK);
+    assert!(!revoked.is_active);
+    assert_eq!(root_client.delete("/v1/api-keys/9999").await.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn session_takes_precedence_and_header_name_is_configurable() {
+    let config = Config {
+        api_key_header: "X-Patchouli-Key".to_string(),
+        ..Config::default()
+    };
+    let Setup { state, root, alice, client, .. } = setup(config).await;
+    let bob = UserFixture::new("Bob").invited_by(&root).insert(&state.database).await;
+    let alice_client = client.with_session(&login_as(&state, &alice).await);
+    let body = json!({ "name": "CI", "scopes": ["read:users"] });
+    let created: CreateApiKeyResponse = alice_client.post("/v1/api-keys", &body).await.expect(StatusCode::CREATED);
+
+    // 既定のヘッダー名は使われない
+    let response = client.with_header("X-Api-Key", &created.key).get("/v1/userinfo").await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    let key_client = client.with_header("X-Patchouli-Key", &created.key);
+    let info: UserInfoResponse = key_client.get("/v1/userinfo").await.expect(StatusCode::OK);
+    assert_eq!(info.email, alice.email);
+
+    // Authorizationヘッダーがあればセッションを優先する
+    let both = key_client.with_session(&login_as(&state, &bob).await);
+    let info: UserInfoResponse = both.get("/v1/userinfo").await.expect(StatusCode::OK);
+    assert_eq!(info.email, bob.email);
+}
+
+fn hash(key: &str) -> String {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(key.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,133 @@
+mod common;
+
+use axum::{http::StatusCode, Router};
+use common::{add_session, add_session_for, fixtures::UserFixture};
+use patchouli::{
+    build_router,
+    config::Config,
+    error::{ErrorCode, ErrorResponse},
+    DashboardResponse,
+};
+
+const ROOT_SESSION: &str = "root-session";
+const USER_SESSION: &str = "user-session";
+const BANNED_SESSION: &str = "banned-session";
+const UNREGISTERED_SESSION: &str = "unregistered-session";
+
+/// rootユーザー・一般ユーザー・利用停止中のユーザー・未登録のメールアドレスのセッションを用意する
+async fn app() -> Router {
+    let state = common::state(Config::default()).await;
+
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let user = UserFixture::new("User").invited_by(&root).insert(&state.database).await;
+    let banned = UserFixture::new("Banned").invited_by(&root).banned().insert(&state.database).await;
+
+    add_session(&state, ROOT_SESSION, &root).await;
+    add_session(&state, USER_SESSION, &user).await;
+    add_session(&state, BANNED_SESSION, &banned).await;
+    add_session_for(&state, UNREGISTERED_SESSION, "google-unknown", "unknown@example.com").await;
+
+    build_router(state)
+}
+
+async fn get(app: &Router, uri: &str) -> (StatusCode, Vec<u8>) {
+    let response = common::get(app, uri).await;
+    (response.status, response.body)
+}
+
+async fn get_error(app: &Router, uri: &str) -> (StatusCode, ErrorCode) {
+    let response = common::get(app, uri).await;
+    (response.status, response.json::<ErrorResponse>().error)
+}
+
+/// 認証の要否・rootユーザーの要否ごとの代表的なエンドポイント
+const PUBLIC: &[&str] = &["/healthz", "/v1/system/errors", "/v1/root/exists"];
+const AUTHENTICATED: &[&str] = &["/v1/dashboard", "/v1/invite/list", "/v1/users/2/permissions"];
+const ROOT_ONLY: &[&str] = &["/v1/admin/users", "/v1/admin/stats", "/v1/system/pending-actions"];
+
+fn with_session(uri: &str, session_id: &str) -> String {
+    format!("{}?session_id={}", uri, session_id)
+}
+
+#[tokio::test]
+async fn public_endpoints_need_no_session() {
+    let app = app().await;
+    for uri in PUBLIC {
+        assert_eq!(get(&app, uri).await.0, StatusCode::OK, "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn missing_session_id_is_rejected() {
+    let app = app().await;
+    for uri in AUTHENTICATED.iter().chain(ROOT_ONLY) {
+        assert_eq!(get_error(&app, uri).await, (StatusCode::BAD_REQUEST, ErrorCode::ValidationFailed), "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn unknown_session_is_unauthorized() {
+    let app = app().await;
+    for uri in AUTHENTICATED.iter().chain(ROOT_ONLY) {
+        let uri = with_session(uri, "no-such-session");
+        assert_eq!(get_error(&app, &uri).await, (StatusCode::UNAUTHORIZED, ErrorCode::InvalidSession), "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn session_of_unregistered_email_is_forbidden() {
+    let app = app().await;
+    for uri in AUTHENTICATED.iter().chain(ROOT_ONLY) {
+        let uri = with_session(uri, UNREGISTERED_SESSION);
+        assert_eq!(get_error(&app, &uri).await, (StatusCode::FORBIDDEN, ErrorCode::UserNotRegistered), "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn banned_user_is_forbidden() {
+    let app = app().await;
+    for uri in AUTHENTICATED.iter().chain(ROOT_ONLY) {
+        let uri = with_session(uri, BANNED_SESSION);
+        assert_eq!(get_error(&app, &uri).await, (StatusCode::FORBIDDEN, ErrorCode::UserSuspended), "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn regular_user_cannot_access_root_endpoints() {
+    let app = app().await;
+    for uri in AUTHENTICATED {
+        let uri = with_session(uri, USER_SESSION);
+        assert_eq!(get(&app, &uri).await.0, StatusCode::OK, "{}", uri);
+    }
+    for uri in ROOT_ONLY {
+        let uri = with_session(uri, USER_SESSION);
+        assert_eq!(
+            get_error(&app, &uri).await,
+            (StatusCode::FORBIDDEN, ErrorCode::InsufficientPermission),
+            "{}",
+            uri
+        );
+    }
+    // 全ユーザーの招待コード一覧はrootユーザーのみ
+    let uri = format!("{}&all=true", with_session("/v1/invite/list", USER_SESSION));
+    assert_eq!(get_error(&app, &uri).await, (StatusCode::FORBIDDEN, ErrorCode::InsufficientPermission));
+}
+
+#[tokio::test]
+async fn root_user_can_access_everything() {
+    let app = app().await;
+    for uri in AUTHENTICATED.iter().chain(ROOT_ONLY) {
+        let uri = with_session(uri, ROOT_SESSION);
+        assert_eq!(get(&app, &uri).await.0, StatusCode::OK, "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn dashboard_returns_the_session_user() {
+    let app = app().await;
+    let (status, body) = get(&app, &with_session("/v1/dashboard", USER_SESSION)).await;
+    assert_eq!(status, StatusCode::OK);
+    let dashboard: DashboardResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(dashboard.user.email, "user@example.com");
+    assert!(!dashboard.user.is_root);
+}
@@ -0,0 +1,75 @@
+//! 招待権限の変更（`PATCH /v1/users/:user_id/can-invite`）と監査ログ
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{fixtures::UserFixture, login_as, TestClient};
+use patchouli::{build_router, config::Config, error::ErrorCode, AppState, SetCanInviteResponse};
+use serde_json::json;
+
+async fn setup() -> (AppState, TestClient) {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let session = login_as(&state, &root).await;
+    let client = TestClient::new(build_router(state.clone())).with_session(&session);
+    (state, client)
+}
+
+#[tokio::test]
+async fn toggles_invite_permission_with_a_distinct_audit_entry() {
+    let (state, client) = setup().await;
+    let root = state.database.get_user_by_email("root@example.com").await.unwrap().unwrap();
+    let alice = UserFixture::new("Alice").invited_by(&root).insert(&state.database).await;
+    let uri = format!("/v1/users/{}/can-invite", alice.id);
+
+    let response: SetCanInviteResponse =
+        client.patch(&uri, &json!({ "can_invite": true })).await.expect(StatusCode::OK);
+    assert_eq!((response.user_id, response.can_invite), (alice.id, true));
+    assert!(state.database.get_user_by_id(alice.id).await.unwrap().unwrap().can_invite);
+
+    // 変更は次のリクエストからすぐに反映される（ユーザーキャッシュを破棄する）
+    let alice_client = client.with_session(&login_as(&state, &alice).await);
+    alice_client.get("/v1/invite/create").await.expect::<serde_json::Value>(StatusCode::OK);
+
+    client.patch(&uri, &json!({ "can_invite": false })).await.expect::<SetCanInviteResponse>(StatusCode::OK);
+    let response = alice_client.get("/v1/invite/create").await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    let audit = state.database.get_audit_export_page(None, None, 10).await.unwrap();
+    assert_eq!(audit.len(), 2);
+    assert!(audit.iter().all(|entry| entry.action == "set_can_invite"));
+    assert!(audit.iter().all(|entry| entry.target_email.as_deref() == Some("alice@example.com")));
+    assert_eq!(audit[0].metadata, json!({ "can_invite": false }));
+    assert_eq!(audit[1].metadata, json!({ "can_invite": true }));
+}
+
+#[tokio::test]
+async fn root_cannot_revoke_own_invite_permission() {
+    let (state, client) = setup().await;
+    let root = state.database.get_user_by_email("root@example.com").await.unwrap().unwrap();
+    let uri = format!("/v1/users/{}/can-invite", root.id);
+
+    let response = client.patch(&uri, &json!({ "can_invite": false })).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.error_code(), ErrorCode::CannotTargetSelf);
+    assert!(state.database.get_user_by_id(root.id).await.unwrap().unwrap().can_invite);
+    assert!(state.database.get_audit_export_page(None, None, 10).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn requires_root_and_an_existing_user() {
+    let (state, client) = setup().await;
+    let root = state.database.get_user_by_email("root@example.com").await.unwrap().unwrap();
+    let alice = UserFixture::new("Alice").invited_by(&root).can_invite().insert(&state.database).await;
+    let body = json!({ "can_invite": true });
+
+    let alice_client = client.with_session(&login_as(&state, &alice).await);
+    let response = alice_client.patch(&format!("/v1/users/{}/can-invite", alice.id), &body).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    let response = client.patch("/v1/users/9999/can-invite", &body).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let response = client.patch(&format!("/v1/users/{}/can-invite", alice.id), &json!({})).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,53 @@
+//! ハンドラーのpanicはJSONのエラー（500 `internal_error`）になり、サーバーは処理を続ける
+
+mod common;
+
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use common::{fixtures::UserFixture, login_as, TestClient};
+use patchouli::{
+    build_router,
+    clock::Clock,
+    config::Config,
+    error::{ErrorCode, ErrorResponse},
+};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// `armed`の間は現在時刻を求められるとpanicする時計（ハンドラーの中でpanicさせる）
+#[derive(Default)]
+struct PanickingClock {
+    armed: AtomicBool,
+}
+
+impl Clock for PanickingClock {
+    fn now(&self) -> DateTime<Utc> {
+        assert!(!self.armed.load(Ordering::SeqCst), "clock exploded");
+        Utc::now()
+    }
+}
+
+#[tokio::test]
+async fn panicking_handlers_return_json_500() {
+    let clock = Arc::new(PanickingClock::default());
+    let state = common::state_with_clock(Config::default(), clock.clone()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let client = TestClient::new(build_router(state.clone()))
+        .with_session(&login_as(&state, &root).await)
+        .with_header("x-request-id", "panic-1");
+
+    clock.armed.store(true, Ordering::SeqCst);
+    let response = client.get("/v1/invite/create").await;
+    assert_eq!(response.headers["content-type"], "application/json");
+    assert_eq!(response.headers["x-request-id"], "panic-1");
+    let error: ErrorResponse = response.expect(StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(error.error, ErrorCode::InternalError);
+    assert_eq!(error.request_id.as_deref(), Some("panic-1"));
+    // panicのメッセージはレスポンスに含めない
+    assert!(!String::from_utf8_lossy(&response.body).contains("clock exploded"));
+
+    clock.armed.store(false, Ordering::SeqCst);
+    assert_eq!(client.get("/v1/invite/create").await.status, StatusCode::OK);
+}
@@ -0,0 +1,164 @@
+//! 送信元のIPアドレス（`TRUSTED_PROXIES`のプロキシの`Forwarded`・`X-Forwarded-For`と`ClientIp`、ログインの監査ログ）
+
+mod common;
+
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{HeaderMap, HeaderValue, Request, StatusCode},
+    middleware,
+    routing::get,
+    Router,
+};
+use common::{fixtures::UserFixture, google};
+use ipnet::IpNet;
+use patchouli::{
+    build_router,
+    client_ip::{self, client_ip, ClientIp},
+    config::{Config, TrustedProxy},
+    AuthResponse,
+};
+use serde_json::json;
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+use tower::ServiceExt;
+
+const PROXY: &str = "10.0.0.2";
+const CLIENT: &str = "203.0.113.7";
+
+fn trusted() -> Vec<IpNet> {
+    vec!["10.0.0.0/8".parse().unwrap(), "2001:db8:ffff::/48".parse().unwrap()]
+}
+
+fn ip(value: &str) -> IpAddr {
+    value.parse().unwrap()
+}
+
+fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        headers.append(*name, HeaderValue::from_str(value).unwrap());
+    }
+    headers
+}
+
+#[test]
+fn headers_from_untrusted_peers_are_ignored() {
+    // プロキシを経由せずに直接接続したクライアントが偽のヘッダーを送っても接続元のアドレスを使う
+    let spoofed = headers(&[("x-forwarded-for", "198.51.100.1"), ("forwarded", "for=198.51.100.2")]);
+    assert_eq!(client_ip(ip(CLIENT), &spoofed, &trusted()), ip(CLIENT));
+    assert_eq!(client_ip(ip(PROXY), &spoofed, &[]), ip(PROXY));
+}
+
+#[test]
+fn uses_the_rightmost_untrusted_hop() {
+    // 左端はクライアントが書いた値のため信用しない
+    let forwarded = headers(&[("x-forwarded-for", &format!("198.51.100.1, {}, 10.0.0.9", CLIENT))]);
+    assert_eq!(client_ip(ip(PROXY), &forwarded, &trusted()), ip(CLIENT));
+
+    // 複数のヘッダーは順に連結する
+    let split = headers(&[("x-forwarded-for", "198.51.100.1"), ("x-forwarded-for", &format!("{}, 10.0.0.9", CLIENT))]);
+    assert_eq!(client_ip(ip(PROXY), &split, &trusted()), ip(CLIENT));
+
+    // 全て信頼できるプロキシなら最も遠いもの
+    let internal = headers(&[("x-forwarded-for", "10.1.1.1, 10.0.0.9")]);
+    assert_eq!(client_ip(ip(PROXY), &internal, &trusted()), ip("10.1.1.1"));
+
+    // ヘッダーがなければ接続元
+    assert_eq!(client_ip(ip(PROXY), &HeaderMap::new(), &trusted()), ip(PROXY));
+}
+
+#[test]
+fn forwarded_takes_precedence_and_accepts_ports_and_ipv6() {
+    let both = headers(&[
+        ("x-forwarded-for", "198.51.100.1"),
+        ("forwarded", "for=\"[2001:db8::17]:4711\";proto=https, for=10.0.0.9:8080"),
+    ]);
+    assert_eq!(client_ip(ip(PROXY), &both, &trusted()), ip("2001:db8::17"));
+
+    let ipv6_proxy = headers(&[("forwarded", &format!("For={};by=10.0.0.1", CLIENT))]);
+    assert_eq!(client_ip(ip("2001:db8:ffff::1"), &ipv6_proxy, &trusted()), ip(CLIENT));
+}
+
+#[test]
+fn unreadable_hops_stop_at_the_nearest_trusted_proxy() {
+    // `unknown`・難読化した識別子より左は信用しない
+    let unknown = headers(&[("forwarded", &format!("for={}, for=unknown, for=10.0.0.9", CLIENT))]);
+    assert_eq!(client_ip(ip(PROXY), &unknown, &trusted()), ip("10.0.0.9"));
+
+    let garbage = headers(&[("x-forwarded-for", &format!("{}, not-an-ip", CLIENT))]);
+    assert_eq!(client_ip(ip(PROXY), &garbage, &trusted()), ip(PROXY));
+}
+
+#[test]
+fn trusted_proxies_accept_cidrs_and_addresses() {
+    let proxies: Vec<TrustedProxy> = ["10.0.0.0/8", "127.0.0.1", "::1"].iter().map(|p| p.parse().unwrap()).collect();
+    assert!(proxies.iter().any(|proxy| proxy.0.contains(&ip("127.0.0.1"))));
+    assert!(!proxies.iter().any(|proxy| proxy.0.contains(&ip("127.0.0.2"))));
+    assert!("10.0.0.0/33".parse::<TrustedProxy>().is_err());
+    assert!("proxy.internal".parse::<TrustedProxy>().is_err());
+
+    // 設定ファイルの値も読み込み時に検証する
+    let config: Config = toml::from_str(r#"trusted_proxies = ["10.0.0.0/8", "::1"]"#).unwrap();
+    assert_eq!(config.trusted_proxies.len(), 2);
+    assert!(toml::from_str::<Config>(r#"trusted_proxies = ["10.0.0.0/33"]"#).is_err());
+}
+
+#[tokio::test]
+async fn middleware_exposes_the_client_ip_to_handlers() {
+    let trusted: Arc<[IpNet]> = trusted().into();
+    let app = Router::new()
+        .route("/", get(|ClientIp(ip): ClientIp| async move { format!("{:?}", ip) }))
+        .layer(middleware::from_fn_with_state(trusted, client_ip::resolve));
+
+    let send = |peer: Option<&str>, forwarded_for: &str| {
+        let mut request = Request::get("/").header("x-forwarded-for", forwarded_for).body(Body::empty()).unwrap();
+        if let Some(peer) = peer {
+            request.extensions_mut().insert(ConnectInfo(SocketAddr::new(ip(peer), 54321)));
+        }
+        let app = app.clone();
+        async move {
+            let response = app.oneshot(request).await.unwrap();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            String::from_utf8(body.to_vec()).unwrap()
+        }
+    };
+
+    assert_eq!(send(Some(PROXY), CLIENT).await, format!("Some({})", CLIENT));
+    assert_eq!(send(Some(CLIENT), "198.51.100.1").await, format!("Some({})", CLIENT));
+    // 接続元が分からなければヘッダーも使わない
+    assert_eq!(send(None, CLIENT).await, "None");
+}
+
+#[tokio::test]
+async fn logins_record_the_client_ip_in_the_audit_log() {
+    let state = common::state(Config {
+        google_client_id: google::CLIENT_ID.to_string(),
+        google_jwks_url: google::jwks_server().await,
+        trusted_proxies: vec![PROXY.parse().unwrap()],
+        ..Config::default()
+    })
+    .await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let app = build_router(state.clone());
+
+    let body = json!({
+        "grant_type": "google_id_token",
+        "id_token": google::id_token(&root.google_id, &root.email, &root.name),
+    });
+    let mut request = Request::post("/v1/auth/tokens/google-one-tap")
+        .header("content-type", "application/json")
+        .header("x-forwarded-for", CLIENT)
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    request.extensions_mut().insert(ConnectInfo(SocketAddr::new(ip(PROXY), 54321)));
+    common::send(&app, request).await.expect::<AuthResponse>(StatusCode::OK);
+
+    let entries = state.database.get_audit_export_page(None, None, 10).await.unwrap();
+    let login = entries.iter().find(|entry| entry.action == "login").expect("login should be audited");
+    assert_eq!(login.actor_email.as_deref(), Some(root.email.as_str()));
+    assert_eq!(login.target_email.as_deref(), Some(root.email.as_str()));
+    assert_eq!(login.metadata, json!({ "method": "google_one_tap", "client_ip": CLIENT }));
+}
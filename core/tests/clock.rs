@@ -0,0 +1,85 @@
+//! `MockClock`で時刻を進め、待たずに有効期限を過ぎた状態を確認する
+
+mod common;
+
+use axum::http::StatusCode;
+use chrono::Duration;
+use common::{
+    fixtures::{InviteFixture, UserFixture},
+    google, login_as, TestClient,
+};
+use patchouli::{build_router, clock::MockClock, config::Config, error::ErrorCode, AppState, AuthResponse};
+use serde_json::json;
+use std::sync::Arc;
+
+async fn mock_state(config: Config) -> (AppState, Arc<MockClock>) {
+    let clock = Arc::new(MockClock::starting_now());
+    let config = Config {
+        google_client_id: google::CLIENT_ID.to_string(),
+        google_jwks_url: google::jwks_server().await,
+        ..config
+    };
+    (common::state_with_clock(config, clock.clone()).await, clock)
+}
+
+async fn one_tap(client: &TestClient, id_token: &str, invite_code: Option<&str>) -> common::TestResponse {
+    let body = json!({ "grant_type": "google_id_token", "id_token": id_token, "invite_code": invite_code });
+    client.post("/v1/auth/tokens/google-one-tap", &body).await
+}
+
+#[tokio::test]
+async fn invite_expires_when_the_clock_passes_its_expiry() {
+    let (state, clock) = mock_state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let invite = InviteFixture::new(&root).expires_in(Duration::minutes(30)).insert(&state.database).await;
+
+    clock.advance(Duration::minutes(29));
+    assert!(state.database.validate_invite_code(&invite.code).await.unwrap().is_some());
+    // 有効な間に検証結果をキャッシュしておく
+    assert!(state.invite_cache.validate(&state.database, &invite.code).await.unwrap().is_some());
+
+    clock.advance(Duration::minutes(2));
+    assert!(state.database.validate_invite_code(&invite.code).await.unwrap().is_none());
+    assert!(state.invite_cache.validate(&state.database, &invite.code).await.unwrap().is_none());
+
+    let client = TestClient::new(build_router(state.clone()));
+    let id_token = google::id_token("google-guest", "guest@example.com", "Guest");
+    let response = one_tap(&client, &id_token, Some(&invite.code)).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    assert_eq!(response.error_code(), ErrorCode::InvalidInvite);
+}
+
+#[tokio::test]
+async fn id_token_is_rejected_after_exp() {
+    let (state, clock) = mock_state(Config::default()).await;
+    let client = TestClient::new(build_router(state.clone()));
+    // テスト用のID Tokenは発行から1時間で期限切れ（検証は60秒の猶予を認める）
+    let id_token = google::id_token("google-first", "first@example.com", "First");
+
+    clock.advance(Duration::minutes(61));
+    one_tap(&client, &id_token, None).await.expect::<AuthResponse>(StatusCode::OK);
+
+    clock.advance(Duration::minutes(1));
+    let response = one_tap(&client, &id_token, None).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.error_code(), ErrorCode::InvalidIdToken);
+}
+
+#[tokio::test]
+async fn daily_invite_limit_resets_the_next_day() {
+    let (state, clock) = mock_state(Config {
+        invite_daily_limit: 1,
+        ..Config::default()
+    })
+    .await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let client = TestClient::new(build_router(state.clone())).with_session(&login_as(&state, &root).await);
+
+    client.get("/v1/invite/create").await.expect::<serde_json::Value>(StatusCode::OK);
+    let response = client.get("/v1/invite/create").await;
+    assert_eq!(response.status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.error_code(), ErrorCode::InviteDailyLimitExceeded);
+
+    clock.advance(Duration::days(1));
+    client.get("/v1/invite/create").await.expect::<serde_json::Value>(StatusCode::OK);
+}
@@ -0,0 +1,251 @@
+//! テストデータのビルダー（ユーザー・招待コード・典型的な状態をまとめて作る）
+//!
+//! ```ignore
+//! let root = UserFixture::new("Root").root().insert(&state.database).await;
+//! let alice = UserFixture::new("Alice").invited_by(&root).can_invite().insert(&state.database).await;
+//! let expired = InviteFixture::expired(&alice).insert(&state.database).await;
+//! ```
+
+use chrono::Duration;
+use patchouli::database::{Database, InviteCode, RegisteredUser};
+
+/// 登録ユーザー（メールアドレスは`<名前の小文字>@example.com`、Google IDは`google-<名前の小文字>`）
+///
+/// 最初に登録したユーザーは本番と同じく常にrootユーザーになる。
+pub struct UserFixture {
+    key: String,
+    name: String,
+    root: bool,
+    can_invite: bool,
+    invited_by: Option<RegisteredUser>,
+    invite_code: bool,
+    banned: bool,
+    email_verified: bool,
+}
+
+impl UserFixture {
+    pub fn new(name: &str) -> Self {
+        UserFixture {
+            key: name.to_lowercase(),
+            name: name.to_string(),
+            root: false,
+            can_invite: false,
+            invited_by: None,
+            invite_code: false,
+            banned: false,
+            email_verified: false,
+        }
+    }
+
+    /// 表示名だけを変える（メールアドレス・Google IDは`new`の名前から作る）
+    pub fn display_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// rootユーザーとして登録する（最初のユーザーでなければ`insert`がパニックする）
+    pub fn root(mut self) -> Self {
+        self.root = true;
+        self
+    }
+
+    /// 招待権限を付ける（rootユーザー以外の登録時のデフォルトは権限なし）
+    pub fn can_invite(mut self) -> Self {
+        self.can_invite = true;
+        self
+    }
+
+    /// `inviter`に招待されたユーザーにする（`invited_by`を設定する）
+    pub fn invited_by(mut self, inviter: &RegisteredUser) -> Self {
+        self.invited_by = Some(inviter.clone());
+        self
+    }
+
+    /// 実際の登録と同じく、招待者が作成した招待コードを使って登録する（`invited_by`と併用）
+    pub fn with_invite_code(mut self) -> Self {
+        self.invite_code = true;
+        self
+    }
+
+    /// 登録後に利用停止にする（招待者、いなければ本人が実行したとして監査ログに残る）
+    pub fn banned(mut self) -> Self {
+        self.banned = true;
+        self
+    }
+
+    /// Googleでメールアドレスを確認済みにする
+    pub fn email_verified(mut self) -> Self {
+        self.email_verified = true;
+        self
+    }
+
+    pub async fn insert(self, db: &Database) -> RegisteredUser {
+        let email = format!("{}@example.com", self.key);
+        let google_id = format!("google-{}", self.key);
+        let user = match &self.invited_by {
+            Some(inviter) => {
+                let user = db.register_invited_user(&google_id, &email, &self.name, inviter.id).await.unwrap();
+                if self.invite_code {
+                    let invite = db.create_invite_code(inviter.id).await.unwrap();
+                    db.use_invite_code(&invite.code, user.id).await.unwrap();
+                }
+                user
+            }
+            None => {
+                assert!(!self.invite_code, "with_invite_code() requires invited_by()");
+                db.register_user(&google_id, &email, &self.name, None).await.unwrap()
+            }
+        };
+        assert_eq!(user.is_root, self.root, "only the first registered user becomes root ({})", email);
+
+        let actor_id = self.invited_by.as_ref().map_or(user.id, |inviter| inviter.id);
+        if self.can_invite && !user.can_invite {
+            db.set_can_invite(actor_id, user.id, true).await.unwrap();
+        }
+        if self.email_verified {
+            db.mark_email_verified(&email).await.unwrap();
+        }
+        if self.banned {
+            db.ban_user(actor_id, user.id, 0).await.unwrap();
+        }
+        db.get_user_by_id(user.id).await.unwrap().unwrap()
+    }
+}
+
+/// 招待コード（デフォルトは期限なしの未使用・有効なコード）
+pub struct InviteFixture {
+    created_by: RegisteredUser,
+    note: Option<String>,
+    expires_in: Option<Duration>,
+    used_by: Option<RegisteredUser>,
+    revoked: bool,
+}
+
+impl InviteFixture {
+    pub fn new(created_by: &RegisteredUser) -> Self {
+        InviteFixture {
+            created_by: created_by.clone(),
+            note: None,
+            expires_in: None,
+            used_by: None,
+            revoked: false,
+        }
+    }
+
+    /// 1時間前に期限切れになった招待コード
+    pub fn expired(created_by: &RegisteredUser) -> Self {
+        InviteFixture::new(created_by).expires_in(Duration::hours(-1))
+    }
+
+    /// 作成時刻（データベースの時計の現在時刻）から`duration`後に期限切れにする（負の値なら期限切れ）
+    pub fn expires_in(mut self, duration: Duration) -> Self {
+        self.expires_in = Some(duration);
+        self
+    }
+
+    pub fn note(mut self, note: &str) -> Self {
+        self.note = Some(note.to_string());
+        self
+    }
+
+    /// `user`が使用済みにする（招待したユーザーの登録とは別に、コードの状態だけを変える）
+    pub fn used_by(mut self, user: &RegisteredUser) -> Self {
+        self.used_by = Some(user.clone());
+        self
+    }
+
+    /// 無効化する
+    pub fn revoked(mut self) -> Self {
+        self.revoked = true;
+        self
+    }
+
+    pub async fn insert(self, db: &Database) -> InviteCode {
+        let invite = db.create_invite_code(self.created_by.id).await.unwrap();
+        if self.note.is_some() || self.expires_in.is_some() {
+            let expires_at = self.expires_in.map(|duration| invite.created_at + duration);
+            db.update_invite(invite.id, self.note.as_deref(), expires_at).await.unwrap();
+        }
+        if let Some(user) = &self.used_by {
+            db.use_invite_code(&invite.code, user.id).await.unwrap();
+        }
+        if self.revoked {
+            db.deactivate_invite(invite.id).await.unwrap().expect("only unused invites can be revoked");
+        }
+        db.get_invite_code_by_id(invite.id).await.unwrap().unwrap()
+    }
+}
+
+/// 典型的な運用中の状態
+pub struct Scenario {
+    /// 最初に登録したrootユーザー
+    pub root: RegisteredUser,
+    /// rootユーザーが招待し、招待権限を与えたユーザー
+    pub inviter: RegisteredUser,
+    /// `inviter`の招待コードで登録したユーザー（`ScenarioBuilder::invitees`の人数）
+    pub invitees: Vec<RegisteredUser>,
+    /// rootユーザーが招待し、利用停止にしたユーザー
+    pub banned: RegisteredUser,
+    /// `inviter`の未使用・期限切れ・無効化済みの招待コード
+    pub pending_invite: InviteCode,
+    pub expired_invite: InviteCode,
+    pub revoked_invite: InviteCode,
+}
+
+impl Scenario {
+    /// 利用停止中のユーザーを含む全ユーザー
+    pub fn users(&self) -> Vec<&RegisteredUser> {
+        let mut users = vec![&self.root, &self.inviter, &self.banned];
+        users.extend(&self.invitees);
+        users
+    }
+}
+
+pub struct ScenarioBuilder {
+    invitees: usize,
+}
+
+impl Default for ScenarioBuilder {
+    fn default() -> Self {
+        ScenarioBuilder { invitees: 2 }
+    }
+}
+
+impl ScenarioBuilder {
+    /// `inviter`の招待コードで登録するユーザーの人数（デフォルト: 2）
+    pub fn invitees(mut self, count: usize) -> Self {
+        self.invitees = count;
+        self
+    }
+
+    pub async fn insert(self, db: &Database) -> Scenario {
+        let root = UserFixture::new("Root").root().email_verified().insert(db).await;
+        let inviter = UserFixture::new("Inviter")
+            .invited_by(&root)
+            .with_invite_code()
+            .can_invite()
+            .email_verified()
+            .insert(db)
+            .await;
+        let mut invitees = Vec::with_capacity(self.invitees);
+        for index in 0..self.invitees {
+            let invitee = UserFixture::new(&format!("Invitee{}", index + 1))
+                .invited_by(&inviter)
+                .with_invite_code()
+                .insert(db)
+                .await;
+            invitees.push(invitee);
+        }
+        let banned = UserFixture::new("Banned").invited_by(&root).with_invite_code().banned().insert(db).await;
+
+        Scenario {
+            pending_invite: InviteFixture::new(&inviter).note("pending").insert(db).await,
+            expired_invite: InviteFixture::expired(&inviter).insert(db).await,
+            revoked_invite: InviteFixture::new(&inviter).revoked().insert(db).await,
+            root,
+            inviter,
+            invitees,
+            banned,
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Google One TapのID Tokenをテスト用の鍵で発行する
+//!
+//! `fixtures/google_test_key.pem`はテスト専用に生成したRSA鍵で、対応する公開鍵が
+//! `fixtures/google_test_jwks.json`。`jwks_server`で配信し、`GOOGLE_JWKS_URL`に指定して使う。
+//! ブラウザのOAuthログイン（`/callback`）は`oauth_server`を`GOOGLE_TOKEN_URL`・`GOOGLE_USERINFO_URL`に指定して使う。
+
+use axum::{
+    http::header::CACHE_CONTROL,
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use serde_json::json;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use tokio::net::TcpListener;
+
+const TEST_KEY_PEM: &[u8] = include_bytes!("../fixtures/google_test_key.pem");
+const TEST_JWKS: &str = include_str!("../fixtures/google_test_jwks.json");
+const TEST_KEY_ID: &str = "patchouli-test";
+
+/// テストで使うOAuthクライアントID（ID Tokenの`aud`）
+pub const CLIENT_ID: &str = "patchouli-test.apps.googleusercontent.com";
+
+/// テスト用の公開鍵を返すJWKsエンドポイントを起動し、そのURLを返す
+pub async fn jwks_server() -> String {
+    jwks_server_with("max-age=3600").await.0
+}
+
+/// `jwks_server`と同じだが、`Cache-Control`を指定でき、公開鍵を取得された回数を返す
+pub async fn jwks_server_with(cache_control: &'static str) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    let app = Router::new().route(
+        "/certs",
+        get(move || async move {
+            counter.fetch_add(1, Ordering::SeqCst);
+            ([(CACHE_CONTROL, cache_control)], TEST_JWKS)
+        }),
+    );
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{}/certs", addr), hits)
+}
+
+/// 認可コードの交換（`/token`）とユーザー情報（`/userinfo`）を返すエンドポイントを起動し、
+/// `(トークンURL, ユーザー情報URL)`を返す（どの認可コードでも`sub`のユーザーとしてログインする）
+pub async fn oauth_server(sub: &str, email: &str, name: &str) -> (String, String) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let user_info = json!({ "id": sub, "email": email, "name": name, "verified_email": true });
+    let token = json!({ "access_token": "test-access-token", "token_type": "Bearer", "expires_in": 3600 });
+    let app = Router::new()
+        .route("/token", post(move || async move { Json(token) }))
+        .route("/userinfo", get(move || async move { Json(user_info) }));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{}/token", addr), format!("http://{}/userinfo", addr))
+}
+
+/// Googleが確認済みのメールアドレスとして署名したID Token
+pub fn id_token(sub: &str, email: &str, name: &str) -> String {
+    id_token_at(sub, email, name, Utc::now())
+}
+
+/// `issued_at`に発行した`id_token`（`MockClock`で時刻を進めるテスト用。有効期限は1時間後）
+pub fn id_token_at(sub: &str, email: &str, name: &str, issued_at: DateTime<Utc>) -> String {
+    let now = issued_at.timestamp();
+    let claims = json!({
+        "iss": "https://accounts.google.com",
+        "aud": CLIENT_ID,
+        "sub": sub,
+        "email": email,
+        "email_verified": true,
+        "name": name,
+        "iat": now,
+        "exp": now + 3600,
+    });
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(TEST_KEY_ID.to_string());
+    let key = EncodingKey::from_rsa_pem(TEST_KEY_PEM).expect("test key should be a valid RSA PEM");
+    encode(&header, &claims, &key).unwrap()
+}
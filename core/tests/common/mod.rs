@@ -0,0 +1,169 @@
+//! 統合テストの共通処理（各テストファイルから`mod common;`で使う）
+#![allow(dead_code)]
+
+pub mod fixtures;
+pub mod google;
+pub mod snapshot;
+pub mod spans;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header::AUTHORIZATION, HeaderMap, Method, Request, StatusCode},
+    Router,
+};
+use patchouli::{
+    build_state_with,
+    clock::{SharedClock, SystemClock},
+    config::Config,
+    database::RegisteredUser,
+    ids::{RandomIds, SharedIdGenerator},
+    AppState, UserSession,
+};
+use serde_json::Value;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+/// インメモリのSQLiteで状態を作る（`database_url`以外は`config`のまま）
+pub async fn state(config: Config) -> AppState {
+    state_with_clock(config, Arc::new(SystemClock)).await
+}
+
+/// `state`と同じだが、現在時刻を`clock`から取る（`MockClock`で時刻を進めるテスト用）
+pub async fn state_with_clock(config: Config, clock: SharedClock) -> AppState {
+    state_with(config, clock, Arc::new(RandomIds)).await
+}
+
+/// `state_with_clock`と同じだが、IDを`ids`で採番する（`SequentialIds`でレスポンスを固定するテスト用）
+pub async fn state_with(config: Config, clock: SharedClock, ids: SharedIdGenerator) -> AppState {
+    let config = Config {
+        database_url: "sqlite::memory:".to_string(),
+        ..config
+    };
+    build_state_with(config, clock, ids).await.expect("state should build with an in-memory database")
+}
+
+/// `user`としてログインしたセッションを追加する
+pub async fn add_session(state: &AppState, session_id: &str, user: &RegisteredUser) {
+    add_session_for(state, session_id, &user.google_id, &user.email).await;
+}
+
+/// 登録されていないユーザーも含め、任意のメールアドレスのセッションを追加する
+pub async fn add_session_for(state: &AppState, session_id: &str, google_id: &str, email: &str) {
+    state.sessions.write().await.insert(
+        session_id.to_string(),
+        UserSession {
+            user_id: google_id.to_string(),
+            email: email.to_string(),
+        },
+    );
+}
+
+/// `user`のセッションを発行してセッションIDを返す（ログイン済みの状態を作る）
+pub async fn login_as(state: &AppState, user: &RegisteredUser) -> String {
+    let session_id = format!("session-{}", user.id);
+    add_session(state, &session_id, user).await;
+    session_id
+}
+
+/// ルーターに直接リクエストを送るクライアント（セッションは`Authorization: Bearer`で付ける）
+#[derive(Clone)]
+pub struct TestClient {
+    app: Router,
+    session_id: Option<String>,
+    headers: Vec<(String, String)>,
+}
+
+impl TestClient {
+    pub fn new(app: Router) -> Self {
+        TestClient {
+            app,
+            session_id: None,
+            headers: Vec::new(),
+        }
+    }
+
+    /// 同じルーターに`session_id`でログインしたクライアント
+    pub fn with_session(&self, session_id: &str) -> Self {
+        TestClient {
+            session_id: Some(session_id.to_string()),
+            ..self.clone()
+        }
+    }
+
+    /// すべてのリクエストに`name: value`ヘッダーを付けるクライアント（`X-Api-Key`等）
+    pub fn with_header(&self, name: &str, value: &str) -> Self {
+        let mut client = self.clone();
+        client.headers.push((name.to_string(), value.to_string()));
+        client
+    }
+
+    pub async fn request(&self, method: Method, uri: &str, body: Option<&Value>) -> TestResponse {
+        let mut builder = Request::builder().method(method).uri(uri);
+        if let Some(session_id) = &self.session_id {
+            builder = builder.header(AUTHORIZATION, format!("Bearer {}", session_id));
+        }
+        for (name, value) in &self.headers {
+            builder = builder.header(name, value);
+        }
+        let request = match body {
+            Some(body) => builder
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string())),
+            None => builder.body(Body::empty()),
+        };
+        send(&self.app, request.unwrap()).await
+    }
+
+    pub async fn get(&self, uri: &str) -> TestResponse {
+        self.request(Method::GET, uri, None).await
+    }
+
+    pub async fn post(&self, uri: &str, body: &Value) -> TestResponse {
+        self.request(Method::POST, uri, Some(body)).await
+    }
+
+    pub async fn patch(&self, uri: &str, body: &Value) -> TestResponse {
+        self.request(Method::PATCH, uri, Some(body)).await
+    }
+
+    pub async fn delete(&self, uri: &str) -> TestResponse {
+        self.request(Method::DELETE, uri, None).await
+    }
+}
+
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl TestResponse {
+    /// ステータスが`expected`であることを確認してJSONを返す（失敗時はボディを表示する）
+    pub fn expect<T: serde::de::DeserializeOwned>(&self, expected: StatusCode) -> T {
+        assert_eq!(self.status, expected, "{}", String::from_utf8_lossy(&self.body));
+        self.json()
+    }
+
+    /// エラーレスポンスの`error`
+    pub fn error_code(&self) -> patchouli::error::ErrorCode {
+        self.json::<patchouli::error::ErrorResponse>().error
+    }
+
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> T {
+        serde_json::from_slice(&self.body).unwrap_or_else(|e| {
+            panic!("response body should be JSON ({}): {}", e, String::from_utf8_lossy(&self.body))
+        })
+    }
+}
+
+pub async fn send(app: &Router, request: Request<Body>) -> TestResponse {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec();
+    TestResponse { status, headers, body }
+}
+
+pub async fn get(app: &Router, uri: &str) -> TestResponse {
+    send(app, Request::get(uri).body(Body::empty()).unwrap()).await
+}
@@ -0,0 +1,42 @@
+//! レスポンスのスナップショット（`tests/snapshots/<名前>.json`に保存したステータス・ボディと比較する）
+//!
+//! 差分があれば失敗する。意図した変更であれば`UPDATE_SNAPSHOTS=1 cargo test`で書き換え、
+//! 差分をレビューしてからコミットする。
+
+use super::TestResponse;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+fn snapshot_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots").join(format!("{}.json", name))
+}
+
+/// 保存する形式（JSONでないボディ（CSV等）は文字列として保存する）
+fn render(response: &TestResponse) -> String {
+    let body = serde_json::from_slice::<Value>(&response.body)
+        .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&response.body).into_owned()));
+    let snapshot = json!({ "status": response.status.as_u16(), "body": body });
+    format!("{}\n", serde_json::to_string_pretty(&snapshot).unwrap())
+}
+
+pub fn assert_snapshot(name: &str, response: &TestResponse) {
+    let path = snapshot_path(name);
+    let actual = render(response);
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!("snapshot {} does not exist (run with UPDATE_SNAPSHOTS=1 to create it):\n{}", path.display(), actual)
+    });
+    assert!(
+        expected == actual,
+        "response does not match snapshot {} (run with UPDATE_SNAPSHOTS=1 if the change is intended)\n\
+         --- expected\n{}\n+++ actual\n{}",
+        path.display(),
+        expected,
+        actual
+    );
+}
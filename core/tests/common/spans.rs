@@ -0,0 +1,96 @@
+//! テスト中に作られたスパンをフィールドごと記録する（`tracing-test`の代わり）
+//!
+//! `capture()`が返すガードの間、同じスレッドの`tracing`を記録する。`#[tokio::test]`は既定で
+//! 単一スレッドのランタイムのため、ルーターの処理や`tokio::spawn`したタスクのスパンも記録される。
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    subscriber::DefaultGuard,
+    Subscriber,
+};
+use tracing_subscriber::{layer::Context, prelude::*, registry::LookupSpan, Layer};
+
+/// 記録したスパン（フィールドの値は`Debug`・`Display`で文字列にしたもの）
+#[derive(Debug, Clone)]
+pub struct CapturedSpan {
+    pub name: String,
+    pub fields: HashMap<String, String>,
+    /// 親のスパンの名前
+    pub parent: Option<String>,
+}
+
+impl CapturedSpan {
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields.get(name).map(String::as_str)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Spans(Arc<Mutex<Vec<CapturedSpan>>>);
+
+impl Spans {
+    /// `name`のスパン（作られた順）
+    pub fn named(&self, name: &str) -> Vec<CapturedSpan> {
+        self.0.lock().unwrap().iter().filter(|span| span.name == name).cloned().collect()
+    }
+}
+
+/// 記録を開始する（ガードをdropすると終わる）
+pub fn capture() -> (Spans, DefaultGuard) {
+    let spans = Spans::default();
+    let subscriber = tracing_subscriber::registry().with(CaptureLayer(spans.clone()));
+    (spans, tracing::subscriber::set_default(subscriber))
+}
+
+/// `Spans`の何番目に記録したか（スパンのextensionsに入れる）
+struct Index(usize);
+
+struct CaptureLayer(Spans);
+
+impl<S> Layer<S> for CaptureLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let span = ctx.span(id).expect("new spans are registered");
+        let mut fields = FieldMap(HashMap::new());
+        attrs.record(&mut fields);
+        let mut spans = self.0 .0.lock().unwrap();
+        spans.push(CapturedSpan {
+            name: span.name().to_string(),
+            fields: fields.0,
+            parent: span.parent().map(|parent| parent.name().to_string()),
+        });
+        span.extensions_mut().insert(Index(spans.len() - 1));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let Some(Index(index)) = span.extensions().get::<Index>().map(|index| Index(index.0)) else {
+            return;
+        };
+        let mut fields = FieldMap(HashMap::new());
+        values.record(&mut fields);
+        self.0 .0.lock().unwrap()[index].fields.extend(fields.0);
+    }
+}
+
+struct FieldMap(HashMap<String, String>);
+
+impl Visit for FieldMap {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
@@ -0,0 +1,97 @@
+//! レスポンスの圧縮（`Accept-Encoding`のネゴシエーション）とリクエストボディの上限（JSONの413）
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{
+        header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, VARY},
+        Request, StatusCode,
+    },
+};
+use common::{fixtures::UserFixture, login_as, TestClient};
+use flate2::read::GzDecoder;
+use patchouli::{
+    build_router,
+    config::Config,
+    error::{ErrorCode, ErrorResponse},
+    UsersListResponse,
+};
+use serde_json::Value;
+use std::{convert::Infallible, io::Read};
+
+#[tokio::test]
+async fn large_responses_are_compressed_when_accepted() {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    for n in 0..20 {
+        UserFixture::new(&format!("User{}", n)).invited_by(&root).insert(&state.database).await;
+    }
+    let client = TestClient::new(build_router(state.clone())).with_session(&login_as(&state, &root).await);
+
+    let plain = client.get("/v1/admin/users").await;
+    assert_eq!(plain.status, StatusCode::OK);
+    assert!(!plain.headers.contains_key(CONTENT_ENCODING));
+    assert!(plain.body.len() > 1024, "the list should be above the compression threshold");
+
+    let gzip = client.with_header("accept-encoding", "gzip").get("/v1/admin/users").await;
+    assert_eq!(gzip.status, StatusCode::OK);
+    assert_eq!(gzip.headers[CONTENT_ENCODING], "gzip");
+    assert!(gzip.headers.get_all(VARY).iter().any(|value| value == "accept-encoding"));
+    assert!(gzip.body.len() < plain.body.len());
+    let mut decompressed = Vec::new();
+    GzDecoder::new(gzip.body.as_slice()).read_to_end(&mut decompressed).unwrap();
+    assert_eq!(decompressed, plain.body);
+    serde_json::from_slice::<UsersListResponse>(&decompressed).unwrap();
+
+    // 対応していない方式だけなら圧縮しない。qが高い方式を選ぶ
+    let identity = client.with_header("accept-encoding", "compress").get("/v1/admin/users").await;
+    assert!(!identity.headers.contains_key(CONTENT_ENCODING));
+    let brotli = client.with_header("accept-encoding", "gzip;q=0.5, br").get("/v1/admin/users").await;
+    assert_eq!(brotli.headers[CONTENT_ENCODING], "br");
+}
+
+#[tokio::test]
+async fn small_responses_are_not_compressed() {
+    let state = common::state(Config::default()).await;
+    let client = TestClient::new(build_router(state)).with_header("accept-encoding", "gzip");
+
+    let response = client.get("/healthz").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(!response.headers.contains_key(CONTENT_ENCODING));
+    serde_json::from_slice::<Value>(&response.body).unwrap();
+}
+
+#[tokio::test]
+async fn oversized_bodies_are_rejected_as_json() {
+    let state = common::state(Config {
+        request_body_limit_bytes: 1024,
+        ..Config::default()
+    })
+    .await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let session_id = login_as(&state, &root).await;
+    let app = build_router(state);
+    let uri = format!("/v1/users/{}/metadata", root.id);
+    let body = format!(r#"{{"note": "{}"}}"#, "x".repeat(2048));
+    let request = || {
+        Request::patch(&uri)
+            .header("authorization", format!("Bearer {}", session_id))
+            .header(CONTENT_TYPE, "application/json")
+            .header(ACCEPT_ENCODING, "gzip")
+    };
+
+    // Content-Lengthで上限を超えると分かる場合（`RequestBodyLimitLayer`）
+    let with_length = request().header(CONTENT_LENGTH, body.len()).body(Body::from(body.clone())).unwrap();
+    // Content-Lengthのないストリーミングのボディ（読み込み中に上限を超える）
+    let chunks: Vec<_> = body.as_bytes().chunks(256).map(|chunk| Ok::<_, Infallible>(chunk.to_vec())).collect();
+    let streamed = request().body(Body::from_stream(tokio_stream::iter(chunks))).unwrap();
+
+    for request in [with_length, streamed] {
+        let response = common::send(&app, request).await;
+        assert_eq!(response.headers[CONTENT_TYPE], "application/json");
+        let error: ErrorResponse = response.expect(StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(error.error, ErrorCode::PayloadTooLarge);
+        assert!(error.request_id.is_some());
+    }
+}
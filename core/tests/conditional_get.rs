@@ -0,0 +1,79 @@
+//! `.conditional()`のルートの`ETag`と、`If-None-Match`が一致したときの304
+
+mod common;
+
+use axum::http::{
+    header::{CACHE_CONTROL, ETAG},
+    StatusCode,
+};
+use common::{
+    fixtures::{InviteFixture, UserFixture},
+    login_as, TestClient,
+};
+use patchouli::{build_router, config::Config};
+
+#[tokio::test]
+async fn matching_if_none_match_returns_not_modified() {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let alice = UserFixture::new("Alice").can_invite().invited_by(&root).insert(&state.database).await;
+    let client = TestClient::new(build_router(state.clone())).with_session(&login_as(&state, &root).await);
+
+    let uris = [
+        "/v1/invite/list".to_string(),
+        format!("/v1/users/{}/permissions", alice.id),
+        format!("/v1/users/{}/metadata", alice.id),
+        "/v1/admin/users".to_string(),
+    ];
+    for uri in &uris {
+        let response = client.get(uri).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", uri);
+        assert_eq!(response.headers[CACHE_CONTROL], "private, no-cache", "{}", uri);
+        let etag = response.headers[ETAG].to_str().unwrap().to_string();
+        assert!(etag.starts_with("W/\""), "{}: {}", uri, etag);
+
+        // 一致すればボディなしの304（弱い比較のため`W/`の有無やカンマ区切りの候補も一致とみなす）
+        let strong = etag.trim_start_matches("W/").to_string();
+        for if_none_match in [etag.clone(), strong, format!("\"other\", {}", etag), "*".to_string()] {
+            let response = client.with_header("if-none-match", &if_none_match).get(uri).await;
+            assert_eq!(response.status, StatusCode::NOT_MODIFIED, "{}: {}", uri, if_none_match);
+            assert_eq!(response.headers[ETAG], etag.as_str());
+            assert!(response.body.is_empty());
+        }
+
+        let response = client.with_header("if-none-match", "W/\"0000000000000000\"").get(uri).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", uri);
+        assert_eq!(response.headers[ETAG], etag.as_str());
+        assert!(!response.body.is_empty());
+    }
+}
+
+#[tokio::test]
+async fn etag_changes_with_the_response() {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let client = TestClient::new(build_router(state.clone())).with_session(&login_as(&state, &root).await);
+
+    let before = client.get("/v1/invite/list").await.headers[ETAG].clone();
+    InviteFixture::new(&root).insert(&state.database).await;
+
+    let response = client.with_header("if-none-match", before.to_str().unwrap()).get("/v1/invite/list").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_ne!(response.headers[ETAG], before);
+}
+
+#[tokio::test]
+async fn other_routes_have_no_etag() {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let client = TestClient::new(build_router(state.clone())).with_session(&login_as(&state, &root).await);
+
+    let response = client.get("/v1/dashboard").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(!response.headers.contains_key(ETAG));
+
+    // エラーのレスポンスにも付けない
+    let response = client.get("/v1/users/999999/permissions").await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    assert!(!response.headers.contains_key(ETAG));
+}
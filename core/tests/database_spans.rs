@@ -0,0 +1,41 @@
+//! データベースのメソッドのスパン（引数のフィールドと、リクエストのスパンの子になること）
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{fixtures::UserFixture, login_as, spans, TestClient};
+use patchouli::{build_router, config::Config, DashboardResponse};
+
+#[tokio::test]
+async fn database_calls_create_spans_with_their_arguments() {
+    let state = common::state(Config::default()).await;
+    let (spans, _guard) = spans::capture();
+
+    let user = state.database.register_user("google-alice", "alice@example.com", "Alice", None).await.unwrap();
+    state.database.get_user_by_id(user.id).await.unwrap();
+
+    let register = spans.named("register_user");
+    assert_eq!(register.len(), 1);
+    assert_eq!(register[0].field("google_id"), Some("google-alice"));
+    assert_eq!(register[0].field("email"), Some("alice@example.com"));
+    assert_eq!(register[0].parent, None);
+
+    let get = spans.named("get_user_by_id");
+    assert_eq!(get.len(), 1);
+    assert_eq!(get[0].field("user_id"), Some(user.id.to_string().as_str()));
+}
+
+#[tokio::test]
+async fn database_spans_are_children_of_the_request_span() {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let client = TestClient::new(build_router(state.clone())).with_session(&login_as(&state, &root).await);
+    let (spans, _guard) = spans::capture();
+
+    client.get("/v1/dashboard").await.expect::<DashboardResponse>(StatusCode::OK);
+
+    let summary = spans.named("get_invite_summary_by_user");
+    assert_eq!(summary.len(), 1);
+    assert_eq!(summary[0].field("user_id"), Some(root.id.to_string().as_str()));
+    assert_eq!(summary[0].parent.as_deref(), Some("request"));
+}
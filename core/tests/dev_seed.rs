@@ -0,0 +1,76 @@
+//! 負荷試験用のシード（`POST /v1/dev/seed`、`dev-tools`フィーチャーでビルドした場合のみ）
+#![cfg(feature = "dev-tools")]
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{fixtures::UserFixture, login_as, TestClient};
+use patchouli::{build_router, config::Config, error::ErrorCode, AppState};
+use serde_json::{json, Value};
+
+async fn setup(dev_seed_enabled: bool) -> (AppState, TestClient) {
+    let state = common::state(Config {
+        dev_seed_enabled,
+        ..Config::default()
+    })
+    .await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let session = login_as(&state, &root).await;
+    let client = TestClient::new(build_router(state.clone())).with_session(&session);
+    (state, client)
+}
+
+#[tokio::test]
+async fn seeds_users_and_invites() {
+    let (state, client) = setup(true).await;
+
+    let body = json!({ "users": 50, "invites": 200 });
+    let response: Value = client.post("/v1/dev/seed", &body).await.expect(StatusCode::OK);
+    assert_eq!(response["users"], 50);
+    assert_eq!(response["invites"], 200);
+    assert!(response["rows_per_second"].as_f64().unwrap() > 0.0);
+    assert_eq!(state.database.count_registered_users().await.unwrap(), 51);
+    assert_eq!(state.database.count_users_outside_domain("seed.invalid").await.unwrap(), 1);
+
+    // 繰り返し実行しても重複しない
+    let response: Value = client.post("/v1/dev/seed", &json!({ "users": 50 })).await.expect(StatusCode::OK);
+    assert_eq!((response["users"].as_u64(), response["invites"].as_u64()), (Some(50), Some(0)));
+    assert_eq!(state.database.count_registered_users().await.unwrap(), 101);
+}
+
+#[tokio::test]
+async fn refuses_databases_with_real_users() {
+    let (state, client) = setup(true).await;
+    let root = state.database.get_user_by_email("root@example.com").await.unwrap().unwrap();
+    for index in 0..5 {
+        UserFixture::new(&format!("User{}", index)).invited_by(&root).insert(&state.database).await;
+    }
+
+    let response = client.post("/v1/dev/seed", &json!({ "users": 10 })).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.error_code(), ErrorCode::ValidationFailed);
+    assert_eq!(state.database.count_registered_users().await.unwrap(), 6);
+}
+
+#[tokio::test]
+async fn rejects_invalid_counts_and_non_root_users() {
+    let (state, client) = setup(true).await;
+
+    for body in [json!({}), json!({ "users": 0, "invites": 0 }), json!({ "users": 200_001 })] {
+        let response = client.post("/v1/dev/seed", &body).await;
+        assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY, "{}", body);
+    }
+
+    let root = state.database.get_user_by_email("root@example.com").await.unwrap().unwrap();
+    let alice = UserFixture::new("Alice").invited_by(&root).can_invite().insert(&state.database).await;
+    let alice_client = client.with_session(&login_as(&state, &alice).await);
+    let response = alice_client.post("/v1/dev/seed", &json!({ "users": 1 })).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn route_is_absent_unless_enabled() {
+    let (_state, client) = setup(false).await;
+    let response = client.post("/v1/dev/seed", &json!({ "users": 1 })).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,97 @@
+//! Sentryに送るイベント（`TestTransport`で記録し、リクエストID・ルート・ハッシュ化したユーザーIDを確かめる）
+//!
+//! Sentryのクライアントはプロセスに1つのため、このファイルのテストは1つだけにする。
+
+mod common;
+
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use common::{fixtures::UserFixture, login_as, TestClient};
+use patchouli::{
+    build_router,
+    clock::Clock,
+    config::Config,
+    error::{ErrorCode, ErrorResponse},
+    error_reporting, DashboardResponse,
+};
+use sentry::{protocol::Event, test::TestTransport};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use tracing_subscriber::prelude::*;
+
+/// `armed`の間は現在時刻を求められるとpanicする時計（ハンドラーの中でpanicさせる）
+#[derive(Default)]
+struct PanickingClock {
+    armed: AtomicBool,
+}
+
+impl Clock for PanickingClock {
+    fn now(&self) -> DateTime<Utc> {
+        assert!(!self.armed.load(Ordering::SeqCst), "clock exploded");
+        Utc::now()
+    }
+}
+
+fn tag<'a>(event: &'a Event<'_>, name: &str) -> Option<&'a str> {
+    event.tags.get(name).map(String::as_str)
+}
+
+fn user_id<'a>(event: &'a Event<'_>) -> Option<&'a str> {
+    event.user.as_ref()?.id.as_deref()
+}
+
+#[tokio::test]
+async fn internal_errors_and_panics_are_reported_with_request_context() {
+    let config = Config {
+        sentry_dsn: Some("https://public@sentry.invalid/1".to_string()),
+        ..Config::default()
+    };
+    let transport = TestTransport::new();
+    let mut options = error_reporting::client_options(&config).unwrap();
+    options.transport = Some(Arc::new(transport.clone()));
+    let _sentry = sentry::init(options);
+    let subscriber = tracing_subscriber::registry().with(error_reporting::tracing_layer());
+    let _tracing = tracing::subscriber::set_default(subscriber);
+
+    let clock = Arc::new(PanickingClock::default());
+    let state = common::state_with_clock(config, clock.clone()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let client = TestClient::new(build_router(state.clone())).with_session(&login_as(&state, &root).await);
+
+    // 正常なリクエストは何も送らない
+    client.get("/v1/dashboard").await.expect::<DashboardResponse>(StatusCode::OK);
+    assert!(transport.fetch_and_clear_events().is_empty());
+
+    // AppError::Internal（ユーザーはキャッシュ済みのため、データベースを閉じてもハンドラーまで届く）
+    state.database.close().await;
+    let response = client.with_header("x-request-id", "internal-1").get("/v1/dashboard").await;
+    let error: ErrorResponse = response.expect(StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(error.error, ErrorCode::InternalError);
+    let events = transport.fetch_and_clear_events();
+    assert_eq!(events.len(), 1, "{:?}", events);
+    let event = &events[0];
+    assert_eq!(tag(event, "request_id"), Some("internal-1"));
+    assert_eq!(tag(event, "route"), Some("/v1/dashboard"));
+    assert_eq!(tag(event, "method"), Some("GET"));
+    assert_eq!(event.release.as_deref(), Some(concat!("patchouli@", env!("CARGO_PKG_VERSION"))));
+    // ユーザーIDは連番のまま送らない
+    let hashed = user_id(event).expect("user should be attached").to_string();
+    assert_eq!(hashed.len(), 16);
+    assert_ne!(hashed, root.id.to_string());
+
+    // panic（パニックフックが1件だけ送り、同じリクエストの情報が付く）
+    clock.armed.store(true, Ordering::SeqCst);
+    let response = client.with_header("x-request-id", "panic-1").get("/v1/invite/create").await;
+    assert_eq!(response.status, StatusCode::INTERNAL_SERVER_ERROR);
+    let events = transport.fetch_and_clear_events();
+    assert_eq!(events.len(), 1, "{:?}", events);
+    let event = &events[0];
+    let exception = &event.exception.values[0];
+    assert_eq!(exception.ty, "panic");
+    assert_eq!(exception.value.as_deref(), Some("clock exploded"));
+    assert_eq!(tag(event, "request_id"), Some("panic-1"));
+    assert_eq!(tag(event, "route"), Some("/v1/invite/create"));
+    assert_eq!(user_id(event), Some(hashed.as_str()));
+}
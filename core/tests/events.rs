@@ -0,0 +1,178 @@
+//! イベントストリーム（`/v1/events`）の配信と、認証できなくなった購読者の切断
+
+mod common;
+
+use axum::{
+    body::{Body, BodyDataStream},
+    http::{header::AUTHORIZATION, Request, StatusCode},
+    Router,
+};
+use common::{
+    fixtures::{InviteFixture, UserFixture},
+    login_as, TestClient,
+};
+use patchouli::{build_router, config::Config, database::RegisteredUser, AppState};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio_stream::StreamExt;
+use tower::ServiceExt;
+
+/// 購読者のユーザーを確認し直す間隔（ハートビートと同じ）より十分に長い待ち時間
+const WAIT: Duration = Duration::from_secs(5);
+
+async fn setup() -> (AppState, RegisteredUser, RegisteredUser, Router) {
+    let state = common::state(Config {
+        sse_heartbeat_secs: 1,
+        ..Config::default()
+    })
+    .await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let alice = UserFixture::new("Alice").can_invite().invited_by(&root).insert(&state.database).await;
+    let app = build_router(state.clone());
+    (state, root, alice, app)
+}
+
+async fn subscribe(app: &Router, session_id: &str) -> BodyDataStream {
+    let request = Request::get("/v1/events")
+        .header(AUTHORIZATION, format!("Bearer {}", session_id))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    response.into_body().into_data_stream()
+}
+
+/// 次の`event:`付きのイベント（ハートビートのコメント行は読み飛ばす）。ストリームが閉じられたら`None`
+async fn next_event(stream: &mut BodyDataStream) -> Option<String> {
+    tokio::time::timeout(WAIT, async {
+        while let Some(chunk) = stream.next().await {
+            let chunk = String::from_utf8(chunk.unwrap().to_vec()).unwrap();
+            if chunk.contains("event:") {
+                return Some(chunk);
+            }
+        }
+        None
+    })
+    .await
+    .expect("event stream should yield an event or close")
+}
+
+/// `event:`付きのイベントが届かないこと（ハートビートは読み飛ばす）
+async fn assert_no_event(stream: &mut BodyDataStream) {
+    let nothing = tokio::time::timeout(Duration::from_millis(1500), async {
+        loop {
+            let chunk = stream.next().await.unwrap().unwrap();
+            if chunk.windows(6).any(|window| window == b"event:") {
+                return String::from_utf8(chunk.to_vec()).unwrap();
+            }
+        }
+    })
+    .await;
+    assert!(nothing.is_err(), "unexpected event: {:?}", nothing);
+}
+
+/// イベントの`data:`行のJSON
+fn event_data(event: &str) -> Value {
+    let data = event.lines().find_map(|line| line.strip_prefix("data:")).expect("event should have data");
+    serde_json::from_str(data.trim()).unwrap()
+}
+
+#[tokio::test]
+async fn subscribers_receive_only_visible_events() {
+    let (state, root, alice, app) = setup().await;
+    let bob = UserFixture::new("Bob").invited_by(&root).insert(&state.database).await;
+    let invite = InviteFixture::new(&alice).insert(&state.database).await;
+    let mut root_events = subscribe(&app, &login_as(&state, &root).await).await;
+    let mut alice_events = subscribe(&app, &login_as(&state, &alice).await).await;
+    let mut bob_events = subscribe(&app, &login_as(&state, &bob).await).await;
+
+    let uri = format!("/v1/invite/{}/resend-notification", invite.id);
+    let root_client = TestClient::new(app.clone()).with_session(&login_as(&state, &root).await);
+    root_client.post(&uri, &json!({})).await.expect::<Value>(StatusCode::OK);
+
+    for stream in [&mut root_events, &mut alice_events] {
+        let event = next_event(stream).await.unwrap();
+        assert!(event.contains("event: invite.resent"), "{}", event);
+    }
+    // 作成者でもrootでもないユーザーには届かない
+    assert_no_event(&mut bob_events).await;
+}
+
+#[tokio::test]
+async fn user_updates_reach_root_users_and_the_affected_user() {
+    let (state, root, alice, app) = setup().await;
+    let bob = UserFixture::new("Bob").invited_by(&root).insert(&state.database).await;
+    let mut root_events = subscribe(&app, &login_as(&state, &root).await).await;
+    let mut alice_events = subscribe(&app, &login_as(&state, &alice).await).await;
+    let mut bob_events = subscribe(&app, &login_as(&state, &bob).await).await;
+    let root_client = TestClient::new(app.clone()).with_session(&login_as(&state, &root).await);
+    let alice_client = TestClient::new(app.clone()).with_session(&login_as(&state, &alice).await);
+
+    let expect_update = |event: Option<String>, user_id: i64, change: &str, updated_by: i64| {
+        let event = event.expect("stream should stay open");
+        assert!(event.contains("event: user.updated"), "{}", event);
+        let data = event_data(&event);
+        assert_eq!((data["user_id"].as_i64(), data["change"].as_str()), (Some(user_id), Some(change)), "{}", data);
+        assert_eq!(data["updated_by"].as_i64(), Some(updated_by));
+    };
+
+    let uri = format!("/v1/users/{}/can-invite", alice.id);
+    root_client.patch(&uri, &json!({ "can_invite": false })).await.expect::<Value>(StatusCode::OK);
+    for stream in [&mut root_events, &mut alice_events] {
+        expect_update(next_event(stream).await, alice.id, "can_invite", root.id);
+    }
+    assert_no_event(&mut bob_events).await;
+
+    let uri = format!("/v1/users/{}/metadata", alice.id);
+    alice_client.patch(&uri, &json!({ "theme": "dark" })).await.expect::<Value>(StatusCode::OK);
+    for stream in [&mut root_events, &mut alice_events] {
+        expect_update(next_event(stream).await, alice.id, "metadata", alice.id);
+    }
+
+    let uri = format!("/v1/users/{}/promote", bob.id);
+    root_client.post(&uri, &json!({ "confirm_action": "PROMOTE_TO_ROOT" })).await.expect::<Value>(StatusCode::OK);
+    for stream in [&mut root_events, &mut bob_events] {
+        expect_update(next_event(stream).await, bob.id, "promoted", root.id);
+    }
+    assert_no_event(&mut alice_events).await;
+
+    // bobはrootユーザーになったため、他のユーザーの変更も届く。利用停止された本人のストリームはイベントを送る前に閉じる
+    let uri = format!("/v1/admin/users/{}/ban", alice.id);
+    root_client.post(&uri, &json!({})).await.expect::<Value>(StatusCode::OK);
+    for stream in [&mut root_events, &mut bob_events] {
+        expect_update(next_event(stream).await, alice.id, "banned", root.id);
+    }
+    assert_eq!(next_event(&mut alice_events).await, None);
+
+    let uri = format!("/v1/admin/users/{}/unban", alice.id);
+    root_client.post(&uri, &json!({})).await.expect::<Value>(StatusCode::OK);
+    for stream in [&mut root_events, &mut bob_events] {
+        expect_update(next_event(stream).await, alice.id, "unbanned", root.id);
+    }
+}
+
+#[tokio::test]
+async fn banned_subscribers_are_disconnected() {
+    let (state, root, alice, app) = setup().await;
+    let mut alice_events = subscribe(&app, &login_as(&state, &alice).await).await;
+
+    let root_client = TestClient::new(app.clone()).with_session(&login_as(&state, &root).await);
+    let uri = format!("/v1/admin/users/{}/ban", alice.id);
+    root_client.post(&uri, &json!({})).await.expect::<Value>(StatusCode::OK);
+
+    assert_eq!(next_event(&mut alice_events).await, None);
+    // 切断すると同時接続数も戻る
+    assert!(state.event_connections.try_acquire(alice.id).is_some());
+}
+
+#[tokio::test]
+async fn logging_out_closes_the_stream() {
+    let (state, root, _, app) = setup().await;
+    let session_id = login_as(&state, &root).await;
+    let mut root_events = subscribe(&app, &session_id).await;
+
+    let response = common::get(&app, &format!("/logout?session_id={}", session_id)).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    assert_eq!(next_event(&mut root_events).await, None);
+}
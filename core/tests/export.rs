@@ -0,0 +1,269 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        Request, StatusCode,
+    },
+    Router,
+};
+use common::{
+    add_session,
+    fixtures::{InviteFixture, UserFixture},
+};
+use chrono::Duration;
+use patchouli::{
+    build_router,
+    clock::{Clock, MockClock, SharedClock, SystemClock},
+    config::Config,
+    database::AuditImportCounts,
+    AppState,
+};
+use serde_json::json;
+use std::sync::Arc;
+
+const ROOT_SESSION: &str = "root-session";
+const USER_SESSION: &str = "user-session";
+
+/// rootユーザーと、CSVでエスケープが必要な名前の一般ユーザーを用意する
+async fn setup(config: Config) -> (AppState, Router) {
+    setup_with_clock(config, Arc::new(SystemClock)).await
+}
+
+async fn setup_with_clock(config: Config, clock: SharedClock) -> (AppState, Router) {
+    let state = common::state_with_clock(config, clock).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let user = UserFixture::new("User")
+        .display_name("Doe, \"Jane\"")
+        .invited_by(&root)
+        .insert(&state.database)
+        .await;
+    add_session(&state, ROOT_SESSION, &root).await;
+    add_session(&state, USER_SESSION, &user).await;
+    (state.clone(), build_router(state))
+}
+
+fn parse_csv(body: &[u8]) -> (csv::StringRecord, Vec<csv::StringRecord>) {
+    let mut reader = csv::Reader::from_reader(body);
+    let header = reader.headers().unwrap().clone();
+    let rows = reader.records().collect::<Result<_, _>>().unwrap();
+    (header, rows)
+}
+
+#[tokio::test]
+async fn users_csv_is_a_downloadable_rfc4180_file() {
+    let (_, app) = setup(Config::default()).await;
+
+    let response = common::get(&app, &format!("/v1/admin/export/users.csv?session_id={}", ROOT_SESSION)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers[CONTENT_TYPE], "text/csv; charset=utf-8");
+    assert_eq!(response.headers[CONTENT_DISPOSITION], "attachment; filename=\"users.csv\"");
+
+    let (header, rows) = parse_csv(&response.body);
+    assert_eq!(
+        header.iter().collect::<Vec<_>>(),
+        ["id", "email", "name", "is_root", "can_invite", "created_at", "last_login"]
+    );
+    assert_eq!(rows.len(), 2);
+    // IDの降順で、カンマ・引用符を含む名前もそのまま読み戻せる
+    assert_eq!(&rows[0][1], "user@example.com");
+    assert_eq!(&rows[0][2], "Doe, \"Jane\"");
+    assert_eq!(&rows[1][3], "true");
+    assert!(rows[1][5].ends_with('Z'), "{}", &rows[1][5]);
+}
+
+#[tokio::test]
+async fn users_csv_is_root_only() {
+    let (_, app) = setup(Config::default()).await;
+
+    let response = common::get(&app, &format!("/v1/admin/export/users.csv?session_id={}", USER_SESSION)).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+}
+
+/// rootユーザーが作成した招待コードを2件（1件は一般ユーザーが使用済み・メモ付き）用意する
+async fn add_invites(state: &AppState) -> String {
+    let root = state.database.get_user_by_email("root@example.com").await.unwrap().unwrap();
+    let user = state.database.get_user_by_email("user@example.com").await.unwrap().unwrap();
+    InviteFixture::new(&root).note("team, \"alpha\"").used_by(&user).insert(&state.database).await;
+    InviteFixture::new(&root).insert(&state.database).await.code
+}
+
+#[tokio::test]
+async fn invites_csv_joins_user_emails() {
+    let (state, app) = setup(Config::default()).await;
+    let pending_code = add_invites(&state).await;
+
+    let response = common::get(&app, &format!("/v1/admin/export/invites.csv?session_id={}", ROOT_SESSION)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers[CONTENT_DISPOSITION], "attachment; filename=\"invites.csv\"");
+
+    let (header, rows) = parse_csv(&response.body);
+    assert_eq!(
+        header.iter().collect::<Vec<_>>(),
+        [
+            "id",
+            "code",
+            "created_by_email",
+            "created_at",
+            "expires_at",
+            "used_by_email",
+            "used_at",
+            "is_active",
+            "note"
+        ]
+    );
+    assert_eq!(rows.len(), 2);
+
+    // 未使用の招待コードはexpires_at・used_by_email・used_at・noteが空
+    let pending = &rows[0];
+    assert_eq!(&pending[1], pending_code);
+    assert_eq!(&pending[2], "root@example.com");
+    assert_eq!((&pending[4], &pending[5], &pending[6], &pending[8]), ("", "", "", ""));
+
+    let used = &rows[1];
+    assert_eq!(&used[5], "user@example.com");
+    assert!(used[6].ends_with('Z'), "{}", &used[6]);
+    assert_eq!(&used[8], "team, \"alpha\"");
+}
+
+#[tokio::test]
+async fn invites_csv_masks_codes_when_configured() {
+    let (state, app) = setup(Config {
+        export_mask_codes: true,
+        ..Config::default()
+    })
+    .await;
+    let pending_code = add_invites(&state).await;
+
+    let response = common::get(&app, &format!("/v1/admin/export/invites.csv?session_id={}", ROOT_SESSION)).await;
+    let (_, rows) = parse_csv(&response.body);
+    assert_eq!(&rows[0][1], &pending_code[..8]);
+    assert!(rows.iter().all(|row| row[1].len() == 8));
+}
+
+#[tokio::test]
+async fn audit_log_csv_filters_by_since() {
+    let clock = Arc::new(MockClock::starting_now());
+    let (state, app) = setup_with_clock(Config::default(), clock.clone()).await;
+    let root = state.database.get_user_by_email("root@example.com").await.unwrap().unwrap();
+    let user = state.database.get_user_by_email("user@example.com").await.unwrap().unwrap();
+    state.database.ban_user(root.id, user.id, 0).await.unwrap();
+    clock.advance(Duration::seconds(1));
+    let since = clock.now();
+    clock.advance(Duration::seconds(1));
+    state.database.unban_user(root.id, user.id).await.unwrap();
+
+    let uri = format!("/v1/admin/export/audit-log.csv?session_id={}", ROOT_SESSION);
+    let response = common::get(&app, &uri).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers[CONTENT_DISPOSITION], "attachment; filename=\"audit-log.csv\"");
+    let (header, rows) = parse_csv(&response.body);
+    assert_eq!(
+        header.iter().collect::<Vec<_>>(),
+        ["id", "actor_email", "action", "target_email", "metadata", "created_at"]
+    );
+    assert_eq!(rows.len(), 2);
+    // 新しい順で、実行者・対象はメールアドレスになる
+    assert_eq!(&rows[0][2], "unban_user");
+    assert_eq!((&rows[0][1], &rows[0][3]), ("root@example.com", "user@example.com"));
+
+    let since = urlencoding::encode(&since.to_rfc3339()).into_owned();
+    let response = common::get(&app, &format!("{}&since={}", uri, since)).await;
+    let (_, rows) = parse_csv(&response.body);
+    assert_eq!(rows.len(), 1);
+    assert_eq!(&rows[0][2], "unban_user");
+
+    let response = common::get(&app, &format!("{}&since=yesterday", uri)).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+}
+
+async fn import_audit_log(app: &Router, ndjson: String) -> common::TestResponse {
+    let request = Request::post(format!("/v1/admin/import/audit-log?session_id={}", ROOT_SESSION))
+        .header(CONTENT_TYPE, "application/x-ndjson")
+        .body(Body::from(ndjson))
+        .unwrap();
+    common::send(app, request).await
+}
+
+async fn export_audit_log(app: &Router) -> Vec<csv::StringRecord> {
+    let response = common::get(app, &format!("/v1/admin/export/audit-log.csv?session_id={}", ROOT_SESSION)).await;
+    parse_csv(&response.body).1
+}
+
+/// エクスポートしたCSVを、同じユーザーがいる別のインスタンスにNDJSONで取り込む
+#[tokio::test]
+async fn audit_log_round_trips_through_import() {
+    let (source, source_app) = setup(Config::default()).await;
+    let root = source.database.get_user_by_email("root@example.com").await.unwrap().unwrap();
+    let user = source.database.get_user_by_email("user@example.com").await.unwrap().unwrap();
+    source.database.ban_user(root.id, user.id, 0).await.unwrap();
+    source.database.unban_user(root.id, user.id).await.unwrap();
+    let exported = export_audit_log(&source_app).await;
+    assert_eq!(exported.len(), 2);
+
+    let (target, target_app) = setup(Config::default()).await;
+    let mut ndjson = String::new();
+    for row in exported.iter().rev() {
+        let user_id = |email: &str| match email {
+            "" => None,
+            "root@example.com" => Some(root.id),
+            _ => Some(user.id),
+        };
+        let entry = json!({
+            "actor_user_id": user_id(&row[1]),
+            "action": &row[2],
+            "target_user_id": user_id(&row[3]),
+            "metadata": serde_json::from_str::<serde_json::Value>(&row[4]).unwrap(),
+            "created_at": &row[5],
+        });
+        ndjson.push_str(&format!("{}\n", entry));
+    }
+
+    let counts: AuditImportCounts = import_audit_log(&target_app, ndjson.clone()).await.expect(StatusCode::OK);
+    assert_eq!((counts.imported, counts.skipped), (2, 0));
+    let imported = export_audit_log(&target_app).await;
+    // IDは振り直されるが、それ以外の列はすべて一致する
+    let without_id = |rows: &[csv::StringRecord]| -> Vec<Vec<String>> {
+        rows.iter().map(|row| row.iter().skip(1).map(str::to_string).collect()).collect()
+    };
+    assert_eq!(without_id(&imported), without_id(&exported));
+
+    // 同じ内容を再度取り込んでも重複しない
+    let counts: AuditImportCounts = import_audit_log(&target_app, ndjson).await.expect(StatusCode::OK);
+    assert_eq!((counts.imported, counts.skipped), (0, 2));
+    assert_eq!(target.database.get_audit_export_page(None, None, 10).await.unwrap().len(), 2);
+
+    // 取り込んだ古い記録はIDが大きくても記録日時の順に並び、ページングでも同じ順になる
+    let old_entry = json!({ "actor_user_id": root.id, "action": "legacy_import", "created_at": "2020-01-01T00:00:00Z" });
+    import_audit_log(&target_app, old_entry.to_string()).await.expect::<AuditImportCounts>(StatusCode::OK);
+    let actions: Vec<String> = export_audit_log(&target_app).await.iter().map(|row| row[2].to_string()).collect();
+    assert_eq!(actions, ["unban_user", "ban_user", "legacy_import"]);
+    let mut paged = Vec::new();
+    let mut before_id = None;
+    while let [entry] = target.database.get_audit_export_page(None, before_id, 1).await.unwrap().as_slice() {
+        paged.push(entry.action.clone());
+        before_id = Some(entry.id);
+    }
+    assert_eq!(paged, actions);
+}
+
+#[tokio::test]
+async fn audit_log_import_rejects_unknown_users_atomically() {
+    let (_, app) = setup(Config::default()).await;
+    let ndjson = [
+        json!({ "actor_user_id": 1, "action": "ban_user", "target_user_id": 2, "created_at": "2024-01-01T00:00:00Z" }),
+        json!({ "actor_user_id": 1, "action": "ban_user", "target_user_id": 99, "created_at": "2024-01-02T00:00:00Z" }),
+    ]
+    .map(|entry| entry.to_string())
+    .join("\n");
+
+    let response = import_audit_log(&app, ndjson).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let body: serde_json::Value = response.json();
+    assert!(body["details"]["target_user_id"].as_str().unwrap().starts_with("2行目"), "{}", body);
+    assert!(export_audit_log(&app).await.is_empty());
+
+    let response = import_audit_log(&app, "{not json}".to_string()).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,87 @@
+//! テスト用ビルダーが意図した状態をデータベースに作れていることの確認
+
+mod common;
+
+use chrono::Utc;
+use common::fixtures::{InviteFixture, ScenarioBuilder, UserFixture};
+use patchouli::{
+    config::Config,
+    database::{InviteCode, InviteFilterParams, UserFilterParams},
+    AppState,
+};
+
+async fn invites_created_by(state: &AppState, user_id: i64) -> Vec<InviteCode> {
+    let filter = InviteFilterParams {
+        created_by: Some(user_id),
+        ..InviteFilterParams::default()
+    };
+    state.database.get_invite_codes(&filter).await.unwrap()
+}
+
+#[tokio::test]
+async fn user_fixture_applies_flags() {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    assert!(root.is_root && root.can_invite && root.is_active);
+    assert_eq!((root.email.as_str(), root.name.as_str()), ("root@example.com", "Root"));
+
+    let alice = UserFixture::new("Alice")
+        .display_name("Alice Liddell")
+        .invited_by(&root)
+        .with_invite_code()
+        .can_invite()
+        .email_verified()
+        .insert(&state.database)
+        .await;
+    assert_eq!((alice.email.as_str(), alice.name.as_str()), ("alice@example.com", "Alice Liddell"));
+    assert_eq!(alice.invited_by, Some(root.id));
+    assert!(!alice.is_root && alice.can_invite && alice.email_verified);
+    let invites = invites_created_by(&state, root.id).await;
+    assert_eq!(invites[0].used_by, Some(alice.id));
+
+    let bob = UserFixture::new("Bob").invited_by(&alice).banned().insert(&state.database).await;
+    assert!(!bob.is_active && !bob.can_invite && !bob.email_verified);
+}
+
+#[tokio::test]
+async fn invite_fixture_applies_states() {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let alice = UserFixture::new("Alice").invited_by(&root).insert(&state.database).await;
+
+    let pending = InviteFixture::new(&root).note("for Bob").insert(&state.database).await;
+    assert!(pending.is_active && pending.used_by.is_none() && pending.expires_at.is_none());
+    assert_eq!(pending.note.as_deref(), Some("for Bob"));
+    assert!(state.database.validate_invite_code(&pending.code).await.unwrap().is_some());
+
+    let expired = InviteFixture::expired(&root).insert(&state.database).await;
+    assert!(expired.expires_at.unwrap() < Utc::now());
+    assert!(state.database.validate_invite_code(&expired.code).await.unwrap().is_none());
+
+    let used = InviteFixture::new(&root).used_by(&alice).insert(&state.database).await;
+    assert_eq!(used.used_by, Some(alice.id));
+    assert!(used.used_at.is_some());
+
+    let revoked = InviteFixture::new(&root).revoked().insert(&state.database).await;
+    assert!(!revoked.is_active);
+    assert!(state.database.validate_invite_code(&revoked.code).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn scenario_builds_a_populated_instance() {
+    let state = common::state(Config::default()).await;
+    let scenario = ScenarioBuilder::default().invitees(3).insert(&state.database).await;
+
+    assert_eq!(scenario.invitees.len(), 3);
+    assert_eq!(scenario.users().len(), 6);
+    assert_eq!(state.database.get_all_registered_users(&UserFilterParams::default()).await.unwrap().len(), 6);
+    assert!(scenario.invitees.iter().all(|user| user.invited_by == Some(scenario.inviter.id)));
+    assert!(scenario.inviter.can_invite && !scenario.banned.is_active);
+
+    // 登録に使った招待コード（3件）に加えて、未使用・期限切れ・無効化済みの3件
+    let invites = invites_created_by(&state, scenario.inviter.id).await;
+    assert_eq!(invites.len(), 6);
+    assert_eq!(invites.iter().filter(|invite| invite.used_by.is_some()).count(), 3);
+    assert!(!scenario.revoked_invite.is_active);
+    assert!(scenario.expired_invite.expires_at.unwrap() < Utc::now());
+}
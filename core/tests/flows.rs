@@ -0,0 +1,346 @@
+//! 登録からユーザー管理・招待コードのライフサイクルまでの主要なフロー
+
+mod common;
+
+use axum::http::{Method, StatusCode};
+use chrono::Duration;
+use common::{
+    fixtures::{InviteFixture, UserFixture},
+    google, login_as, TestClient,
+};
+use patchouli::{
+    build_router,
+    clock::{Clock, MockClock},
+    config::Config,
+    database::InviteCode,
+    error::ErrorCode,
+    AppState, AuthResponse, BanUserResponse, CanBeDeletedResponse, DashboardResponse, DeleteExpiredInvitesResponse,
+    DeleteUserResponse, DeletionBlockerReason, InviteCodeResponse, InviteCodesListResponse, UserInfoResponse,
+    UsersListResponse,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+/// Google One Tapのログインをテスト用のJWKsで検証する状態
+async fn one_tap_state() -> AppState {
+    common::state(Config {
+        google_client_id: google::CLIENT_ID.to_string(),
+        google_jwks_url: google::jwks_server().await,
+        ..Config::default()
+    })
+    .await
+}
+
+async fn one_tap(client: &TestClient, sub: &str, email: &str, invite_code: Option<&str>) -> common::TestResponse {
+    let body = json!({
+        "grant_type": "google_id_token",
+        "id_token": google::id_token(sub, email, "Test User"),
+        "invite_code": invite_code,
+    });
+    client.post("/v1/auth/tokens/google-one-tap", &body).await
+}
+
+#[tokio::test]
+async fn first_user_registers_as_root_without_invite() {
+    let client = TestClient::new(build_router(one_tap_state().await));
+
+    let auth: AuthResponse = one_tap(&client, "google-first", "first@example.com", None)
+        .await
+        .expect(StatusCode::OK);
+    assert_eq!(auth.user_email, "first@example.com");
+
+    let session = client.with_session(&auth.session_id);
+    let dashboard: DashboardResponse = session.get("/v1/dashboard").await.expect(StatusCode::OK);
+    assert!(dashboard.user.is_root);
+    let userinfo: Value = session.get("/v1/userinfo").await.expect(StatusCode::OK);
+    assert_eq!(userinfo["email_verified"], json!(true));
+
+    // 2人目以降は招待コードが必要
+    let response = one_tap(&client, "google-second", "second@example.com", None).await;
+    assert_eq!((response.status, response.error_code()), (StatusCode::FORBIDDEN, ErrorCode::InviteRequired));
+}
+
+#[tokio::test]
+async fn invited_user_registers_once_per_code() {
+    let state = one_tap_state().await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let root_session = login_as(&state, &root).await;
+    let client = TestClient::new(build_router(state));
+    let root_client = client.with_session(&root_session);
+
+    let invite: InviteCodeResponse = root_client.get("/v1/invite/create").await.expect(StatusCode::OK);
+    let unknown_code = "00000000-0000-4000-8000-000000000000";
+    let response = one_tap(&client, "google-guest", "guest@example.com", Some(unknown_code)).await;
+    assert_eq!((response.status, response.error_code()), (StatusCode::FORBIDDEN, ErrorCode::InvalidInvite));
+
+    let auth: AuthResponse = one_tap(&client, "google-guest", "guest@example.com", Some(&invite.invite_code))
+        .await
+        .expect(StatusCode::OK);
+    let dashboard: DashboardResponse =
+        client.with_session(&auth.session_id).get("/v1/dashboard").await.expect(StatusCode::OK);
+    assert!(!dashboard.user.is_root);
+
+    // 使用済みの招待コードでは登録できず、一覧では使用者が記録されている
+    let response = one_tap(&client, "google-other", "other@example.com", Some(&invite.invite_code)).await;
+    assert_eq!((response.status, response.error_code()), (StatusCode::FORBIDDEN, ErrorCode::InvalidInvite));
+    let list: InviteCodesListResponse = root_client.get("/v1/invite/list").await.expect(StatusCode::OK);
+    let users: UsersListResponse = root_client.get("/v1/admin/users").await.expect(StatusCode::OK);
+    let guest = users.users.iter().find(|user| user.email == "guest@example.com").unwrap();
+    assert_eq!(guest.invited_by, Some(root.id));
+    assert_eq!(list.invite_codes[0].used_by, Some(guest.id));
+}
+
+#[tokio::test]
+async fn root_user_manages_users() {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let alice = UserFixture::new("Alice").invited_by(&root).insert(&state.database).await;
+    let root_session = login_as(&state, &root).await;
+    let alice_session = login_as(&state, &alice).await;
+    let client = TestClient::new(build_router(state));
+    let root_client = client.with_session(&root_session);
+    let alice_client = client.with_session(&alice_session);
+
+    let users: UsersListResponse = root_client.get("/v1/admin/users").await.expect(StatusCode::OK);
+    assert_eq!(users.users.len(), 2);
+
+    let metadata: Value = alice_client
+        .patch(&format!("/v1/users/{}/metadata", alice.id), &json!({ "locale": "ja" }))
+        .await
+        .expect(StatusCode::OK);
+    assert_eq!(metadata, json!({ "locale": "ja" }));
+
+    // BANするとセッションが失効し、解除後は再ログインできる
+    let banned: BanUserResponse = root_client
+        .post(&format!("/v1/admin/users/{}/ban", alice.id), &json!({}))
+        .await
+        .expect(StatusCode::OK);
+    assert!(banned.banned);
+    assert_eq!(alice_client.get("/v1/dashboard").await.status, StatusCode::UNAUTHORIZED);
+    root_client
+        .post(&format!("/v1/admin/users/{}/unban", alice.id), &json!({}))
+        .await
+        .expect::<Value>(StatusCode::OK);
+
+    let check: CanBeDeletedResponse = root_client
+        .get(&format!("/v1/admin/users/{}/can-be-deleted", alice.id))
+        .await
+        .expect(StatusCode::OK);
+    assert!(check.can_delete);
+    let deleted: DeleteUserResponse =
+        root_client.delete(&format!("/v1/admin/users/{}", alice.id)).await.expect(StatusCode::OK);
+    assert!(deleted.success);
+    let users: UsersListResponse = root_client.get("/v1/admin/users").await.expect(StatusCode::OK);
+    assert_eq!(users.users.len(), 1);
+
+    // rootユーザーは自分自身を削除できない
+    let deleted: DeleteUserResponse =
+        root_client.delete(&format!("/v1/admin/users/{}", root.id)).await.expect(StatusCode::OK);
+    assert!(!deleted.success);
+}
+
+/// `can-be-deleted`の結果を`(can_delete, [(理由, 件数)])`で返す
+async fn deletion_check(client: &TestClient, user_id: i64) -> (bool, Vec<(DeletionBlockerReason, Option<usize>)>) {
+    let uri = format!("/v1/admin/users/{}/can-be-deleted", user_id);
+    let check: CanBeDeletedResponse = client.get(&uri).await.expect(StatusCode::OK);
+    (check.can_delete, check.blockers.iter().map(|blocker| (blocker.reason, blocker.count)).collect())
+}
+
+#[tokio::test]
+async fn can_be_deleted_reports_root_and_self_blockers() {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    // 招待者のいないユーザーにして、rootユーザーに`has_invitees`が付かないようにする
+    let alice = UserFixture::new("Alice").insert(&state.database).await;
+    let bob = UserFixture::new("Bob").insert(&state.database).await;
+    let app = build_router(state.clone());
+    let root_client = TestClient::new(app.clone()).with_session(&login_as(&state, &root).await);
+    let body = json!({ "confirm_action": "PROMOTE_TO_ROOT" });
+    root_client.post(&format!("/v1/users/{}/promote", bob.id), &body).await.expect::<Value>(StatusCode::OK);
+    let bob_client = TestClient::new(app).with_session(&login_as(&state, &bob).await);
+
+    use DeletionBlockerReason::{IsRoot, IsSelf};
+    // 他のrootユーザー
+    assert_eq!(deletion_check(&root_client, bob.id).await, (false, vec![(IsRoot, None)]));
+    assert_eq!(deletion_check(&bob_client, root.id).await, (false, vec![(IsRoot, None)]));
+    // 自分自身（確認できるのはrootユーザーだけなので`is_root`も付く）
+    assert_eq!(deletion_check(&root_client, root.id).await, (false, vec![(IsRoot, None), (IsSelf, None)]));
+    assert_eq!(deletion_check(&bob_client, bob.id).await, (false, vec![(IsRoot, None), (IsSelf, None)]));
+    // 招待コードも招待したユーザーもない一般ユーザー
+    assert_eq!(deletion_check(&root_client, alice.id).await, (true, vec![]));
+    assert_eq!(deletion_check(&bob_client, alice.id).await, (true, vec![]));
+}
+
+#[tokio::test]
+async fn unbanned_user_can_log_in_again() {
+    let state = one_tap_state().await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let alice = UserFixture::new("Alice").invited_by(&root).insert(&state.database).await;
+    let client = TestClient::new(build_router(state.clone()));
+    let root_client = client.with_session(&login_as(&state, &root).await);
+
+    let auth: AuthResponse = one_tap(&client, &alice.google_id, &alice.email, None).await.expect(StatusCode::OK);
+    let old_session = client.with_session(&auth.session_id);
+    old_session.get("/v1/userinfo").await.expect::<UserInfoResponse>(StatusCode::OK);
+
+    let uri = format!("/v1/admin/users/{}/ban", alice.id);
+    root_client.post(&uri, &json!({})).await.expect::<BanUserResponse>(StatusCode::OK);
+    // BANで失効したセッションは401、BAN後に残ったセッション・新しいログインは403
+    assert_eq!(old_session.get("/v1/userinfo").await.status, StatusCode::UNAUTHORIZED);
+    let other_session = client.with_session(&login_as(&state, &alice).await);
+    let response = other_session.get("/v1/userinfo").await;
+    assert_eq!((response.status, response.error_code()), (StatusCode::FORBIDDEN, ErrorCode::UserSuspended));
+    let response = one_tap(&client, &alice.google_id, &alice.email, None).await;
+    assert_eq!((response.status, response.error_code()), (StatusCode::FORBIDDEN, ErrorCode::UserSuspended));
+
+    let uri = format!("/v1/admin/users/{}/unban", alice.id);
+    root_client.post(&uri, &json!({})).await.expect::<Value>(StatusCode::OK);
+    // 利用停止中として読み込んだユーザーキャッシュは解除で無効化される
+    let info: UserInfoResponse = other_session.get("/v1/userinfo").await.expect(StatusCode::OK);
+    assert_eq!(info.email, alice.email);
+    let auth: AuthResponse = one_tap(&client, &alice.google_id, &alice.email, None).await.expect(StatusCode::OK);
+    let info: UserInfoResponse = client.with_session(&auth.session_id).get("/v1/userinfo").await.expect(StatusCode::OK);
+    assert_eq!(info.sub, alice.id.to_string());
+    // 失効したセッションは復元しない
+    assert_eq!(old_session.get("/v1/userinfo").await.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn browser_login_redirects_to_the_configured_frontend() {
+    let (token_url, userinfo_url) = google::oauth_server("google-alice", "alice@example.com", "Alice").await;
+    let state = common::state(Config {
+        frontend_url: "https://app.example.com/portal".to_string(),
+        google_token_url: token_url,
+        google_userinfo_url: userinfo_url,
+        ..Config::default()
+    })
+    .await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    UserFixture::new("Alice").invited_by(&root).insert(&state.database).await;
+    let client = TestClient::new(build_router(state));
+
+    let response = client.get("/callback?code=test-code&state=login").await;
+    assert_eq!(response.status, StatusCode::OK);
+    let html = String::from_utf8(response.body).unwrap();
+    let prefix = "https://app.example.com/portal/callback?session_id=";
+    let start = html.find(prefix).unwrap_or_else(|| panic!("redirect to the frontend_url: {}", html)) + prefix.len();
+    let (session_id, rest) = html[start..].split_once('&').unwrap();
+    assert!(rest.starts_with("user_email=alice%40example.com'"), "{}", rest);
+    let info: UserInfoResponse = client.with_session(session_id).get("/v1/userinfo").await.expect(StatusCode::OK);
+    assert_eq!(info.email, "alice@example.com");
+}
+
+#[tokio::test]
+async fn invite_lifecycle() {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let alice = UserFixture::new("Alice").invited_by(&root).insert(&state.database).await;
+    let root_session = login_as(&state, &root).await;
+    let alice_session = login_as(&state, &alice).await;
+    let client = TestClient::new(build_router(state));
+    let root_client = client.with_session(&root_session);
+    let alice_client = client.with_session(&alice_session);
+
+    // 招待されたユーザーは招待権限がない
+    let response = alice_client.get("/v1/invite/create").await;
+    assert_eq!((response.status, response.error_code()), (StatusCode::FORBIDDEN, ErrorCode::InsufficientPermission));
+
+    let created: InviteCodeResponse = root_client.get("/v1/invite/create").await.expect(StatusCode::OK);
+    let list: InviteCodesListResponse = root_client.get("/v1/invite/list").await.expect(StatusCode::OK);
+    let invite = &list.invite_codes[0];
+    assert_eq!(invite.code, created.invite_code);
+
+    let updated: InviteCode = root_client
+        .patch(&format!("/v1/invite/{}", invite.id), &json!({ "note": "for Bob", "expires_in_hours": 1 }))
+        .await
+        .expect(StatusCode::OK);
+    assert_eq!(updated.note.as_deref(), Some("for Bob"));
+    assert!(updated.expires_at.is_some());
+
+    // 複製はメモを引き継いだ新しい招待コードになる
+    let cloned: InviteCodeResponse = root_client
+        .post(&format!("/v1/invite/{}/clone", invite.id), &json!({}))
+        .await
+        .expect(StatusCode::CREATED);
+    assert_ne!(cloned.invite_code, invite.code);
+    let list: InviteCodesListResponse = root_client.get("/v1/invite/list").await.expect(StatusCode::OK);
+    assert_eq!(list.invite_codes.len(), 2);
+    assert_eq!(list.invite_codes[0].code, cloned.invite_code);
+    assert_eq!(list.invite_codes[0].note.as_deref(), Some("for Bob"));
+    // 他のユーザーの招待コードを含む一覧はrootユーザーのみ
+    let response = alice_client.get("/v1/invite/list?all=true").await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn cloning_a_used_invite_creates_a_fresh_usable_code() {
+    let clock = Arc::new(MockClock::new("2026-03-01T00:00:00Z".parse().unwrap()));
+    let state = common::state_with_clock(Config::default(), clock.clone()).await;
+    let db = &state.database;
+    let root = UserFixture::new("Root").root().insert(db).await;
+    let alice = UserFixture::new("Alice").invited_by(&root).insert(db).await;
+    let original =
+        InviteFixture::new(&root).note("for Carol").expires_in(Duration::days(2)).used_by(&alice).insert(db).await;
+    // 元の期限も過ぎた時刻に複製する
+    clock.advance(Duration::days(5));
+    let client = TestClient::new(build_router(state.clone())).with_session(&login_as(&state, &root).await);
+
+    let cloned: InviteCodeResponse = client
+        .post(&format!("/v1/invite/{}/clone", original.id), &json!({}))
+        .await
+        .expect(StatusCode::CREATED);
+    assert_ne!(cloned.invite_code, original.code);
+    // 複製は未使用で、メモと有効期間（2日）を引き継ぎ、期限は複製した時刻から数える
+    let clone = db.validate_invite_code(&cloned.invite_code).await.unwrap().expect("clone should be usable");
+    assert_eq!((clone.created_by, clone.used_by), (root.id, None));
+    assert_eq!(clone.note.as_deref(), Some("for Carol"));
+    assert_eq!(clone.created_at, clock.now());
+    assert_eq!(clone.expires_at, Some(clock.now() + Duration::days(2)));
+    // 元の招待コードは使用済みのまま
+    let original = db.get_invite_code_by_id(original.id).await.unwrap().unwrap();
+    assert_eq!(original.used_by, Some(alice.id));
+}
+
+/// rootユーザーが見られる全招待コードのID
+async fn all_invite_ids(client: &TestClient) -> Vec<i64> {
+    let list: InviteCodesListResponse = client.get("/v1/invite/list?all=true").await.expect(StatusCode::OK);
+    let mut ids: Vec<i64> = list.invite_codes.into_iter().map(|invite| invite.id).collect();
+    ids.sort();
+    ids
+}
+
+#[tokio::test]
+async fn deleting_expired_invites_keeps_used_and_unexpired_ones() {
+    let state = common::state(Config::default()).await;
+    let db = &state.database;
+    let root = UserFixture::new("Root").root().insert(db).await;
+    let alice = UserFixture::new("Alice").invited_by(&root).insert(db).await;
+    let unlimited = InviteFixture::new(&root).insert(db).await.id;
+    let valid = InviteFixture::new(&root).expires_in(Duration::days(1)).insert(db).await.id;
+    let used = InviteFixture::new(&root).used_by(&alice).insert(db).await.id;
+    // 期限切れでも使用済みなら招待履歴として残る
+    let expired_used = InviteFixture::expired(&root).used_by(&alice).insert(db).await.id;
+    InviteFixture::expired(&root).insert(db).await;
+    InviteFixture::expired(&root).note("stale").insert(db).await;
+    let client = TestClient::new(build_router(state.clone())).with_session(&login_as(&state, &root).await);
+    let before = all_invite_ids(&client).await;
+    assert_eq!(before.len(), 6);
+
+    // dry_runは件数だけを返し、何も削除しない
+    let dry_run: DeleteExpiredInvitesResponse = client
+        .request(Method::DELETE, "/v1/invite/expired?dry_run=true", None)
+        .await
+        .expect(StatusCode::OK);
+    assert_eq!((dry_run.deleted, dry_run.dry_run), (2, true));
+    assert_eq!(all_invite_ids(&client).await, before);
+
+    let deleted: DeleteExpiredInvitesResponse =
+        client.request(Method::DELETE, "/v1/invite/expired", None).await.expect(StatusCode::OK);
+    assert_eq!((deleted.deleted, deleted.dry_run), (2, false));
+    assert_eq!(all_invite_ids(&client).await, [unlimited, valid, used, expired_used]);
+
+    // 削除対象がなければ0件
+    let deleted: DeleteExpiredInvitesResponse =
+        client.request(Method::DELETE, "/v1/invite/expired", None).await.expect(StatusCode::OK);
+    assert_eq!(deleted.deleted, 0);
+}
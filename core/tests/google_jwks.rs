@@ -0,0 +1,57 @@
+//! Googleの公開鍵のキャッシュ（TTL内の検証ではJWKsを取得し直さないこと）
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{google, TestClient};
+use patchouli::{
+    build_router,
+    config::Config,
+    google_auth::{cached_google_jwks, verify_google_id_token},
+    AuthResponse,
+};
+use serde_json::json;
+use std::{sync::atomic::Ordering, time::Duration};
+use tokio::sync::RwLock;
+
+#[tokio::test]
+async fn validations_within_min_ttl_fetch_the_keys_once() {
+    // max-age=0でもmin_ttl（google_jwks_min_ttl_secs）の間は再取得しない
+    let (jwks_url, hits) = google::jwks_server_with("max-age=0").await;
+    let state = common::state(Config {
+        google_client_id: google::CLIENT_ID.to_string(),
+        google_jwks_url: jwks_url,
+        google_jwks_min_ttl_secs: 60,
+        ..Config::default()
+    })
+    .await;
+    let client = TestClient::new(build_router(state));
+
+    // 2回目は登録済みのユーザーとしてのログイン
+    for _ in 0..2 {
+        let body = json!({
+            "grant_type": "google_id_token",
+            "id_token": google::id_token("google-first", "first@example.com", "Test User"),
+        });
+        client.post("/v1/auth/tokens/google-one-tap", &body).await.expect::<AuthResponse>(StatusCode::OK);
+    }
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn keys_are_fetched_again_after_expiry() {
+    let (jwks_url, hits) = google::jwks_server_with("public, max-age=3600").await;
+    let cache = RwLock::new(None);
+    let id_token = google::id_token("google-user", "user@example.com", "Test User");
+
+    for _ in 0..2 {
+        let keys = cached_google_jwks(&cache, &jwks_url, Duration::ZERO).await.unwrap();
+        verify_google_id_token(&id_token, &keys, google::CLIENT_ID, chrono::Utc::now()).unwrap();
+    }
+    assert_eq!(hits.load(Ordering::SeqCst), 1, "max-age should keep the keys cached");
+
+    // 期限切れのキャッシュは次の検証で取得し直す
+    cache.write().await.as_mut().unwrap().expires_at = std::time::Instant::now();
+    cached_google_jwks(&cache, &jwks_url, Duration::ZERO).await.unwrap();
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
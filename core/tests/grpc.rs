@@ -0,0 +1,217 @@
+mod common;
+
+use common::{add_session, fixtures::UserFixture};
+use patchouli::{
+    config::Config,
+    grpc::{self, proto::*, ERROR_CODE_METADATA},
+};
+use tokio::{net::TcpListener, sync::oneshot, task::JoinHandle};
+use tonic::{
+    codec::ProstCodec,
+    codegen::http::uri::PathAndQuery,
+    transport::{Channel, Endpoint},
+    Code, Status,
+};
+
+const ROOT_SESSION: &str = "root-session";
+const USER_SESSION: &str = "user-session";
+
+struct TestServer {
+    channel: Channel,
+    shutdown: oneshot::Sender<()>,
+    server: JoinHandle<anyhow::Result<()>>,
+}
+
+/// rootユーザーと一般ユーザーのセッションを用意して、空いているポートでgRPCサーバーを起動する
+async fn start() -> TestServer {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let user = UserFixture::new("User").invited_by(&root).insert(&state.database).await;
+    add_session(&state, ROOT_SESSION, &root).await;
+    add_session(&state, USER_SESSION, &user).await;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (shutdown, signal) = oneshot::channel::<()>();
+    let server = tokio::spawn(grpc::serve(listener, state, async {
+        let _ = signal.await;
+    }));
+    let channel = Endpoint::from_shared(format!("http://{}", addr)).unwrap().connect().await.unwrap();
+    TestServer {
+        channel,
+        shutdown,
+        server,
+    }
+}
+
+async fn call<Req, Resp>(server: &TestServer, path: &'static str, message: Req, session: Option<&str>) -> Result<Resp, Status>
+where
+    Req: prost::Message + Send + Sync + 'static,
+    Resp: prost::Message + Default + Send + Sync + 'static,
+{
+    let mut client = tonic::client::Grpc::new(server.channel.clone());
+    client.ready().await.unwrap();
+    let mut request = tonic::Request::new(message);
+    if let Some(session) = session {
+        request
+            .metadata_mut()
+            .insert("authorization", format!("Bearer {}", session).parse().unwrap());
+    }
+    client
+        .unary(request, PathAndQuery::from_static(path), ProstCodec::default())
+        .await
+        .map(tonic::Response::into_inner)
+}
+
+fn error_code(status: &Status) -> &str {
+    status.metadata().get(ERROR_CODE_METADATA).unwrap().to_str().unwrap()
+}
+
+#[tokio::test]
+async fn root_user_manages_users_and_invites() {
+    let server = start().await;
+
+    let users: ListUsersResponse =
+        call(&server, "/patchouli.v1.UserService/ListUsers", ListUsersRequest {}, Some(ROOT_SESSION)).await.unwrap();
+    let mut emails: Vec<_> = users.users.iter().map(|user| user.email.as_str()).collect();
+    emails.sort();
+    assert_eq!(emails, ["root@example.com", "user@example.com"]);
+
+    let invite: Invite =
+        call(&server, "/patchouli.v1.InviteService/CreateInvite", CreateInviteRequest {}, Some(ROOT_SESSION))
+            .await
+            .unwrap();
+    assert!(invite.is_active);
+    assert!(invite.invite_url.contains(&invite.code));
+    assert!(invite.created_at.ends_with('Z'), "{}", invite.created_at);
+
+    let invites: ListInvitesResponse = call(
+        &server,
+        "/patchouli.v1.InviteService/ListInvites",
+        ListInvitesRequest { all: false },
+        Some(ROOT_SESSION),
+    )
+    .await
+    .unwrap();
+    assert_eq!(invites.invites, std::slice::from_ref(&invite));
+
+    let revoked: Invite = call(
+        &server,
+        "/patchouli.v1.InviteService/RevokeInvite",
+        RevokeInviteRequest { invite_id: invite.id },
+        Some(ROOT_SESSION),
+    )
+    .await
+    .unwrap();
+    assert!(!revoked.is_active);
+
+    let user = users.users.iter().find(|user| user.email == "user@example.com").unwrap();
+    let _: DeleteUserResponse = call(
+        &server,
+        "/patchouli.v1.UserService/DeleteUser",
+        DeleteUserRequest { user_id: user.id },
+        Some(ROOT_SESSION),
+    )
+    .await
+    .unwrap();
+    let status = call::<_, User>(
+        &server,
+        "/patchouli.v1.UserService/GetUser",
+        GetUserRequest { user_id: user.id },
+        Some(ROOT_SESSION),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!((status.code(), error_code(&status)), (Code::NotFound, "user_not_found"));
+}
+
+#[tokio::test]
+async fn authentication_matches_the_rest_api() {
+    let server = start().await;
+
+    let status = call::<_, ListUsersResponse>(&server, "/patchouli.v1.UserService/ListUsers", ListUsersRequest {}, None)
+        .await
+        .unwrap_err();
+    assert_eq!((status.code(), error_code(&status)), (Code::InvalidArgument, "validation_failed"));
+
+    let status = call::<_, ListUsersResponse>(
+        &server,
+        "/patchouli.v1.UserService/ListUsers",
+        ListUsersRequest {},
+        Some("no-such-session"),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!((status.code(), error_code(&status)), (Code::Unauthenticated, "invalid_session"));
+
+    let status = call::<_, ListUsersResponse>(
+        &server,
+        "/patchouli.v1.UserService/ListUsers",
+        ListUsersRequest {},
+        Some(USER_SESSION),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!((status.code(), error_code(&status)), (Code::PermissionDenied, "insufficient_permission"));
+
+    // 本人の情報は一般ユーザーでも取得できる
+    let introspected: IntrospectTokenResponse = call(
+        &server,
+        "/patchouli.v1.AuthService/IntrospectToken",
+        IntrospectTokenRequest {
+            token: USER_SESSION.to_string(),
+        },
+        None,
+    )
+    .await
+    .unwrap();
+    assert!(introspected.active);
+    let user = introspected.user.unwrap();
+    let fetched: User = call(
+        &server,
+        "/patchouli.v1.UserService/GetUser",
+        GetUserRequest { user_id: user.id },
+        Some(USER_SESSION),
+    )
+    .await
+    .unwrap();
+    assert_eq!(fetched, user);
+
+    let introspected: IntrospectTokenResponse = call(
+        &server,
+        "/patchouli.v1.AuthService/IntrospectToken",
+        IntrospectTokenRequest {
+            token: "no-such-session".to_string(),
+        },
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(introspected, IntrospectTokenResponse { active: false, user: None });
+}
+
+#[tokio::test]
+async fn server_stops_on_shutdown_signal() {
+    let server = start().await;
+    let _: IntrospectTokenResponse = call(
+        &server,
+        "/patchouli.v1.AuthService/IntrospectToken",
+        IntrospectTokenRequest::default(),
+        None,
+    )
+    .await
+    .unwrap();
+
+    let TestServer {
+        channel,
+        shutdown,
+        server,
+    } = server;
+    drop(channel);
+    shutdown.send(()).unwrap();
+    tokio::time::timeout(std::time::Duration::from_secs(5), server)
+        .await
+        .expect("server should stop after the shutdown signal")
+        .unwrap()
+        .unwrap();
+}
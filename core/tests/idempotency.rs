@@ -0,0 +1,111 @@
+//! POSTの`Idempotency-Key`（同じ資格情報での再送のみ保存したレスポンスを返す・409・保存しないレスポンス）
+
+mod common;
+
+use axum::{http::StatusCode, routing::get, Router};
+use common::{
+    fixtures::{InviteFixture, UserFixture},
+    google, login_as, TestClient,
+};
+use patchouli::{build_router, config::Config, error::ErrorCode, CreateApiKeyResponse, InviteCodeResponse};
+use serde_json::json;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+const REPLAYED: &str = "idempotent-replayed";
+
+#[tokio::test]
+async fn retries_with_the_same_credential_are_replayed() {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let invite = InviteFixture::new(&root).insert(&state.database).await;
+    let app = build_router(state.clone());
+    let client = TestClient::new(app.clone()).with_session(&login_as(&state, &root).await);
+    let uri = format!("/v1/invite/{}/clone", invite.id);
+
+    let first = client.with_header("idempotency-key", "clone-1").post(&uri, &json!({})).await;
+    let created: InviteCodeResponse = first.expect(StatusCode::CREATED);
+    assert!(!first.headers.contains_key(REPLAYED));
+
+    let retry = client.with_header("idempotency-key", "clone-1").post(&uri, &json!({})).await;
+    assert_eq!(retry.headers[REPLAYED], "true");
+    assert_eq!(retry.headers["content-type"], "application/json");
+    assert_eq!(retry.expect::<InviteCodeResponse>(StatusCode::CREATED).invite_code, created.invite_code);
+
+    // 同じユーザーでも別のセッションには保存したレスポンスを返さない（もう一度処理する）
+    common::add_session(&state, "second-session", &root).await;
+    let other = TestClient::new(app).with_session("second-session");
+    let response = other.with_header("idempotency-key", "clone-1").post(&uri, &json!({})).await;
+    assert!(!response.headers.contains_key(REPLAYED));
+    assert_ne!(response.expect::<InviteCodeResponse>(StatusCode::CREATED).invite_code, created.invite_code);
+}
+
+#[tokio::test]
+async fn reusing_a_key_for_another_request_conflicts() {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let first = InviteFixture::new(&root).insert(&state.database).await;
+    let second = InviteFixture::new(&root).insert(&state.database).await;
+    let client = TestClient::new(build_router(state.clone()))
+        .with_session(&login_as(&state, &root).await)
+        .with_header("idempotency-key", "clone-1");
+
+    let uri = format!("/v1/invite/{}/clone", first.id);
+    client.post(&uri, &json!({})).await.expect::<InviteCodeResponse>(StatusCode::CREATED);
+
+    let response = client.post(&format!("/v1/invite/{}/clone", second.id), &json!({})).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(response.error_code(), ErrorCode::IdempotencyConflict);
+}
+
+/// 応答しないGoogleの公開鍵のエンドポイント（最初のリクエストを処理中のまま止める）
+async fn stalled_jwks_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = Router::new().route("/certs", get(|| tokio::time::sleep(Duration::from_secs(60))));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}/certs", addr)
+}
+
+#[tokio::test]
+async fn retries_while_the_first_request_is_running_conflict() {
+    let state = common::state(Config {
+        google_client_id: google::CLIENT_ID.to_string(),
+        google_jwks_url: stalled_jwks_server().await,
+        ..Config::default()
+    })
+    .await;
+    let client = TestClient::new(build_router(state)).with_header("idempotency-key", "login-1");
+    let id_token = google::id_token("google-guest", "guest@example.com", "Guest");
+    let body = json!({ "grant_type": "google_id_token", "id_token": id_token });
+
+    let first = tokio::spawn({
+        let (client, body) = (client.clone(), body.clone());
+        async move { client.post("/v1/auth/tokens/google-one-tap", &body).await }
+    });
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let response = client.post("/v1/auth/tokens/google-one-tap", &body).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(response.error_code(), ErrorCode::IdempotencyInProgress);
+    first.abort();
+}
+
+#[tokio::test]
+async fn created_api_keys_are_not_stored_for_replay() {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let client = TestClient::new(build_router(state.clone()))
+        .with_session(&login_as(&state, &root).await)
+        .with_header("idempotency-key", "key-1");
+    let body = json!({ "name": "ci", "scopes": ["read:users"] });
+
+    let first = client.post("/v1/api-keys", &body).await;
+    assert_eq!(first.headers["cache-control"], "no-store");
+    let first: CreateApiKeyResponse = first.expect(StatusCode::CREATED);
+
+    // キーを保存しないため、再送はもう一度処理される（最初のキーは返さない）
+    let retry = client.post("/v1/api-keys", &body).await;
+    assert!(!retry.headers.contains_key(REPLAYED));
+    assert_ne!(retry.expect::<CreateApiKeyResponse>(StatusCode::CREATED).key, first.key);
+}
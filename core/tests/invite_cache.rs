@@ -0,0 +1,92 @@
+//! 招待コードの検証キャッシュが、使用・譲渡・作成者の利用停止の直後に古い結果を返さないことを確かめる
+//!
+//! どのテストも先に`invite_cache.validate`で結果をキャッシュしてから状態を変え、
+//! キャッシュの有効期間内（デフォルトの30秒）に再び検証する。
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{
+    fixtures::{InviteFixture, UserFixture},
+    google, login_as, TestClient,
+};
+use patchouli::{build_router, config::Config, error::ErrorCode, AppState, AuthResponse};
+use serde_json::{json, Value};
+
+async fn one_tap_state() -> AppState {
+    common::state(Config {
+        google_client_id: google::CLIENT_ID.to_string(),
+        google_jwks_url: google::jwks_server().await,
+        ..Config::default()
+    })
+    .await
+}
+
+async fn one_tap(client: &TestClient, sub: &str, email: &str, invite_code: &str) -> common::TestResponse {
+    let body = json!({
+        "grant_type": "google_id_token",
+        "id_token": google::id_token(sub, email, "Test User"),
+        "invite_code": invite_code,
+    });
+    client.post("/v1/auth/tokens/google-one-tap", &body).await
+}
+
+#[tokio::test]
+async fn used_invite_is_not_served_from_cache() {
+    let state = one_tap_state().await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let invite = InviteFixture::new(&root).insert(&state.database).await;
+    let client = TestClient::new(build_router(state.clone()));
+
+    assert!(state.invite_cache.validate(&state.database, &invite.code).await.unwrap().is_some());
+    one_tap(&client, "google-guest", "guest@example.com", &invite.code).await.expect::<AuthResponse>(StatusCode::OK);
+
+    assert!(state.invite_cache.validate(&state.database, &invite.code).await.unwrap().is_none());
+    let response = one_tap(&client, "google-other", "other@example.com", &invite.code).await;
+    assert_eq!((response.status, response.error_code()), (StatusCode::FORBIDDEN, ErrorCode::InvalidInvite));
+}
+
+#[tokio::test]
+async fn transferred_invite_registers_under_the_new_owner() {
+    let state = one_tap_state().await;
+    let db = &state.database;
+    let root = UserFixture::new("Root").root().insert(db).await;
+    let alice = UserFixture::new("Alice").invited_by(&root).can_invite().insert(db).await;
+    let bob = UserFixture::new("Bob").invited_by(&root).can_invite().insert(db).await;
+    let invite = InviteFixture::new(&alice).insert(db).await;
+    let app = build_router(state.clone());
+    let root_client = TestClient::new(app.clone()).with_session(&login_as(&state, &root).await);
+
+    let cached = state.invite_cache.validate(db, &invite.code).await.unwrap().unwrap();
+    assert_eq!(cached.created_by, alice.id);
+    let uri = format!("/v1/invite/{}/transfer", invite.id);
+    root_client.post(&uri, &json!({ "new_owner_id": bob.id })).await.expect::<Value>(StatusCode::OK);
+
+    let validated = state.invite_cache.validate(db, &invite.code).await.unwrap().unwrap();
+    assert_eq!(validated.created_by, bob.id);
+    // 登録したユーザーの招待者は譲渡先になる
+    let client = TestClient::new(app);
+    one_tap(&client, "google-guest", "guest@example.com", &invite.code).await.expect::<AuthResponse>(StatusCode::OK);
+    let guest = db.get_user_by_email("guest@example.com").await.unwrap().unwrap();
+    assert_eq!(guest.invited_by, Some(bob.id));
+}
+
+#[tokio::test]
+async fn invites_of_a_banned_user_are_not_served_from_cache() {
+    let state = one_tap_state().await;
+    let db = &state.database;
+    let root = UserFixture::new("Root").root().insert(db).await;
+    let alice = UserFixture::new("Alice").invited_by(&root).can_invite().insert(db).await;
+    let invite = InviteFixture::new(&alice).insert(db).await;
+    let app = build_router(state.clone());
+    let root_client = TestClient::new(app.clone()).with_session(&login_as(&state, &root).await);
+
+    assert!(state.invite_cache.validate(db, &invite.code).await.unwrap().is_some());
+    // 利用停止にすると作成した招待コードも無効になる
+    let uri = format!("/v1/admin/users/{}/ban", alice.id);
+    root_client.post(&uri, &json!({})).await.expect::<Value>(StatusCode::OK);
+
+    assert!(state.invite_cache.validate(db, &invite.code).await.unwrap().is_none());
+    let response = one_tap(&TestClient::new(app), "google-guest", "guest@example.com", &invite.code).await;
+    assert_eq!((response.status, response.error_code()), (StatusCode::FORBIDDEN, ErrorCode::InvalidInvite));
+}
@@ -0,0 +1,252 @@
+//! 招待コードの状態遷移をランダムな操作列で検査する
+//!
+//! 作成（有効期限はランダム）・検証・使用・無効化・時間の経過をランダムに並べ、操作ごとに
+//! 単純なモデルの予測と実装の結果を突き合わせる。失敗時のメッセージに出るシードを
+//! `INVITE_LIFECYCLE_SEED`に指定すると同じ操作列を再現できる。
+//!
+//! 時間の経過は`MockClock`を進めて表す。
+
+mod common;
+
+use axum::http::StatusCode;
+use chrono::{DateTime, Duration, Utc};
+use common::{fixtures::UserFixture, google, login_as, TestClient};
+use patchouli::{
+    build_router,
+    clock::{Clock, MockClock},
+    config::Config,
+    database::{InviteFilterParams, UserFilterParams},
+    error::ErrorCode,
+    AppState, InviteCodeResponse,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde_json::json;
+use std::sync::Arc;
+
+const SEQUENCES: usize = 24;
+const OPERATIONS: usize = 40;
+const DAILY_LIMIT: usize = 12;
+const TOTAL_LIMIT: usize = 4;
+
+#[derive(Debug, Clone, Copy)]
+enum Operation {
+    /// `ttl_hours`がNoneなら期限なし
+    Create { ttl_hours: Option<i64> },
+    Validate { invite: usize },
+    Redeem { invite: usize },
+    Revoke { invite: usize },
+    AdvanceTime { hours: i64 },
+}
+
+impl Operation {
+    fn random(rng: &mut StdRng, invites: usize) -> Self {
+        if invites == 0 {
+            return Operation::Create { ttl_hours: random_ttl(rng) };
+        }
+        let invite = rng.gen_range(0..invites);
+        match rng.gen_range(0..10) {
+            0..=2 => Operation::Create { ttl_hours: random_ttl(rng) },
+            3 => Operation::Validate { invite },
+            4..=6 => Operation::Redeem { invite },
+            7 => Operation::Revoke { invite },
+            _ => Operation::AdvanceTime { hours: rng.gen_range(1..=24) },
+        }
+    }
+}
+
+fn random_ttl(rng: &mut StdRng) -> Option<i64> {
+    rng.gen_bool(0.6).then(|| rng.gen_range(1..=48))
+}
+
+/// モデル上の招待コード
+#[derive(Debug)]
+struct ModelInvite {
+    id: i64,
+    code: String,
+    created_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+    used_by: Option<i64>,
+    revoked: bool,
+}
+
+impl ModelInvite {
+    fn admits(&self, now: DateTime<Utc>) -> bool {
+        self.used_by.is_none() && !self.revoked && self.expires_at.is_none_or(|expires_at| now <= expires_at)
+    }
+
+    /// 作成者の未使用の招待コードの上限に数えるか（期限切れでも使用・無効化されるまで数える）
+    fn outstanding(&self) -> bool {
+        self.used_by.is_none() && !self.revoked
+    }
+}
+
+struct Run {
+    state: AppState,
+    clock: Arc<MockClock>,
+    client: TestClient,
+    inviter: TestClient,
+    inviter_id: i64,
+    invites: Vec<ModelInvite>,
+    registrations: usize,
+    seed: u64,
+    history: Vec<Operation>,
+}
+
+impl Run {
+    async fn new(seed: u64) -> Self {
+        let clock = Arc::new(MockClock::starting_now());
+        let config = Config {
+            google_client_id: google::CLIENT_ID.to_string(),
+            google_jwks_url: google::jwks_server().await,
+            invite_daily_limit: DAILY_LIMIT as u32,
+            invite_total_limit: TOTAL_LIMIT as u32,
+            ..Config::default()
+        };
+        let state = common::state_with_clock(config, clock.clone()).await;
+        let root = UserFixture::new("Root").root().insert(&state.database).await;
+        let inviter = UserFixture::new("Inviter").invited_by(&root).can_invite().insert(&state.database).await;
+        let session = login_as(&state, &inviter).await;
+        let client = TestClient::new(build_router(state.clone()));
+        Run {
+            inviter: client.with_session(&session),
+            inviter_id: inviter.id,
+            client,
+            state,
+            clock,
+            invites: Vec::new(),
+            registrations: 0,
+            seed,
+            history: Vec::new(),
+        }
+    }
+
+    /// 失敗時に再現に必要な情報を付ける
+    fn context(&self) -> String {
+        format!("seed={} operations={:?}", self.seed, self.history)
+    }
+
+    async fn apply(&mut self, operation: Operation) {
+        self.history.push(operation);
+        match operation {
+            Operation::Create { ttl_hours } => self.create(ttl_hours).await,
+            Operation::Validate { invite } => {
+                let invite = &self.invites[invite];
+                let valid = self.state.invite_cache.validate(&self.state.database, &invite.code).await.unwrap();
+                let admits = invite.admits(self.clock.now());
+                assert_eq!(valid.is_some(), admits, "validate {:?}: {}", invite, self.context());
+            }
+            Operation::Redeem { invite } => self.redeem(invite).await,
+            Operation::Revoke { invite } => {
+                // gRPCのRevokeInviteと同じく、無効化したらキャッシュからも消す
+                let revoked = self.state.database.deactivate_invite(self.invites[invite].id).await.unwrap();
+                self.state.invite_cache.invalidate(&self.invites[invite].code).await;
+                let model = &self.invites[invite];
+                assert_eq!(revoked.is_some(), model.used_by.is_none(), "revoke {:?}: {}", model, self.context());
+                self.invites[invite].revoked |= revoked.is_some();
+            }
+            Operation::AdvanceTime { hours } => self.clock.advance(Duration::hours(hours)),
+        }
+        self.check_invariants().await;
+    }
+
+    async fn create(&mut self, ttl_hours: Option<i64>) {
+        let response = self.inviter.get("/v1/invite/create").await;
+        let outstanding = self.invites.iter().filter(|invite| invite.outstanding()).count();
+        // 1日の上限は今日（UTC）作成した数で判定する
+        let today = self.clock.now().date_naive();
+        let created_today = self.invites.iter().filter(|invite| invite.created_at.date_naive() == today).count();
+        let expected = if created_today >= DAILY_LIMIT {
+            Some(ErrorCode::InviteDailyLimitExceeded)
+        } else if outstanding >= TOTAL_LIMIT {
+            Some(ErrorCode::InviteTotalLimitExceeded)
+        } else {
+            None
+        };
+        if let Some(code) = expected {
+            assert_eq!(response.status, StatusCode::TOO_MANY_REQUESTS, "create: {}", self.context());
+            assert_eq!(response.error_code(), code, "{}", self.context());
+            return;
+        }
+
+        assert_eq!(response.status, StatusCode::OK, "create: {}", self.context());
+        let created: InviteCodeResponse = response.json();
+        let id = self.state.database.validate_invite_code(&created.invite_code).await.unwrap().unwrap().id;
+        let expires_at = ttl_hours.map(|hours| self.clock.now() + Duration::hours(hours));
+        if expires_at.is_some() {
+            self.state.database.update_invite(id, None, expires_at).await.unwrap();
+        }
+        self.invites.push(ModelInvite {
+            id,
+            code: created.invite_code,
+            created_at: self.clock.now(),
+            expires_at,
+            used_by: None,
+            revoked: false,
+        });
+    }
+
+    /// 毎回新しいユーザーがGoogle One Tapで登録を試みる
+    async fn redeem(&mut self, invite: usize) {
+        self.registrations += 1;
+        let email = format!("guest{}@example.com", self.registrations);
+        let sub = format!("google-guest{}", self.registrations);
+        let body = json!({
+            "grant_type": "google_id_token",
+            // 進めた時刻で期限切れにならないよう、時計の現在時刻に発行する
+            "id_token": google::id_token_at(&sub, &email, "Guest", self.clock.now()),
+            "invite_code": self.invites[invite].code,
+        });
+        let response = self.client.post("/v1/auth/tokens/google-one-tap", &body).await;
+
+        let model = &self.invites[invite];
+        if !model.admits(self.clock.now()) {
+            // ステータスを先に確認する（成功した場合はエラーコードを読めずシードを表示できない）
+            assert_eq!(response.status, StatusCode::FORBIDDEN, "redeem {:?}: {}", model, self.context());
+            assert_eq!(response.error_code(), ErrorCode::InvalidInvite, "{}", self.context());
+            return;
+        }
+        assert_eq!(response.status, StatusCode::OK, "redeem {:?}: {}", model, self.context());
+        let user = self.state.database.get_user_by_email(&email).await.unwrap().unwrap();
+        self.invites[invite].used_by = Some(user.id);
+    }
+
+    async fn check_invariants(&self) {
+        // 使用済みの招待コードは使用者が変わらず、1つのコードで登録できるのは1人だけ
+        let filter = InviteFilterParams {
+            created_by: Some(self.inviter_id),
+            ..InviteFilterParams::default()
+        };
+        let stored = self.state.database.get_invite_codes(&filter).await.unwrap();
+        assert_eq!(stored.len(), self.invites.len(), "{}", self.context());
+        for model in &self.invites {
+            let invite = stored.iter().find(|invite| invite.id == model.id).unwrap();
+            assert_eq!(invite.used_by, model.used_by, "{:?}: {}", model, self.context());
+            assert_eq!(invite.is_active, !model.revoked, "{:?}: {}", model, self.context());
+        }
+
+        // 登録できたユーザー数は使用済みの招待コード数と一致する（root・inviterを除く）
+        let users = self.state.database.get_all_registered_users(&UserFilterParams::default()).await.unwrap();
+        let used = self.invites.iter().filter(|invite| invite.used_by.is_some()).count();
+        assert_eq!(users.len() - 2, used, "{}", self.context());
+    }
+}
+
+#[tokio::test]
+async fn random_operations_match_the_model() {
+    let base_seed: u64 = match std::env::var("INVITE_LIFECYCLE_SEED") {
+        Ok(seed) => seed.parse().expect("INVITE_LIFECYCLE_SEED must be an integer"),
+        Err(_) => rand::random(),
+    };
+    // シードを指定した場合はその1系列だけを実行する
+    let sequences = if std::env::var("INVITE_LIFECYCLE_SEED").is_ok() { 1 } else { SEQUENCES };
+
+    for index in 0..sequences {
+        let seed = base_seed.wrapping_add(index as u64);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut run = Run::new(seed).await;
+        for _ in 0..OPERATIONS {
+            let operation = Operation::random(&mut rng, run.invites.len());
+            run.apply(operation).await;
+        }
+    }
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header::RETRY_AFTER, Request, StatusCode},
+};
+use common::{add_session, fixtures::UserFixture};
+use patchouli::{
+    build_router,
+    config::Config,
+    error::{ErrorCode, ErrorResponse},
+    InviteCodeResponse,
+};
+
+const SESSION: &str = "root-session";
+
+#[tokio::test]
+async fn daily_limit_rejects_invites_beyond_the_limit() {
+    let state = common::state(Config {
+        invite_daily_limit: 2,
+        ..Config::default()
+    })
+    .await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    add_session(&state, SESSION, &root).await;
+    let app = build_router(state);
+
+    let uri = format!("/v1/invite/create?session_id={}", SESSION);
+    for _ in 0..2 {
+        assert_eq!(common::get(&app, &uri).await.status, StatusCode::OK);
+    }
+
+    let response = common::get(&app, &uri).await;
+    assert_eq!(response.status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.json::<ErrorResponse>().error, ErrorCode::InviteDailyLimitExceeded);
+    let retry_after: u64 = response.headers[RETRY_AFTER].to_str().unwrap().parse().unwrap();
+    assert!((1..=24 * 60 * 60).contains(&retry_after), "Retry-After: {}", retry_after);
+
+    // 複製も招待コードの作成として数える
+    let request = Request::post(format!("/v1/invite/1/clone?session_id={}", SESSION))
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(common::send(&app, request).await.status, StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn zero_daily_limit_means_unlimited() {
+    let state = common::state(Config {
+        invite_daily_limit: 0,
+        ..Config::default()
+    })
+    .await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    add_session(&state, SESSION, &root).await;
+    let app = build_router(state);
+
+    let uri = format!("/v1/invite/create?session_id={}", SESSION);
+    for _ in 0..15 {
+        assert_eq!(common::get(&app, &uri).await.status, StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn total_limit_counts_unused_invites_until_one_is_used() {
+    let state = common::state(Config {
+        invite_total_limit: 2,
+        ..Config::default()
+    })
+    .await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    add_session(&state, SESSION, &root).await;
+    let database = state.database.clone();
+    let app = build_router(state);
+
+    let uri = format!("/v1/invite/create?session_id={}", SESSION);
+    let mut codes = Vec::new();
+    for _ in 0..2 {
+        let response = common::get(&app, &uri).await;
+        assert_eq!(response.status, StatusCode::OK);
+        codes.push(response.json::<InviteCodeResponse>().invite_code);
+    }
+
+    let response = common::get(&app, &uri).await;
+    assert_eq!(response.status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.json::<ErrorResponse>().error, ErrorCode::InviteTotalLimitExceeded);
+    assert!(!response.headers.contains_key(RETRY_AFTER));
+
+    // 使用された招待コードは数えない
+    let invited = UserFixture::new("User").invited_by(&root).insert(&database).await;
+    database.use_invite_code(&codes[0], invited.id).await.unwrap();
+    assert_eq!(common::get(&app, &uri).await.status, StatusCode::OK);
+    assert_eq!(common::get(&app, &uri).await.status, StatusCode::TOO_MANY_REQUESTS);
+}
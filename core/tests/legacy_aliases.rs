@@ -0,0 +1,91 @@
+//! `/v1`を付けない非推奨の旧パス（`Deprecation`・`Sunset`ヘッダーと、`/v1`の導入前からあるルートに限ること）
+
+mod common;
+
+use axum::http::{header::LINK, Method, StatusCode};
+use common::{fixtures::UserFixture, login_as, TestClient};
+use patchouli::{build_router, config::Config, routes, DashboardResponse};
+
+const SUNSET: &str = "Wed, 31 Mar 2027 00:00:00 GMT";
+
+async fn root_client(config: Config) -> TestClient {
+    let state = common::state(config).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    TestClient::new(build_router(state.clone())).with_session(&login_as(&state, &root).await)
+}
+
+#[tokio::test]
+async fn legacy_paths_carry_deprecation_headers() {
+    let client = root_client(Config {
+        api_legacy_sunset: SUNSET.to_string(),
+        ..Config::default()
+    })
+    .await;
+
+    let response = client.get("/dashboard").await;
+    response.expect::<DashboardResponse>(StatusCode::OK);
+    assert_eq!(response.headers["deprecation"], "true");
+    assert_eq!(response.headers["sunset"], SUNSET);
+    assert_eq!(response.headers[LINK], "</v1/dashboard>; rel=\"successor-version\"");
+
+    // /protectedの移行先は/v1/dashboard
+    let response = client.get("/protected").await;
+    response.expect::<DashboardResponse>(StatusCode::OK);
+    assert_eq!(response.headers[LINK], "</v1/dashboard>; rel=\"successor-version\"");
+
+    let response = client.get("/v1/dashboard").await;
+    response.expect::<DashboardResponse>(StatusCode::OK);
+    assert!(!response.headers.contains_key("deprecation"));
+    assert!(!response.headers.contains_key("sunset"));
+}
+
+#[tokio::test]
+async fn endpoints_added_after_v1_have_no_legacy_alias() {
+    let client = root_client(Config::default()).await;
+
+    for uri in ["/userinfo", "/api-keys", "/system/status", "/admin/export/users.csv", "/admin/overview"] {
+        assert_eq!(client.get(uri).await.status, StatusCode::NOT_FOUND, "{}", uri);
+        assert_ne!(client.get(&format!("/v1{}", uri)).await.status, StatusCode::NOT_FOUND, "/v1{}", uri);
+    }
+}
+
+#[test]
+fn legacy_routes_are_frozen() {
+    // 旧パスの別名は/v1の導入時のルートに限る（新しいルートに`.legacy()`を付けない）
+    let legacy: Vec<(Method, &str)> = routes::legacy().into_iter().map(|route| (route.method, route.path)).collect();
+    assert_eq!(
+        legacy,
+        [
+            (Method::GET, "/login/api"),
+            (Method::GET, "/callback/api"),
+            (Method::GET, "/auth/status/:token"),
+            (Method::POST, "/auth/tokens/google-one-tap"),
+            (Method::GET, "/dashboard"),
+            (Method::GET, "/invite/create"),
+            (Method::GET, "/invite/list"),
+            (Method::POST, "/invite/:invite_id/resend-notification"),
+            (Method::GET, "/admin/users"),
+            (Method::DELETE, "/admin/users/:user_id"),
+            (Method::OPTIONS, "/admin/users/:user_id"),
+            (Method::POST, "/admin/users/:user_id/ban"),
+            (Method::POST, "/admin/users/:user_id/unban"),
+            (Method::GET, "/admin/stats"),
+            (Method::GET, "/admin/stats/timeseries"),
+            (Method::GET, "/root/exists"),
+            (Method::GET, "/events"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn legacy_aliases_can_be_disabled() {
+    let client = root_client(Config {
+        api_legacy_aliases: false,
+        ..Config::default()
+    })
+    .await;
+
+    assert_eq!(client.get("/dashboard").await.status, StatusCode::NOT_FOUND);
+    assert_eq!(client.get("/protected").await.status, StatusCode::NOT_FOUND);
+    client.get("/v1/dashboard").await.expect::<DashboardResponse>(StatusCode::OK);
+}
@@ -0,0 +1,240 @@
+//! ユーザー一覧・招待コード一覧の絞り込み条件
+
+mod common;
+
+use axum::http::StatusCode;
+use chrono::{DateTime, Duration, Utc};
+use common::{
+    fixtures::{InviteFixture, UserFixture},
+    login_as, TestClient,
+};
+use patchouli::{
+    build_router,
+    clock::MockClock,
+    config::Config,
+    error::{ErrorCode, ErrorResponse},
+    InviteCodesListResponse, UsersListResponse,
+};
+use std::sync::Arc;
+
+/// 1人目（root）を登録した時刻。以降は1日ごとに登録・作成する
+const START: &str = "2026-03-01T00:00:00Z";
+
+fn day(days: i64) -> DateTime<Utc> {
+    START.parse::<DateTime<Utc>>().unwrap() + Duration::days(days)
+}
+
+/// `DateTime`のクエリ値（`+00:00`はURLで空白になるため`Z`で書く）
+fn at(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+/// `uri`の一覧のメールアドレス（ID順）
+async fn user_emails(client: &TestClient, uri: &str) -> Vec<String> {
+    let mut users = client.get(uri).await.expect::<UsersListResponse>(StatusCode::OK).users;
+    users.sort_by_key(|user| user.id);
+    users.into_iter().map(|user| user.email).collect()
+}
+
+/// `uri`の一覧のユーザーID（ID順）
+async fn user_ids(client: &TestClient, uri: &str) -> Vec<i64> {
+    let users = client.get(uri).await.expect::<UsersListResponse>(StatusCode::OK).users;
+    let mut ids: Vec<i64> = users.into_iter().map(|user| user.id).collect();
+    ids.sort();
+    ids
+}
+
+/// `uri`の一覧の招待コードID（ID順）
+async fn invite_ids(client: &TestClient, uri: &str) -> Vec<i64> {
+    let invites = client.get(uri).await.expect::<InviteCodesListResponse>(StatusCode::OK).invite_codes;
+    let mut ids: Vec<i64> = invites.into_iter().map(|invite| invite.id).collect();
+    ids.sort();
+    ids
+}
+
+#[tokio::test]
+async fn users_are_filtered_by_root_and_invite_permission() {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    UserFixture::new("Alice").can_invite().invited_by(&root).insert(&state.database).await;
+    UserFixture::new("Bob").invited_by(&root).insert(&state.database).await;
+    let client = TestClient::new(build_router(state.clone())).with_session(&login_as(&state, &root).await);
+
+    assert_eq!(
+        user_emails(&client, "/v1/admin/users?can_invite=true").await,
+        ["root@example.com", "alice@example.com"]
+    );
+    assert_eq!(user_emails(&client, "/v1/admin/users?can_invite=false").await, ["bob@example.com"]);
+    assert_eq!(user_emails(&client, "/v1/admin/users?is_root=true").await, ["root@example.com"]);
+    assert_eq!(
+        user_emails(&client, "/v1/admin/users?is_root=false&can_invite=true").await,
+        ["alice@example.com"]
+    );
+
+    let response = client.get("/v1/admin/users?can_invite=yes").await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn users_are_filtered_by_inviter() {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let alice = UserFixture::new("Alice").can_invite().invited_by(&root).insert(&state.database).await;
+    let bob = UserFixture::new("Bob").invited_by(&root).insert(&state.database).await;
+    let carol = UserFixture::new("Carol").can_invite().invited_by(&alice).insert(&state.database).await;
+    let dave = UserFixture::new("Dave").invited_by(&alice).insert(&state.database).await;
+    // root以外で招待者のいないユーザー（招待なしの登録）
+    let eve = UserFixture::new("Eve").insert(&state.database).await;
+    let client = TestClient::new(build_router(state.clone())).with_session(&login_as(&state, &root).await);
+
+    let uri = format!("/v1/admin/users?invited_by={}", root.id);
+    assert_eq!(user_ids(&client, &uri).await, [alice.id, bob.id]);
+    let uri = format!("/v1/admin/users?invited_by={}", alice.id);
+    assert_eq!(user_ids(&client, &uri).await, [carol.id, dave.id]);
+    let uri = format!("/v1/admin/users?invited_by={}", bob.id);
+    assert_eq!(user_ids(&client, &uri).await, Vec::<i64>::new());
+    assert_eq!(user_ids(&client, "/v1/admin/users?invited_by=9999").await, Vec::<i64>::new());
+
+    // 0とnullは招待者なし
+    for value in ["0", "null"] {
+        let uri = format!("/v1/admin/users?invited_by={}", value);
+        assert_eq!(user_ids(&client, &uri).await, [root.id, eve.id], "{}", value);
+    }
+
+    // 他の条件と組み合わせる
+    let uri = format!("/v1/admin/users?invited_by={}&can_invite=true", alice.id);
+    assert_eq!(user_ids(&client, &uri).await, [carol.id]);
+    let uri = format!("/v1/admin/users?invited_by={}&can_invite=false", alice.id);
+    assert_eq!(user_ids(&client, &uri).await, [dave.id]);
+    assert_eq!(user_ids(&client, "/v1/admin/users?invited_by=0&is_root=false").await, [eve.id]);
+    assert_eq!(user_ids(&client, "/v1/admin/users?invited_by=null&is_root=true").await, [root.id]);
+    let uri = format!("/v1/admin/users?invited_by={}&is_root=true", root.id);
+    assert_eq!(user_ids(&client, &uri).await, Vec::<i64>::new());
+
+    for value in ["alice", "1.5", ""] {
+        let error: ErrorResponse =
+            client.get(&format!("/v1/admin/users?invited_by={}", value)).await.expect(StatusCode::BAD_REQUEST);
+        assert_eq!(error.error, ErrorCode::ValidationFailed, "{}", value);
+        assert!(error.details.unwrap().get("invited_by").is_some(), "{}", value);
+    }
+}
+
+#[tokio::test]
+async fn users_are_filtered_by_registration_date() {
+    let clock = Arc::new(MockClock::new(day(0)));
+    let state = common::state_with_clock(Config::default(), clock.clone()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    clock.set(day(1));
+    UserFixture::new("Alice").invited_by(&root).insert(&state.database).await;
+    clock.set(day(2));
+    UserFixture::new("Bob").invited_by(&root).insert(&state.database).await;
+    let client = TestClient::new(build_router(state.clone())).with_session(&login_as(&state, &root).await);
+
+    // 片側だけの指定（境界の時刻ちょうどに登録したユーザーを含む）
+    let uri = format!("/v1/admin/users?registered_after={}", at(day(1)));
+    assert_eq!(user_emails(&client, &uri).await, ["alice@example.com", "bob@example.com"]);
+    let uri = format!("/v1/admin/users?registered_before={}", at(day(1)));
+    assert_eq!(user_emails(&client, &uri).await, ["root@example.com", "alice@example.com"]);
+
+    // 両側の指定（タイムゾーン付きの時刻はUTCに直して比べる）
+    let uri = format!(
+        "/v1/admin/users?registered_after={}&registered_before=2026-03-02T18:00:00%2B09:00",
+        at(day(1) - Duration::hours(1)),
+    );
+    assert_eq!(user_emails(&client, &uri).await, ["alice@example.com"]);
+
+    // 開始が終了より後（同じ時刻も含む）の範囲は400
+    for (after, before) in [(day(2), day(1)), (day(1), day(1))] {
+        let uri = format!("/v1/admin/users?registered_after={}&registered_before={}", at(after), at(before));
+        let error: ErrorResponse = client.get(&uri).await.expect(StatusCode::BAD_REQUEST);
+        assert_eq!(error.error, ErrorCode::ValidationFailed);
+        assert!(error.details.unwrap().get("registered_before").is_some());
+    }
+    let response = client.get("/v1/admin/users?registered_after=yesterday").await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn invites_are_filtered_by_creation_date() {
+    let clock = Arc::new(MockClock::new(day(0)));
+    let state = common::state_with_clock(Config::default(), clock.clone()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let alice = UserFixture::new("Alice").can_invite().invited_by(&root).insert(&state.database).await;
+    let mut invites = Vec::new();
+    for (days, creator) in [(0, &root), (1, &alice), (2, &root), (3, &alice)] {
+        clock.set(day(days));
+        invites.push(InviteFixture::new(creator).insert(&state.database).await.id);
+    }
+    let app = build_router(state.clone());
+    let root_client = TestClient::new(app.clone()).with_session(&login_as(&state, &root).await);
+    let alice_client = TestClient::new(app).with_session(&login_as(&state, &alice).await);
+
+    // rootユーザーはall=trueで全ユーザーの招待コードを絞り込める
+    let uri = format!("/v1/invite/list?all=true&created_after={}", at(day(1)));
+    assert_eq!(invite_ids(&root_client, &uri).await, invites[1..]);
+    let uri = format!("/v1/invite/list?all=true&created_after={}&created_before={}", at(day(1)), at(day(2)));
+    assert_eq!(invite_ids(&root_client, &uri).await, invites[1..3]);
+    // all=trueでなければ自分が作成したもののみ
+    let uri = format!("/v1/invite/list?created_before={}", at(day(2)));
+    assert_eq!(invite_ids(&root_client, &uri).await, [invites[0], invites[2]]);
+
+    // 一般ユーザーは自分の招待コードのみ（rootユーザーが同じ期間に作成したものは含まない）
+    let uri = format!("/v1/invite/list?created_after={}", at(day(1)));
+    assert_eq!(invite_ids(&alice_client, &uri).await, [invites[1], invites[3]]);
+    // タイムゾーン付きの時刻（3日目の09:00+09:00は3日目の00:00 UTC）
+    let uri = "/v1/invite/list?created_after=2026-03-04T09:00:00%2B09:00";
+    assert_eq!(invite_ids(&alice_client, uri).await, [invites[3]]);
+    let uri = "/v1/invite/list?created_after=2026-03-04T09:00:01%2B09:00";
+    assert_eq!(invite_ids(&alice_client, uri).await, Vec::<i64>::new());
+
+    let uri = format!("/v1/invite/list?created_after={}&created_before={}", at(day(3)), at(day(1)));
+    let error: ErrorResponse = alice_client.get(&uri).await.expect(StatusCode::BAD_REQUEST);
+    assert!(error.details.unwrap().get("created_before").is_some());
+}
+
+#[tokio::test]
+async fn invites_are_filtered_by_state() {
+    let clock = Arc::new(MockClock::new(day(0)));
+    let state = common::state_with_clock(Config::default(), clock.clone()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let bob = UserFixture::new("Bob").invited_by(&root).insert(&state.database).await;
+    let carol = UserFixture::new("Carol").invited_by(&root).insert(&state.database).await;
+
+    // 全て0日目に作成し、1日目の時点で絞り込む
+    let db = &state.database;
+    let unlimited = InviteFixture::new(&root).insert(db).await.id;
+    let valid = InviteFixture::new(&root).expires_in(Duration::days(2)).insert(db).await.id;
+    let used = InviteFixture::new(&root).used_by(&bob).insert(db).await.id;
+    let revoked = InviteFixture::new(&root).revoked().insert(db).await.id;
+    let expired = InviteFixture::new(&root).expires_in(Duration::hours(12)).insert(db).await.id;
+    let expired_used = InviteFixture::new(&root).expires_in(Duration::hours(12)).used_by(&carol).insert(db).await.id;
+    // 期限ちょうどはまだ有効、その1秒前に切れたものは期限切れ
+    let expires_now = InviteFixture::new(&root).expires_in(Duration::days(1)).insert(db).await.id;
+    let just_expired = InviteFixture::new(&root).expires_in(Duration::days(1) - Duration::seconds(1));
+    let just_expired = just_expired.insert(db).await.id;
+    clock.set(day(1));
+    let client = TestClient::new(build_router(state.clone())).with_session(&login_as(&state, &root).await);
+
+    let cases: [(&str, Vec<i64>); 10] = [
+        ("is_active=true", vec![unlimited, valid, used, expired, expired_used, expires_now, just_expired]),
+        ("is_active=false", vec![revoked]),
+        ("used=true", vec![used, expired_used]),
+        ("used=false", vec![unlimited, valid, revoked, expired, expires_now, just_expired]),
+        ("expired=true", vec![expired, expired_used, just_expired]),
+        ("expired=false", vec![unlimited, valid, used, revoked, expires_now]),
+        ("expired=true&used=false", vec![expired, just_expired]),
+        ("expired=true&used=true", vec![expired_used]),
+        ("is_active=true&used=false&expired=false", vec![unlimited, valid, expires_now]),
+        ("is_active=false&expired=true", vec![]),
+    ];
+    for (query, expected) in cases {
+        assert_eq!(invite_ids(&client, &format!("/v1/invite/list?{}", query)).await, expected, "{}", query);
+    }
+
+    // 時計が進むと期限切れの判定も変わる
+    clock.set(day(2) + Duration::seconds(1));
+    assert_eq!(invite_ids(&client, "/v1/invite/list?expired=false").await, [unlimited, used, revoked]);
+
+    let response = client.get("/v1/invite/list?expired=maybe").await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+}
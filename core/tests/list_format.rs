@@ -0,0 +1,103 @@
+//! 一覧のNDJSON・CSV（`Accept`で選ぶストリーミングのレスポンス。空の一覧・CSVのエスケープ・ページの境界・権限）
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{
+    fixtures::{InviteFixture, UserFixture},
+    login_as, TestClient,
+};
+use patchouli::{build_router, config::Config, error::ErrorCode};
+use serde_json::Value;
+use std::collections::HashSet;
+
+const INVITE_CSV_HEADER: &str = "id,code,created_by,created_at,expires_at,used_by,used_at,is_active,note,metadata\n";
+
+#[tokio::test]
+async fn empty_lists_stream_no_records() {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let client = TestClient::new(build_router(state.clone())).with_session(&login_as(&state, &root).await);
+
+    let ndjson = client.with_header("accept", "application/x-ndjson").get("/v1/invite/list").await;
+    assert_eq!(ndjson.status, StatusCode::OK);
+    assert_eq!(ndjson.headers["content-type"], "application/x-ndjson");
+    assert_eq!(ndjson.headers["vary"], "accept");
+    assert!(ndjson.body.is_empty(), "{:?}", String::from_utf8_lossy(&ndjson.body));
+
+    // CSVはヘッダー行だけ
+    let csv = client.with_header("accept", "text/csv").get("/v1/invite/list").await;
+    assert_eq!(csv.status, StatusCode::OK);
+    assert_eq!(csv.headers["content-type"], "text/csv; charset=utf-8");
+    assert_eq!(String::from_utf8(csv.body).unwrap(), INVITE_CSV_HEADER);
+}
+
+#[tokio::test]
+async fn csv_fields_are_escaped() {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().display_name("Root, \"the\" admin").insert(&state.database).await;
+    let note = "for \"Bob\", then\nfor Carol";
+    InviteFixture::new(&root).note(note).insert(&state.database).await;
+    let client = TestClient::new(build_router(state.clone()))
+        .with_session(&login_as(&state, &root).await)
+        .with_header("accept", "text/csv");
+
+    let invites = String::from_utf8(client.get("/v1/invite/list").await.body).unwrap();
+    assert!(invites.contains("\"for \"\"Bob\"\", then\nfor Carol\""), "{}", invites);
+    let mut reader = csv::Reader::from_reader(invites.as_bytes());
+    let headers = reader.headers().unwrap().clone();
+    let records: Vec<_> = reader.records().map(Result::unwrap).collect();
+    assert_eq!(records.len(), 1);
+    let column = |name: &str| headers.iter().position(|header| header == name).unwrap();
+    assert_eq!(&records[0][column("note")], note);
+    // JSONの列（引用符・カンマを含む）も1つのフィールドのまま読める
+    serde_json::from_str::<Value>(&records[0][column("metadata")]).unwrap();
+
+    let users = String::from_utf8(client.get("/v1/admin/users").await.body).unwrap();
+    assert!(users.contains(r#""Root, ""the"" admin""#), "{}", users);
+    let mut reader = csv::Reader::from_reader(users.as_bytes());
+    let name = reader.headers().unwrap().iter().position(|header| header == "name").unwrap();
+    let record = reader.records().next().unwrap().unwrap();
+    assert_eq!(&record[name], "Root, \"the\" admin");
+}
+
+#[tokio::test]
+async fn ndjson_streams_every_record_across_pages() {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    // 1ページ（500件）を超える件数
+    let count = 501;
+    for _ in 0..count {
+        InviteFixture::new(&root).insert(&state.database).await;
+    }
+    let client = TestClient::new(build_router(state.clone()))
+        .with_session(&login_as(&state, &root).await)
+        .with_header("accept", "application/x-ndjson");
+
+    let body = String::from_utf8(client.get("/v1/invite/list").await.body).unwrap();
+    let ids: Vec<i64> = body
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap()["id"].as_i64().unwrap())
+        .collect();
+    assert_eq!(ids.len(), count);
+    assert_eq!(ids.iter().collect::<HashSet<_>>().len(), count, "pages must not overlap");
+    assert!(ids.windows(2).all(|pair| pair[0] > pair[1]), "records should be newest first");
+}
+
+#[tokio::test]
+async fn streaming_formats_are_authorized_like_json() {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let alice = UserFixture::new("Alice").invited_by(&root).insert(&state.database).await;
+    let client = TestClient::new(build_router(state.clone())).with_session(&login_as(&state, &alice).await);
+
+    for accept in ["application/json", "application/x-ndjson", "text/csv"] {
+        let client = client.with_header("accept", accept);
+        let response = client.get("/v1/admin/users").await;
+        assert_eq!(response.status, StatusCode::FORBIDDEN, "{}", accept);
+        assert_eq!(response.headers["content-type"], "application/json");
+        assert_eq!(response.error_code(), ErrorCode::InsufficientPermission);
+        let response = client.get("/v1/invite/list?all=true").await;
+        assert_eq!(response.error_code(), ErrorCode::InsufficientPermission, "{}", accept);
+    }
+}
@@ -0,0 +1,86 @@
+//! rootユーザーへの昇格（`/promote`と別名の`/make-root`）と確認用の文字列のチェック
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{fixtures::UserFixture, login_as, TestClient};
+use patchouli::{build_router, config::Config, error::ErrorCode, AppState, PromoteUserResponse};
+use serde_json::json;
+
+const ENDPOINTS: [&str; 2] = ["promote", "make-root"];
+
+async fn setup() -> (AppState, TestClient) {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let session = login_as(&state, &root).await;
+    let client = TestClient::new(build_router(state.clone())).with_session(&session);
+    (state, client)
+}
+
+#[tokio::test]
+async fn confirmation_is_case_sensitive() {
+    let (state, client) = setup().await;
+    let root = state.database.get_user_by_email("root@example.com").await.unwrap().unwrap();
+    let alice = UserFixture::new("Alice").invited_by(&root).insert(&state.database).await;
+
+    for endpoint in ENDPOINTS {
+        let uri = format!("/v1/users/{}/{}", alice.id, endpoint);
+        for confirm_action in ["promote_to_root", "Promote_To_Root", "PROMOTE_TO_ROOT ", ""] {
+            let response = client.post(&uri, &json!({ "confirm_action": confirm_action })).await;
+            assert_eq!(response.status, StatusCode::BAD_REQUEST, "{} {:?}", endpoint, confirm_action);
+            assert_eq!(response.error_code(), ErrorCode::ConfirmationMismatch);
+        }
+        // 確認用の文字列がなければボディを読み取れない
+        let response = client.post(&uri, &json!({})).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert_eq!(response.error_code(), ErrorCode::ValidationFailed);
+    }
+
+    let alice = state.database.get_user_by_id(alice.id).await.unwrap().unwrap();
+    assert!(!alice.is_root);
+}
+
+#[tokio::test]
+async fn both_endpoints_promote_with_confirmation() {
+    let (state, client) = setup().await;
+    let root = state.database.get_user_by_email("root@example.com").await.unwrap().unwrap();
+    let body = json!({ "confirm_action": "PROMOTE_TO_ROOT" });
+
+    for (index, endpoint) in ENDPOINTS.into_iter().enumerate() {
+        let user = UserFixture::new(&format!("User{}", index)).invited_by(&root).insert(&state.database).await;
+        let uri = format!("/v1/users/{}/{}", user.id, endpoint);
+        let response: PromoteUserResponse = client.post(&uri, &body).await.expect(StatusCode::OK);
+        assert!(response.promoted, "{}", endpoint);
+        let user = state.database.get_user_by_id(user.id).await.unwrap().unwrap();
+        assert!(user.is_root && user.can_invite);
+
+        // 既にrootユーザーなら何もしない
+        let response: PromoteUserResponse = client.post(&uri, &body).await.expect(StatusCode::OK);
+        assert!(!response.promoted);
+    }
+
+    let audit = state.database.get_audit_export_page(None, None, 10).await.unwrap();
+    assert_eq!(audit.len(), 2);
+    assert!(audit.iter().all(|entry| entry.action == "promote_to_root"));
+    assert!(audit.iter().all(|entry| entry.actor_email.as_deref() == Some("root@example.com")));
+}
+
+#[tokio::test]
+async fn promotion_requires_root_and_an_active_target() {
+    let (state, client) = setup().await;
+    let root = state.database.get_user_by_email("root@example.com").await.unwrap().unwrap();
+    let alice = UserFixture::new("Alice").invited_by(&root).can_invite().insert(&state.database).await;
+    let banned = UserFixture::new("Banned").invited_by(&root).banned().insert(&state.database).await;
+    let body = json!({ "confirm_action": "PROMOTE_TO_ROOT" });
+
+    let alice_client = client.with_session(&login_as(&state, &alice).await);
+    let response = alice_client.post(&format!("/v1/users/{}/promote", alice.id), &body).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    let response = client.post("/v1/users/9999/make-root", &body).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let response = client.post(&format!("/v1/users/{}/promote", banned.id), &body).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert!(!state.database.get_user_by_id(banned.id).await.unwrap().unwrap().is_root);
+}
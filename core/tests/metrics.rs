@@ -0,0 +1,71 @@
+//! `GET /metrics`のPrometheusのテキスト形式（ルートのパターンごとのリクエスト数・処理時間とゲージ）
+//!
+//! レコーダーはプロセスに一度しか登録できないため、`metrics_enabled`の状態を作るテストはこのファイルの1つだけにする。
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{
+    fixtures::{InviteFixture, UserFixture},
+    login_as, TestClient,
+};
+use patchouli::{build_router, config::Config, error::ErrorCode};
+
+const TOKEN: &str = "scrape-secret";
+
+#[tokio::test]
+async fn metrics_are_exposed_per_route_pattern() {
+    let state = common::state(Config {
+        metrics_enabled: true,
+        metrics_token: Some(TOKEN.to_string()),
+        ..Config::default()
+    })
+    .await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let alice = UserFixture::new("Alice").invited_by(&root).insert(&state.database).await;
+    InviteFixture::new(&root).insert(&state.database).await;
+    let app = build_router(state.clone());
+    let client = TestClient::new(app.clone()).with_session(&login_as(&state, &root).await);
+
+    for user in [&root, &alice] {
+        let uri = format!("/v1/users/{}/permissions", user.id);
+        assert_eq!(client.get(&uri).await.status, StatusCode::OK);
+    }
+    assert_eq!(client.get("/v1/users/not-a-number/permissions").await.status, StatusCode::BAD_REQUEST);
+    assert_eq!(client.get("/no/such/route").await.status, StatusCode::NOT_FOUND);
+
+    let scraper = TestClient::new(app);
+    assert_eq!(scraper.get("/metrics").await.error_code(), ErrorCode::InvalidMetricsToken);
+    let wrong = scraper.with_header("authorization", "Bearer wrong").get("/metrics").await;
+    assert_eq!(wrong.status, StatusCode::UNAUTHORIZED);
+
+    let response = scraper.with_header("authorization", &format!("Bearer {}", TOKEN)).get("/metrics").await;
+    assert_eq!(response.status, StatusCode::OK);
+    let body = String::from_utf8(response.body.to_vec()).unwrap();
+    let lines: Vec<&str> = body.lines().collect();
+    let has = |line: &str| lines.contains(&line);
+
+    // ラベルのルートはユーザーIDを含まないパターン
+    let labels = r#"method="GET",route="/v1/users/:user_id/permissions""#;
+    assert!(has(&format!(r#"http_requests_total{{{},status="200"}} 2"#, labels)), "{}", body);
+    assert!(has(&format!(r#"http_requests_total{{{},status="400"}} 1"#, labels)), "{}", body);
+    assert!(has(r#"http_requests_total{method="GET",route="unmatched",status="404"} 1"#), "{}", body);
+    assert!(!body.contains(&format!("/v1/users/{}/", alice.id)));
+    let bucket = format!(r#"http_request_duration_seconds_bucket{{{},status="200",le="0.005"}}"#, labels);
+    assert!(lines.iter().any(|line| line.starts_with(&bucket)), "{}", body);
+    assert!(has(&format!(r#"http_request_duration_seconds_count{{{},status="200"}} 2"#, labels)), "{}", body);
+
+    // スクレイプ時点のゲージ
+    assert!(has("patchouli_registered_users 2"), "{}", body);
+    assert!(has("patchouli_active_invites 1"), "{}", body);
+    assert!(has("patchouli_pending_auth_entries 0"), "{}", body);
+    assert!(lines.iter().any(|line| line.starts_with(r#"patchouli_db_pool_connections{state="idle"}"#)));
+    assert!(lines.iter().any(|line| line.starts_with(r#"patchouli_db_pool_connections{state="in_use"}"#)));
+    assert!(lines.iter().any(|line| line.starts_with("patchouli_db_pool_acquires_total ")));
+}
+
+#[tokio::test]
+async fn metrics_endpoint_is_absent_when_disabled() {
+    let state = common::state(Config::default()).await;
+    assert_eq!(common::get(&build_router(state), "/metrics").await.status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,77 @@
+//! 起動時のマイグレーションと`GET /v1/system/migrations`
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{fixtures::UserFixture, login_as, TestClient};
+use patchouli::{build_router, build_state, config::Config, database::MigrationRecord};
+use sqlx::{Connection, SqliteConnection};
+
+#[tokio::test]
+async fn lists_applied_migrations_for_root() {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let alice = UserFixture::new("Alice").invited_by(&root).can_invite().insert(&state.database).await;
+    let client = TestClient::new(build_router(state.clone()));
+
+    let root_client = client.with_session(&login_as(&state, &root).await);
+    let migrations: Vec<MigrationRecord> = root_client.get("/v1/system/migrations").await.expect(StatusCode::OK);
+    assert!(!migrations.is_empty());
+    assert_eq!((migrations[0].version, migrations[0].description.as_str()), (1, "initial schema"));
+    assert!(migrations.iter().all(|migration| migration.success));
+
+    let alice_client = client.with_session(&login_as(&state, &alice).await);
+    assert_eq!(alice_client.get("/v1/system/migrations").await.status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn upgrades_a_database_created_before_migrations() {
+    let path = std::env::temp_dir().join(format!("patchouli-migrations-{}.db", std::process::id()));
+    let database_url = format!("sqlite://{}?mode=rwc", path.display());
+
+    // マイグレーション導入前の初期のスキーマ（後から追加したカラムがない）
+    let mut connection = SqliteConnection::connect(&database_url).await.unwrap();
+    sqlx::query(
+        "CREATE TABLE registered_users (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            google_id TEXT NOT NULL UNIQUE,
+            email TEXT NOT NULL UNIQUE,
+            name TEXT NOT NULL,
+            registered_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            last_login DATETIME
+        )",
+    )
+    .execute(&mut connection)
+    .await
+    .unwrap();
+    sqlx::query("INSERT INTO registered_users (google_id, email, name) VALUES ('google-old', 'old@example.com', 'Old')")
+        .execute(&mut connection)
+        .await
+        .unwrap();
+    connection.close().await.unwrap();
+
+    let config = || Config {
+        database_url: database_url.clone(),
+        ..Config::default()
+    };
+    let state = build_state(config()).await.unwrap();
+    state.database.check_schema().await.unwrap();
+    let old = state.database.get_user_by_email("old@example.com").await.unwrap().unwrap();
+    assert!(!old.is_root && old.is_active && !old.email_verified);
+    let applied = state.database.get_applied_migrations().await.unwrap();
+    assert_eq!(applied[0].version, 1);
+    drop(state);
+
+    // 再起動しても適用済みのマイグレーションは再度実行しない
+    let state = build_state(config()).await.unwrap();
+    let reapplied = state.database.get_applied_migrations().await.unwrap();
+    assert_eq!(
+        reapplied.iter().map(|migration| (migration.version, migration.installed_on)).collect::<Vec<_>>(),
+        applied.iter().map(|migration| (migration.version, migration.installed_on)).collect::<Vec<_>>()
+    );
+    drop(state);
+
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+    }
+}
@@ -0,0 +1,106 @@
+//! ルーターの表（`routes`）とOpenAPIの定義が一致していることの確認
+
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestClient;
+use patchouli::{build_router, config::Config, routes};
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+/// `/v1/users/:user_id` → `/v1/users/{user_id}`
+fn openapi_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| match segment.strip_prefix(':') {
+            Some(name) => format!("{{{}}}", name),
+            None => segment.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+async fn spec() -> Value {
+    let state = common::state(Config {
+        api_docs_enabled: true,
+        ..Config::default()
+    })
+    .await;
+    TestClient::new(build_router(state)).get("/openapi.json").await.expect(StatusCode::OK)
+}
+
+fn spec_operations(spec: &Value) -> BTreeSet<(String, String)> {
+    let paths = spec["paths"].as_object().expect("spec has paths");
+    paths
+        .iter()
+        .flat_map(|(path, item)| {
+            let methods = item.as_object().expect("path item is an object").keys();
+            methods.map(move |method| (method.to_string(), path.to_string()))
+        })
+        .collect()
+}
+
+fn route_operations() -> BTreeSet<(String, String)> {
+    routes::documented()
+        .iter()
+        .map(|route| (route.method.as_str().to_lowercase(), openapi_path(&route.path)))
+        .collect()
+}
+
+#[tokio::test]
+async fn every_route_is_documented_and_every_documented_path_is_routed() {
+    let spec = spec().await;
+    let documented = spec_operations(&spec);
+    let routed = route_operations();
+
+    let undocumented: Vec<_> = routed.difference(&documented).collect();
+    assert!(undocumented.is_empty(), "routes missing from the OpenAPI spec: {:?}", undocumented);
+    let unrouted: Vec<_> = documented.difference(&routed).collect();
+    assert!(unrouted.is_empty(), "OpenAPI operations without a route: {:?}", unrouted);
+}
+
+#[tokio::test]
+async fn error_statuses_and_security_are_declared() {
+    let spec = spec().await;
+    let mut missing = Vec::new();
+    for route in routes::documented() {
+        let path = openapi_path(&route.path);
+        let operation = &spec["paths"][&path][route.method.as_str().to_lowercase()];
+        let responses = operation["responses"].as_object().expect("operation has responses");
+        for code in route.access.errors().iter().chain(route.errors) {
+            let status = code.status().as_u16().to_string();
+            if !responses.contains_key(&status) {
+                missing.push(format!("{} {} does not declare {} for {:?}", route.method, path, status, code));
+            }
+        }
+        let secured = operation["security"].as_array().is_some_and(|security| !security.is_empty());
+        if secured != (route.access != routes::Access::Public) {
+            missing.push(format!("{} {} declares security = {} for {:?}", route.method, path, secured, route.access));
+        }
+    }
+    assert!(missing.is_empty(), "{}", missing.join("\n"));
+}
+
+#[tokio::test]
+async fn every_documented_route_is_served() {
+    let state = common::state(Config::default()).await;
+    let client = TestClient::new(build_router(state));
+    for route in routes::documented() {
+        // パスパラメーターは存在しないIDにする（ハンドラーまで届けばよい）
+        let uri = route
+            .path
+            .split('/')
+            .map(|segment| if segment.starts_with(':') { "9999" } else { segment })
+            .collect::<Vec<_>>()
+            .join("/");
+        let body = (route.method != Method::GET && route.method != Method::DELETE).then(|| serde_json::json!({}));
+        let response = client.request(route.method.clone(), &uri, body.as_ref()).await;
+        assert_ne!(response.status, StatusCode::METHOD_NOT_ALLOWED, "{} {}", route.method, uri);
+        // ルートがない場合のaxumの404はボディが空（ハンドラーの404はJSONのエラー）
+        assert!(
+            response.status != StatusCode::NOT_FOUND || !response.body.is_empty(),
+            "{} {} is not routed",
+            route.method,
+            uri
+        );
+    }
+}
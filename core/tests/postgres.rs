@@ -0,0 +1,162 @@
+//! PostgreSQLバックエンド（`--features postgres`）で登録・招待コード・削除のフローを確かめる
+//!
+//! `DATABASE_URL`にPostgreSQLのURLを指定したときだけ実行する（未設定なら何もせず成功する）。
+//! テストごとにURLのデータベース名へ`_<ランダムな文字列>`を付けたデータベースを作成し、削除はしない
+//! （CIでは使い捨てのコンテナに対して実行する）。ローカルでは`docker compose up -d postgres`で起動できる。
+#![cfg(feature = "postgres")]
+
+mod common;
+
+use axum::http::StatusCode;
+use chrono::{DateTime, Duration, Utc};
+use common::{
+    fixtures::{InviteFixture, UserFixture},
+    google, login_as, TestClient,
+};
+use patchouli::{
+    build_router, build_state_with_clock,
+    clock::{MockClock, SharedClock, SystemClock},
+    config::Config,
+    error::ErrorCode,
+    AppState, AuthResponse, CanBeDeletedResponse, CreateApiKeyResponse, DeleteUserResponse, InviteCodeResponse,
+    InviteCodesListResponse, UsersListResponse,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+/// `DATABASE_URL`のPostgreSQLにこのテスト用のデータベースを作って状態を作る（未設定なら`None`）
+async fn postgres_state(config: Config, clock: SharedClock) -> Option<AppState> {
+    let Ok(database_url) = std::env::var("DATABASE_URL") else {
+        eprintln!("DATABASE_URL is not set; skipping PostgreSQL test");
+        return None;
+    };
+    let mut url = reqwest::Url::parse(&database_url).expect("DATABASE_URL should be a URL");
+    assert!(url.scheme().starts_with("postgres"), "DATABASE_URL should point to PostgreSQL: {}", url.scheme());
+    let name = url.path().trim_start_matches('/');
+    let name = if name.is_empty() { "patchouli_test" } else { name };
+    let suffix = uuid::Uuid::new_v4().simple().to_string();
+    url.set_path(&format!("{}_{}", name, &suffix[..12]));
+
+    let config = Config {
+        database_url: url.to_string(),
+        ..config
+    };
+    Some(build_state_with_clock(config, clock).await.expect("state should build with PostgreSQL"))
+}
+
+async fn one_tap(client: &TestClient, sub: &str, email: &str, invite_code: Option<&str>) -> common::TestResponse {
+    let body = json!({
+        "grant_type": "google_id_token",
+        "id_token": google::id_token(sub, email, "Test User"),
+        "invite_code": invite_code,
+    });
+    client.post("/v1/auth/tokens/google-one-tap", &body).await
+}
+
+#[tokio::test]
+async fn users_register_with_invites() {
+    let config = Config {
+        google_client_id: google::CLIENT_ID.to_string(),
+        google_jwks_url: google::jwks_server().await,
+        ..Config::default()
+    };
+    let Some(state) = postgres_state(config, Arc::new(SystemClock)).await else {
+        return;
+    };
+    let client = TestClient::new(build_router(state));
+
+    // 最初のユーザーは招待なしでrootユーザーになる
+    let auth: AuthResponse = one_tap(&client, "google-root", "root@example.com", None).await.expect(StatusCode::OK);
+    let root_client = client.with_session(&auth.session_id);
+    let response = one_tap(&client, "google-guest", "guest@example.com", None).await;
+    assert_eq!((response.status, response.error_code()), (StatusCode::FORBIDDEN, ErrorCode::InviteRequired));
+
+    let invite: InviteCodeResponse = root_client.get("/v1/invite/create").await.expect(StatusCode::OK);
+    let auth: AuthResponse = one_tap(&client, "google-guest", "guest@example.com", Some(&invite.invite_code))
+        .await
+        .expect(StatusCode::OK);
+    let guest: Value = client.with_session(&auth.session_id).get("/v1/dashboard").await.expect(StatusCode::OK);
+    assert_eq!(guest["user"]["is_root"], json!(false));
+
+    // 使用済みの招待コードでは登録できない
+    let response = one_tap(&client, "google-other", "other@example.com", Some(&invite.invite_code)).await;
+    assert_eq!((response.status, response.error_code()), (StatusCode::FORBIDDEN, ErrorCode::InvalidInvite));
+
+    let users: UsersListResponse = root_client.get("/v1/admin/users").await.expect(StatusCode::OK);
+    assert_eq!(users.users.len(), 2);
+    let root = users.users.iter().find(|user| user.email == "root@example.com").unwrap();
+    let guest = users.users.iter().find(|user| user.email == "guest@example.com").unwrap();
+    assert!(root.is_root && !guest.is_root);
+    assert_eq!(guest.invited_by, Some(root.id));
+    let invites: InviteCodesListResponse = root_client.get("/v1/invite/list?used=true").await.expect(StatusCode::OK);
+    assert_eq!(invites.invite_codes.len(), 1);
+    assert_eq!(invites.invite_codes[0].used_by, Some(guest.id));
+}
+
+fn day(days: i64) -> DateTime<Utc> {
+    "2026-03-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap() + Duration::days(days)
+}
+
+#[tokio::test]
+async fn invites_are_filtered_by_state() {
+    let clock = Arc::new(MockClock::new(day(0)));
+    let Some(state) = postgres_state(Config::default(), clock.clone()).await else {
+        return;
+    };
+    let db = &state.database;
+    let root = UserFixture::new("Root").root().insert(db).await;
+    let bob = UserFixture::new("Bob").invited_by(&root).insert(db).await;
+    let unlimited = InviteFixture::new(&root).insert(db).await.id;
+    let used = InviteFixture::new(&root).used_by(&bob).insert(db).await.id;
+    let revoked = InviteFixture::new(&root).revoked().insert(db).await.id;
+    let expired = InviteFixture::new(&root).expires_in(Duration::hours(12)).insert(db).await.id;
+    let expires_now = InviteFixture::new(&root).expires_in(Duration::days(1)).insert(db).await.id;
+    clock.set(day(1));
+    let client = TestClient::new(build_router(state.clone())).with_session(&login_as(&state, &root).await);
+
+    for (query, expected) in [
+        ("is_active=false", vec![revoked]),
+        ("used=true", vec![used]),
+        ("expired=true", vec![expired]),
+        ("expired=false&used=false&is_active=true", vec![unlimited, expires_now]),
+    ] {
+        let list: InviteCodesListResponse =
+            client.get(&format!("/v1/invite/list?{}", query)).await.expect(StatusCode::OK);
+        let mut ids: Vec<i64> = list.invite_codes.into_iter().map(|invite| invite.id).collect();
+        ids.sort();
+        assert_eq!(ids, expected, "{}", query);
+    }
+}
+
+#[tokio::test]
+async fn deleting_a_user_removes_their_invites_and_keys() {
+    let Some(state) = postgres_state(Config::default(), Arc::new(SystemClock)).await else {
+        return;
+    };
+    let db = &state.database;
+    let root = UserFixture::new("Root").root().insert(db).await;
+    let alice = UserFixture::new("Alice").can_invite().invited_by(&root).insert(db).await;
+    let app = build_router(state.clone());
+    let root_client = TestClient::new(app.clone()).with_session(&login_as(&state, &root).await);
+    let alice_client = TestClient::new(app.clone()).with_session(&login_as(&state, &alice).await);
+
+    alice_client.get("/v1/invite/create").await.expect::<InviteCodeResponse>(StatusCode::OK);
+    let body = json!({ "name": "CI", "scopes": ["read:users"] });
+    let key: CreateApiKeyResponse = alice_client.post("/v1/api-keys", &body).await.expect(StatusCode::CREATED);
+    let key_client = TestClient::new(app).with_header("X-Api-Key", &key.key);
+    key_client.get("/v1/userinfo").await.expect::<Value>(StatusCode::OK);
+
+    let uri = format!("/v1/admin/users/{}/can-be-deleted", alice.id);
+    let check: CanBeDeletedResponse = root_client.get(&uri).await.expect(StatusCode::OK);
+    assert!(!check.can_delete);
+
+    let deleted: DeleteUserResponse =
+        root_client.delete(&format!("/v1/admin/users/{}", alice.id)).await.expect(StatusCode::OK);
+    assert!(deleted.success);
+    let users: UsersListResponse = root_client.get("/v1/admin/users").await.expect(StatusCode::OK);
+    assert_eq!(users.users.iter().map(|user| user.id).collect::<Vec<_>>(), [root.id]);
+    let invites: InviteCodesListResponse = root_client.get("/v1/invite/list?all=true").await.expect(StatusCode::OK);
+    assert!(invites.invite_codes.is_empty());
+    assert_eq!(key_client.get("/v1/userinfo").await.status, StatusCode::UNAUTHORIZED);
+    assert!(db.get_user_by_id(alice.id).await.unwrap().is_none());
+}
@@ -0,0 +1,66 @@
+//! 認証付きリクエストあたりのデータベースへの問い合わせ（コネクションプールが接続を渡した回数）
+//!
+//! 認証で取得したユーザーはextensionsの`Arc<RegisteredUser>`でハンドラーに渡すため、
+//! ハンドラーが同じユーザーを取得し直すことはない（以前は認証とハンドラーで2回取得していた）。
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{fixtures::UserFixture, login_as, TestClient, TestResponse};
+use patchouli::{build_router, config::Config, database::RegisteredUser, AppState, CreateApiKeyResponse};
+use serde_json::json;
+
+/// rootユーザーとしてログインした状態（`user_cache_ttl_secs`が0なら認証のたびにユーザーを取得する）
+async fn setup(user_cache_ttl_secs: u64) -> (AppState, RegisteredUser, TestClient) {
+    let config = Config {
+        user_cache_ttl_secs,
+        ..Config::default()
+    };
+    let state = common::state(config).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let client = TestClient::new(build_router(state.clone()));
+    (state, root, client)
+}
+
+/// `request`の間にプールが接続を渡した回数
+async fn acquires(state: &AppState, request: impl Future<Output = TestResponse>) -> u64 {
+    let before = state.database.pool_status().acquired;
+    let response = request.await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+    state.database.pool_status().acquired - before
+}
+
+#[tokio::test]
+async fn handlers_reuse_the_authenticated_user() {
+    let (state, root, client) = setup(0).await;
+    let client = client.with_session(&login_as(&state, &root).await);
+
+    // 認証のユーザー取得の1回だけ（ハンドラーはextensionsのユーザーを使う）
+    assert_eq!(acquires(&state, client.get("/v1/userinfo")).await, 1);
+    assert_eq!(acquires(&state, client.get(&format!("/v1/users/{}/permissions", root.id))).await, 1);
+    assert_eq!(acquires(&state, client.get(&format!("/v1/users/{}/metadata", root.id))).await, 1);
+
+    // RootUserとハンドラーの処理（一覧の取得）で2回
+    assert_eq!(acquires(&state, client.get("/v1/admin/users")).await, 2);
+}
+
+#[tokio::test]
+async fn user_cache_removes_the_authentication_query() {
+    let (state, root, client) = setup(60).await;
+    let client = client.with_session(&login_as(&state, &root).await);
+
+    assert_eq!(acquires(&state, client.get("/v1/userinfo")).await, 1);
+    assert_eq!(acquires(&state, client.get("/v1/userinfo")).await, 0);
+}
+
+#[tokio::test]
+async fn api_keys_add_only_the_key_lookup() {
+    let (state, root, client) = setup(0).await;
+    let session_client = client.with_session(&login_as(&state, &root).await);
+    let body = json!({ "name": "CI", "scopes": ["read:users"] });
+    let created: CreateApiKeyResponse = session_client.post("/v1/api-keys", &body).await.expect(StatusCode::CREATED);
+    let key_client = client.with_header("X-Api-Key", &created.key);
+
+    // キーの検索と作成者の取得
+    assert_eq!(acquires(&state, key_client.get("/v1/userinfo")).await, 2);
+}
@@ -0,0 +1,53 @@
+//! `/healthz`・`/readyz`（データベースに接続できない・スキーマが古い場合の503と理由）
+
+mod common;
+
+use axum::http::StatusCode;
+use common::TestClient;
+use patchouli::{build_router, build_state, config::Config, HealthResponse};
+use sqlx::{Connection, SqliteConnection};
+
+#[tokio::test]
+async fn closed_pool_makes_readyz_unavailable() {
+    let state = common::state(Config::default()).await;
+    let client = TestClient::new(build_router(state.clone()));
+    let ready: HealthResponse = client.get("/readyz").await.expect(StatusCode::OK);
+    assert_eq!(ready.status, "ready");
+
+    state.database.close().await;
+
+    let unavailable: HealthResponse = client.get("/readyz").await.expect(StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(unavailable.status, "unavailable");
+    assert_eq!(unavailable.reason.as_deref(), Some("database unreachable"));
+    // 生存確認はデータベースに触れない
+    let live: HealthResponse = client.get("/healthz").await.expect(StatusCode::OK);
+    assert_eq!(live.status, "ok");
+}
+
+#[tokio::test]
+async fn outdated_schema_makes_readyz_unavailable() {
+    let path = std::env::temp_dir().join(format!("patchouli-readiness-{}.db", std::process::id()));
+    let database_url = format!("sqlite://{}", path.display());
+    let state = build_state(Config {
+        database_url: database_url.clone(),
+        ..Config::default()
+    })
+    .await
+    .unwrap();
+    let client = TestClient::new(build_router(state.clone()));
+    client.get("/readyz").await.expect::<HealthResponse>(StatusCode::OK);
+
+    // 別のプロセスが古いバージョンのスキーマに戻した状態（新しいテーブルがない）
+    let mut connection = SqliteConnection::connect(&database_url).await.unwrap();
+    sqlx::query("ALTER TABLE api_keys RENAME TO api_keys_old").execute(&mut connection).await.unwrap();
+    connection.close().await.unwrap();
+
+    let unavailable: HealthResponse = client.get("/readyz").await.expect(StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(unavailable.reason.as_deref(), Some("database schema is not up to date"));
+
+    state.database.close().await;
+    // WALのファイルは接続が閉じるまで残ることがある
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+    }
+}
@@ -0,0 +1,59 @@
+//! `X-Request-Id`の引き継ぎ・採番と、エラーのボディ・リクエストのスパンへの反映
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{spans, TestClient};
+use patchouli::{
+    build_router,
+    clock::SystemClock,
+    config::Config,
+    error::{ErrorCode, ErrorResponse},
+    ids::SequentialIds,
+};
+use std::sync::Arc;
+
+async fn client() -> TestClient {
+    let state = common::state_with(Config::default(), Arc::new(SystemClock), Arc::new(SequentialIds::new())).await;
+    // 存在しないセッションで401のエラーを返させる
+    TestClient::new(build_router(state)).with_session("unknown-session")
+}
+
+#[tokio::test]
+async fn incoming_request_ids_are_passed_through() {
+    let (spans, _guard) = spans::capture();
+    let response = client().await.with_header("x-request-id", "abc-123").get("/v1/dashboard").await;
+
+    assert_eq!(response.headers["x-request-id"], "abc-123");
+    let error: ErrorResponse = response.expect(StatusCode::UNAUTHORIZED);
+    assert_eq!(error.error, ErrorCode::InvalidSession);
+    assert_eq!(error.request_id.as_deref(), Some("abc-123"));
+
+    let requests = spans.named("request");
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].field("request_id"), Some("abc-123"));
+    assert_eq!(requests[0].field("uri"), Some("/v1/dashboard"));
+}
+
+#[tokio::test]
+async fn request_ids_are_generated_when_missing_or_unacceptable() {
+    let (spans, _guard) = spans::capture();
+    let client = client().await;
+    let long = "a".repeat(129);
+
+    let responses = [
+        client.get("/v1/dashboard").await,
+        client.with_header("x-request-id", &long).get("/v1/dashboard").await,
+        client.with_header("x-request-id", "has space").get("/v1/dashboard").await,
+    ];
+
+    let requests = spans.named("request");
+    assert_eq!(requests.len(), responses.len());
+    for (n, (response, span)) in responses.iter().zip(&requests).enumerate() {
+        let expected = format!("00000000-0000-0000-0000-{:012}", n + 1);
+        assert_eq!(response.headers["x-request-id"], expected.as_str());
+        let error: ErrorResponse = response.expect(StatusCode::UNAUTHORIZED);
+        assert_eq!(error.request_id.as_deref(), Some(expected.as_str()));
+        assert_eq!(span.field("request_id"), Some(expected.as_str()));
+    }
+}
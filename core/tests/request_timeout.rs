@@ -0,0 +1,49 @@
+//! `request_timeout_secs`を超えたリクエストはJSONのエラー（504 `timeout`）になる
+
+mod common;
+
+use axum::{http::StatusCode, routing::get, Router};
+use common::{google, TestClient};
+use patchouli::{
+    build_router,
+    config::Config,
+    error::{ErrorCode, ErrorResponse},
+};
+use serde_json::json;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+
+/// 応答しないGoogleの公開鍵のエンドポイント（ID Tokenの検証を止める）
+async fn stalled_jwks_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = Router::new().route("/certs", get(|| tokio::time::sleep(Duration::from_secs(60))));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}/certs", addr)
+}
+
+#[tokio::test]
+async fn slow_requests_time_out_as_json() {
+    let state = common::state(Config {
+        google_client_id: google::CLIENT_ID.to_string(),
+        google_jwks_url: stalled_jwks_server().await,
+        request_timeout_secs: 1,
+        ..Config::default()
+    })
+    .await;
+    let client = TestClient::new(build_router(state)).with_header("x-request-id", "slow-1");
+
+    let started = Instant::now();
+    let id_token = google::id_token("google-guest", "guest@example.com", "Guest");
+    let body = json!({ "grant_type": "google_id_token", "id_token": id_token });
+    let response = client.post("/v1/auth/tokens/google-one-tap", &body).await;
+    assert!(started.elapsed() < Duration::from_secs(10));
+
+    assert_eq!(response.headers["content-type"], "application/json");
+    let error: ErrorResponse = response.expect(StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(error.error, ErrorCode::Timeout);
+    assert_eq!(error.request_id.as_deref(), Some("slow-1"));
+
+    // 他のリクエストには影響しない
+    assert_eq!(client.get("/healthz").await.status, StatusCode::OK);
+}
@@ -0,0 +1,250 @@
+//! 公開しているレスポンスの形をスナップショットで固定する（フィールド名の変更等を見逃さないため）
+//!
+//! 時刻は`MockClock`、セッションID・招待コード等は`SequentialIds`で固定する。
+//! `/v1/system/migrations`（適用日時はデータベースが記録する）とSSEの`/v1/events`は対象外。
+//! レスポンスを変えた場合は`UPDATE_SNAPSHOTS=1 cargo test --test response_snapshots`で`tests/snapshots/`を更新し、
+//! 差分をレビューしてコミットする。
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Method, Request, StatusCode},
+};
+use chrono::{DateTime, Duration, Utc};
+use common::{
+    fixtures::{InviteFixture, Scenario, ScenarioBuilder, UserFixture},
+    google, login_as,
+    snapshot::assert_snapshot,
+    TestClient,
+};
+use patchouli::{
+    build_router,
+    clock::{Clock, MockClock},
+    config::Config,
+    database::WeeklyStats,
+    ids::SequentialIds,
+    AppState,
+};
+use serde_json::json;
+use std::sync::Arc;
+
+fn fixed_now() -> DateTime<Utc> {
+    "2024-01-15T09:00:00Z".parse().unwrap()
+}
+
+async fn setup() -> (AppState, Arc<MockClock>, Scenario) {
+    let clock = Arc::new(MockClock::new(fixed_now()));
+    let config = Config {
+        google_client_id: google::CLIENT_ID.to_string(),
+        google_jwks_url: google::jwks_server().await,
+        ..Config::default()
+    };
+    let state = common::state_with(config, clock.clone(), Arc::new(SequentialIds::new())).await;
+    let scenario = ScenarioBuilder::default().insert(&state.database).await;
+    // 登録・招待より後の時刻にして、統計の「今週」・期限切れ等が毎回同じになるようにする
+    clock.advance(Duration::hours(1));
+    (state, clock, scenario)
+}
+
+async fn clients(state: &AppState, scenario: &Scenario) -> (TestClient, TestClient, TestClient) {
+    let anonymous = TestClient::new(build_router(state.clone()));
+    let root = anonymous.with_session(&login_as(state, &scenario.root).await);
+    let inviter = anonymous.with_session(&login_as(state, &scenario.inviter).await);
+    (anonymous, root, inviter)
+}
+
+#[tokio::test]
+async fn system_and_auth_responses() {
+    let (state, clock, scenario) = setup().await;
+    let (anonymous, root, _) = clients(&state, &scenario).await;
+
+    assert_snapshot("healthz", &anonymous.get("/healthz").await);
+    assert_snapshot("readyz", &anonymous.get("/readyz").await);
+    assert_snapshot("root_exists", &anonymous.get("/v1/root/exists").await);
+    assert_snapshot("system_status", &anonymous.get("/v1/system/status").await);
+    assert_snapshot("system_errors", &anonymous.get("/v1/system/errors").await);
+    assert_snapshot("system_pending_actions", &root.get("/v1/system/pending-actions").await);
+
+    let response = anonymous.get("/v1/login/api").await;
+    assert_snapshot("login_api", &response);
+    let auth_token = response.json::<serde_json::Value>()["auth_token"].as_str().unwrap().to_string();
+    assert_snapshot("auth_status_pending", &anonymous.get(&format!("/v1/auth/status/{}", auth_token)).await);
+    assert_snapshot("auth_status_not_found", &anonymous.get("/v1/auth/status/unknown").await);
+
+    let invite = &scenario.pending_invite;
+    let id_token = google::id_token_at("google-guest", "guest@example.com", "Guest", clock.now());
+    let body = json!({ "grant_type": "google_id_token", "id_token": id_token, "invite_code": invite.code });
+    assert_snapshot("google_one_tap", &anonymous.post("/v1/auth/tokens/google-one-tap", &body).await);
+    let body = json!({ "grant_type": "google_id_token", "id_token": "not-a-token" });
+    assert_snapshot("google_one_tap_invalid_token", &anonymous.post("/v1/auth/tokens/google-one-tap", &body).await);
+    let id_token = google::id_token_at("google-stranger", "stranger@example.com", "Stranger", clock.now());
+    let body = json!({ "grant_type": "google_id_token", "id_token": id_token });
+    assert_snapshot("google_one_tap_invite_required", &anonymous.post("/v1/auth/tokens/google-one-tap", &body).await);
+
+    assert_snapshot("missing_session", &anonymous.get("/v1/dashboard").await);
+    assert_snapshot("invalid_session", &anonymous.with_session("expired").get("/v1/dashboard").await);
+    assert_snapshot("dashboard", &root.get("/v1/dashboard").await);
+    assert_snapshot("userinfo", &root.get("/v1/userinfo").await);
+}
+
+#[tokio::test]
+async fn invite_responses() {
+    let (state, _clock, scenario) = setup().await;
+    let (_, root, inviter) = clients(&state, &scenario).await;
+    let banned = TestClient::new(build_router(state.clone())).with_session(&login_as(&state, &scenario.banned).await);
+    let invitee =
+        TestClient::new(build_router(state.clone())).with_session(&login_as(&state, &scenario.invitees[0]).await);
+
+    assert_snapshot("invite_create", &inviter.get("/v1/invite/create").await);
+    assert_snapshot("invite_create_forbidden", &invitee.get("/v1/invite/create").await);
+    assert_snapshot("banned", &banned.get("/v1/invite/create").await);
+    assert_snapshot("invite_list", &inviter.get("/v1/invite/list").await);
+    assert_snapshot("invite_list_all", &root.get("/v1/invite/list?all=true&expired=false").await);
+
+    let pending = scenario.pending_invite.id;
+    let body = json!({ "note": "updated", "expires_in_hours": 48 });
+    assert_snapshot("invite_update", &inviter.patch(&format!("/v1/invite/{}", pending), &body).await);
+    let body = json!({ "note": "x".repeat(201), "expires_in_hours": 0 });
+    assert_snapshot("invite_update_invalid", &inviter.patch(&format!("/v1/invite/{}", pending), &body).await);
+    assert_snapshot("invite_update_not_found", &inviter.patch("/v1/invite/9999", &json!({ "note": "x" })).await);
+    let uri = format!("/v1/invite/{}/resend-notification", pending);
+    assert_snapshot("invite_resend_notification", &inviter.post(&uri, &json!({})).await);
+    let uri = format!("/v1/invite/{}/clone", scenario.expired_invite.id);
+    assert_snapshot("invite_clone", &inviter.post(&uri, &json!({})).await);
+    let uri = format!("/v1/invite/{}/transfer", pending);
+    assert_snapshot("invite_transfer", &root.post(&uri, &json!({ "new_owner_id": scenario.root.id })).await);
+    assert_snapshot("invite_transfer_invalid", &root.post(&uri, &json!({ "new_owner_id": 0 })).await);
+    let response = root.request(Method::DELETE, "/v1/invite/expired?dry_run=true", None).await;
+    assert_snapshot("invite_delete_expired_dry_run", &response);
+    assert_snapshot("invite_delete_expired", &root.request(Method::DELETE, "/v1/invite/expired", None).await);
+}
+
+#[tokio::test]
+async fn user_responses() {
+    let (state, _clock, scenario) = setup().await;
+    let (_, root, inviter) = clients(&state, &scenario).await;
+    let target = scenario.invitees[0].id;
+
+    assert_snapshot("user_permissions", &inviter.get(&format!("/v1/users/{}/permissions", scenario.inviter.id)).await);
+    assert_snapshot("user_permissions_forbidden", &inviter.get(&format!("/v1/users/{}/permissions", target)).await);
+    let uri = format!("/v1/users/{}/metadata", target);
+    assert_snapshot("user_metadata_update", &root.patch(&uri, &json!({ "theme": "dark", "locale": "ja" })).await);
+    assert_snapshot("user_metadata", &root.get(&uri).await);
+    assert_snapshot("user_metadata_invalid_body", &root.patch(&uri, &json!([1, 2])).await);
+
+    let uri = format!("/v1/users/{}/can-invite", target);
+    assert_snapshot("user_can_invite", &root.patch(&uri, &json!({ "can_invite": true })).await);
+    let uri = format!("/v1/users/{}/can-invite", scenario.root.id);
+    assert_snapshot("user_can_invite_self", &root.patch(&uri, &json!({ "can_invite": false })).await);
+
+    let uri = format!("/v1/users/{}/promote", target);
+    assert_snapshot("user_promote_mismatch", &root.post(&uri, &json!({ "confirm_action": "promote" })).await);
+    let uri = format!("/v1/users/{}/make-root", target);
+    assert_snapshot("user_make_root", &root.post(&uri, &json!({ "confirm_action": "PROMOTE_TO_ROOT" })).await);
+    let body = json!({ "confirm_action": "PROMOTE_TO_ROOT" });
+    assert_snapshot("user_not_found", &root.post("/v1/users/9999/promote", &body).await);
+}
+
+#[tokio::test]
+async fn admin_responses() {
+    let (state, _clock, scenario) = setup().await;
+    let (_, root, inviter) = clients(&state, &scenario).await;
+    let target = scenario.invitees[1].id;
+
+    assert_snapshot("admin_forbidden", &inviter.get("/v1/admin/users").await);
+    assert_snapshot("admin_users", &root.get("/v1/admin/users").await);
+    assert_snapshot("admin_users_invalid_filter", &root.get("/v1/admin/users?invited_by=abc").await);
+    assert_snapshot("admin_stats", &root.get("/v1/admin/stats").await);
+    assert_snapshot("admin_stats_timeseries", &root.get("/v1/admin/stats/timeseries?weeks=2").await);
+    assert_snapshot("admin_overview", &root.get("/v1/admin/overview").await);
+
+    let uri = format!("/v1/admin/users/{}/can-be-deleted", scenario.inviter.id);
+    assert_snapshot("admin_user_can_be_deleted", &root.get(&uri).await);
+    assert_snapshot("admin_user_ban", &root.post(&format!("/v1/admin/users/{}/ban", target), &json!({})).await);
+    let uri = format!("/v1/admin/users/{}/ban", scenario.root.id);
+    assert_snapshot("admin_user_ban_self", &root.post(&uri, &json!({})).await);
+    assert_snapshot("admin_user_unban", &root.post(&format!("/v1/admin/users/{}/unban", target), &json!({})).await);
+    assert_snapshot("admin_user_delete", &root.delete(&format!("/v1/admin/users/{}", target)).await);
+
+    let entries = [
+        json!({ "actor_user_id": scenario.root.id, "action": "ban_user", "target_user_id": scenario.banned.id,
+                "created_at": "2023-12-01T00:00:00Z" }),
+        json!({ "actor_user_id": null, "action": "note", "target_user_id": null, "metadata": { "source": "legacy" },
+                "created_at": "2023-12-02T00:00:00Z" }),
+    ];
+    let body: String = entries.iter().map(|entry| format!("{}\n", entry)).collect();
+    let request = Request::post("/v1/admin/import/audit-log")
+        .header("authorization", format!("Bearer session-{}", scenario.root.id))
+        .header("content-type", "application/x-ndjson")
+        .body(Body::from(body))
+        .unwrap();
+    let app = build_router(state.clone());
+    assert_snapshot("admin_import_audit_log", &common::send(&app, request).await);
+
+    assert_snapshot("admin_export_users", &root.get("/v1/admin/export/users.csv").await);
+    assert_snapshot("admin_export_invites", &root.get("/v1/admin/export/invites.csv").await);
+    assert_snapshot("admin_export_audit_log", &root.get("/v1/admin/export/audit-log.csv").await);
+}
+
+/// `time`（UTC）に時計を合わせる
+fn at(clock: &MockClock, time: &str) {
+    clock.set(time.parse().unwrap());
+}
+
+#[tokio::test]
+async fn admin_stats_timeseries_buckets_by_week() {
+    let clock = Arc::new(MockClock::new(fixed_now()));
+    let state = common::state_with_clock(Config::default(), clock.clone()).await;
+    let db = &state.database;
+
+    // 範囲外（2023-12-25の週）の最後の1秒
+    at(&clock, "2023-12-31T23:59:59Z");
+    let root = UserFixture::new("Root").root().insert(db).await;
+    // 2024-01-01の週: 開始時刻ちょうどの登録と、最後の1秒に作成した招待コード
+    at(&clock, "2024-01-01T00:00:00Z");
+    UserFixture::new("Eve").invited_by(&root).insert(db).await;
+    at(&clock, "2024-01-07T23:59:59Z");
+    let early_invite = InviteFixture::new(&root).insert(db).await;
+    // 2024-01-08の週は何もしない
+    // 2024-01-15の週: 前の週に作成した招待コードの使用（使用は使用日時の週に数える）
+    at(&clock, "2024-01-15T00:00:00Z");
+    InviteFixture::new(&root).insert(db).await;
+    at(&clock, "2024-01-17T10:00:00Z");
+    let alice = UserFixture::new("Alice").invited_by(&root).insert(db).await;
+    db.use_invite_code(&early_invite.code, alice.id).await.unwrap();
+    // 2024-01-22の週（今週）
+    at(&clock, "2024-01-22T00:00:00Z");
+    InviteFixture::new(&root).insert(db).await;
+    InviteFixture::new(&root).insert(db).await;
+    at(&clock, "2024-01-23T08:00:00Z");
+    UserFixture::new("Bob").invited_by(&root).insert(db).await;
+    at(&clock, "2024-01-25T12:00:00Z");
+
+    let client = TestClient::new(build_router(state.clone())).with_session(&login_as(&state, &root).await);
+    let weeks = |weeks: u32| {
+        let client = client.clone();
+        async move {
+            let uri = format!("/v1/admin/stats/timeseries?weeks={}", weeks);
+            let stats: Vec<WeeklyStats> = client.get(&uri).await.expect(StatusCode::OK);
+            stats
+                .into_iter()
+                .map(|week| (week.week_start, week.new_users, week.new_invites, week.invite_uses))
+                .collect::<Vec<_>>()
+        }
+    };
+    let bucket = |week_start: &str, users: i64, invites: i64, uses: i64| (week_start.to_string(), users, invites, uses);
+
+    assert_eq!(
+        weeks(4).await,
+        [
+            bucket("2024-01-01", 1, 1, 0),
+            bucket("2024-01-08", 0, 0, 0),
+            bucket("2024-01-15", 1, 1, 1),
+            bucket("2024-01-22", 1, 2, 0),
+        ]
+    );
+    // 範囲を広げると前の週も含む。1週なら今週のみ
+    assert_eq!(weeks(5).await[0], bucket("2023-12-25", 1, 0, 0));
+    assert_eq!(weeks(1).await, [bucket("2024-01-22", 1, 2, 0)]);
+}
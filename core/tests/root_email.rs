@@ -0,0 +1,103 @@
+//! `ROOT_EMAIL`によるrootユーザーの決定（登録順に関係なく設定したメールアドレスのユーザーがrootになる）
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{fixtures::UserFixture, google, TestClient};
+use patchouli::{build_router, build_state, config::Config, error::ErrorCode, AppState, AuthResponse};
+use serde_json::json;
+
+const ROOT_EMAIL: &str = "admin@example.com";
+
+async fn one_tap_state(root_email: &str) -> AppState {
+    common::state(Config {
+        google_client_id: google::CLIENT_ID.to_string(),
+        google_jwks_url: google::jwks_server().await,
+        root_email: Some(root_email.to_string()),
+        ..Config::default()
+    })
+    .await
+}
+
+async fn one_tap(client: &TestClient, sub: &str, email: &str, invite_code: Option<&str>) -> common::TestResponse {
+    let body = json!({
+        "grant_type": "google_id_token",
+        "id_token": google::id_token(sub, email, "Test User"),
+        "invite_code": invite_code,
+    });
+    client.post("/v1/auth/tokens/google-one-tap", &body).await
+}
+
+#[tokio::test]
+async fn first_user_is_not_root_when_root_email_is_set() {
+    let state = one_tap_state(ROOT_EMAIL).await;
+    let client = TestClient::new(build_router(state.clone()));
+
+    // 最初の登録でもROOT_EMAIL以外は招待コードが必要
+    let response = one_tap(&client, "google-first", "first@example.com", None).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    assert_eq!(response.error_code(), ErrorCode::InviteRequired);
+    assert_eq!(state.database.count_registered_users().await.unwrap(), 0);
+
+    // 大文字・小文字を区別せずに一致すれば招待コードなしでrootユーザーになる
+    let auth: AuthResponse = one_tap(&client, "google-admin", "Admin@Example.com", None).await.expect(StatusCode::OK);
+    let admin = state.database.get_user_by_email(&auth.user_email).await.unwrap().unwrap();
+    assert!(admin.is_root && admin.can_invite);
+
+    let invite = state.database.create_invite_code(admin.id).await.unwrap();
+    one_tap(&client, "google-first", "first@example.com", Some(&invite.code)).await.expect::<AuthResponse>(StatusCode::OK);
+    let first = state.database.get_user_by_email("first@example.com").await.unwrap().unwrap();
+    assert!(!first.is_root);
+}
+
+#[tokio::test]
+async fn root_email_registers_as_root_after_other_users() {
+    let state = one_tap_state(ROOT_EMAIL).await;
+    // ROOT_EMAILを設定する前から運用していたインスタンス
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    UserFixture::new("Alice").invited_by(&root).insert(&state.database).await;
+    let client = TestClient::new(build_router(state.clone()));
+
+    one_tap(&client, "google-admin", ROOT_EMAIL, None).await.expect::<AuthResponse>(StatusCode::OK);
+    let admin = state.database.get_user_by_email(ROOT_EMAIL).await.unwrap().unwrap();
+    assert!(admin.is_root && admin.can_invite);
+    assert_eq!(admin.invited_by, None);
+}
+
+#[tokio::test]
+async fn existing_user_is_granted_root_on_startup() {
+    let path = std::env::temp_dir().join(format!("patchouli-root-email-{}.db", std::process::id()));
+    let database_url = format!("sqlite://{}", path.display());
+    let config = |root_email: Option<&str>| Config {
+        database_url: database_url.clone(),
+        root_email: root_email.map(str::to_string),
+        ..Config::default()
+    };
+
+    let state = build_state(config(None)).await.unwrap();
+    UserFixture::new("Root").root().insert(&state.database).await;
+    // 招待なしで登録した2人目以降は一般ユーザーになる
+    let admin = UserFixture::new("Admin").insert(&state.database).await;
+    assert!(!admin.is_root);
+    drop(state);
+
+    // 再起動時にROOT_EMAILのユーザーが一般ユーザーならrootユーザーに変更し、監査ログに残す
+    let state = build_state(config(Some("ADMIN@example.com"))).await.unwrap();
+    let admin = state.database.get_user_by_id(admin.id).await.unwrap().unwrap();
+    assert!(admin.is_root && admin.can_invite);
+    let audit = state.database.get_audit_export_page(None, None, 10).await.unwrap();
+    assert_eq!(audit[0].action, "grant_root");
+    assert_eq!((audit[0].actor_email.as_deref(), audit[0].target_email.as_deref()), (None, Some(ROOT_EMAIL)));
+    drop(state);
+
+    // すでにrootユーザーなら何もしない
+    let state = build_state(config(Some(ROOT_EMAIL))).await.unwrap();
+    assert!(state.database.grant_root(ROOT_EMAIL).await.unwrap().is_none());
+    assert_eq!(state.database.get_audit_export_page(None, None, 10).await.unwrap().len(), audit.len());
+    drop(state);
+
+    // WALのファイルは接続が閉じるまで残ることがある
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+    }
+}
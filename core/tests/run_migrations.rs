@@ -0,0 +1,54 @@
+//! 開発用の`POST /v1/system/run-migrations`（`dev`フィーチャー（`dev-tools`に含まれる）のデバッグビルドのみ）
+#![cfg(all(feature = "dev", debug_assertions))]
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{fixtures::UserFixture, login_as, TestClient};
+use patchouli::{build_router, build_state, config::Config, database::MigrationRecord};
+use serde_json::json;
+use sqlx::{Connection, SqliteConnection};
+
+const URI: &str = "/v1/system/run-migrations";
+
+#[tokio::test]
+async fn applies_pending_migrations_once() {
+    let path = std::env::temp_dir().join(format!("patchouli-run-migrations-{}.db", std::process::id()));
+    let database_url = format!("sqlite://{}?mode=rwc", path.display());
+    let state = build_state(Config {
+        database_url: database_url.clone(),
+        ..Config::default()
+    })
+    .await
+    .unwrap();
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let alice = UserFixture::new("Alice").insert(&state.database).await;
+    let client = TestClient::new(build_router(state.clone()));
+    let root_client = client.with_session(&login_as(&state, &root).await);
+
+    // 起動時に全て適用済み
+    let applied: Vec<MigrationRecord> = root_client.post(URI, &json!({})).await.expect(StatusCode::OK);
+    assert!(applied.is_empty());
+
+    let versions: Vec<i64> =
+        state.database.get_applied_migrations().await.unwrap().iter().map(|migration| migration.version).collect();
+
+    // 起動後に追加されたマイグレーションの代わりに、適用の記録を消して未適用の状態にする
+    let mut connection = SqliteConnection::connect(&database_url).await.unwrap();
+    sqlx::query("DELETE FROM _sqlx_migrations").execute(&mut connection).await.unwrap();
+    connection.close().await.unwrap();
+
+    let applied: Vec<MigrationRecord> = root_client.post(URI, &json!({})).await.expect(StatusCode::OK);
+    assert_eq!(applied.iter().map(|migration| migration.version).collect::<Vec<_>>(), versions);
+    let applied: Vec<MigrationRecord> = root_client.post(URI, &json!({})).await.expect(StatusCode::OK);
+    assert!(applied.is_empty());
+    assert_eq!(state.database.get_applied_migrations().await.unwrap().len(), versions.len());
+
+    let alice_client = client.with_session(&login_as(&state, &alice).await);
+    assert_eq!(alice_client.post(URI, &json!({})).await.status, StatusCode::FORBIDDEN);
+
+    drop((client, root_client, alice_client, state));
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+    }
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::fixtures::{InviteFixture, ScenarioBuilder};
+use patchouli::{build_router, config::Config, SystemStatusResponse};
+
+#[tokio::test]
+async fn counts_invites_by_state() {
+    let state = common::state(Config::default()).await;
+    let scenario = ScenarioBuilder::default().insert(&state.database).await;
+    let app = build_router(state);
+
+    // 認証不要
+    let response = common::get(&app, "/v1/system/status").await;
+    assert_eq!(response.status, StatusCode::OK);
+    let status: SystemStatusResponse = response.json();
+    assert_eq!(status.users_registered, scenario.users().len() as i64);
+
+    // 登録に使った4件、未使用・期限切れ・無効化済みが1件ずつ
+    let stats = status.invite_stats;
+    assert_eq!((stats.total, stats.active, stats.used, stats.expired), (7, 1, 4, 1));
+    let revoked = 1;
+    assert_eq!(stats.active + stats.used + stats.expired + revoked, stats.total);
+}
+
+#[tokio::test]
+async fn caches_the_result() {
+    let state = common::state(Config::default()).await;
+    let scenario = ScenarioBuilder::default().invitees(0).insert(&state.database).await;
+    let database = state.database.clone();
+    let app = build_router(state);
+
+    let before: SystemStatusResponse = common::get(&app, "/v1/system/status").await.json();
+    InviteFixture::new(&scenario.root).insert(&database).await;
+    let after: SystemStatusResponse = common::get(&app, "/v1/system/status").await.json();
+    assert_eq!(after.invite_stats.total, before.invite_stats.total);
+}
+
+#[tokio::test]
+async fn refreshes_after_the_ttl() {
+    let state = common::state(Config {
+        system_status_ttl_secs: 0,
+        ..Config::default()
+    })
+    .await;
+    let scenario = ScenarioBuilder::default().invitees(0).insert(&state.database).await;
+    let database = state.database.clone();
+    let app = build_router(state);
+
+    let before: SystemStatusResponse = common::get(&app, "/v1/system/status").await.json();
+    InviteFixture::new(&scenario.root).insert(&database).await;
+    let after: SystemStatusResponse = common::get(&app, "/v1/system/status").await.json();
+    assert_eq!(after.invite_stats.total, before.invite_stats.total + 1);
+    assert_eq!(after.invite_stats.active, before.invite_stats.active + 1);
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header::ACCEPT, Request, StatusCode},
+};
+use chrono::{DateTime, SecondsFormat};
+use common::{
+    add_session,
+    fixtures::{InviteFixture, UserFixture},
+};
+use patchouli::{build_router, config::Config};
+use serde_json::Value;
+
+const SESSION: &str = "root-session";
+
+/// 日時の項目（`*_at`・`last_login`）の値をすべて集める
+fn collect_timestamps(value: &Value, found: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                if (key.ends_with("_at") || key == "last_login")
+                    && let Value::String(timestamp) = value
+                {
+                    found.push(timestamp.clone());
+                } else {
+                    collect_timestamps(value, found);
+                }
+            }
+        }
+        Value::Array(values) => values.iter().for_each(|value| collect_timestamps(value, found)),
+        _ => {}
+    }
+}
+
+/// RFC 3339・UTCは`Z`（`+00:00`や`2024-05-01 12:03:11 UTC`ではない）
+fn assert_rfc3339_utc(timestamp: &str) {
+    let parsed = DateTime::parse_from_rfc3339(timestamp).unwrap_or_else(|e| panic!("{}: {}", timestamp, e));
+    assert_eq!(timestamp, parsed.to_rfc3339_opts(SecondsFormat::AutoSi, true));
+}
+
+#[tokio::test]
+async fn responses_use_rfc3339_with_z_suffix() {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    InviteFixture::new(&root).insert(&state.database).await;
+    add_session(&state, SESSION, &root).await;
+    let app = build_router(state);
+
+    let mut found = Vec::new();
+    for uri in ["/v1/dashboard", "/v1/invite/list", "/v1/admin/users", "/v1/admin/overview"] {
+        let response = common::get(&app, &format!("{}?session_id={}", uri, SESSION)).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", uri);
+        collect_timestamps(&response.json::<Value>(), &mut found);
+    }
+    // registered_at・last_login・created_at・generated_at
+    assert!(found.len() >= 4, "{:?}", found);
+    found.iter().for_each(|timestamp| assert_rfc3339_utc(timestamp));
+}
+
+#[tokio::test]
+async fn csv_timestamps_match_json() {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    add_session(&state, SESSION, &root).await;
+    let app = build_router(state);
+
+    let uri = format!("/v1/admin/users?session_id={}", SESSION);
+    let mut json = Vec::new();
+    collect_timestamps(&common::get(&app, &uri).await.json::<Value>(), &mut json);
+
+    let request = Request::get(&uri).header(ACCEPT, "text/csv").body(Body::empty()).unwrap();
+    let csv = String::from_utf8(common::send(&app, request).await.body).unwrap();
+    let row: Vec<&str> = csv.lines().nth(1).unwrap().split(',').collect();
+    // registered_at・last_login
+    for timestamp in [row[4], row[5]] {
+        assert!(json.iter().any(|value| value == timestamp), "{} not in {:?}", timestamp, json);
+    }
+}
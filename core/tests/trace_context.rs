@@ -0,0 +1,109 @@
+//! リクエストの`traceparent`・`tracestate`をリクエストのスパンの親にすること（W3C Trace Context）
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{fixtures::UserFixture, login_as, TestClient};
+use opentelemetry::{
+    global,
+    trace::{SpanId, TraceId, TracerProvider as _},
+};
+use opentelemetry_sdk::{
+    export::trace::{ExportResult, SpanData, SpanExporter},
+    propagation::TraceContextPropagator,
+    trace::TracerProvider,
+};
+use patchouli::{build_router, config::Config, DashboardResponse};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+use tracing::subscriber::DefaultGuard;
+use tracing_subscriber::prelude::*;
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+const PARENT_ID: &str = "00f067aa0ba902b7";
+
+/// 送信したスパンを記録するエクスポーター
+#[derive(Debug, Clone, Default)]
+struct Collected(Arc<Mutex<Vec<SpanData>>>);
+
+impl SpanExporter for Collected {
+    fn export(&mut self, batch: Vec<SpanData>) -> Pin<Box<dyn Future<Output = ExportResult> + Send + 'static>> {
+        self.0.lock().unwrap().extend(batch);
+        Box::pin(std::future::ready(Ok(())))
+    }
+}
+
+struct Tracing {
+    provider: TracerProvider,
+    exporter: Collected,
+    _guard: DefaultGuard,
+}
+
+impl Tracing {
+    /// OpenTelemetryのレイヤーを付けた`tracing`を、このスレッドで有効にする
+    fn start() -> Self {
+        // `telemetry::init`がOTLPを有効にしたときと同じプロパゲーター
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let exporter = Collected::default();
+        let provider = TracerProvider::builder().with_simple_exporter(exporter.clone()).build();
+        let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("test"));
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+        Tracing { provider, exporter, _guard }
+    }
+
+    /// これまでに閉じられた`name`のスパン（新しい順）
+    fn exported(&self, name: &str) -> Vec<SpanData> {
+        self.provider.force_flush();
+        let spans = self.exporter.0.lock().unwrap();
+        spans.iter().rev().filter(|span| span.name == name).cloned().collect()
+    }
+}
+
+async fn root_client() -> TestClient {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    TestClient::new(build_router(state.clone())).with_session(&login_as(&state, &root).await)
+}
+
+#[tokio::test]
+async fn incoming_trace_context_becomes_the_request_parent() {
+    let tracing = Tracing::start();
+    let traceparent = format!("00-{}-{}-01", TRACE_ID, PARENT_ID);
+    let client = root_client().await.with_header("traceparent", &traceparent).with_header("tracestate", "vendor=value");
+
+    assert_eq!(client.get("/healthz").await.status, StatusCode::OK);
+    let request = tracing.exported("request").remove(0);
+    assert_eq!(request.span_context.trace_id(), TraceId::from_hex(TRACE_ID).unwrap());
+    assert_eq!(request.parent_span_id, SpanId::from_hex(PARENT_ID).unwrap());
+    assert_eq!(request.span_context.trace_state().header(), "vendor=value");
+    assert!(request.span_context.is_sampled());
+
+    // データベースのスパンも同じトレースでリクエストのスパンの子になる（SQLiteの接続を開いたリクエストのスパンは
+    // sqlxのワーカースレッドが持ち続けて閉じられないことがあるため、子のスパンで確かめる）
+    client.get("/v1/dashboard").await.expect::<DashboardResponse>(StatusCode::OK);
+    let summary = tracing.exported("get_invite_summary_by_user").remove(0);
+    assert_eq!(summary.span_context.trace_id(), TraceId::from_hex(TRACE_ID).unwrap());
+    assert_eq!(summary.span_context.trace_state().header(), "vendor=value");
+    assert_ne!(summary.parent_span_id, SpanId::from_hex(PARENT_ID).unwrap());
+    assert_ne!(summary.parent_span_id, SpanId::INVALID);
+}
+
+#[tokio::test]
+async fn requests_without_trace_context_start_a_new_trace() {
+    let tracing = Tracing::start();
+    let client = root_client().await;
+    let invalid = "00-00000000000000000000000000000000-0000000000000000-01";
+
+    let mut trace_ids = Vec::new();
+    for client in [client.clone(), client.with_header("traceparent", invalid)] {
+        assert_eq!(client.get("/healthz").await.status, StatusCode::OK);
+        let request = tracing.exported("request").remove(0);
+        assert!(request.span_context.is_valid());
+        assert_eq!(request.parent_span_id, SpanId::INVALID);
+        trace_ids.push(request.span_context.trace_id());
+    }
+    assert_ne!(trace_ids[0], trace_ids[1]);
+}
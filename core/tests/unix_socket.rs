@@ -0,0 +1,128 @@
+//! Unixドメインソケットでの待ち受け（リクエストの処理、ソケットファイルの権限、終了時に処理中の接続を待つこと）
+
+mod common;
+
+use axum::{body::Body, http::StatusCode, routing::get, Router};
+use common::google;
+use http_body_util::BodyExt;
+use hyper::{client::conn::http1, Request};
+use hyper_util::rt::TokioIo;
+use patchouli::{build_router, config::Config, unix_socket, HealthResponse};
+use serde_json::{json, Value};
+use std::{
+    os::unix::fs::{FileTypeExt, PermissionsExt},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::{
+    net::{TcpListener, UnixStream},
+    sync::oneshot,
+    task::JoinHandle,
+};
+
+/// テストごとのソケットファイルのパス
+fn socket_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("patchouli-{}-{}.sock", name, std::process::id()))
+}
+
+/// `config`のアプリを`path`で起動する（`oneshot::Sender`を送るかdropすると終了する）
+async fn start(config: Config, path: &Path) -> (oneshot::Sender<()>, JoinHandle<anyhow::Result<()>>) {
+    let app = build_router(common::state(config).await);
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(unix_socket::serve(path.to_path_buf(), 0o660, app, async {
+        let _ = stopped.await;
+    }));
+    for _ in 0..100 {
+        if path.exists() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    (stop, server)
+}
+
+/// `path`のソケットに接続してリクエストを送り、ステータスとJSONのボディを返す
+async fn send(path: &Path, request: Request<Body>) -> (StatusCode, Value) {
+    let stream = UnixStream::connect(path).await.unwrap();
+    let (mut sender, connection) = http1::handshake(TokioIo::new(stream)).await.unwrap();
+    tokio::spawn(connection);
+    let response = sender.send_request(request).await.unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+fn get_request(uri: &str) -> Request<Body> {
+    Request::get(uri).header("host", "localhost").body(Body::empty()).unwrap()
+}
+
+#[tokio::test]
+async fn requests_are_served_over_the_socket() {
+    let path = socket_path("serve");
+    let (stop, server) = start(Config::default(), &path).await;
+
+    let metadata = std::fs::symlink_metadata(&path).unwrap();
+    assert!(metadata.file_type().is_socket());
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o660);
+
+    let (status, body) = send(&path, get_request("/healthz")).await;
+    assert_eq!(status, StatusCode::OK);
+    serde_json::from_value::<HealthResponse>(body).unwrap();
+    // 認証が必要なルートもTCPと同じくJSONのエラーを返す
+    let (status, body) = send(&path, get_request("/v1/dashboard")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "validation_failed");
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+    assert!(!path.exists());
+}
+
+/// 応答しないGoogleの公開鍵のエンドポイント（ID Tokenの検証を止める）
+async fn stalled_jwks_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = Router::new().route("/certs", get(|| tokio::time::sleep(Duration::from_secs(60))));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}/certs", addr)
+}
+
+#[tokio::test]
+async fn shutdown_waits_for_in_flight_requests() {
+    let path = socket_path("drain");
+    let config = Config {
+        google_client_id: google::CLIENT_ID.to_string(),
+        google_jwks_url: stalled_jwks_server().await,
+        request_timeout_secs: 2,
+        ..Config::default()
+    };
+    let (stop, server) = start(config, &path).await;
+
+    // 公開鍵の取得で止まり、request_timeout_secsの後に504を返すリクエスト
+    let body = json!({
+        "grant_type": "google_id_token",
+        "id_token": google::id_token("google-guest", "guest@example.com", "Guest"),
+    });
+    let request = Request::post("/v1/auth/tokens/google-one-tap")
+        .header("host", "localhost")
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let in_flight = tokio::spawn({
+        let path = path.clone();
+        async move { send(&path, request).await }
+    });
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    stop.send(()).unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(!server.is_finished(), "server should wait for the in-flight request");
+    // 新しい接続は受け付けない
+    assert!(UnixStream::connect(&path).await.is_err());
+
+    let (status, body) = in_flight.await.unwrap();
+    assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(body["error"], "timeout");
+    tokio::time::timeout(Duration::from_secs(5), server).await.unwrap().unwrap().unwrap();
+    assert!(!path.exists());
+}
@@ -0,0 +1,79 @@
+//! 認証時のユーザーキャッシュ（権限の変更がTTLを待たずに反映されること・TTLが0なら無効になること）
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{fixtures::UserFixture, login_as, TestClient};
+use patchouli::{
+    build_router, config::Config, database::RegisteredUser, error::ErrorCode, AppState, CreateApiKeyResponse,
+    UserInfoResponse,
+};
+use serde_json::{json, Value};
+
+async fn setup(user_cache_ttl_secs: u64) -> (AppState, RegisteredUser, RegisteredUser, TestClient) {
+    let config = Config {
+        user_cache_ttl_secs,
+        ..Config::default()
+    };
+    let state = common::state(config).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let alice = UserFixture::new("Alice").invited_by(&root).insert(&state.database).await;
+    let client = TestClient::new(build_router(state.clone()));
+    (state, root, alice, client)
+}
+
+#[tokio::test]
+async fn revoking_invite_permission_takes_effect_immediately() {
+    // TTLが十分に長くても、変更時の無効化で次のリクエストから反映される
+    let (state, root, alice, client) = setup(3600).await;
+    let root_client = client.with_session(&login_as(&state, &root).await);
+    let alice_client = client.with_session(&login_as(&state, &alice).await);
+    let uri = format!("/v1/users/{}/can-invite", alice.id);
+
+    root_client.patch(&uri, &json!({ "can_invite": true })).await.expect::<Value>(StatusCode::OK);
+    // キャッシュを温める
+    alice_client.get("/v1/invite/create").await.expect::<Value>(StatusCode::OK);
+    alice_client.get("/v1/userinfo").await.expect::<UserInfoResponse>(StatusCode::OK);
+
+    root_client.patch(&uri, &json!({ "can_invite": false })).await.expect::<Value>(StatusCode::OK);
+    assert_eq!(alice_client.get("/v1/invite/create").await.status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn banning_and_deleting_invalidate_api_key_lookups() {
+    let (state, root, alice, client) = setup(3600).await;
+    let root_client = client.with_session(&login_as(&state, &root).await);
+    let alice_client = client.with_session(&login_as(&state, &alice).await);
+    let body = json!({ "name": "CI", "scopes": ["read:users"] });
+    let created: CreateApiKeyResponse = alice_client.post("/v1/api-keys", &body).await.expect(StatusCode::CREATED);
+    let key_client = client.with_header("X-Api-Key", &created.key);
+    key_client.get("/v1/userinfo").await.expect::<UserInfoResponse>(StatusCode::OK);
+
+    let uri = format!("/v1/admin/users/{}/ban", alice.id);
+    root_client.post(&uri, &json!({})).await.expect::<Value>(StatusCode::OK);
+    let response = key_client.get("/v1/userinfo").await;
+    assert_eq!((response.status, response.error_code()), (StatusCode::FORBIDDEN, ErrorCode::UserSuspended));
+
+    let uri = format!("/v1/admin/users/{}/unban", alice.id);
+    root_client.post(&uri, &json!({})).await.expect::<Value>(StatusCode::OK);
+    key_client.get("/v1/userinfo").await.expect::<UserInfoResponse>(StatusCode::OK);
+
+    let uri = format!("/v1/admin/users/{}", alice.id);
+    let deleted: Value = root_client.delete(&uri).await.expect(StatusCode::OK);
+    assert_eq!(deleted["success"], true);
+    assert_eq!(key_client.get("/v1/userinfo").await.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn zero_ttl_disables_the_cache() {
+    // 無効化を経由しない変更（データベースの直接の更新）で、キャッシュの有無を確かめる
+    for (ttl, expected) in [(3600, StatusCode::OK), (0, StatusCode::FORBIDDEN)] {
+        let (state, root, alice, client) = setup(ttl).await;
+        state.database.set_can_invite(root.id, alice.id, true).await.unwrap();
+        let alice_client = client.with_session(&login_as(&state, &alice).await);
+        alice_client.get("/v1/invite/create").await.expect::<Value>(StatusCode::OK);
+
+        state.database.set_can_invite(root.id, alice.id, false).await.unwrap();
+        assert_eq!(alice_client.get("/v1/invite/create").await.status, expected, "ttl={}", ttl);
+    }
+}
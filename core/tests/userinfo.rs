@@ -0,0 +1,70 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header::AUTHORIZATION, Request, StatusCode},
+    Router,
+};
+use common::{add_session, fixtures::UserFixture};
+use patchouli::{
+    build_router,
+    config::Config,
+    error::{ErrorCode, ErrorResponse},
+    AppState,
+};
+use serde_json::{json, Value};
+
+const USER_SESSION: &str = "user-session";
+
+async fn setup() -> (AppState, Router) {
+    let state = common::state(Config::default()).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let user = UserFixture::new("User").invited_by(&root).insert(&state.database).await;
+    add_session(&state, USER_SESSION, &user).await;
+    (state.clone(), build_router(state))
+}
+
+async fn userinfo(app: &Router, authorization: &str) -> common::TestResponse {
+    let request = Request::get("/v1/userinfo")
+        .header(AUTHORIZATION, authorization)
+        .body(Body::empty())
+        .unwrap();
+    common::send(app, request).await
+}
+
+#[tokio::test]
+async fn returns_oidc_claims_for_bearer_session() {
+    let (state, app) = setup().await;
+
+    let response = userinfo(&app, &format!("Bearer {}", USER_SESSION)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(
+        response.json::<Value>(),
+        json!({ "sub": "2", "email": "user@example.com", "name": "User", "email_verified": false })
+    );
+
+    // Googleログインで確認済みになったらキャッシュを経由しても反映される
+    state.database.mark_email_verified("user@example.com").await.unwrap();
+    state.user_cache.invalidate("user@example.com").await;
+    let response = userinfo(&app, &format!("bearer {}", USER_SESSION)).await;
+    assert_eq!(response.json::<Value>()["email_verified"], json!(true));
+
+    // 他のエンドポイントと同じくsession_idクエリでも取得できる
+    let response = common::get(&app, &format!("/v1/userinfo?session_id={}", USER_SESSION)).await;
+    assert_eq!(response.json::<Value>()["sub"], json!("2"));
+}
+
+#[tokio::test]
+async fn rejects_missing_or_unknown_token() {
+    let (_, app) = setup().await;
+
+    let response = userinfo(&app, "Bearer no-such-session").await;
+    assert_eq!(
+        (response.status, response.json::<ErrorResponse>().error),
+        (StatusCode::UNAUTHORIZED, ErrorCode::InvalidSession)
+    );
+
+    // Bearer以外のスキームは無視され、session_idもないため400
+    let response = userinfo(&app, &format!("Basic {}", USER_SESSION)).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,99 @@
+//! Webhookで送信するペイロードの形（イベントごとの型付きペイロードと`event`）
+
+mod common;
+
+use axum::{http::StatusCode, routing::post, Json, Router};
+use chrono::{DateTime, Utc};
+use common::{
+    fixtures::{InviteFixture, UserFixture},
+    google, login_as, TestClient,
+};
+use patchouli::{build_router, clock::MockClock, config::Config, AuthResponse};
+use serde_json::{json, Value};
+use std::{sync::Arc, time::Duration};
+use tokio::{net::TcpListener, sync::mpsc};
+
+/// 受け取ったWebhookのボディを順に返すエンドポイントを起動し、そのURLを返す
+async fn webhook_server() -> (String, mpsc::UnboundedReceiver<Value>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let app = Router::new().route(
+        "/hooks/secret-token",
+        post(move |Json(body): Json<Value>| async move {
+            sender.send(body).unwrap();
+            StatusCode::NO_CONTENT
+        }),
+    );
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{}/hooks/secret-token", addr), receiver)
+}
+
+async fn next_payload(receiver: &mut mpsc::UnboundedReceiver<Value>) -> Value {
+    tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+        .await
+        .expect("webhook should be delivered")
+        .unwrap()
+}
+
+#[tokio::test]
+async fn events_are_delivered_as_typed_payloads() {
+    let now: DateTime<Utc> = "2026-01-02T03:04:05Z".parse().unwrap();
+    let (webhook_url, mut payloads) = webhook_server().await;
+    let config = Config {
+        google_client_id: google::CLIENT_ID.to_string(),
+        google_jwks_url: google::jwks_server().await,
+        webhook_url: Some(webhook_url),
+        ..Config::default()
+    };
+    let state = common::state_with_clock(config, Arc::new(MockClock::new(now))).await;
+    let root = UserFixture::new("Root").root().insert(&state.database).await;
+    let invite = InviteFixture::new(&root).insert(&state.database).await;
+    let client = TestClient::new(build_router(state.clone()));
+
+    let body = json!({
+        "grant_type": "google_id_token",
+        "id_token": google::id_token_at("google-guest", "guest@example.com", "Guest", now),
+        "invite_code": invite.code,
+    });
+    client.post("/v1/auth/tokens/google-one-tap", &body).await.expect::<AuthResponse>(StatusCode::OK);
+    let guest = state.database.get_user_by_email("guest@example.com").await.unwrap().unwrap();
+
+    assert_eq!(
+        next_payload(&mut payloads).await,
+        json!({
+            "event": "user.created",
+            "user_id": guest.id,
+            "email": "guest@example.com",
+            "invited_by": root.id,
+            "timestamp": "2026-01-02T03:04:05Z",
+        })
+    );
+    assert_eq!(
+        next_payload(&mut payloads).await,
+        json!({
+            "event": "invite.used",
+            "invite_id": invite.id,
+            "code": invite.code,
+            "created_by": root.id,
+            "used_by": guest.id,
+            "timestamp": "2026-01-02T03:04:05Z",
+        })
+    );
+
+    let pending = InviteFixture::new(&root).insert(&state.database).await;
+    let root_client = client.with_session(&login_as(&state, &root).await);
+    let uri = format!("/v1/invite/{}/resend-notification", pending.id);
+    root_client.post(&uri, &json!({})).await.expect::<Value>(StatusCode::OK);
+    assert_eq!(
+        next_payload(&mut payloads).await,
+        json!({
+            "event": "invite.resent",
+            "invite_id": pending.id,
+            "code": pending.code,
+            "created_by": root.id,
+            "resent_by": root.id,
+            "timestamp": "2026-01-02T03:04:05Z",
+        })
+    );
+}
@@ -17,14 +17,14 @@ export class PatchouliClient {
 
   async getProtectedContent(sessionId: string): Promise<string> {
     try {
-      const response = await this.client.get('/protected', {
+      const response = await this.client.get('/v1/dashboard', {
         params: {
           session_id: sessionId,
         },
       });
 
       if (response.status === 200) {
-        return response.data;
+        return JSON.stringify(response.data, null, 2);
       } else {
         throw new Error(`Unexpected response status: ${response.status}`);
       }
@@ -48,7 +48,7 @@ export class PatchouliClient {
   async authenticateForDiscordUser(discordUserId: string): Promise<string> {
     try {
       // Discord用の認証トークンとログインURLを取得
-      const response = await this.client.get('/login/api', {
+      const response = await this.client.get('/v1/login/api', {
         params: {
           discord_user: discordUserId
         }
@@ -69,7 +69,7 @@ export class PatchouliClient {
 
   async checkAuthStatus(authToken: string): Promise<{status: string, session_id?: string, user_email?: string}> {
     try {
-      const response = await this.client.get(`/auth/status/${authToken}`);
+      const response = await this.client.get(`/v1/auth/status/${authToken}`);
       return response.data;
     } catch (error) {
       if (axios.isAxiosError(error) && error.response?.status === 404) {
@@ -29,6 +29,8 @@ export interface InviteCode {
   used_by: number | null;
   used_at: string | null;
   is_active: boolean;
+  note: string | null;
+  metadata: Record<string, unknown>;
 }
 
 export interface InviteCodesListResponse {
@@ -45,6 +47,7 @@ export interface RegisteredUser {
   is_root: boolean;
   can_invite: boolean;
   invited_by: number | null;
+  metadata: Record<string, unknown>;
 }
 
 export interface UsersListResponse {
@@ -79,10 +82,10 @@ class PatchouliAPI {
   }
 
   async getProtectedContent(sessionId: string): Promise<string> {
-    const response = await this.client.get('/protected', {
+    const response = await this.client.get('/v1/dashboard', {
       params: { session_id: sessionId },
     });
-    return response.data;
+    return JSON.stringify(response.data, null, 2);
   }
 
   async logout(sessionId: string): Promise<void> {
@@ -101,34 +104,34 @@ class PatchouliAPI {
   }
 
   async createInviteCode(sessionId: string): Promise<InviteCodeResponse> {
-    const response = await this.client.get('/invite/create', {
+    const response = await this.client.get('/v1/invite/create', {
       params: { session_id: sessionId },
     });
     return response.data;
   }
 
   async listInviteCodes(sessionId: string): Promise<InviteCodesListResponse> {
-    const response = await this.client.get('/invite/list', {
+    const response = await this.client.get('/v1/invite/list', {
       params: { session_id: sessionId },
     });
     return response.data;
   }
 
   async listUsers(sessionId: string): Promise<UsersListResponse> {
-    const response = await this.client.get('/admin/users', {
+    const response = await this.client.get('/v1/admin/users', {
       params: { session_id: sessionId },
     });
     return response.data;
   }
 
   async deleteUser(sessionId: string, userId: number): Promise<DeleteUserResponse> {
-    const response = await this.client.delete(`/admin/users/${userId}?session_id=${encodeURIComponent(sessionId)}`);
+    const response = await this.client.delete(`/v1/admin/users/${userId}?session_id=${encodeURIComponent(sessionId)}`);
     return response.data;
   }
 
   async validateSession(sessionId: string): Promise<boolean> {
     try {
-      const response = await this.client.get('/protected', {
+      const response = await this.client.get('/v1/dashboard', {
         params: { session_id: sessionId },
       });
       return response.status === 200;
@@ -138,7 +141,7 @@ class PatchouliAPI {
   }
 
   async checkRootExists(): Promise<RootExistsResponse> {
-    const response = await this.client.get('/root/exists');
+    const response = await this.client.get('/v1/root/exists');
     return response.data;
   }
 }
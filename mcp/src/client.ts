@@ -20,14 +20,14 @@ export class PatchouliClient {
 
   async getProtectedContent(sessionId: string): Promise<string> {
     try {
-      const response = await this.client.get('/protected', {
+      const response = await this.client.get('/v1/dashboard', {
         params: {
           session_id: sessionId,
         },
       });
 
       if (response.status === 200) {
-        return response.data;
+        return JSON.stringify(response.data, null, 2);
       } else {
         throw new Error(`Unexpected response status: ${response.status}`);
       }
@@ -51,7 +51,7 @@ export class PatchouliClient {
   async authenticate(): Promise<string> {
     try {
       // 認証トークンとログインURLを取得
-      const response = await this.client.get('/login/api');
+      const response = await this.client.get('/v1/login/api');
       const { auth_token, login_url } = response.data;
 
       console.error('Opening browser for authentication...');
@@ -76,7 +76,7 @@ export class PatchouliClient {
 
     for (let attempt = 0; attempt < maxAttempts; attempt++) {
       try {
-        const response = await this.client.get(`/auth/status/${authToken}`);
+        const response = await this.client.get(`/v1/auth/status/${authToken}`);
         const { status, session_id, user_email } = response.data;
 
         switch (status) {